@@ -0,0 +1,153 @@
+-- Migration: Idempotency keys for batch inserts and upserts
+-- Devices send an "Idempotency-Key" header (a hash of the batch's rows as sent) with bulk writes.
+-- The first request records the IDs it inserted under that key and table. A retry whose key is
+-- already recorded inserts nothing new, and the client reads the original rows back from this
+-- table. Rows that already exist still go through: upserting them again is idempotent and keeps
+-- the latest edit. Callers that are not devices write without recording or checking keys.
+
+-- Step 1: Key ledger (one row per device, table and batch)
+CREATE TABLE IF NOT EXISTS "public"."batch_idempotency_keys" (
+  "device_id"  BIGINT NOT NULL,
+  "key"        TEXT NOT NULL,
+  "table_name" TEXT NOT NULL,
+  "row_ids"    BIGINT[] NOT NULL DEFAULT '{}',
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+  PRIMARY KEY ("device_id", "table_name", "key")
+);
+
+ALTER TABLE "public"."batch_idempotency_keys" OWNER TO "postgres";
+
+COMMENT ON TABLE "public"."batch_idempotency_keys" IS 'IDs inserted by each idempotent batch write, so retried batches return the original rows instead of inserting twice. Kept for 30 days.';
+
+CREATE INDEX IF NOT EXISTS "batch_idempotency_keys_created_at_idx" ON "public"."batch_idempotency_keys" ("created_at");
+
+ALTER TABLE "public"."batch_idempotency_keys" ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Batch idempotency keys access: Device API keys" ON "public"."batch_idempotency_keys"
+  FOR SELECT USING ("device_id" = "private"."key_uid"());
+
+-- Step 2: Trigger functions (key_uid() is 0 for callers without a device API key)
+CREATE OR REPLACE FUNCTION "private"."request_idempotency_key"()
+RETURNS text
+LANGUAGE "sql"
+STABLE
+AS $$
+  select nullif(current_setting('request.headers', true)::json->>'idempotency-key', '')
+$$;
+
+ALTER FUNCTION "private"."request_idempotency_key"() OWNER TO "postgres";
+
+CREATE OR REPLACE FUNCTION "private"."skip_replayed_batch"()
+RETURNS trigger
+LANGUAGE "plpgsql"
+SECURITY DEFINER
+AS $$
+declare
+  idempotency_key text := private.request_idempotency_key();
+  caller_device_id bigint;
+  row_exists boolean;
+begin
+  if idempotency_key is null then
+    return new;
+  end if;
+  caller_device_id := private.key_uid();
+  if caller_device_id is null or caller_device_id = 0 or not exists (
+    select 1 from public.batch_idempotency_keys
+    where device_id = caller_device_id and table_name = TG_TABLE_NAME and key = idempotency_key
+  ) then
+    return new;
+  end if;
+  execute format('select exists (select 1 from %I.%I where id = $1)', TG_TABLE_SCHEMA, TG_TABLE_NAME)
+    into row_exists
+    using new.id;
+  if row_exists then
+    return new;
+  end if;
+  return null;
+end;
+$$;
+
+ALTER FUNCTION "private"."skip_replayed_batch"() OWNER TO "postgres";
+
+COMMENT ON FUNCTION "private"."skip_replayed_batch"() IS 'Skips rows a batch write with an Idempotency-Key already processed for the calling device and table would insert again; upserts of existing rows still apply';
+
+CREATE OR REPLACE FUNCTION "private"."record_batch_idempotency_key"()
+RETURNS trigger
+LANGUAGE "plpgsql"
+SECURITY DEFINER
+AS $$
+declare
+  idempotency_key text := private.request_idempotency_key();
+  caller_device_id bigint;
+begin
+  if idempotency_key is null then
+    return null;
+  end if;
+  caller_device_id := private.key_uid();
+  if caller_device_id is null or caller_device_id = 0 then
+    return null;
+  end if;
+  insert into public.batch_idempotency_keys (device_id, key, table_name, row_ids)
+  select caller_device_id, idempotency_key, TG_TABLE_NAME, coalesce(array_agg(id order by id), '{}')
+  from new_rows
+  on conflict (device_id, table_name, key) do nothing;
+  return null;
+end;
+$$;
+
+ALTER FUNCTION "private"."record_batch_idempotency_key"() OWNER TO "postgres";
+
+COMMENT ON FUNCTION "private"."record_batch_idempotency_key"() IS 'Records the IDs inserted by a device''s batch write under its Idempotency-Key and table';
+
+-- Step 3: Triggers on tables written in batches by devices
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."sessions"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."sessions"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."events"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."events"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."tags"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."tags"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."connectivity"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."connectivity"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."operators"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."operators"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."artifacts"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."artifacts"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
+
+-- Step 4: Retention. Devices retry a batch within days, so keys older than 30 days are purged
+-- nightly; a batch replayed after that is written as a new one.
+CREATE OR REPLACE FUNCTION "private"."purge_batch_idempotency_keys"()
+RETURNS void
+LANGUAGE "sql"
+SECURITY DEFINER
+AS $$
+  delete from public.batch_idempotency_keys where created_at < clock_timestamp() - interval '30 days'
+$$;
+
+ALTER FUNCTION "private"."purge_batch_idempotency_keys"() OWNER TO "postgres";
+
+COMMENT ON FUNCTION "private"."purge_batch_idempotency_keys"() IS 'Deletes batch idempotency keys older than 30 days';
+
+SELECT cron.schedule('purge-batch-idempotency-keys', '17 3 * * *', 'select private.purge_batch_idempotency_keys()');
@@ -0,0 +1,68 @@
+-- Migration: Operator authentication for devices
+-- Operators identify on a device with a PIN or badge scan. The device exchanges the credential
+-- for a session-scoped token carrying the operator's user_id, which it stamps on operator rows.
+
+-- Step 1: Credentials (hashed with pgcrypto) and issued sessions
+CREATE TABLE IF NOT EXISTS "private"."operator_credentials" (
+  "id"              BIGSERIAL PRIMARY KEY,
+  "user_id"         UUID NOT NULL REFERENCES "auth"."users"("id") ON DELETE CASCADE,
+  "herd_id"         BIGINT NOT NULL REFERENCES "public"."herds"("id") ON DELETE CASCADE,
+  "credential_type" TEXT NOT NULL CHECK ("credential_type" IN ('pin', 'badge')),
+  "credential_hash" TEXT NOT NULL,
+  "created_at"      TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+);
+
+ALTER TABLE "private"."operator_credentials" OWNER TO "postgres";
+
+COMMENT ON TABLE "private"."operator_credentials" IS 'Operator PINs and badge IDs per herd, hashed with extensions.crypt';
+
+CREATE TABLE IF NOT EXISTS "private"."operator_sessions" (
+  "token"      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  "user_id"    UUID NOT NULL REFERENCES "auth"."users"("id") ON DELETE CASCADE,
+  "device_id"  BIGINT NOT NULL REFERENCES "public"."devices"("id") ON DELETE CASCADE,
+  "expires_at" TIMESTAMPTZ NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+);
+
+ALTER TABLE "private"."operator_sessions" OWNER TO "postgres";
+
+COMMENT ON TABLE "private"."operator_sessions" IS 'Operator sessions issued to devices by authenticate_operator';
+
+-- Step 2: Exchange a credential for a session-scoped user_id token
+CREATE OR REPLACE FUNCTION "public"."authenticate_operator"("credential" text, "credential_type" text)
+RETURNS TABLE ("user_id" uuid, "token" uuid, "expires_at" timestamptz)
+LANGUAGE "plpgsql"
+SECURITY DEFINER
+SET "search_path" TO ''
+AS $$
+declare
+  caller_device_id bigint := private.key_uid();
+  matched_user_id uuid;
+begin
+  if caller_device_id = 0 then
+    raise exception 'Operator authentication requires a device API key';
+  end if;
+
+  -- The operator must hold a role in the device's herd
+  select c.user_id into matched_user_id
+  from private.operator_credentials c
+  join public.users_roles_per_herd r on r.user_id = c.user_id and r.herd_id = c.herd_id
+  where c.herd_id = private.get_herd_id_by_device_id(caller_device_id)
+    and c.credential_type = authenticate_operator.credential_type
+    and c.credential_hash = extensions.crypt(authenticate_operator.credential, c.credential_hash)
+  limit 1;
+
+  if matched_user_id is null then
+    raise exception 'Invalid operator credential';
+  end if;
+
+  return query
+  insert into private.operator_sessions (user_id, device_id, expires_at)
+  values (matched_user_id, caller_device_id, clock_timestamp() + interval '12 hours')
+  returning operator_sessions.user_id, operator_sessions.token, operator_sessions.expires_at;
+end;
+$$;
+
+ALTER FUNCTION "public"."authenticate_operator"("credential" text, "credential_type" text) OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."authenticate_operator"("credential" text, "credential_type" text) IS 'Exchanges an operator PIN or badge scan for a 12 hour session token with the operator user_id, scoped to the calling device';
@@ -0,0 +1,80 @@
+-- Migration: Events linked to multiple sessions (cross-session observations)
+-- An event keeps its own session_id; links attribute it to further sessions, e.g. a sentry
+-- tower observation that also belongs to a drone's active session.
+
+-- Step 1: Create link table (access follows the linked event's device)
+CREATE TABLE IF NOT EXISTS "public"."event_session_links" (
+  "id"         BIGSERIAL PRIMARY KEY,
+  "event_id"   BIGINT NOT NULL,
+  "session_id" BIGINT NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+  CONSTRAINT "event_session_links_event_id_session_id_key" UNIQUE ("event_id", "session_id")
+);
+
+ALTER TABLE "public"."event_session_links" OWNER TO "postgres";
+
+COMMENT ON TABLE "public"."event_session_links" IS 'Attributes events to sessions besides their own. One row per event and linked session.';
+
+ALTER TABLE ONLY "public"."event_session_links"
+  ADD CONSTRAINT "event_session_links_event_id_fkey" FOREIGN KEY ("event_id")
+  REFERENCES "public"."events"("id") ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE ONLY "public"."event_session_links"
+  ADD CONSTRAINT "event_session_links_session_id_fkey" FOREIGN KEY ("session_id")
+  REFERENCES "public"."sessions"("id") ON UPDATE CASCADE ON DELETE CASCADE;
+
+CREATE INDEX "idx_event_session_links_session_id" ON "public"."event_session_links"
+  USING btree ("session_id");
+
+-- Step 2: Enable RLS
+ALTER TABLE "public"."event_session_links" ENABLE ROW LEVEL SECURITY;
+
+-- Step 3: Policies (same pattern as events, resolved through the linked event's device)
+CREATE POLICY "Event session link access: Device API keys and users with view role"
+  ON "public"."event_session_links" FOR SELECT USING (
+  ((SELECT "events"."device_id" FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id") = "private"."key_uid"())
+  OR
+  (
+    ("private"."key_uid"() <> 0)
+    AND "private"."is_approved_gateway_device_type"("private"."key_uid"())
+    AND "private"."herd_has_device"("private"."get_herd_id_by_device_id"("private"."key_uid"()), (SELECT "events"."device_id" FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id"))
+  )
+  OR
+  "private"."has_good_view_role"(
+    (SELECT "auth"."uid"() AS "uid"),
+    (SELECT "private"."get_herd_id_by_device_id"("events"."device_id") FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id")
+  )
+);
+
+CREATE POLICY "Event session link creation: Device API keys and users with edit role"
+  ON "public"."event_session_links" FOR INSERT WITH CHECK (
+  ((SELECT "events"."device_id" FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id") = "private"."key_uid"())
+  OR
+  (
+    ("private"."key_uid"() <> 0)
+    AND "private"."is_approved_gateway_device_type"("private"."key_uid"())
+    AND "private"."herd_has_device"("private"."get_herd_id_by_device_id"("private"."key_uid"()), (SELECT "events"."device_id" FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id"))
+  )
+  OR
+  "private"."has_good_edit_role"(
+    (SELECT "auth"."uid"() AS "uid"),
+    (SELECT "private"."get_herd_id_by_device_id"("events"."device_id") FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id")
+  )
+);
+
+CREATE POLICY "Event session link deletion: Device API keys and users with edit role"
+  ON "public"."event_session_links" FOR DELETE USING (
+  ((SELECT "events"."device_id" FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id") = "private"."key_uid"())
+  OR
+  "private"."has_good_edit_role"(
+    (SELECT "auth"."uid"() AS "uid"),
+    (SELECT "private"."get_herd_id_by_device_id"("events"."device_id") FROM "public"."events" WHERE "events"."id" = "event_session_links"."event_id")
+  )
+);
+
+-- Step 4: Batch idempotency (see 09-batch-idempotency-keys.sql)
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."event_session_links"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."event_session_links"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
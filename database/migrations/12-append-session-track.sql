@@ -0,0 +1,40 @@
+-- Migration: Incremental session track uploads
+-- Devices append track segments while a session is running so the herd map shows live tracks,
+-- instead of uploading the full locations linestring once the session ends.
+
+-- Step 1: Append points (ordered [longitude, latitude] pairs) to a session's locations
+CREATE OR REPLACE FUNCTION "public"."append_session_track"("session_id" bigint, "points" jsonb)
+RETURNS TABLE ("id" bigint, "point_count" integer)
+LANGUAGE "plpgsql"
+SET "search_path" TO ''
+AS $$
+declare
+  segment extensions.geometry;
+begin
+  select extensions.ST_MakeLine(
+    array_agg(
+      extensions.ST_SetSRID(extensions.ST_MakePoint((p.value->>0)::float8, (p.value->>1)::float8), 4326)
+      order by p.ordinality
+    )
+  ) into segment
+  from jsonb_array_elements(append_session_track.points) with ordinality as p(value, ordinality);
+
+  if segment is null then
+    raise exception 'Track segment has no points';
+  end if;
+
+  -- Runs as the caller, so the session modification policy applies
+  return query
+  update public.sessions s
+  set locations = case
+    when s.locations is null then segment::extensions.geography
+    else extensions.ST_MakeLine(s.locations::extensions.geometry, segment)::extensions.geography
+  end
+  where s.id = append_session_track.session_id
+  returning s.id, extensions.ST_NPoints(s.locations::extensions.geometry);
+end;
+$$;
+
+ALTER FUNCTION "public"."append_session_track"("session_id" bigint, "points" jsonb) OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."append_session_track"("session_id" bigint, "points" jsonb) IS 'Appends [longitude, latitude] points to a session locations linestring and returns its new point count';
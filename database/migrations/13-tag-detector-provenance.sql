@@ -0,0 +1,15 @@
+-- Migration: Detector provenance on tags
+-- Auto tags record the name and version of the model that produced them, so detection
+-- counts can be compared across model versions.
+
+-- Step 1: Add provenance columns (null for manual tags and tags created before this migration)
+ALTER TABLE "public"."tags"
+  ADD COLUMN IF NOT EXISTS "detector_name" TEXT,
+  ADD COLUMN IF NOT EXISTS "detector_version" TEXT;
+
+COMMENT ON COLUMN "public"."tags"."detector_name" IS 'Name of the model that produced an auto tag';
+COMMENT ON COLUMN "public"."tags"."detector_version" IS 'Version of the model that produced an auto tag';
+
+-- Step 2: Index for comparing detections per model version
+CREATE INDEX IF NOT EXISTS "idx_tags_detector" ON "public"."tags"
+  USING btree ("detector_name", "detector_version");
@@ -0,0 +1,22 @@
+-- Migration: Model version negotiation
+-- Devices fetch the model versions the server accepts while identifying, and send each
+-- record in the highest version both sides support, so older firmware and older schemas
+-- keep syncing after a model gains fields.
+
+-- Step 1: Versions accepted per table; bump when a table gains the columns of a new model version
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4),
+    'events', jsonb_build_array(1, 2),
+    'tags', jsonb_build_array(1, 2)
+  );
+$$;
+
+ALTER FUNCTION "public"."get_supported_model_versions"() OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."get_supported_model_versions"() IS 'Model versions accepted per table, used by devices to negotiate the version they sync';
@@ -0,0 +1,26 @@
+-- Migration: Event chains
+-- Follow-up observations reference the event they follow up on, so a chain of events
+-- (first sighting, confirmation, resolution) can be read back in order.
+
+-- Step 1: Parent event reference; children keep their history if the parent is deleted
+ALTER TABLE "public"."events"
+  ADD COLUMN IF NOT EXISTS "parent_event_id" bigint REFERENCES "public"."events"("id") ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS "idx_events_parent_event_id" ON "public"."events"
+  USING btree ("parent_event_id");
+
+COMMENT ON COLUMN "public"."events"."parent_event_id" IS 'Event this event follows up on, if any';
+
+-- Step 2: Accept event model version 3
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4),
+    'events', jsonb_build_array(1, 2, 3),
+    'tags', jsonb_build_array(1, 2)
+  );
+$$;
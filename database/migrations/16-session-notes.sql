@@ -0,0 +1,63 @@
+-- Migration: Post-hoc session notes
+-- Reviewers attach notes to sessions after the fact, e.g. explaining a gap in coverage or
+-- flagging a session for re-annotation. Notes are written by devices and herd editors.
+
+-- Step 1: Create notes table
+CREATE TABLE IF NOT EXISTS "public"."session_notes" (
+  "id"         BIGSERIAL PRIMARY KEY,
+  "session_id" BIGINT NOT NULL,
+  "author"     TEXT NOT NULL,
+  "text"       TEXT NOT NULL,
+  "timestamp"  TIMESTAMPTZ NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+);
+
+ALTER TABLE "public"."session_notes" OWNER TO "postgres";
+
+COMMENT ON TABLE "public"."session_notes" IS 'Notes attached to sessions after the fact. author is the operator or reviewer who wrote the note.';
+
+ALTER TABLE ONLY "public"."session_notes"
+  ADD CONSTRAINT "session_notes_session_id_fkey" FOREIGN KEY ("session_id")
+  REFERENCES "public"."sessions"("id") ON UPDATE CASCADE ON DELETE CASCADE;
+
+CREATE INDEX "idx_session_notes_session_id" ON "public"."session_notes"
+  USING btree ("session_id", "timestamp");
+
+-- Step 2: Enable RLS
+ALTER TABLE "public"."session_notes" ENABLE ROW LEVEL SECURITY;
+
+-- Step 3: Policies (same pattern as operators, resolved through the session)
+CREATE POLICY "Session note access: Device API keys and users with view role"
+  ON "public"."session_notes" FOR SELECT USING (
+  ("session_id" IN (SELECT "s"."id" FROM "public"."sessions" "s" WHERE "s"."device_id" = "private"."key_uid"()))
+  OR
+  "private"."has_good_view_role"((SELECT "auth"."uid"() AS "uid"), "private"."get_herd_id_by_session_id"("session_id"))
+);
+
+CREATE POLICY "Session note creation: Device API keys and users with edit role"
+  ON "public"."session_notes" FOR INSERT WITH CHECK (
+  ("session_id" IN (SELECT "s"."id" FROM "public"."sessions" "s" WHERE "s"."device_id" = "private"."key_uid"()))
+  OR
+  "private"."has_good_edit_role"((SELECT "auth"."uid"() AS "uid"), "private"."get_herd_id_by_session_id"("session_id"))
+);
+
+CREATE POLICY "Session note modification: Device API keys and users with edit role"
+  ON "public"."session_notes" FOR UPDATE USING (
+  ("session_id" IN (SELECT "s"."id" FROM "public"."sessions" "s" WHERE "s"."device_id" = "private"."key_uid"()))
+  OR
+  "private"."has_good_edit_role"((SELECT "auth"."uid"() AS "uid"), "private"."get_herd_id_by_session_id"("session_id"))
+);
+
+CREATE POLICY "Session note deletion: Device API keys and users with edit role"
+  ON "public"."session_notes" FOR DELETE USING (
+  ("session_id" IN (SELECT "s"."id" FROM "public"."sessions" "s" WHERE "s"."device_id" = "private"."key_uid"()))
+  OR
+  "private"."has_good_edit_role"((SELECT "auth"."uid"() AS "uid"), "private"."get_herd_id_by_session_id"("session_id"))
+);
+
+-- Step 4: Batch idempotency (see 09-batch-idempotency-keys.sql)
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."session_notes"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."session_notes"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
@@ -0,0 +1,119 @@
+-- Migration: Daily connectivity summaries
+-- Raw connectivity rows are only useful in detail for recent days. Devices periodically ask the
+-- server to roll up their rows older than N days into one summary per device and day, delete
+-- the raw rows, and then delete their local copies of the same rows.
+
+-- Step 1: Create summaries table
+CREATE TABLE IF NOT EXISTS "public"."connectivity_daily_summaries" (
+  "id"                     BIGSERIAL PRIMARY KEY,
+  "device_id"              BIGINT NOT NULL,
+  "day"                    DATE NOT NULL,
+  "sample_count"           BIGINT NOT NULL,
+  "signal_avg"             DOUBLE PRECISION NOT NULL,
+  "signal_min"             DOUBLE PRECISION NOT NULL,
+  "signal_max"             DOUBLE PRECISION NOT NULL,
+  "noise_avg"              DOUBLE PRECISION NOT NULL,
+  "battery_percentage_min" REAL,
+  "created_at"             TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+  CONSTRAINT "connectivity_daily_summaries_device_day_key" UNIQUE ("device_id", "day")
+);
+
+ALTER TABLE "public"."connectivity_daily_summaries" OWNER TO "postgres";
+
+COMMENT ON TABLE "public"."connectivity_daily_summaries" IS 'Per device and UTC day roll-up of connectivity rows removed by compact_connectivity.';
+
+ALTER TABLE ONLY "public"."connectivity_daily_summaries"
+  ADD CONSTRAINT "connectivity_daily_summaries_device_id_fkey" FOREIGN KEY ("device_id")
+  REFERENCES "public"."devices"("id") ON UPDATE CASCADE ON DELETE CASCADE;
+
+-- Step 2: Enable RLS
+ALTER TABLE "public"."connectivity_daily_summaries" ENABLE ROW LEVEL SECURITY;
+
+-- Step 3: Policies (device API keys write their own summaries, herd viewers read them)
+CREATE POLICY "Connectivity summary access: Device API keys and users with view role"
+  ON "public"."connectivity_daily_summaries" FOR SELECT USING (
+  ("device_id" = "private"."key_uid"())
+  OR
+  "private"."has_good_view_role"((SELECT "auth"."uid"() AS "uid"), "private"."get_herd_id_by_device_id"("device_id"))
+);
+
+CREATE POLICY "Connectivity summary creation: Device API keys"
+  ON "public"."connectivity_daily_summaries" FOR INSERT WITH CHECK (
+  "device_id" = "private"."key_uid"()
+);
+
+CREATE POLICY "Connectivity summary modification: Device API keys"
+  ON "public"."connectivity_daily_summaries" FOR UPDATE USING (
+  "device_id" = "private"."key_uid"()
+);
+
+-- Step 4: Roll up and delete a device's rows before the start of the day `older_than_days`
+-- days ago. Whole days only, so a day is never summarized twice from partial data; a day
+-- that already has a summary (e.g. from rows synced late) is merged into it.
+CREATE OR REPLACE FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer)
+RETURNS TABLE ("cutoff" timestamptz, "summarized_days" integer, "deleted_rows" bigint)
+LANGUAGE "plpgsql"
+SECURITY INVOKER
+SET "search_path" TO ''
+AS $$
+declare
+  v_cutoff timestamptz;
+  v_days integer;
+  v_deleted bigint;
+begin
+  if compact_connectivity.older_than_days < 1 then
+    raise exception 'older_than_days must be at least 1, got %', compact_connectivity.older_than_days;
+  end if;
+  v_cutoff := date_trunc('day', now() at time zone 'UTC') at time zone 'UTC'
+    - make_interval(days => compact_connectivity.older_than_days);
+
+  create temporary table compacted on commit drop as
+  select c.id, c.timestamp_start, c.signal, c.noise, c.battery_percentage
+  from public.connectivity c
+  where c.timestamp_start < v_cutoff
+    and (c.device_id = compact_connectivity.device_id
+      or c.session_id in (
+        select s.id from public.sessions s where s.device_id = compact_connectivity.device_id
+      ));
+
+  insert into public.connectivity_daily_summaries as d (
+    device_id, day, sample_count, signal_avg, signal_min, signal_max, noise_avg, battery_percentage_min
+  )
+  select
+    compact_connectivity.device_id,
+    (k.timestamp_start at time zone 'UTC')::date,
+    count(*),
+    avg(k.signal),
+    min(k.signal),
+    max(k.signal),
+    avg(k.noise),
+    min(k.battery_percentage)
+  from compacted k
+  group by (k.timestamp_start at time zone 'UTC')::date
+  on conflict ("device_id", "day") do update set
+    signal_avg = (d.signal_avg * d.sample_count + excluded.signal_avg * excluded.sample_count)
+      / (d.sample_count + excluded.sample_count),
+    noise_avg = (d.noise_avg * d.sample_count + excluded.noise_avg * excluded.sample_count)
+      / (d.sample_count + excluded.sample_count),
+    signal_min = least(d.signal_min, excluded.signal_min),
+    signal_max = greatest(d.signal_max, excluded.signal_max),
+    battery_percentage_min = least(d.battery_percentage_min, excluded.battery_percentage_min),
+    sample_count = d.sample_count + excluded.sample_count;
+  get diagnostics v_days = row_count;
+
+  -- Runs as the caller, so the connectivity deletion policy applies
+  delete from public.connectivity c using compacted k where c.id = k.id;
+  get diagnostics v_deleted = row_count;
+
+  return query select v_cutoff, v_days, v_deleted;
+end;
+$$;
+
+ALTER FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) IS 'Rolls up a device''s connectivity older than N whole days into connectivity_daily_summaries and deletes the raw rows. Returns the cutoff so the device can delete its local copies.';
+
+REVOKE ALL ON FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) FROM PUBLIC;
+GRANT EXECUTE ON FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) TO "anon";
+GRANT EXECUTE ON FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) TO "authenticated";
+GRANT EXECUTE ON FUNCTION "public"."compact_connectivity"("device_id" bigint, "older_than_days" integer) TO "service_role";
@@ -0,0 +1,28 @@
+-- Migration: Ordered operator actions
+-- Operator actions such as StartMission and EndMission are numbered per session on the
+-- device, so the command history of a session can be read back in the order it was given
+-- even when actions reach the server in separate batches.
+
+-- Step 1: Position of the action in its session (null for actions recorded before sequencing)
+ALTER TABLE "public"."operators"
+  ADD COLUMN IF NOT EXISTS "sequence" bigint;
+
+CREATE INDEX IF NOT EXISTS "idx_operators_session_sequence" ON "public"."operators"
+  USING btree ("session_id", "sequence");
+
+COMMENT ON COLUMN "public"."operators"."sequence" IS 'Position of the action among the actions of its session, starting at 1';
+
+-- Step 2: Accept operator model version 2
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4),
+    'events', jsonb_build_array(1, 2, 3),
+    'tags', jsonb_build_array(1, 2),
+    'operators', jsonb_build_array(1, 2)
+  );
+$$;
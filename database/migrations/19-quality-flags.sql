@@ -0,0 +1,37 @@
+-- Migration: Data quality flags
+-- Devices check samples at ingest (GPS HDOP too high, altitude spikes, timestamps going
+-- backwards) and send the problems found with events and connectivity, so analysts can
+-- filter low-quality samples. Rows from before the checks have no flags.
+
+-- Step 1: HDOP reported by the GPS receiver and the flags set at ingest
+ALTER TABLE "public"."events"
+  ADD COLUMN IF NOT EXISTS "hdop" double precision,
+  ADD COLUMN IF NOT EXISTS "quality_flags" text[];
+
+ALTER TABLE "public"."connectivity"
+  ADD COLUMN IF NOT EXISTS "hdop" double precision,
+  ADD COLUMN IF NOT EXISTS "quality_flags" text[];
+
+CREATE INDEX IF NOT EXISTS "idx_events_quality_flags" ON "public"."events"
+  USING gin ("quality_flags");
+
+CREATE INDEX IF NOT EXISTS "idx_connectivity_quality_flags" ON "public"."connectivity"
+  USING gin ("quality_flags");
+
+COMMENT ON COLUMN "public"."events"."quality_flags" IS 'Quality problems found at ingest: high_hdop, altitude_spike, timestamp_backwards';
+COMMENT ON COLUMN "public"."connectivity"."quality_flags" IS 'Quality problems found at ingest: high_hdop, altitude_spike, timestamp_backwards';
+
+-- Step 2: Accept connectivity model version 5 and event model version 4
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4, 5),
+    'events', jsonb_build_array(1, 2, 3, 4),
+    'tags', jsonb_build_array(1, 2),
+    'operators', jsonb_build_array(1, 2)
+  );
+$$;
@@ -0,0 +1,31 @@
+-- Migration: Tag media anchors
+-- Tags on video and audio events can point at the moment they were drawn on: a video
+-- frame number and/or an offset into the recording. Tags without them cover the whole
+-- media, as before.
+
+-- Step 1: Frame number and media offset of tags
+ALTER TABLE "public"."tags"
+  ADD COLUMN IF NOT EXISTS "frame_number" bigint,
+  ADD COLUMN IF NOT EXISTS "media_offset_ms" bigint;
+
+ALTER TABLE "public"."tags"
+  ADD CONSTRAINT "tags_frame_number_check" CHECK ("frame_number" >= 0),
+  ADD CONSTRAINT "tags_media_offset_ms_check" CHECK ("media_offset_ms" >= 0);
+
+COMMENT ON COLUMN "public"."tags"."frame_number" IS 'Video frame the tag was drawn on, counted from 0';
+COMMENT ON COLUMN "public"."tags"."media_offset_ms" IS 'Offset of the tag into the video or audio recording, in milliseconds';
+
+-- Step 2: Accept tag model version 3
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4, 5),
+    'events', jsonb_build_array(1, 2, 3, 4),
+    'tags', jsonb_build_array(1, 2, 3),
+    'operators', jsonb_build_array(1, 2)
+  );
+$$;
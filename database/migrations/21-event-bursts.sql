@@ -0,0 +1,28 @@
+-- Migration: Event bursts
+-- Trail cameras shoot bursts of 3-5 frames that are one observation. Events of a burst
+-- share a burst id, so they can be reviewed once. Events without one stand alone.
+
+-- Step 1: Burst id of events
+ALTER TABLE "public"."events"
+  ADD COLUMN IF NOT EXISTS "burst_id" text;
+
+CREATE INDEX IF NOT EXISTS "idx_events_burst_id" ON "public"."events"
+  USING btree ("device_id", "burst_id")
+  WHERE "burst_id" IS NOT NULL;
+
+COMMENT ON COLUMN "public"."events"."burst_id" IS 'Shared by the frames of a burst, assigned on the device';
+
+-- Step 2: Accept event model version 5
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4, 5),
+    'events', jsonb_build_array(1, 2, 3, 4, 5),
+    'tags', jsonb_build_array(1, 2, 3),
+    'operators', jsonb_build_array(1, 2)
+  );
+$$;
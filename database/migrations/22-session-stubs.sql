@@ -0,0 +1,14 @@
+-- Migration: Session stubs
+-- Devices can upload a session as soon as it starts, with only its device, start time and
+-- software version, and patch the statistics while it runs. Until the first patch, the
+-- statistics of a stub are zero.
+
+ALTER TABLE "public"."sessions"
+  ALTER COLUMN "altitude_max" SET DEFAULT 0,
+  ALTER COLUMN "altitude_min" SET DEFAULT 0,
+  ALTER COLUMN "altitude_average" SET DEFAULT 0,
+  ALTER COLUMN "velocity_max" SET DEFAULT 0,
+  ALTER COLUMN "velocity_min" SET DEFAULT 0,
+  ALTER COLUMN "velocity_average" SET DEFAULT 0,
+  ALTER COLUMN "distance_total" SET DEFAULT 0,
+  ALTER COLUMN "distance_max_from_start" SET DEFAULT 0;
@@ -0,0 +1,25 @@
+-- Migration: Session visibility
+-- Some sessions are public outreach material, others stay herd-private. Sessions get the
+-- is_public flag events already have; devices set it on a session and its events together.
+
+-- Step 1: Visibility of sessions
+ALTER TABLE "public"."sessions"
+  ADD COLUMN IF NOT EXISTS "is_public" boolean DEFAULT false NOT NULL;
+
+COMMENT ON COLUMN "public"."sessions"."is_public" IS 'Public outreach material rather than herd-private';
+
+-- Step 2: Accept session model version 2
+CREATE OR REPLACE FUNCTION "public"."get_supported_model_versions"()
+RETURNS jsonb
+LANGUAGE "sql"
+STABLE
+SET "search_path" TO ''
+AS $$
+  select jsonb_build_object(
+    'connectivity', jsonb_build_array(1, 2, 3, 4, 5),
+    'events', jsonb_build_array(1, 2, 3, 4, 5),
+    'tags', jsonb_build_array(1, 2, 3),
+    'operators', jsonb_build_array(1, 2),
+    'sessions', jsonb_build_array(1, 2)
+  );
+$$;
@@ -0,0 +1,85 @@
+-- Migration: Device API key rotation
+-- Security policy requires devices to rotate their API keys periodically. A device requests a
+-- new key with its current one; both stay valid until the device, having stored and verified
+-- the new key, revokes the old one (or the new one, to roll back).
+
+-- Step 1: Issue a new key for the device owning the given key
+CREATE OR REPLACE FUNCTION "public"."rotate_device_api_key"("device_api_key" "text") RETURNS "text"
+    LANGUAGE "plpgsql" SECURITY DEFINER
+    SET "search_path" TO ''
+    AS $$
+declare
+  id_of_device bigint := private.get_device_id_by_api_key(rotate_device_api_key.device_api_key);
+  device_api_key_secret text;
+  project_api_key_secret text;
+  project_jwt_secret text;
+  time_stamp bigint := trunc(extract(epoch from now()), 0);
+  jwt text;
+  api_key text;
+begin
+  if id_of_device is null then
+    raise exception 'Invalid device API key';
+  end if;
+
+  select decrypted_secret into device_api_key_secret from vault.decrypted_secrets where name = id_of_device::text;
+  select decrypted_secret into project_api_key_secret from vault.decrypted_secrets where name = 'project_api_key_secret';
+  select decrypted_secret into project_jwt_secret from vault.decrypted_secrets where name = 'project_jwt_secret';
+  jwt := extensions.sign(jsonb_build_object(
+    'role', 'authenticated',
+    'aud', 'authenticated',
+    'iss', 'supabase',
+    'iat', time_stamp,
+    'exp', time_stamp + trunc(extract(epoch from interval '100 years'), 0),
+    'jti', gen_random_uuid())::json, project_jwt_secret);
+  api_key := encode(extensions.hmac(jwt, device_api_key_secret, 'sha512'), 'hex');
+
+  insert into private.jwts (secret_id, device_id)
+  values (vault.create_secret(jwt, encode(extensions.hmac(api_key, project_api_key_secret, 'sha512'), 'hex')), id_of_device);
+  return api_key;
+end;
+$$;
+
+ALTER FUNCTION "public"."rotate_device_api_key"("device_api_key" "text") OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."rotate_device_api_key"("device_api_key" "text") IS 'Issues a new API key for the device owning the given key; the given key stays valid until revoked';
+
+-- Step 2: Revoke another key of the same device
+CREATE OR REPLACE FUNCTION "public"."revoke_device_api_key"("device_api_key" "text", "revoked_api_key" "text") RETURNS "void"
+    LANGUAGE "plpgsql" SECURITY DEFINER
+    SET "search_path" TO ''
+    AS $$
+declare
+  id_of_device bigint := private.get_device_id_by_api_key(revoke_device_api_key.device_api_key);
+  project_api_key_secret text;
+  revoked_secret_id uuid;
+begin
+  if id_of_device is null then
+    raise exception 'Invalid device API key';
+  end if;
+  if revoke_device_api_key.device_api_key = revoke_device_api_key.revoked_api_key then
+    raise exception 'A device cannot revoke the key it authenticates with';
+  end if;
+
+  select decrypted_secret into project_api_key_secret from vault.decrypted_secrets where name = 'project_api_key_secret';
+  select s.id into revoked_secret_id
+  from vault.secrets s
+  join private.jwts j on j.secret_id = s.id
+  where s.name = encode(extensions.hmac(revoke_device_api_key.revoked_api_key, project_api_key_secret, 'sha512'), 'hex')
+    and j.device_id = id_of_device;
+
+  if revoked_secret_id is null then
+    raise exception 'Revoked key is not a key of this device';
+  end if;
+  delete from private.jwts where secret_id = revoked_secret_id;
+  delete from vault.secrets where id = revoked_secret_id;
+end;
+$$;
+
+ALTER FUNCTION "public"."revoke_device_api_key"("device_api_key" "text", "revoked_api_key" "text") OWNER TO "postgres";
+
+COMMENT ON FUNCTION "public"."revoke_device_api_key"("device_api_key" "text", "revoked_api_key" "text") IS 'Revokes another API key of the device owning the given key';
+
+GRANT ALL ON FUNCTION "public"."rotate_device_api_key"("device_api_key" "text") TO "anon";
+GRANT ALL ON FUNCTION "public"."rotate_device_api_key"("device_api_key" "text") TO "authenticated";
+GRANT ALL ON FUNCTION "public"."revoke_device_api_key"("device_api_key" "text", "revoked_api_key" "text") TO "anon";
+GRANT ALL ON FUNCTION "public"."revoke_device_api_key"("device_api_key" "text", "revoked_api_key" "text") TO "authenticated";
@@ -0,0 +1,22 @@
+-- Migration: Heartbeat metadata
+-- Devices send heartbeats on a schedule, queued locally while offline. Each heartbeat now
+-- carries what the device knew at the time, so fleet views can tell a flat battery or a lost
+-- link from a device that stopped.
+
+ALTER TABLE "public"."heartbeats"
+  ADD COLUMN IF NOT EXISTS "battery_percentage" real,
+  ADD COLUMN IF NOT EXISTS "uptime_seconds" bigint,
+  ADD COLUMN IF NOT EXISTS "signal" double precision,
+  ADD COLUMN IF NOT EXISTS "connectivity_mode" text;
+
+COMMENT ON COLUMN "public"."heartbeats"."battery_percentage" IS 'Battery level from 0 to 100';
+COMMENT ON COLUMN "public"."heartbeats"."uptime_seconds" IS 'Seconds since the device process started';
+COMMENT ON COLUMN "public"."heartbeats"."signal" IS 'Signal in dBm of the latest connectivity reading';
+COMMENT ON COLUMN "public"."heartbeats"."connectivity_mode" IS 'Link mode of the latest connectivity reading, e.g. lora or wifi';
+
+-- Batch idempotency (see 09-batch-idempotency-keys.sql), heartbeat batches carry a key too
+CREATE TRIGGER "skip_replayed_batch" BEFORE INSERT ON "public"."heartbeats"
+  FOR EACH ROW EXECUTE FUNCTION "private"."skip_replayed_batch"();
+CREATE TRIGGER "record_batch_idempotency_key" AFTER INSERT ON "public"."heartbeats"
+  REFERENCING NEW TABLE AS "new_rows"
+  FOR EACH STATEMENT EXECUTE FUNCTION "private"."record_batch_idempotency_key"();
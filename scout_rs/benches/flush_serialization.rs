@@ -0,0 +1,103 @@
+//! Compares serializing a flush batch by converting local records to API structs first
+//! with serializing them in place through `AsRemote`. Reports time, bytes allocated and
+//! peak heap growth for each path.
+
+use scout_rs::models::{AsRemote, Connectivity, ConnectivityLocal, Event, EventLocal};
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const BATCH_SIZE: usize = 1000;
+
+/// Counts heap usage so each path's allocations can be measured
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Runs `serialize` and prints its time, total allocation and peak heap growth
+fn measure(name: &str, serialize: impl FnOnce() -> String) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    ALLOCATED.store(0, Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let start = Instant::now();
+    let body = serialize();
+    let elapsed = start.elapsed();
+    println!(
+        "{:<40} {:>10.2?} {:>10} KiB allocated {:>10} KiB peak ({} KiB body)",
+        name,
+        elapsed,
+        ALLOCATED.load(Ordering::Relaxed) / 1024,
+        (PEAK.load(Ordering::Relaxed) - baseline) / 1024,
+        body.len() / 1024
+    );
+}
+
+fn compare<L, R>(table: &str, locals: &[L])
+where
+    L: scout_rs::models::RemoteFields + Clone + Into<R>,
+    R: Serialize,
+{
+    measure(&format!("{} via API structs", table), || {
+        let rows: Vec<R> = locals.iter().map(|local| local.clone().into()).collect();
+        serde_json::to_string(&rows).unwrap()
+    });
+    measure(&format!("{} via AsRemote", table), || {
+        let rows: Vec<AsRemote<L>> = locals.iter().map(AsRemote).collect();
+        serde_json::to_string(&rows).unwrap()
+    });
+}
+
+fn main() {
+    let events: Vec<EventLocal> = (0..BATCH_SIZE)
+        .map(|i| EventLocal {
+            id_local: Some(format!("event_{}", i)),
+            message: Some("Elephant near the water hole".to_string()),
+            location: Some("POINT(-155.15393 19.754824)".to_string()),
+            device_id: 1,
+            timestamp_observation: "2026-01-01T00:00:00Z".to_string(),
+            ancestor_id_local: Some("session_1".to_string()),
+            embedding_qwen_vl_2b: Some(vec![0.25; 2000]),
+            embedding_vertex_mm_01: Some(vec![0.5; 1408]),
+            ..Default::default()
+        })
+        .collect();
+    let connectivity: Vec<ConnectivityLocal> = (0..BATCH_SIZE)
+        .map(|i| {
+            let mut connectivity = ConnectivityLocal {
+                id_local: Some(format!("connectivity_{}", i)),
+                session_id: Some(1),
+                timestamp_start: "2026-01-01T00:00:00Z".to_string(),
+                location: Some("POINT(-155.15393 19.754824)".to_string()),
+                ancestor_id_local: Some("session_1".to_string()),
+                ..Default::default()
+            };
+            connectivity
+                .set_h3_indexes("8e2800000000007".parse().unwrap())
+                .unwrap();
+            connectivity
+        })
+        .collect();
+
+    compare::<_, Event>("events", &events);
+    compare::<_, Connectivity>("connectivity", &connectivity);
+}
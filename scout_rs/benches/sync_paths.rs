@@ -0,0 +1,106 @@
+//! Sync paths over synthetic data (`synthetic` feature): splitting 100k stored rows into a
+//! flush batch, linking descendants to new session IDs, serializing flush bodies and
+//! cleaning synced sessions. Run before and after performance-sensitive changes, e.g. new
+//! indexes, and compare with criterion's report:
+//!
+//! `cargo bench --features synthetic --bench sync_paths`
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use scout_rs::models::{ConnectivityLocal, EventLocal, SessionLocal};
+use scout_rs::sync::SyncEngine;
+use scout_rs::synthetic::{self, SyntheticData};
+
+/// A fresh engine holding the records of `shape`
+fn loaded_engine(shape: &SyntheticData) -> SyncEngine {
+    let mut sync_engine = synthetic::in_memory_engine().unwrap();
+    shape.generate().load_into(&mut sync_engine).unwrap();
+    sync_engine
+}
+
+fn plan_flush_batch(c: &mut Criterion) {
+    let sync_engine = loaded_engine(&SyntheticData {
+        sessions: 100,
+        connectivity_per_session: 0,
+        events_per_session: 1000,
+        tags_per_event: 0,
+        ..Default::default()
+    });
+    let mut group = c.benchmark_group("plan_flush_batch");
+    group.sample_size(10);
+    group.bench_function("events_100k", |b| {
+        b.iter(|| sync_engine.plan_flush_batch::<EventLocal>().unwrap())
+    });
+    group.finish();
+}
+
+fn link_session_descendants(c: &mut Criterion) {
+    let shape = SyntheticData::default();
+    let remote_ids: Vec<(String, i64)> = shape
+        .generate()
+        .sessions
+        .iter()
+        .zip(1..)
+        .map(|(session, id)| (session.id_local.clone().unwrap(), id))
+        .collect();
+    let mut group = c.benchmark_group("link_session_descendants");
+    group.sample_size(10);
+    group.bench_function("10_sessions_4k_descendants", |b| {
+        b.iter_batched(
+            || loaded_engine(&shape),
+            |mut sync_engine| sync_engine.link_session_descendants(&remote_ids).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn serialize_for_sync(c: &mut Criterion) {
+    let sync_engine = synthetic::in_memory_engine().unwrap();
+    let dataset = SyntheticData::default().generate();
+    let mut group = c.benchmark_group("serialize_for_sync");
+    group.bench_function("events_1k", |b| {
+        b.iter(|| {
+            sync_engine
+                .serialize_for_sync::<EventLocal>("events", &dataset.events)
+                .unwrap()
+        })
+    });
+    group.bench_function("connectivity_1k", |b| {
+        b.iter(|| {
+            sync_engine
+                .serialize_for_sync::<ConnectivityLocal>("connectivity", &dataset.connectivity)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn clean(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let shape = SyntheticData {
+        synced: true,
+        ..Default::default()
+    };
+    let mut group = c.benchmark_group("clean");
+    group.sample_size(10);
+    group.bench_function("10_synced_sessions", |b| {
+        b.iter_batched(
+            || loaded_engine(&shape),
+            |mut sync_engine| {
+                runtime.block_on(sync_engine.clean()).unwrap();
+                assert_eq!(sync_engine.get_table_count::<SessionLocal>().unwrap(), 0);
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    plan_flush_batch,
+    link_session_descendants,
+    serialize_for_sync,
+    clean
+);
+criterion_main!(benches);
@@ -64,13 +64,24 @@ struct Args {
     #[arg(long, name = "output_dir")]
     output_dir: Option<String>,
 
-    /// Database path for sync engine operations (export_sync_engine, wipe_sync_engine)
+    /// Database path for sync engine operations (export_sync_engine, wipe_sync_engine,
+    /// repair_sync_engine)
     #[arg(long, name = "db_path")]
     db_path: Option<String>,
 
-    /// Output path for export_sync_engine command
+    /// Output path for export_sync_engine and generate_models commands
     #[arg(long, name = "output_path")]
     output_path: Option<String>,
+
+    /// Table to generate a model for (generate_models command, requires the codegen feature)
+    #[arg(long, name = "table")]
+    table: Option<String>,
+
+    /// native_model id and version of the generated model (generate_models command)
+    #[arg(long, name = "model_id")]
+    model_id: Option<u32>,
+    #[arg(long, name = "model_version", default_value_t = 1)]
+    model_version: u32,
 }
 
 // example usage:
@@ -86,6 +97,8 @@ struct Args {
 // SCOUT_DEVICE_API_KEY=1234567890 ./target/release/scout_cli --command post_event --event_json '{"message": "Test event", "media_url": "https://example.com/image.jpg", "file_path": "path/to/image.jpg", "location": "POINT(0,0)", "altitude": 20.3, "heading": 90.0, "media_type": "image", "device_id": "123", "earthranger_url": null, "timestamp_observation": "2024-01-01T00:00:00Z", "is_public": true, "session_id": null}' --tags_json '[{"x": 0.5, "y": 0.5, "width": 0.2, "height": 0.2, "conf": 0.9, "observation_type": "manual", "class_name": "animal", "event_id": 0}]' --file_path 'path/to/image.jpg' --tag_latitude 40.7128 --tag_longitude -74.0060
 // SCOUT_DEVICE_API_KEY=1234567890 ./target/release/scout_cli --command update_event --event_id 123 --event_json '{"message": "Updated event", "media_url": "https://example.com/updated.jpg", "file_path": "path/to/image.jpg", "location": "POINT(0,0)", "altitude": 25.0, "heading": 180.0, "media_type": "image", "device_id": "123", "earthranger_url": null, "timestamp_observation": "2024-01-01T00:00:00Z", "is_public": false, "session_id": null, "id": 123}'
 // SCOUT_DEVICE_API_KEY=1234567890 ./target/release/scout_cli --command delete_event --event_id 123
+// SCOUT_DEVICE_API_KEY=1234567890 ./target/release/scout_cli --command repair_sync_engine --db_path scout.db
+// SCOUT_DEVICE_API_KEY=1234567890 cargo run --features codegen --bin scout_cli -- --command generate_models --table heartbeats --model_id 30
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -448,10 +461,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             sync_engine.wipe(None)?;
             println!("Successfully wiped all data from sync engine");
         }
+        "repair_sync_engine" => {
+            let db_path = args
+                .db_path
+                .expect("db_path required for repair_sync_engine");
+
+            let config_db = DatabaseConfig::from_env()?;
+            let scout_client = ScoutClient::new(config_db);
+            let mut sync_engine = SyncEngine::with_defaults(scout_client, db_path)?;
+
+            let mut stranded = 0;
+            for (table, versions) in sync_engine.model_versions()? {
+                for (version, rows) in &versions {
+                    println!("{} v{}: {} rows", table, version, rows);
+                }
+                stranded += versions
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .map(|(_, rows)| rows)
+                    .sum::<u64>();
+            }
+            if stranded == 0 {
+                println!("All rows are in the latest model versions");
+                return Ok(());
+            }
+
+            print!("Migrate {} rows into the latest version? [y/N] ", stranded);
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!("Nothing migrated");
+                return Ok(());
+            }
+
+            let report = sync_engine.repair_models(1000, |progress| {
+                println!("Migrated {}/{} rows", progress.done, progress.total);
+            })?;
+            println!(
+                "Repair complete: {} migrated, {} superseded by newer copies",
+                report.migrated, report.superseded
+            );
+        }
+        #[cfg(feature = "codegen")]
+        "generate_models" => {
+            use scout_rs::codegen;
+
+            let config_db = DatabaseConfig::from_env()?;
+            let tables = codegen::table_schemas(&codegen::fetch_openapi(&config_db).await?)?;
+
+            let mut drifted = 0;
+            for diff in codegen::diff_known_models(&tables) {
+                if diff.is_empty() {
+                    continue;
+                }
+                drifted += 1;
+                eprintln!("{}:", diff.table);
+                for column in &diff.missing_in_model {
+                    eprintln!("  + {} (column not in model)", column);
+                }
+                for field in &diff.missing_in_schema {
+                    eprintln!("  - {} (field not in schema)", field);
+                }
+            }
+            if drifted == 0 {
+                eprintln!("All models match the schema");
+            }
+
+            if let Some(table_name) = args.table {
+                let table = tables
+                    .iter()
+                    .find(|table| table.name == table_name)
+                    .ok_or_else(|| format!("Table {} not found in schema", table_name))?;
+                let model_id = args
+                    .model_id
+                    .expect("model_id required to generate a model");
+                let code = codegen::generate_model(table, model_id, args.model_version);
+                match args.output_path {
+                    Some(output_path) => {
+                        std::fs::write(&output_path, code)?;
+                        println!("Wrote {} model to {}", table_name, output_path);
+                    }
+                    None => print!("{}", code),
+                }
+            }
+        }
         _ => {
             eprintln!("Unknown command: {}", args.command);
             eprintln!(
-                "Available commands: get_device, get_herd, get_plans_by_herd, get_plan_by_id, create_plan, update_plan, delete_plan, post_event, update_event, delete_event, download_artifacts, export_sync_engine, wipe_sync_engine"
+                "Available commands: get_device, get_herd, get_plans_by_herd, get_plan_by_id, create_plan, update_plan, delete_plan, post_event, update_event, delete_event, download_artifacts, export_sync_engine, wipe_sync_engine, repair_sync_engine, generate_models (codegen feature)"
             );
             std::process::exit(1);
         }
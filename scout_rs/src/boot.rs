@@ -0,0 +1,192 @@
+//! Boot and crash tracking. A marker file records when the running process started and is
+//! refreshed by heartbeats; a marker left without a clean shutdown at the next start means
+//! the previous run crashed or lost power. Each start is recorded as a text event so fleet
+//! health issues surface in Scout once it syncs.
+//!
+//! ```no_run
+//! # fn run(mut sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::boot::BootTracker;
+//!
+//! let (tracker, boot) = BootTracker::start("/var/lib/scout/boot.json")?;
+//! boot.record(&mut sync_engine, 42)?;
+//! // ... call tracker.heartbeat() periodically while running
+//! tracker.shutdown()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::{EventLocal, MediaType};
+use crate::sync::SyncEngine;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Why the device booted, judged from the marker the previous run left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootReason {
+    /// No marker: first run with tracking enabled
+    FirstBoot,
+    /// The previous run shut down cleanly
+    CleanRestart,
+    /// The previous run ended without a clean shutdown (crash, power loss, kill)
+    UncleanShutdown,
+}
+
+/// A start of the process, with what is known about the previous run
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootEvent {
+    pub reason: BootReason,
+    pub booted_at: DateTime<Utc>,
+    /// Uptime of the previous run, up to its shutdown or, after a crash, its last heartbeat
+    pub previous_uptime: Option<Duration>,
+}
+
+impl BootEvent {
+    /// The boot as a text event of `device_id`, not attached to a session
+    pub fn to_event_local(&self, device_id: i64) -> EventLocal {
+        let reason = match self.reason {
+            BootReason::FirstBoot => "first boot",
+            BootReason::CleanRestart => "clean restart",
+            BootReason::UncleanShutdown => "unclean shutdown",
+        };
+        let message = match self.previous_uptime {
+            Some(uptime) => format!(
+                "Boot after {} (previous uptime {}s)",
+                reason,
+                uptime.as_secs()
+            ),
+            None => format!("Boot after {}", reason),
+        };
+        EventLocal {
+            message: Some(message),
+            media_type: MediaType::Text,
+            device_id,
+            timestamp_observation: self.booted_at.to_rfc3339(),
+            ..Default::default()
+        }
+    }
+
+    /// Stores the boot event locally; it reaches the server on the next flush
+    pub fn record(&self, sync_engine: &mut SyncEngine, device_id: i64) -> Result<EventLocal> {
+        let mut event = self.to_event_local(device_id);
+        event.id_local = Some(sync_engine.generate_unique_id::<EventLocal>()?.to_string());
+        sync_engine.upsert_items(vec![event.clone()])?;
+        Ok(event)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BootMarker {
+    started_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    clean_shutdown: bool,
+}
+
+/// Marker of the running process; see the module docs
+#[derive(Debug)]
+pub struct BootTracker {
+    path: PathBuf,
+    started_at: DateTime<Utc>,
+}
+
+impl BootTracker {
+    /// Reads the marker left by the previous run and replaces it with one for this run
+    pub fn start(path: impl Into<PathBuf>) -> Result<(Self, BootEvent)> {
+        let path = path.into();
+        let previous = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<BootMarker>(&bytes).ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let booted_at = Utc::now();
+        let boot = BootEvent {
+            reason: match &previous {
+                None => BootReason::FirstBoot,
+                Some(marker) if marker.clean_shutdown => BootReason::CleanRestart,
+                Some(_) => BootReason::UncleanShutdown,
+            },
+            booted_at,
+            previous_uptime: previous
+                .and_then(|marker| (marker.last_seen_at - marker.started_at).to_std().ok()),
+        };
+
+        let tracker = Self {
+            path,
+            started_at: booted_at,
+        };
+        tracker.write(false)?;
+        Ok((tracker, boot))
+    }
+
+    /// Records that the process is still running, bounding the uptime reported after a crash
+    pub fn heartbeat(&self) -> Result<()> {
+        self.write(false)
+    }
+
+    /// Marks the run as cleanly shut down
+    pub fn shutdown(self) -> Result<()> {
+        self.write(true)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Replaces the marker atomically so a crash mid-write never leaves it unreadable
+    fn write(&self, clean_shutdown: bool) -> Result<()> {
+        let marker = BootMarker {
+            started_at: self.started_at,
+            last_seen_at: Utc::now(),
+            clean_shutdown,
+        };
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&marker)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::offline_test_engine;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_boot_tracker_detects_unclean_shutdown() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let marker_path = temp_dir.path().join("boot.json");
+
+        let (tracker, boot) = BootTracker::start(&marker_path)?;
+        assert_eq!(boot.reason, BootReason::FirstBoot);
+        assert_eq!(boot.previous_uptime, None);
+        tracker.heartbeat()?;
+
+        // Dropped without a shutdown, as after a crash
+        drop(tracker);
+        let (tracker, boot) = BootTracker::start(&marker_path)?;
+        assert_eq!(boot.reason, BootReason::UncleanShutdown);
+        assert!(boot.previous_uptime.is_some());
+
+        tracker.shutdown()?;
+        let (_tracker, boot) = BootTracker::start(&marker_path)?;
+        assert_eq!(boot.reason, BootReason::CleanRestart);
+
+        let mut sync_engine = offline_test_engine()?;
+        let event = boot.record(&mut sync_engine, 7)?;
+        assert_eq!(event.media_type, MediaType::Text);
+        assert_eq!(event.device_id, 7);
+        assert!(event
+            .message
+            .as_deref()
+            .is_some_and(|message| message.starts_with("Boot after clean restart")));
+        assert_eq!(sync_engine.get_table_count::<EventLocal>()?, 1);
+        Ok(())
+    }
+}
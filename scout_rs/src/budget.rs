@@ -0,0 +1,118 @@
+//! Monthly sync budget. Usage is recorded per calendar month (UTC) in the local store as
+//! payloads are sent; once a cap is reached, flushes sync critical data only until the
+//! next month.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::budget::SyncBudget;
+//!
+//! let sync_engine = sync_engine.with_budget(SyncBudget {
+//!     max_rows_per_month: Some(500_000),
+//!     max_bytes_per_month: None,
+//! });
+//! let remaining = sync_engine.get_remaining_budget()?;
+//! println!("{:?} rows left in {}", remaining.rows_remaining, remaining.month);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::SyncBudgetLocal;
+use crate::store::{LocalStore, Store};
+use anyhow::Result;
+
+/// Monthly cap on data sent to the remote database (e.g. Supabase free-tier row limits).
+/// Once either cap is reached, `flush` switches to critical-only mode and skips
+/// connectivity and operator sync until the next month.
+#[derive(Debug, Clone, Default)]
+pub struct SyncBudget {
+    pub max_rows_per_month: Option<u64>,
+    pub max_bytes_per_month: Option<u64>,
+}
+
+/// Usage and remaining allowance for the current month (None = uncapped)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncBudgetRemaining {
+    pub month: String,
+    pub rows_sent: u64,
+    pub bytes_sent: u64,
+    pub rows_remaining: Option<u64>,
+    pub bytes_remaining: Option<u64>,
+}
+
+impl SyncBudgetRemaining {
+    pub fn is_exhausted(&self) -> bool {
+        self.rows_remaining == Some(0) || self.bytes_remaining == Some(0)
+    }
+}
+
+impl SyncBudget {
+    /// Computes the remaining allowance given the usage recorded for a month
+    pub fn remaining(&self, usage: &SyncBudgetLocal) -> SyncBudgetRemaining {
+        SyncBudgetRemaining {
+            month: usage.month.clone(),
+            rows_sent: usage.rows_sent,
+            bytes_sent: usage.bytes_sent,
+            rows_remaining: self
+                .max_rows_per_month
+                .map(|max| max.saturating_sub(usage.rows_sent)),
+            bytes_remaining: self
+                .max_bytes_per_month
+                .map(|max| max.saturating_sub(usage.bytes_sent)),
+        }
+    }
+
+    /// Adds a sent payload to `usage`; the remaining allowance if the payload used it up
+    pub(crate) fn add_usage(
+        &self,
+        usage: &mut SyncBudgetLocal,
+        rows: u64,
+        bytes: u64,
+    ) -> Option<SyncBudgetRemaining> {
+        let was_exhausted = self.remaining(usage).is_exhausted();
+        usage.rows_sent += rows;
+        usage.bytes_sent += bytes;
+        let remaining = self.remaining(usage);
+        (remaining.is_exhausted() && !was_exhausted).then_some(remaining)
+    }
+}
+
+/// Usage recorded for the current month, empty if nothing was sent yet
+pub(crate) fn current_usage(store: &Store) -> Result<SyncBudgetLocal> {
+    let month = chrono::Utc::now().format("%Y-%m").to_string();
+    Ok(store
+        .get::<SyncBudgetLocal>(&month)?
+        .unwrap_or_else(|| SyncBudgetLocal::new(month)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_budget_remaining() {
+        let mut usage = SyncBudgetLocal::new("2024-01".to_string());
+        usage.rows_sent = 90;
+        usage.bytes_sent = 4_000;
+
+        let budget = SyncBudget {
+            max_rows_per_month: Some(100),
+            max_bytes_per_month: None,
+        };
+        let remaining = budget.remaining(&usage);
+        assert_eq!(remaining.rows_remaining, Some(10));
+        assert_eq!(remaining.bytes_remaining, None);
+        assert!(!remaining.is_exhausted());
+
+        let used_up = budget.add_usage(&mut usage, 60, 1_000);
+        let remaining = budget.remaining(&usage);
+        assert_eq!(used_up, Some(remaining.clone()));
+        assert_eq!((remaining.rows_sent, remaining.bytes_sent), (150, 5_000));
+        assert_eq!(remaining.rows_remaining, Some(0));
+        assert!(remaining.is_exhausted());
+        // Only the payload that used it up reports it
+        assert_eq!(budget.add_usage(&mut usage, 1, 10), None);
+
+        // No caps configured never exhausts
+        assert!(!SyncBudget::default().remaining(&usage).is_exhausted());
+    }
+}
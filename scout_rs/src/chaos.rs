@@ -0,0 +1,170 @@
+//! Failure injection for testing sync recovery (`chaos` feature). Failures are
+//! deterministic: every Nth request is dropped and every Nth response corrupted, counting
+//! from the injector's creation, so a test sees the same failures on every run.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine) {
+//! use scout_rs::chaos::FailureInjector;
+//! use std::time::Duration;
+//!
+//! let injector = FailureInjector::new()
+//!     .with_dropped_requests(3)
+//!     .with_corrupted_responses(5)
+//!     .with_transaction_delay(Duration::from_millis(200));
+//! let sync_engine = sync_engine.with_failure_injector(injector.clone());
+//! // ... flush, then check the failures the engine recovered from
+//! let stats = injector.stats();
+//! # }
+//! ```
+
+use anyhow::{Error, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Failures injected so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChaosStats {
+    pub requests: u64,
+    pub dropped_requests: u64,
+    pub responses: u64,
+    pub corrupted_responses: u64,
+    pub delayed_transactions: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    dropped_requests: AtomicU64,
+    responses: AtomicU64,
+    corrupted_responses: AtomicU64,
+    delayed_transactions: AtomicU64,
+}
+
+/// Injects failures into the requests of a [`crate::db_client::ScoutDbClient`]. Clones share
+/// their counters, so one injector can be handed to the engine and inspected by the test.
+#[derive(Debug, Clone, Default)]
+pub struct FailureInjector {
+    drop_every: Option<u64>,
+    corrupt_every: Option<u64>,
+    transaction_delay: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl FailureInjector {
+    /// Injector that lets every request through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails every `n`th request before it is sent (0 disables)
+    pub fn with_dropped_requests(mut self, n: u64) -> Self {
+        self.drop_every = (n > 0).then_some(n);
+        self
+    }
+
+    /// Truncates every `n`th response body so it no longer parses (0 disables)
+    pub fn with_corrupted_responses(mut self, n: u64) -> Self {
+        self.corrupt_every = (n > 0).then_some(n);
+        self
+    }
+
+    /// Waits `delay` before each write (insert, upsert, update, delete) is sent
+    pub fn with_transaction_delay(mut self, delay: Duration) -> Self {
+        self.transaction_delay = Some(delay).filter(|delay| !delay.is_zero());
+        self
+    }
+
+    pub fn stats(&self) -> ChaosStats {
+        let counters = &self.counters;
+        ChaosStats {
+            requests: counters.requests.load(Ordering::Relaxed),
+            dropped_requests: counters.dropped_requests.load(Ordering::Relaxed),
+            responses: counters.responses.load(Ordering::Relaxed),
+            corrupted_responses: counters.corrupted_responses.load(Ordering::Relaxed),
+            delayed_transactions: counters.delayed_transactions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counts a request, failing it if it is one to drop
+    pub(crate) fn request(&self, operation: &str) -> Result<()> {
+        let request = self.counters.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.drop_every.is_some_and(|n| request.is_multiple_of(n)) {
+            self.counters
+                .dropped_requests
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(Error::msg(format!(
+                "Injected failure: dropped {} request #{}",
+                operation, request
+            )));
+        }
+        Ok(())
+    }
+
+    /// Counts a response body, truncating it if it is one to corrupt
+    pub(crate) fn response(&self, body: String) -> String {
+        let response = self.counters.responses.fetch_add(1, Ordering::Relaxed) + 1;
+        if self
+            .corrupt_every
+            .is_none_or(|n| !response.is_multiple_of(n))
+        {
+            return body;
+        }
+        self.counters
+            .corrupted_responses
+            .fetch_add(1, Ordering::Relaxed);
+        let half = body.chars().count() / 2;
+        body.chars().take(half).chain(['\u{fffd}']).collect()
+    }
+
+    /// Waits the configured transaction delay, if any
+    pub(crate) async fn transaction(&self) {
+        if let Some(delay) = self.transaction_delay {
+            self.counters
+                .delayed_transactions
+                .fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_client::{offline_test_config, ScoutDbClient};
+
+    #[tokio::test]
+    async fn test_failures_are_injected_deterministically() -> Result<()> {
+        let injector = FailureInjector::new()
+            .with_dropped_requests(2)
+            .with_corrupted_responses(3)
+            .with_transaction_delay(Duration::from_millis(1));
+        let mut db_client = ScoutDbClient::new(offline_test_config());
+        db_client.set_failure_injector(injector.clone());
+
+        // Every second request never reaches the (unreachable) server
+        for request in 1..=4 {
+            let result = db_client.delete(|client| client.from("events").eq("id", "1"));
+            let error = result.await.unwrap_err().to_string();
+            assert_eq!(error.starts_with("Injected failure"), request % 2 == 0);
+        }
+
+        let corrupted: Vec<String> = (0..3)
+            .map(|_| injector.response(r#"[{"id":1}]"#.to_string()))
+            .collect();
+        assert!(serde_json::from_str::<serde_json::Value>(&corrupted[1]).is_ok());
+        assert!(serde_json::from_str::<serde_json::Value>(&corrupted[2]).is_err());
+
+        assert_eq!(
+            injector.stats(),
+            ChaosStats {
+                requests: 4,
+                dropped_requests: 2,
+                responses: 3,
+                corrupted_responses: 1,
+                delayed_transactions: 4,
+            }
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,489 @@
 use anyhow::{anyhow, Result};
-
-use crate::db_client::{DatabaseConfig, ScoutDbClient};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::db_client::{
+    DatabaseConfig, FailedPayload, HttpOptions, PostgrestError, ScoutDbClient,
+    DEFAULT_RESPONSE_CACHE_CAPACITY, STAGING_MARKED_COLUMNS,
+};
+use crate::logging;
 use crate::models::*;
+use crate::secrets::{SecretProvider, SCOUT_API_KEY};
+
+// ===== MODEL VERSION NEGOTIATION =====
+
+/// Latest connectivity model version (v5: HDOP and quality flags)
+pub const CONNECTIVITY_MODEL_VERSION: u32 = 5;
+/// Latest event model version (v5: burst grouping)
+pub const EVENT_MODEL_VERSION: u32 = 5;
+/// Latest tag model version (v3: video frame / media offset anchor)
+pub const TAG_MODEL_VERSION: u32 = 3;
+/// Latest operator model version (v2: per-session sequence)
+pub const OPERATOR_MODEL_VERSION: u32 = 2;
+/// Latest session model version (v2: visibility)
+pub const SESSION_MODEL_VERSION: u32 = 2;
+
+/// Tables records are synced to; a server without one keeps its records local
+pub const SYNCED_TABLES: [&str; 9] = [
+    "sessions",
+    "connectivity",
+    "events",
+    "event_session_links",
+    "operators",
+    "session_notes",
+    "tags",
+    "artifacts",
+    "heartbeats",
+];
+
+/// Rows removed by [`ScoutClient::purge_staging_data`]; descendants of the sessions
+/// removed by the server's cascade are not counted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StagingPurgeReport {
+    pub sessions: usize,
+    pub events: usize,
+}
+
+/// Time range and page of [`ScoutClient::query_heartbeats_by_device`]. Heartbeats come
+/// newest first; [`HeartbeatQuery::next_page`] continues after the last one of a page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeartbeatQuery {
+    /// Earliest timestamp, inclusive
+    pub since: Option<String>,
+    /// Latest timestamp, inclusive
+    pub until: Option<String>,
+    /// Page size; None returns every heartbeat in the range
+    pub limit: Option<usize>,
+    /// Timestamp and ID of the last heartbeat of the previous page
+    pub cursor: Option<(String, i64)>,
+}
+
+impl HeartbeatQuery {
+    /// The newest `limit` heartbeats
+    pub fn latest(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    /// The query for the page after `page`, None once a page comes back short
+    pub fn next_page(&self, page: &[Heartbeat]) -> Option<Self> {
+        let last = page.last()?;
+        if page.len() < self.limit? {
+            return None;
+        }
+        Some(Self {
+            cursor: Some((last.timestamp.clone(), last.id?)),
+            ..self.clone()
+        })
+    }
+
+    /// PostgREST `or` filter for heartbeats after the cursor in newest-first order;
+    /// heartbeats sharing a timestamp are ordered by ID
+    fn cursor_filter(&self) -> Option<String> {
+        let (timestamp, id) = self.cursor.as_ref()?;
+        Some(format!(
+            "timestamp.lt.\"{0}\",and(timestamp.eq.\"{0}\",id.lt.{1})",
+            timestamp, id
+        ))
+    }
+}
+
+/// A file fetched by [`ScoutClient::download_artifact`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadedArtifact {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Bytes kept from an interrupted earlier download
+    pub resumed_bytes: u64,
+}
+
+/// Hex SHA-256 and size of a file
+pub(crate) fn sha256_file(path: &Path) -> Result<(String, u64)> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((sha256, size))
+}
+
+/// The SHA-256 of an RFC 3230 `Digest` header (`sha-256=<base64>`), as hex
+fn digest_header_sha256(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let value = headers.get("digest")?.to_str().ok()?;
+    value.split(',').find_map(|digest| {
+        let (algorithm, encoded) = digest.trim().split_once('=')?;
+        if !algorithm.eq_ignore_ascii_case("sha-256") {
+            return None;
+        }
+        let bytes = base64::decode(encoded).ok()?;
+        Some(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    })
+}
+
+/// An optional server feature, backed by an RPC function that self-hosted servers may
+/// not have. Callers fall back to generic REST queries when it is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Events with their tags in one call; the fallback embeds tags in an events query
+    EventsWithTags,
+    /// Artifacts of a herd; the fallback filters artifacts by their device's herd
+    ArtifactsForHerd,
+    /// Appending to a session's track; without it track segments stay local
+    SessionTrackAppend,
+    /// Supported model versions; without it the latest versions are assumed
+    ModelVersions,
+    /// Rolling up old connectivity into daily summaries; without it nothing is compacted
+    ConnectivityCompaction,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::EventsWithTags,
+        Capability::ArtifactsForHerd,
+        Capability::SessionTrackAppend,
+        Capability::ModelVersions,
+        Capability::ConnectivityCompaction,
+    ];
+
+    /// The RPC function providing the capability
+    pub fn rpc_name(&self) -> &'static str {
+        match self {
+            Capability::EventsWithTags => "get_events_and_tags_for_device",
+            Capability::ArtifactsForHerd => "get_artifacts_for_herd",
+            Capability::SessionTrackAppend => "append_session_track",
+            Capability::ModelVersions => "get_supported_model_versions",
+            Capability::ConnectivityCompaction => "compact_connectivity",
+        }
+    }
+}
+
+/// The optional capabilities a server offers. Until probed, all are assumed available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities(BTreeSet<Capability>);
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self(Capability::ALL.into_iter().collect())
+    }
+}
+
+impl Capabilities {
+    /// The capabilities whose RPC functions are among `rpc_names`
+    pub fn from_rpc_names(rpc_names: &BTreeSet<String>) -> Self {
+        Self(
+            Capability::ALL
+                .into_iter()
+                .filter(|capability| rpc_names.contains(capability.rpc_name()))
+                .collect(),
+        )
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.0.contains(&capability)
+    }
+
+    /// Capabilities the server lacks
+    pub fn missing(&self) -> Vec<Capability> {
+        Capability::ALL
+            .into_iter()
+            .filter(|capability| !self.supports(*capability))
+            .collect()
+    }
+}
+
+/// Model versions the server accepts, keyed by table. Returned by the
+/// `get_supported_model_versions` RPC (see migration 14).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ModelVersions(pub HashMap<String, Vec<u32>>);
+
+impl ModelVersions {
+    /// Highest version of a table's model supported by both the server and this client.
+    /// Tables the server does not list are assumed to accept the latest version.
+    pub fn negotiate(&self, table: &str, latest: u32) -> Result<u32> {
+        let Some(supported) = self.0.get(table) else {
+            return Ok(latest);
+        };
+        supported
+            .iter()
+            .copied()
+            .filter(|version| *version <= latest)
+            .max()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Server supports {} model versions {:?}, this client supports up to {}",
+                    table,
+                    supported,
+                    latest
+                )
+            })
+    }
+}
+
+/// Re-serializes records as an older model version, warning about populated fields the
+/// older version drops
+pub(crate) fn downgrade_records<T, U>(table: &str, items: &[T]) -> Result<Vec<U>>
+where
+    T: Serialize,
+    U: Serialize + DeserializeOwned,
+{
+    let mut dropped = BTreeSet::new();
+    let downgraded = items
+        .iter()
+        .map(|item| {
+            let value = serde_json::to_value(item)?;
+            let downgraded: U = serde_json::from_value(value.clone())
+                .map_err(|e| anyhow!("Cannot send {} as an older model version: {}", table, e))?;
+            let kept = serde_json::to_value(&downgraded)?;
+            if let (Some(fields), Some(kept)) = (value.as_object(), kept.as_object()) {
+                for (field, value) in fields {
+                    if !value.is_null() && !kept.contains_key(field) {
+                        dropped.insert(field.clone());
+                    }
+                }
+            }
+            Ok(downgraded)
+        })
+        .collect::<Result<Vec<U>>>()?;
+    if !dropped.is_empty() {
+        logging::warn!(
+            "Server requires an older {} model version; dropping fields: {}",
+            table,
+            dropped.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(downgraded)
+}
+
+// ===== CIRCUIT BREAKER =====
+
+/// State of an endpoint's circuit, see [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through
+    Closed,
+    /// Requests are skipped until `until`
+    Open {
+        until: chrono::DateTime<chrono::Utc>,
+    },
+    /// The open period has passed; the next request probes whether the endpoint recovered
+    HalfOpen,
+}
+
+/// Stops sending to a server endpoint (a table or RPC) after `failure_threshold`
+/// consecutive failures, for `open_for`. Afterwards the circuit half-opens: one success
+/// closes it, one failure opens it again.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: std::time::Duration,
+    endpoints: HashMap<String, CircuitBreakerLocal>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_for: std::time::Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            endpoints: HashMap::new(),
+        }
+    }
+
+    pub fn state(&self, endpoint: &str) -> CircuitState {
+        self.state_at(endpoint, chrono::Utc::now())
+    }
+
+    pub fn state_at(&self, endpoint: &str, now: chrono::DateTime<chrono::Utc>) -> CircuitState {
+        let opened_at = self
+            .endpoints
+            .get(endpoint)
+            .and_then(|record| record.opened_at.as_deref())
+            .and_then(|opened_at| chrono::DateTime::parse_from_rfc3339(opened_at).ok());
+        let Some(opened_at) = opened_at else {
+            return CircuitState::Closed;
+        };
+        let open_for = chrono::Duration::from_std(self.open_for).unwrap_or(chrono::Duration::MAX);
+        match opened_at
+            .with_timezone(&chrono::Utc)
+            .checked_add_signed(open_for)
+        {
+            Some(until) if now < until => CircuitState::Open { until },
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Open {
+                until: chrono::DateTime::<chrono::Utc>::MAX_UTC,
+            },
+        }
+    }
+
+    /// Whether requests to the endpoint should be sent now
+    pub fn allows(&self, endpoint: &str) -> bool {
+        !matches!(self.state(endpoint), CircuitState::Open { .. })
+    }
+
+    /// Closes the endpoint's circuit
+    pub fn record_success(&mut self, endpoint: &str) {
+        if self.endpoints.remove(endpoint).is_some() {
+            logging::info!("Circuit of {} closed", endpoint);
+        }
+    }
+
+    /// Counts a failure, opening the circuit at the threshold or when a probe fails
+    pub fn record_failure(&mut self, endpoint: &str) {
+        let threshold = self.failure_threshold;
+        let record = self
+            .endpoints
+            .entry(endpoint.to_string())
+            .or_insert_with(|| CircuitBreakerLocal {
+                endpoint: endpoint.to_string(),
+                consecutive_failures: 0,
+                opened_at: None,
+            });
+        record.consecutive_failures = record.consecutive_failures.saturating_add(1);
+        if record.consecutive_failures >= threshold {
+            logging::warn!(
+                "Circuit of {} open after {} consecutive failures",
+                endpoint,
+                record.consecutive_failures
+            );
+            record.opened_at = Some(chrono::Utc::now().to_rfc3339());
+        }
+    }
+
+    /// Failure record of an endpoint, None while it has not failed since its last success
+    pub fn record(&self, endpoint: &str) -> Option<&CircuitBreakerLocal> {
+        self.endpoints.get(endpoint)
+    }
+
+    /// Restores failure records kept from an earlier run
+    pub fn restore(&mut self, records: impl IntoIterator<Item = CircuitBreakerLocal>) {
+        for record in records {
+            self.endpoints.insert(record.endpoint.clone(), record);
+        }
+    }
+
+    /// Endpoints whose circuit is not closed, with their state
+    pub fn states(&self) -> Vec<(String, CircuitState)> {
+        let mut states: Vec<(String, CircuitState)> = self
+            .endpoints
+            .keys()
+            .map(|endpoint| (endpoint.clone(), self.state(endpoint)))
+            .filter(|(_, state)| *state != CircuitState::Closed)
+            .collect();
+        states.sort_by(|a, b| a.0.cmp(&b.0));
+        states
+    }
+}
+
+// ===== CLIENT BUILDER =====
+
+/// Builds a [`ScoutClient`] with tuned HTTP behavior, e.g. for field devices on slow or
+/// flaky satellite links:
+///
+/// ```no_run
+/// use scout_rs::client::ScoutClient;
+/// use scout_rs::db_client::DatabaseConfig;
+/// use std::time::Duration;
+///
+/// let client = ScoutClient::builder(DatabaseConfig::from_env()?)
+///     .with_connect_timeout(Duration::from_secs(20))
+///     .with_request_timeout(Duration::from_secs(120))
+///     .with_retries(3, Duration::from_secs(2))
+///     .with_rate_limit(2.0)
+///     .with_proxy("http://10.0.0.1:3128")
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScoutClientBuilder {
+    config_db: DatabaseConfig,
+    http_options: HttpOptions,
+    staging_marker: Option<String>,
+}
+
+impl ScoutClientBuilder {
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http_options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits each request, including reading the response body
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.http_options.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Retries failed connections, timeouts and overloaded responses up to `max_retries`
+    /// times, waiting `backoff` before the first retry and doubling it for each further one
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.http_options.max_retries = max_retries;
+        self.http_options.retry_backoff = backoff;
+        self
+    }
+
+    /// Spaces requests to at most `requests_per_second`
+    pub fn with_rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.http_options.max_requests_per_second = Some(requests_per_second);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.http_options.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sends all requests through an HTTP proxy, or a SOCKS proxy (`socks5://`) when the
+    /// application enables reqwest's `socks` feature
+    pub fn with_proxy(mut self, url: impl Into<String>) -> Self {
+        self.http_options.proxy = Some(url.into());
+        self
+    }
+
+    /// Marks the sessions and events the client writes as test data, see
+    /// [`ScoutClient::set_staging_marker`]
+    pub fn with_staging_marker(mut self, marker: impl Into<String>) -> Self {
+        self.staging_marker = Some(marker.into());
+        self
+    }
+
+    /// The client, failing if the options do not make an HTTP client, e.g. for an invalid
+    /// proxy URL or a rate limit that is not positive, or for an invalid staging marker
+    pub fn build(self) -> Result<ScoutClient> {
+        if let Some(per_second) = self.http_options.max_requests_per_second {
+            if !(per_second > 0.0 && per_second.is_finite()) {
+                return Err(anyhow!(
+                    "Rate limit must be a positive number of requests per second"
+                ));
+            }
+        }
+        self.http_options.client()?;
+        let mut client = ScoutClient::new(self.config_db);
+        client.http_options = self.http_options;
+        client.set_staging_marker(self.staging_marker)?;
+        Ok(client)
+    }
+}
 
 // ===== CLIENT IMPLEMENTATION =====
 
@@ -10,8 +492,21 @@ pub struct ScoutClient {
     pub config_db: DatabaseConfig,
     pub device: Option<DevicePrettyLocation>,
     pub herd: Option<Herd>,
+    /// Model versions the server accepts, fetched during identify
+    pub model_versions: ModelVersions,
+    capabilities: Capabilities,
+    /// Synced tables the server does not have
+    missing_tables: BTreeSet<String>,
     db_client: Option<ScoutDbClient>,
     is_offline: bool,
+    failed_payload_capacity: usize,
+    response_cache_capacity: usize,
+    trace_context: Option<crate::trace::TraceContext>,
+    circuit_breaker: Option<CircuitBreaker>,
+    http_options: HttpOptions,
+    staging_marker: Option<String>,
+    #[cfg(feature = "chaos")]
+    failure_injector: Option<crate::chaos::FailureInjector>,
 }
 
 impl ScoutClient {
@@ -24,9 +519,53 @@ impl ScoutClient {
             config_db,
             device: None,
             herd: None,
+            model_versions: ModelVersions::default(),
+            capabilities: Capabilities::default(),
+            missing_tables: BTreeSet::new(),
             db_client: None,
             is_offline: false,
+            failed_payload_capacity: 0,
+            response_cache_capacity: DEFAULT_RESPONSE_CACHE_CAPACITY,
+            trace_context: None,
+            circuit_breaker: None,
+            http_options: HttpOptions::default(),
+            staging_marker: None,
+            #[cfg(feature = "chaos")]
+            failure_injector: None,
+        }
+    }
+
+    /// A builder for a client with tuned HTTP behavior, e.g. for satellite links
+    pub fn builder(config_db: DatabaseConfig) -> ScoutClientBuilder {
+        ScoutClientBuilder {
+            config_db,
+            http_options: HttpOptions::default(),
+            staging_marker: None,
+        }
+    }
+
+    /// HTTP behavior of the client's requests, see [`ScoutClientBuilder`]
+    pub fn http_options(&self) -> &HttpOptions {
+        &self.http_options
+    }
+
+    /// Puts `marker`, e.g. `"[e2e] "`, before the software version of sessions and the
+    /// message of events written from now on, including rows written before and sent
+    /// again, so end-to-end tests against a real server can remove their rows with
+    /// [`Self::purge_staging_data`]. The marker must not contain `*`, `%` or `_`.
+    pub fn set_staging_marker(&mut self, marker: Option<String>) -> Result<()> {
+        if let Some(marker) = &marker {
+            crate::db_client::validate_staging_marker(marker)?;
+        }
+        if let Some(db_client) = self.db_client.as_mut() {
+            db_client.set_staging_marker(marker.clone())?;
         }
+        self.staging_marker = marker;
+        Ok(())
+    }
+
+    pub fn staging_marker(&self) -> Option<&str> {
+        self.staging_marker.as_deref()
     }
 
     /// Initializes the client in offline mode with default placeholder values
@@ -58,7 +597,16 @@ impl ScoutClient {
         }
 
         let mut db_client = ScoutDbClient::new(self.config_db.clone());
+        db_client.set_http_options(self.http_options.clone())?;
+        db_client.set_staging_marker(self.staging_marker.clone())?;
         db_client.connect()?;
+        db_client.capture_failed_payloads(self.failed_payload_capacity);
+        db_client.set_response_cache_capacity(self.response_cache_capacity);
+        db_client.set_trace_context(self.trace_context);
+        #[cfg(feature = "chaos")]
+        if let Some(injector) = &self.failure_injector {
+            db_client.set_failure_injector(injector.clone());
+        }
 
         self.db_client = Some(db_client);
 
@@ -68,10 +616,129 @@ impl ScoutClient {
 
         self.device = Some(device);
         self.herd = Some(herd);
+        self.probe_server().await;
+        self.model_versions = if self.capabilities.supports(Capability::ModelVersions) {
+            self.get_model_versions_from_db().await
+        } else {
+            ModelVersions::default()
+        };
 
         Ok(())
     }
 
+    /// Optional server capabilities, probed during identify
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Whether the server has `table`, one of [`SYNCED_TABLES`]. Tables are assumed present
+    /// until the probe during identify or a rejected request says otherwise.
+    pub fn has_table(&self, table: &str) -> bool {
+        !self.missing_tables.contains(table)
+    }
+
+    /// Probes the RPC functions and tables the server exposes. Servers that do not
+    /// describe them are assumed to have all; a missing one is then noticed when first
+    /// called.
+    async fn probe_server(&mut self) {
+        let paths = async { self.get_db_client()?.get_api_paths().await }.await;
+        let paths = match paths {
+            Ok(paths) => paths,
+            Err(e) => {
+                logging::warn!("Capabilities unavailable, assuming all: {}", e);
+                self.capabilities = Capabilities::default();
+                self.missing_tables.clear();
+                return;
+            }
+        };
+        let rpc_names = paths
+            .iter()
+            .filter_map(|path| path.strip_prefix("rpc/"))
+            .map(str::to_string)
+            .collect();
+        self.capabilities = Capabilities::from_rpc_names(&rpc_names);
+        let missing = self.capabilities.missing();
+        if !missing.is_empty() {
+            logging::info!("Server lacks capabilities {:?}, using fallbacks", missing);
+        }
+        self.missing_tables = SYNCED_TABLES
+            .into_iter()
+            .filter(|table| !paths.contains(*table))
+            .map(str::to_string)
+            .collect();
+        if !self.missing_tables.is_empty() {
+            logging::warn!(
+                "Server lacks tables {:?}, their records stay local",
+                self.missing_tables
+            );
+        }
+    }
+
+    /// Marks `table` missing if `error` says the server has no such table, returning
+    /// whether it did
+    pub fn is_missing_table(&mut self, table: &str, error: &anyhow::Error) -> bool {
+        let missing = PostgrestError::from_error(error)
+            .is_some_and(|error| error.code == PostgrestError::TABLE_NOT_FOUND);
+        if missing {
+            logging::warn!("Server has no {} table, its records stay local", table);
+            self.missing_tables.insert(table.to_string());
+        }
+        missing
+    }
+
+    /// Marks `capability` missing if `error` says its RPC function does not exist
+    fn is_missing_rpc(&mut self, capability: Capability, error: &anyhow::Error) -> bool {
+        let missing = PostgrestError::from_error(error)
+            .is_some_and(|error| error.code == PostgrestError::FUNCTION_NOT_FOUND);
+        if missing {
+            logging::warn!(
+                "Server has no {} function, falling back",
+                capability.rpc_name()
+            );
+            self.capabilities.0.remove(&capability);
+        }
+        missing
+    }
+
+    /// Gets the model versions the server accepts. Servers without the RPC are assumed to
+    /// accept the latest versions.
+    async fn get_model_versions_from_db(&mut self) -> ModelVersions {
+        let result: Result<ModelVersions> = async {
+            let db_client = self.get_db_client()?;
+            let request = db_client
+                .get_client()?
+                .rpc("get_supported_model_versions", "{}");
+            let response = db_client.send(request).await?;
+            let body = response.text().await?;
+            Ok(serde_json::from_str(&body)?)
+        }
+        .await;
+        result.unwrap_or_else(|e| {
+            logging::warn!("Model versions unavailable, assuming latest: {}", e);
+            ModelVersions::default()
+        })
+    }
+
+    /// Upserts records as an older model version and converts the returned rows back
+    async fn upsert_downgraded<S, T, U>(
+        &mut self,
+        table: &str,
+        items: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<Vec<T>>
+    where
+        S: Serialize,
+        T: From<U>,
+        U: Serialize + DeserializeOwned,
+    {
+        let downgraded: Vec<U> = downgrade_records(table, items)?;
+        let result = self
+            .get_db_client()?
+            .upsert_bulk(table, &downgraded, idempotency_key)
+            .await?;
+        Ok(result.into_iter().map(T::from).collect())
+    }
+
     /// Gets device information using get_device_by_api_key function and parsing JSON response
     async fn get_device_from_db(&mut self) -> Result<DevicePrettyLocation> {
         let config_db = self.config_db.clone();
@@ -145,6 +812,91 @@ impl ScoutClient {
             || self.is_offline
     }
 
+    /// Rotates the device API key: requests a new key with the current one, identifies
+    /// with the new key, stores it in `secrets` as [`SCOUT_API_KEY`] and then revokes the
+    /// old key. If the new key does not identify this device or cannot be stored, the
+    /// client goes back to the old key and the new one is revoked. Storage clients built
+    /// with the old key must be rebuilt with [`DatabaseConfig::get_scout_api_key`]
+    /// afterwards.
+    pub async fn rotate_api_key(&mut self, secrets: &dyn SecretProvider) -> Result<()> {
+        let old_api_key = self.config_db.scout_api_key.clone();
+        let device_id = self
+            .device
+            .as_ref()
+            .and_then(|device| device.id)
+            .ok_or_else(|| anyhow!("Client must be identified before rotating its API key"))?;
+
+        let body = self
+            .call_key_rpc(
+                "rotate_device_api_key",
+                serde_json::json!({ "device_api_key": old_api_key }),
+            )
+            .await?;
+        let new_api_key: String = serde_json::from_str(&body)
+            .map_err(|e| anyhow!("Failed to parse rotated API key: {}", e))?;
+
+        self.config_db.scout_api_key = new_api_key.clone();
+        let validated = match self.identify().await {
+            Ok(()) if self.device.as_ref().and_then(|device| device.id) == Some(device_id) => {
+                secrets.set(SCOUT_API_KEY, &new_api_key)
+            }
+            Ok(()) => Err(anyhow!("New API key identifies a different device")),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = validated {
+            logging::error!("Rotated API key failed validation, rolling back: {}", e);
+            self.config_db.scout_api_key = old_api_key.clone();
+            // The rejected key is revoked even if identifying with the old one fails, and
+            // the validation error is what the caller gets either way
+            let identified = self.identify().await;
+            let revoked = self.revoke_api_key(&old_api_key, &new_api_key).await;
+            if let Err(identify_error) = identified {
+                logging::error!(
+                    "Failed to identify with the previous API key: {}",
+                    identify_error
+                );
+            }
+            if let Err(revoke_error) = revoked {
+                logging::warn!("Failed to revoke rejected API key: {}", revoke_error);
+            }
+            return Err(e);
+        }
+
+        // The new key is in use and stored; the old one only lingers if this fails
+        if let Err(e) = self.revoke_api_key(&new_api_key, &old_api_key).await {
+            logging::warn!("Failed to revoke previous API key: {}", e);
+        }
+        logging::info!("Rotated API key of device {}", device_id);
+        Ok(())
+    }
+
+    /// Revokes `revoked_api_key` of this device, authenticating with `api_key`
+    async fn revoke_api_key(&mut self, api_key: &str, revoked_api_key: &str) -> Result<()> {
+        self.call_key_rpc(
+            "revoke_device_api_key",
+            serde_json::json!({
+                "device_api_key": api_key,
+                "revoked_api_key": revoked_api_key
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Calls an API key RPC function (see migration 24) and returns the response body,
+    /// failing on an error response
+    async fn call_key_rpc(&mut self, function: &str, params: serde_json::Value) -> Result<String> {
+        let db_client = self.get_db_client()?;
+        let request = db_client.get_client()?.rpc(function, params.to_string());
+        let response = db_client.send(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("{} failed ({}): {}", function, status, body));
+        }
+        Ok(body)
+    }
+
     // ===== HELPER METHODS =====
 
     /// Checks if a session exists in the database by device_id, start timestamp, and end timestamp
@@ -181,6 +933,115 @@ impl ScoutClient {
             .await
     }
 
+    /// Returns the subset of `ids` that exist in `table`
+    pub async fn get_existing_ids(
+        &mut self,
+        table: &str,
+        ids: &[i64],
+    ) -> Result<ResponseScout<Vec<i64>>> {
+        if ids.is_empty() {
+            return Ok(Self::success_response(Vec::new()));
+        }
+        let db_client = self.get_db_client()?;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct IdOnly {
+            id: i64,
+        }
+
+        let results: Vec<IdOnly> = db_client.query_by_ids(table, "id", "id", ids).await?;
+
+        Ok(Self::success_response(
+            results.into_iter().map(|row| row.id).collect(),
+        ))
+    }
+
+    /// Exchanges an operator PIN or badge scan for a session-scoped token carrying the
+    /// operator's user_id. The operator must hold a role in this device's herd.
+    pub async fn authenticate_operator(
+        &mut self,
+        credential: &str,
+        credential_type: OperatorCredentialType,
+    ) -> Result<ResponseScout<OperatorToken>> {
+        let db_client = self.get_db_client()?;
+
+        let results: Vec<OperatorToken> = db_client
+            .query(|client| {
+                client.rpc(
+                    "authenticate_operator",
+                    serde_json::json!({
+                        "credential": credential,
+                        "credential_type": credential_type
+                    })
+                    .to_string(),
+                )
+            })
+            .await?;
+
+        let token = results
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Operator authentication returned no token"))?;
+        Ok(Self::success_response(token))
+    }
+
+    /// Keeps the last `capacity` write payloads the server rejected, with their responses
+    /// and credential-like fields redacted (0 disables capture)
+    pub fn capture_failed_payloads(&mut self, capacity: usize) {
+        self.failed_payload_capacity = capacity;
+        if let Some(db_client) = self.db_client.as_mut() {
+            db_client.capture_failed_payloads(capacity);
+        }
+    }
+
+    /// Keeps up to `capacity` GET responses so polling an unchanged resource (plans, zones)
+    /// costs a 304 instead of the full body (0 disables caching)
+    pub fn set_response_cache_capacity(&mut self, capacity: usize) {
+        self.response_cache_capacity = capacity;
+        if let Some(db_client) = self.db_client.as_mut() {
+            db_client.set_response_cache_capacity(capacity);
+        }
+    }
+
+    /// Skips endpoints that keep failing (`None` disables the breaker), see [`CircuitBreaker`]
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = circuit_breaker;
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_ref()
+    }
+
+    pub fn circuit_breaker_mut(&mut self) -> Option<&mut CircuitBreaker> {
+        self.circuit_breaker.as_mut()
+    }
+
+    /// Sends a W3C `traceparent` naming `context` as the parent span with every database
+    /// request (`None` stops propagation), see [`crate::trace`]
+    pub fn set_trace_context(&mut self, context: Option<crate::trace::TraceContext>) {
+        self.trace_context = context;
+        if let Some(db_client) = self.db_client.as_mut() {
+            db_client.set_trace_context(context);
+        }
+    }
+
+    /// Injects failures into the database requests (`chaos` feature), see [`crate::chaos`]
+    #[cfg(feature = "chaos")]
+    pub fn set_failure_injector(&mut self, injector: crate::chaos::FailureInjector) {
+        if let Some(db_client) = self.db_client.as_mut() {
+            db_client.set_failure_injector(injector.clone());
+        }
+        self.failure_injector = Some(injector);
+    }
+
+    /// Returns captured rejected writes, oldest first
+    pub fn failed_payloads(&self) -> Vec<FailedPayload> {
+        self.db_client
+            .as_ref()
+            .map(|db_client| db_client.failed_payloads())
+            .unwrap_or_default()
+    }
+
     /// Helper to create a success response
     fn success_response<T>(data: T) -> ResponseScout<T> {
         ResponseScout::new(ResponseScoutStatus::Success, Some(data))
@@ -301,7 +1162,9 @@ impl ScoutClient {
             .collect();
 
         // Use bulk insert for better performance
-        let result = db_client.insert_bulk("tags", &tags_with_event_id).await?;
+        let result = db_client
+            .insert_bulk("tags", &tags_with_event_id, None)
+            .await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
@@ -343,6 +1206,48 @@ impl ScoutClient {
         Self::handle_insert_result(result)
     }
 
+    /// Creates a session with only its device, start time and software version, e.g. as it
+    /// starts; the statistics keep their server defaults until patched
+    pub async fn create_session_stub(
+        &mut self,
+        session: &Session,
+    ) -> Result<ResponseScout<PartialRow>> {
+        let db_client = self.get_db_client()?;
+        let stub = serde_json::json!({
+            "device_id": session.device_id,
+            "timestamp_start": session.timestamp_start,
+            "software_version": session.software_version,
+        });
+        let result = db_client.insert("sessions", &stub).await?;
+        let rows = result
+            .into_iter()
+            .filter_map(|row| match row {
+                serde_json::Value::Object(columns) => Some(PartialRow(columns)),
+                _ => None,
+            })
+            .collect();
+        Self::handle_insert_result(rows)
+    }
+
+    /// Updates only the given columns of a session, e.g. its growing statistics
+    pub async fn patch_session(
+        &mut self,
+        session_id: i64,
+        columns: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<ResponseScout<()>> {
+        let db_client = self.get_db_client()?;
+        let result = db_client
+            .update(&serde_json::Value::Object(columns.clone()), |client| {
+                client.from("sessions").eq("id", session_id.to_string())
+            })
+            .await?;
+        if result.is_empty() {
+            Ok(Self::failure_response())
+        } else {
+            Ok(Self::success_response(()))
+        }
+    }
+
     /// Creates connectivity data directly from the database
     pub async fn create_connectivity(
         &mut self,
@@ -524,9 +1429,10 @@ impl ScoutClient {
         session: &Session,
     ) -> Result<ResponseScout<Session>> {
         let db_client = self.get_db_client()?;
+        let session = db_client.staged("sessions", session)?;
 
         let result = db_client
-            .update(session, |client| {
+            .update(&session, |client| {
                 client.from("sessions").eq("id", session_id.to_string())
             })
             .await?;
@@ -558,16 +1464,60 @@ impl ScoutClient {
         Ok(ResponseScout::new(ResponseScoutStatus::Success, None))
     }
 
-    /// Deletes an event directly from the database
-    /// Database cascade deletion handles dependent records automatically
-    pub async fn delete_event(&mut self, event_id: i64) -> Result<ResponseScout<()>> {
+    /// Deletes the sessions and events of this device carrying the staging marker, see
+    /// [`Self::set_staging_marker`]. The server cascades to the descendants of deleted
+    /// sessions. Fails without a marker, so production data is never matched.
+    pub async fn purge_staging_data(&mut self) -> Result<StagingPurgeReport> {
+        let marker = self
+            .staging_marker
+            .clone()
+            .ok_or_else(|| anyhow!("Refusing to purge: no staging marker set"))?;
+        let device_id = self
+            .device
+            .as_ref()
+            .and_then(|device| device.id)
+            .ok_or_else(|| anyhow!("Device not identified"))?;
         let db_client = self.get_db_client()?;
 
-        let _event_deleted = db_client
-            .delete(|client| client.from("events").eq("id", event_id.to_string()))
-            .await?;
-
-        Ok(ResponseScout::new(ResponseScoutStatus::Success, None))
+        let mut report = StagingPurgeReport::default();
+        // Events first, as those outside marked sessions are not removed by the cascade.
+        // Each table is filtered and deleted in one request, and the response lists
+        // exactly the rows it deleted.
+        for (table, column) in STAGING_MARKED_COLUMNS.iter().rev() {
+            let pattern = format!("{}*", marker);
+            let deleted: Vec<serde_json::Value> = db_client
+                .delete_returning(|client| {
+                    client
+                        .from(table)
+                        .select("id")
+                        .eq("device_id", device_id.to_string())
+                        .like(*column, &pattern)
+                })
+                .await?;
+            if *table == "sessions" {
+                report.sessions = deleted.len();
+            } else {
+                report.events = deleted.len();
+            }
+        }
+        logging::info!(
+            "Purged {} staging sessions and {} staging events",
+            report.sessions,
+            report.events
+        );
+        Ok(report)
+    }
+
+    /// Deletes an event directly from the database
+    /// Database cascade deletion handles dependent records automatically
+    pub async fn delete_event(&mut self, event_id: i64) -> Result<ResponseScout<()>> {
+        let db_client = self.get_db_client()?;
+
+        let _event_deleted = db_client
+            .delete(|client| client.from("events").eq("id", event_id.to_string()))
+            .await?;
+
+        Ok(ResponseScout::new(ResponseScoutStatus::Success, None))
     }
 
     /// Deletes a tag directly from the database
@@ -734,6 +1684,86 @@ impl ScoutClient {
         ))
     }
 
+    /// Gets the events with the given IDs, in as few requests as the URL length allows.
+    /// IDs that don't exist are left out.
+    pub async fn get_events_by_ids(&mut self, ids: &[i64]) -> Result<ResponseScout<Vec<Event>>> {
+        self.get_by_ids("events", ids).await
+    }
+
+    /// Gets the sessions with the given IDs; see [`Self::get_events_by_ids`]
+    pub async fn get_sessions_by_ids(
+        &mut self,
+        ids: &[i64],
+    ) -> Result<ResponseScout<Vec<Session>>> {
+        self.get_by_ids("sessions", ids).await
+    }
+
+    /// Gets the tags with the given IDs; see [`Self::get_events_by_ids`]
+    pub async fn get_tags_by_ids(&mut self, ids: &[i64]) -> Result<ResponseScout<Vec<Tag>>> {
+        self.get_by_ids("tags", ids).await
+    }
+
+    /// Gets the connectivity rows with the given IDs; see [`Self::get_events_by_ids`]
+    pub async fn get_connectivity_by_ids(
+        &mut self,
+        ids: &[i64],
+    ) -> Result<ResponseScout<Vec<Connectivity>>> {
+        self.get_by_ids("connectivity", ids).await
+    }
+
+    /// Gets the devices with the given IDs; see [`Self::get_events_by_ids`]
+    pub async fn get_devices_by_ids(&mut self, ids: &[i64]) -> Result<ResponseScout<Vec<Device>>> {
+        self.get_by_ids("devices", ids).await
+    }
+
+    async fn get_by_ids<T>(&mut self, table: &str, ids: &[i64]) -> Result<ResponseScout<Vec<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        let db_client = self.get_db_client()?;
+        let results = db_client.query_by_ids(table, "*", "id", ids).await?;
+        Ok(Self::success_response(results))
+    }
+
+    /// Gets up to `limit` rows of a herd's sessions, events, tags or connectivity with IDs
+    /// above `after_id`, in ID order. Connectivity is found through its session.
+    pub async fn get_herd_rows_after<T>(
+        &mut self,
+        table: &str,
+        herd_id: i64,
+        after_id: i64,
+        limit: usize,
+    ) -> Result<ResponseScout<Vec<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        let (select, herd_column) = match table {
+            "sessions" | "events" => ("*, devices!inner(herd_id)", "devices.herd_id"),
+            "tags" => (
+                "*, events!inner(devices!inner(herd_id))",
+                "events.devices.herd_id",
+            ),
+            "connectivity" => (
+                "*, sessions!inner(devices!inner(herd_id))",
+                "sessions.devices.herd_id",
+            ),
+            _ => return Err(anyhow!("Cannot pull {} by herd", table)),
+        };
+        let db_client = self.get_db_client()?;
+        let results = db_client
+            .query(|client| {
+                client
+                    .from(table)
+                    .select(select)
+                    .eq(herd_column, herd_id.to_string())
+                    .gt("id", after_id.to_string())
+                    .order("id.asc")
+                    .limit(limit)
+            })
+            .await?;
+        Ok(Self::success_response(results))
+    }
+
     /// Gets all events for a device directly from the database
     pub async fn get_device_events(&mut self, device_id: i64) -> Result<ResponseScout<Vec<Event>>> {
         let db_client = self.get_db_client()?;
@@ -776,27 +1806,45 @@ impl ScoutClient {
         ))
     }
 
-    /// Gets events with tags for a device using the database function
+    /// Gets events with tags for a device using the database function, or an events query
+    /// embedding tags on servers without it
     pub async fn get_device_events_with_tags_via_function(
         &mut self,
         device_id: i64,
         limit: i64,
     ) -> Result<ResponseScout<Vec<Event>>> {
-        let db_client = self.get_db_client()?;
+        if self.capabilities.supports(Capability::EventsWithTags) {
+            let result = self
+                .get_db_client()?
+                .query(|client| {
+                    client.rpc(
+                        Capability::EventsWithTags.rpc_name(),
+                        serde_json::json!({
+                            "device_id_caller": device_id,
+                            "limit_caller": limit
+                        })
+                        .to_string(),
+                    )
+                })
+                .await;
+            match result {
+                Ok(results) => return Ok(Self::handle_query_result(results)),
+                Err(e) if !self.is_missing_rpc(Capability::EventsWithTags, &e) => return Err(e),
+                Err(_) => {}
+            }
+        }
 
-        let results = db_client
+        let results = self
+            .get_db_client()?
             .query(|client| {
-                client.rpc(
-                    "get_events_and_tags_for_device",
-                    serde_json::json!({
-                        "device_id_caller": device_id,
-                        "limit_caller": limit
-                    })
-                    .to_string(),
-                )
+                client
+                    .from("events")
+                    .select("*, tags(*)")
+                    .eq("device_id", device_id.to_string())
+                    .order("timestamp_observation.desc")
+                    .limit(limit.max(0) as usize)
             })
             .await?;
-
         Ok(Self::handle_query_result(results))
     }
 
@@ -824,6 +1872,34 @@ impl ScoutClient {
         ))
     }
 
+    /// Gets events other devices observed within a time range, e.g. to find observations
+    /// neighbouring cameras share with this device
+    pub async fn get_other_device_events_in_timerange(
+        &mut self,
+        device_id: i64,
+        start_time: &str,
+        end_time: &str,
+    ) -> Result<ResponseScout<Vec<Event>>> {
+        let db_client = self.get_db_client()?;
+
+        let results = db_client
+            .query(|client| {
+                client
+                    .from("events")
+                    .select("*")
+                    .neq("device_id", device_id.to_string())
+                    .gte("timestamp_observation", start_time)
+                    .lte("timestamp_observation", end_time)
+                    .order("timestamp_observation.asc")
+            })
+            .await?;
+
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(results),
+        ))
+    }
+
     /// Gets events within a geographic area directly from the database
     pub async fn get_events_in_area(
         &mut self,
@@ -868,7 +1944,7 @@ impl ScoutClient {
         }
 
         // Use bulk insert for better performance
-        let result = db_client.insert_bulk("events", events).await?;
+        let result = db_client.insert_bulk("events", events, None).await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
@@ -890,7 +1966,7 @@ impl ScoutClient {
         }
 
         // Use bulk insert for better performance
-        let result = db_client.insert_bulk("sessions", sessions).await?;
+        let result = db_client.insert_bulk("sessions", sessions, None).await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
@@ -913,7 +1989,7 @@ impl ScoutClient {
 
         // Use bulk insert for better performance
         let result = db_client
-            .insert_bulk("connectivity", connectivity_entries)
+            .insert_bulk("connectivity", connectivity_entries, None)
             .await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
@@ -926,7 +2002,28 @@ impl ScoutClient {
         &mut self,
         sessions: &[Session],
     ) -> Result<ResponseScout<Vec<Session>>> {
-        let db_client = self.get_db_client()?;
+        let response = self.upsert_sessions_batch_partial(sessions, None).await?;
+        let result = response
+            .data
+            .unwrap_or_default()
+            .iter()
+            .map(PartialRow::row)
+            .collect::<Result<_>>()?;
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(result),
+        ))
+    }
+
+    /// Upserts sessions like [`Self::upsert_sessions_batch`], returning the rows with only
+    /// the columns the server sent (see [`PartialRow::merge_into`]). Entries are `Session`
+    /// rows or their JSON, e.g. as rounded by [`crate::sync::SyncPrecision`].
+    pub async fn upsert_sessions_batch_partial<S: Serialize>(
+        &mut self,
+        sessions: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<PartialRow>>> {
+        self.get_db_client()?;
 
         if sessions.is_empty() {
             return Ok(ResponseScout::new(
@@ -935,20 +2032,35 @@ impl ScoutClient {
             ));
         }
 
-        let result = db_client.upsert_bulk("sessions", sessions).await?;
+        let result = match self
+            .model_versions
+            .negotiate("sessions", SESSION_MODEL_VERSION)?
+        {
+            1 => {
+                let downgraded: Vec<data::v1::Session> = downgrade_records("sessions", sessions)?;
+                self.get_db_client()?
+                    .upsert_bulk("sessions", &downgraded, idempotency_key)
+                    .await?
+            }
+            _ => {
+                self.get_db_client()?
+                    .upsert_bulk("sessions", sessions, idempotency_key)
+                    .await?
+            }
+        };
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
         ))
     }
 
-    /// Upserts multiple connectivity entries in a batch (insert or update on conflict)
-    pub async fn upsert_connectivity_batch(
+    /// Upserts multiple connectivity entries in a batch (insert or update on conflict). Entries are
+    /// `Connectivity` rows or local records sent through [`crate::models::AsRemote`].
+    pub async fn upsert_connectivity_batch<S: Serialize>(
         &mut self,
-        connectivity_entries: &[Connectivity],
+        connectivity_entries: &[S],
+        idempotency_key: Option<&str>,
     ) -> Result<ResponseScout<Vec<Connectivity>>> {
-        let db_client = self.get_db_client()?;
-
         if connectivity_entries.is_empty() {
             return Ok(ResponseScout::new(
                 ResponseScoutStatus::Success,
@@ -956,22 +2068,62 @@ impl ScoutClient {
             ));
         }
 
-        let result = db_client
-            .upsert_bulk("connectivity", connectivity_entries)
-            .await?;
+        let table = "connectivity";
+        let result = match self
+            .model_versions
+            .negotiate(table, CONNECTIVITY_MODEL_VERSION)?
+        {
+            1 => {
+                self.upsert_downgraded::<_, _, data::v1::Connectivity>(
+                    table,
+                    connectivity_entries,
+                    idempotency_key,
+                )
+                .await?
+            }
+            2 => {
+                self.upsert_downgraded::<_, _, data::v2::Connectivity>(
+                    table,
+                    connectivity_entries,
+                    idempotency_key,
+                )
+                .await?
+            }
+            3 => {
+                self.upsert_downgraded::<_, _, data::v3::Connectivity>(
+                    table,
+                    connectivity_entries,
+                    idempotency_key,
+                )
+                .await?
+            }
+            4 => {
+                self.upsert_downgraded::<_, _, data::v4::Connectivity>(
+                    table,
+                    connectivity_entries,
+                    idempotency_key,
+                )
+                .await?
+            }
+            _ => {
+                self.get_db_client()?
+                    .upsert_bulk(table, connectivity_entries, idempotency_key)
+                    .await?
+            }
+        };
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
         ))
     }
 
-    /// Upserts multiple events in a batch (insert or update on conflict)
-    pub async fn upsert_events_batch(
+    /// Upserts multiple events in a batch (insert or update on conflict). Entries are
+    /// `Event` rows or local records sent through [`crate::models::AsRemote`].
+    pub async fn upsert_events_batch<S: Serialize>(
         &mut self,
-        events: &[Event],
+        events: &[S],
+        idempotency_key: Option<&str>,
     ) -> Result<ResponseScout<Vec<Event>>> {
-        let db_client = self.get_db_client()?;
-
         if events.is_empty() {
             return Ok(ResponseScout::new(
                 ResponseScoutStatus::Success,
@@ -979,52 +2131,331 @@ impl ScoutClient {
             ));
         }
 
-        let result = db_client.upsert_bulk("events", events).await?;
+        let result = match self
+            .model_versions
+            .negotiate("events", EVENT_MODEL_VERSION)?
+        {
+            1 => {
+                self.upsert_downgraded::<_, _, data::v1::Event>("events", events, idempotency_key)
+                    .await?
+            }
+            2 => {
+                self.upsert_downgraded::<_, _, data::v2::Event>("events", events, idempotency_key)
+                    .await?
+            }
+            3 => {
+                self.upsert_downgraded::<_, _, data::v4::Event>("events", events, idempotency_key)
+                    .await?
+            }
+            4 => {
+                self.upsert_downgraded::<_, _, data::v6::Event>("events", events, idempotency_key)
+                    .await?
+            }
+            _ => {
+                self.get_db_client()?
+                    .upsert_bulk("events", events, idempotency_key)
+                    .await?
+            }
+        };
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(result),
+        ))
+    }
+
+    /// Upserts multiple tags in a batch (insert or update on conflict). Entries are `Tag`
+    /// rows or local records sent through [`crate::models::AsRemote`].
+    pub async fn upsert_tags_batch<S: Serialize>(
+        &mut self,
+        tags: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<Tag>>> {
+        if tags.is_empty() {
+            return Ok(ResponseScout::new(
+                ResponseScoutStatus::Success,
+                Some(Vec::new()),
+            ));
+        }
+
+        let result = match self.model_versions.negotiate("tags", TAG_MODEL_VERSION)? {
+            1 => {
+                self.upsert_downgraded::<_, _, data::v1::Tag>("tags", tags, idempotency_key)
+                    .await?
+            }
+            2 => {
+                self.upsert_downgraded::<_, _, data::v4::Tag>("tags", tags, idempotency_key)
+                    .await?
+            }
+            _ => {
+                self.get_db_client()?
+                    .upsert_bulk("tags", tags, idempotency_key)
+                    .await?
+            }
+        };
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(result),
+        ))
+    }
+
+    /// Upserts multiple operators in a batch (insert or update on conflict). Entries are
+    /// `Operator` rows or local records sent through [`crate::models::AsRemote`].
+    pub async fn upsert_operators_batch<S: Serialize>(
+        &mut self,
+        operators: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<Operator>>> {
+        if operators.is_empty() {
+            return Ok(ResponseScout::new(
+                ResponseScoutStatus::Success,
+                Some(Vec::new()),
+            ));
+        }
+
+        let result = match self
+            .model_versions
+            .negotiate("operators", OPERATOR_MODEL_VERSION)?
+        {
+            1 => {
+                self.upsert_downgraded::<_, _, data::v2::Operator>(
+                    "operators",
+                    operators,
+                    idempotency_key,
+                )
+                .await?
+            }
+            _ => {
+                self.get_db_client()?
+                    .upsert_bulk("operators", operators, idempotency_key)
+                    .await?
+            }
+        };
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
         ))
     }
 
-    /// Upserts multiple tags in a batch (insert or update on conflict)
-    pub async fn upsert_tags_batch(&mut self, tags: &[Tag]) -> Result<ResponseScout<Vec<Tag>>> {
+    /// Upserts multiple event-session links in a batch (insert or update on conflict)
+    pub async fn upsert_event_session_links_batch(
+        &mut self,
+        links: &[EventSessionLink],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<EventSessionLink>>> {
         let db_client = self.get_db_client()?;
 
-        if tags.is_empty() {
+        if links.is_empty() {
             return Ok(ResponseScout::new(
                 ResponseScoutStatus::Success,
                 Some(Vec::new()),
             ));
         }
 
-        let result = db_client.upsert_bulk("tags", tags).await?;
+        let result = db_client
+            .upsert_bulk("event_session_links", links, idempotency_key)
+            .await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
         ))
     }
 
-    /// Upserts multiple operators in a batch (insert or update on conflict)
-    pub async fn upsert_operators_batch(
+    /// Creates a note on a session, e.g. by a reviewer after the session ended
+    pub async fn create_session_note(
+        &mut self,
+        note: &SessionNote,
+    ) -> Result<ResponseScout<SessionNote>> {
+        let db_client = self.get_db_client()?;
+        let result = db_client.insert("session_notes", note).await?;
+        Self::handle_insert_result(result)
+    }
+
+    /// Upserts multiple session notes in a batch (insert or update on conflict)
+    pub async fn upsert_session_notes_batch(
         &mut self,
-        operators: &[data::v2::Operator],
-    ) -> Result<ResponseScout<Vec<data::v2::Operator>>> {
+        notes: &[SessionNote],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<SessionNote>>> {
         let db_client = self.get_db_client()?;
 
-        if operators.is_empty() {
+        if notes.is_empty() {
             return Ok(ResponseScout::new(
                 ResponseScoutStatus::Success,
                 Some(Vec::new()),
             ));
         }
 
-        let result = db_client.upsert_bulk("operators", operators).await?;
+        let result = db_client
+            .upsert_bulk("session_notes", notes, idempotency_key)
+            .await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
         ))
     }
 
+    /// Gets the notes of a session, oldest first
+    pub async fn get_session_notes(
+        &mut self,
+        session_id: i64,
+    ) -> Result<ResponseScout<Vec<SessionNote>>> {
+        let db_client = self.get_db_client()?;
+        let results = db_client
+            .query(|client| {
+                client
+                    .from("session_notes")
+                    .select("*")
+                    .eq("session_id", session_id.to_string())
+                    .order("timestamp.asc")
+            })
+            .await?;
+        Ok(Self::handle_query_result(results))
+    }
+
+    /// Deletes a session note directly from the database
+    pub async fn delete_session_note(&mut self, note_id: i64) -> Result<ResponseScout<()>> {
+        let db_client = self.get_db_client()?;
+
+        db_client
+            .delete(|client| client.from("session_notes").eq("id", note_id.to_string()))
+            .await?;
+
+        Ok(ResponseScout::new(ResponseScoutStatus::Success, None))
+    }
+
+    /// Gets the event-session links of the given events
+    pub async fn get_event_session_links_for_events(
+        &mut self,
+        event_ids: &[i64],
+    ) -> Result<ResponseScout<Vec<EventSessionLink>>> {
+        let db_client = self.get_db_client()?;
+
+        let links: Vec<EventSessionLink> = db_client
+            .query_by_ids("event_session_links", "*", "event_id", event_ids)
+            .await?;
+        Ok(Self::success_response(links))
+    }
+
+    /// Gets the events of a session, including events of other sessions linked to it
+    pub async fn get_events_for_session(
+        &mut self,
+        session_id: i64,
+    ) -> Result<ResponseScout<Vec<Event>>> {
+        let db_client = self.get_db_client()?;
+
+        let mut events: Vec<Event> = db_client
+            .query(|client| {
+                client
+                    .from("events")
+                    .select("*")
+                    .eq("session_id", session_id.to_string())
+            })
+            .await?;
+
+        let links: Vec<EventSessionLink> = db_client
+            .query(|client| {
+                client
+                    .from("event_session_links")
+                    .select("*")
+                    .eq("session_id", session_id.to_string())
+            })
+            .await?;
+        let linked_ids: Vec<String> = links
+            .iter()
+            .filter(|link| !events.iter().any(|event| event.id == Some(link.event_id)))
+            .map(|link| link.event_id.to_string())
+            .collect();
+        if !linked_ids.is_empty() {
+            let linked: Vec<Event> = db_client
+                .query(|client| client.from("events").select("*").in_("id", linked_ids))
+                .await?;
+            events.extend(linked);
+        }
+
+        events.sort_by(|a, b| a.timestamp_observation.cmp(&b.timestamp_observation));
+        Ok(Self::success_response(events))
+    }
+
+    /// Appends (longitude, latitude) points to a session's remote track
+    pub async fn append_session_track(
+        &mut self,
+        session_id: i64,
+        points: &[(f64, f64)],
+    ) -> Result<ResponseScout<SessionTrackAppend>> {
+        if !self.capabilities.supports(Capability::SessionTrackAppend) {
+            return Err(anyhow!("Server does not support appending session tracks"));
+        }
+        let db_client = self.get_db_client()?;
+
+        let result: Result<Vec<SessionTrackAppend>> = db_client
+            .query(|client| {
+                client.rpc(
+                    Capability::SessionTrackAppend.rpc_name(),
+                    serde_json::json!({
+                        "session_id": session_id,
+                        "points": points
+                    })
+                    .to_string(),
+                )
+            })
+            .await;
+        let results = match result {
+            Ok(results) => results,
+            Err(e) => {
+                self.is_missing_rpc(Capability::SessionTrackAppend, &e);
+                return Err(e);
+            }
+        };
+
+        let appended = results
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Session {} not found for track append", session_id))?;
+        Ok(Self::success_response(appended))
+    }
+
+    /// Asks the server to roll up a device's connectivity older than `older_than_days`
+    /// whole days into daily summaries and delete the raw rows
+    pub async fn compact_connectivity(
+        &mut self,
+        device_id: i64,
+        older_than_days: u32,
+    ) -> Result<ResponseScout<ConnectivityCompaction>> {
+        if !self
+            .capabilities
+            .supports(Capability::ConnectivityCompaction)
+        {
+            return Err(anyhow!("Server does not support connectivity compaction"));
+        }
+        let db_client = self.get_db_client()?;
+
+        let result: Result<Vec<ConnectivityCompaction>> = db_client
+            .query(|client| {
+                client.rpc(
+                    Capability::ConnectivityCompaction.rpc_name(),
+                    serde_json::json!({
+                        "device_id": device_id,
+                        "older_than_days": older_than_days
+                    })
+                    .to_string(),
+                )
+            })
+            .await;
+        let results = match result {
+            Ok(results) => results,
+            Err(e) => {
+                self.is_missing_rpc(Capability::ConnectivityCompaction, &e);
+                return Err(e);
+            }
+        };
+
+        let compaction = results
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Connectivity compaction returned no result"))?;
+        Ok(Self::success_response(compaction))
+    }
+
     /// Updates an event directly in the database
     pub async fn update_event(
         &mut self,
@@ -1032,9 +2463,10 @@ impl ScoutClient {
         event: &Event,
     ) -> Result<ResponseScout<Event>> {
         let db_client = self.get_db_client()?;
+        let event = db_client.staged("events", event)?;
 
         let result = db_client
-            .update(event, |client| {
+            .update(&event, |client| {
                 client.from("events").eq("id", event_id.to_string())
             })
             .await?;
@@ -1050,6 +2482,42 @@ impl ScoutClient {
         ))
     }
 
+    /// Sets `is_public` on events directly in the database. Returns the number of events
+    /// updated.
+    pub async fn update_events_visibility(
+        &mut self,
+        event_ids: &[i64],
+        is_public: bool,
+    ) -> Result<ResponseScout<usize>> {
+        if event_ids.is_empty() {
+            return Ok(Self::success_response(0));
+        }
+        let db_client = self.get_db_client()?;
+        let result: Vec<serde_json::Value> = db_client
+            .update(&serde_json::json!({ "is_public": is_public }), |client| {
+                client
+                    .from("events")
+                    .in_("id", event_ids.iter().map(|id| id.to_string()))
+            })
+            .await?;
+        Ok(Self::success_response(result.len()))
+    }
+
+    /// Sets the media URL of an event directly in the database, once its file is uploaded
+    pub async fn update_event_media_url(
+        &mut self,
+        event_id: i64,
+        media_url: &str,
+    ) -> Result<ResponseScout<usize>> {
+        let db_client = self.get_db_client()?;
+        let result: Vec<serde_json::Value> = db_client
+            .update(&serde_json::json!({ "media_url": media_url }), |client| {
+                client.from("events").eq("id", event_id.to_string())
+            })
+            .await?;
+        Ok(Self::success_response(result.len()))
+    }
+
     /// Updates connectivity data directly in the database
     pub async fn update_connectivity(
         &mut self,
@@ -1249,37 +2717,175 @@ impl ScoutClient {
         Ok(Self::handle_query_result(results))
     }
 
-    /// Gets all artifacts for a herd (via sessions) directly from the database
+    /// Gets all artifacts for a herd (via sessions) directly from the database. Servers
+    /// without the database function are queried for artifacts of the herd's devices.
     pub async fn get_artifacts_by_herd(
         &mut self,
         herd_id: i64,
     ) -> Result<ResponseScout<Vec<Artifact>>> {
-        let db_client = self.get_db_client()?;
-
-        let client = db_client.get_client()?;
-        let response = client
-            .rpc(
-                "get_artifacts_for_herd",
-                serde_json::json!({
-                    "herd_id_caller": herd_id,
-                    "limit_caller": 1000,
-                    "offset_caller": 0
+        if self.capabilities.supports(Capability::ArtifactsForHerd) {
+            let result = self
+                .get_db_client()?
+                .query(|client| {
+                    client.rpc(
+                        Capability::ArtifactsForHerd.rpc_name(),
+                        serde_json::json!({
+                            "herd_id_caller": herd_id,
+                            "limit_caller": 1000,
+                            "offset_caller": 0
+                        })
+                        .to_string(),
+                    )
                 })
-                .to_string(),
-            )
-            .execute()
+                .await;
+            match result {
+                Ok(results) => return Ok(Self::handle_query_result(results)),
+                Err(e) if !self.is_missing_rpc(Capability::ArtifactsForHerd, &e) => return Err(e),
+                Err(_) => {}
+            }
+        }
+
+        let results = self
+            .get_db_client()?
+            .query(|client| {
+                client
+                    .from("artifacts")
+                    .select("*, devices!inner(herd_id)")
+                    .eq("devices.herd_id", herd_id.to_string())
+                    .order("created_at.desc")
+                    .limit(1000)
+            })
             .await?;
+        Ok(Self::handle_query_result(results))
+    }
 
-        let body = response.text().await?;
-        let results: Vec<Artifact> = serde_json::from_str(&body).map_err(|e| {
-            anyhow!(
-                "Failed to parse artifacts response: {} - Response: {}",
-                e,
-                body
-            )
-        })?;
+    /// Downloads an artifact's file to `dest`. `source` is an artifact ID, a storage path
+    /// such as `artifacts/1/2/clip.mp4`, or a URL. The file is written to `<dest>.part`
+    /// first, and an interrupted download resumes from it with a range request. When the
+    /// server sends a SHA-256 `Digest` header, the finished file must match it.
+    pub async fn download_artifact(
+        &mut self,
+        source: &str,
+        dest: &Path,
+    ) -> Result<DownloadedArtifact> {
+        let url = self.artifact_download_url(source).await?;
+        let mut part_path = dest.as_os_str().to_os_string();
+        part_path.push(".part");
+        let part_path = PathBuf::from(part_path);
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
 
-        Ok(Self::handle_query_result(results))
+        let http_client = self.http_options.client()?;
+        let api_key = self.config_db.get_supabase_api_key();
+        let mut resume_from = tokio::fs::metadata(&part_path)
+            .await
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        let mut response = loop {
+            let mut request = http_client
+                .get(&url)
+                .header("apikey", api_key)
+                .bearer_auth(api_key);
+            if resume_from > 0 {
+                request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+            }
+            let response = request.send().await?;
+            if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+                // The partial file no longer fits the object; start over
+                tokio::fs::remove_file(&part_path).await?;
+                resume_from = 0;
+                continue;
+            }
+            break response;
+        };
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download artifact {}: HTTP {}",
+                source,
+                response.status()
+            ));
+        }
+
+        let resumed_bytes = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+            resume_from
+        } else {
+            0
+        };
+        let expected_sha256 = digest_header_sha256(response.headers());
+        {
+            use tokio::io::AsyncWriteExt;
+
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed_bytes > 0)
+                .truncate(resumed_bytes == 0)
+                .open(&part_path)
+                .await?;
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+        }
+
+        let (sha256, size_bytes) = sha256_file(&part_path)?;
+        if let Some(expected) = expected_sha256.filter(|expected| *expected != sha256) {
+            tokio::fs::remove_file(&part_path).await?;
+            return Err(anyhow!(
+                "Checksum mismatch downloading artifact {}: expected {}, got {}",
+                source,
+                expected,
+                sha256
+            ));
+        }
+        tokio::fs::rename(&part_path, dest).await?;
+        if resumed_bytes > 0 {
+            logging::info!(
+                "Resumed download of {} after {} bytes",
+                source,
+                resumed_bytes
+            );
+        }
+        Ok(DownloadedArtifact {
+            path: dest.to_path_buf(),
+            size_bytes,
+            sha256,
+            resumed_bytes,
+        })
+    }
+
+    /// The storage URL of an artifact ID, storage path or URL
+    async fn artifact_download_url(&mut self, source: &str) -> Result<String> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(source.to_string());
+        }
+        let file_path = match source.parse::<i64>() {
+            Ok(artifact_id) => {
+                let db_client = self.get_db_client()?;
+                let artifacts: Vec<Artifact> = db_client
+                    .query(|client| {
+                        client
+                            .from("artifacts")
+                            .select("*")
+                            .eq("id", artifact_id.to_string())
+                    })
+                    .await?;
+                artifacts
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("Artifact {} not found", artifact_id))?
+                    .file_path
+            }
+            Err(_) => source.to_string(),
+        };
+        let rest_url = self.config_db.get_rest_url().trim_end_matches('/');
+        let base_url = rest_url.strip_suffix("/rest/v1").unwrap_or(rest_url);
+        let (bucket, object_path) = crate::storage::split_storage_path(&file_path);
+        Ok(format!(
+            "{}/storage/v1/object/{}/{}",
+            base_url, bucket, object_path
+        ))
     }
 
     /// Updates an artifact directly in the database
@@ -1322,6 +2928,7 @@ impl ScoutClient {
     pub async fn create_artifacts_batch(
         &mut self,
         artifacts: &[Artifact],
+        idempotency_key: Option<&str>,
     ) -> Result<ResponseScout<Vec<Artifact>>> {
         let db_client = self.get_db_client()?;
 
@@ -1333,7 +2940,9 @@ impl ScoutClient {
         }
 
         // Use bulk insert for better performance
-        let result = db_client.insert_bulk("artifacts", artifacts).await?;
+        let result = db_client
+            .insert_bulk("artifacts", artifacts, idempotency_key)
+            .await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
@@ -1354,7 +2963,7 @@ impl ScoutClient {
             ));
         }
 
-        let result = db_client.upsert_bulk("artifacts", artifacts).await?;
+        let result = db_client.upsert_bulk("artifacts", artifacts, None).await?;
         Ok(ResponseScout::new(
             ResponseScoutStatus::Success,
             Some(result),
@@ -1371,20 +2980,69 @@ impl ScoutClient {
         Self::handle_insert_result(result)
     }
 
-    /// Gets all heartbeats for a specific device
+    /// Creates multiple heartbeats in a batch, e.g. those queued while offline
+    pub async fn create_heartbeats_batch(
+        &mut self,
+        heartbeats: &[Heartbeat],
+        idempotency_key: Option<&str>,
+    ) -> Result<ResponseScout<Vec<Heartbeat>>> {
+        let db_client = self.get_db_client()?;
+
+        if heartbeats.is_empty() {
+            return Ok(ResponseScout::new(
+                ResponseScoutStatus::Success,
+                Some(Vec::new()),
+            ));
+        }
+
+        let result = db_client
+            .insert_bulk("heartbeats", heartbeats, idempotency_key)
+            .await?;
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(result),
+        ))
+    }
+
+    /// Gets every heartbeat of a device, newest first. Use
+    /// [`Self::query_heartbeats_by_device`] to limit the time range or page through them.
     pub async fn get_heartbeats_by_device(
         &mut self,
         device_id: i64,
+    ) -> Result<ResponseScout<Vec<Heartbeat>>> {
+        self.query_heartbeats_by_device(device_id, &HeartbeatQuery::default())
+            .await
+    }
+
+    /// Gets heartbeats of a device in the query's time range, newest first, a page at a
+    /// time if the query has a limit
+    pub async fn query_heartbeats_by_device(
+        &mut self,
+        device_id: i64,
+        query: &HeartbeatQuery,
     ) -> Result<ResponseScout<Vec<Heartbeat>>> {
         let db_client = self.get_db_client()?;
 
         let results = db_client
             .query(|client| {
-                client
+                let mut builder = client
                     .from("heartbeats")
                     .select("*")
-                    .eq("device_id", device_id.to_string())
-                    .order("timestamp.desc")
+                    .eq("device_id", device_id.to_string());
+                if let Some(since) = &query.since {
+                    builder = builder.gte("timestamp", since);
+                }
+                if let Some(until) = &query.until {
+                    builder = builder.lte("timestamp", until);
+                }
+                if let Some(filter) = query.cursor_filter() {
+                    builder = builder.or(filter);
+                }
+                builder = builder.order("timestamp.desc,id.desc");
+                match query.limit {
+                    Some(limit) => builder.limit(limit),
+                    None => builder,
+                }
             })
             .await?;
 
@@ -1394,6 +3052,22 @@ impl ScoutClient {
         ))
     }
 
+    /// Gets the newest heartbeat of a device; data is None if it has none
+    pub async fn get_latest_heartbeat(
+        &mut self,
+        device_id: i64,
+    ) -> Result<ResponseScout<Heartbeat>> {
+        let response = self
+            .query_heartbeats_by_device(device_id, &HeartbeatQuery::latest(1))
+            .await?;
+        Ok(ResponseScout::new(
+            response.status,
+            response
+                .data
+                .and_then(|heartbeats| heartbeats.into_iter().next()),
+        ))
+    }
+
     /// Deletes a heartbeat record by ID
     ///
     /// **Note:** This method is primarily intended for testing and cleanup purposes.
@@ -1432,8 +3106,13 @@ impl ScoutClient {
                 Some(Vec::new()),
             ));
         }
-        let result = db_client.insert_bulk("health_metrics", metrics).await?;
-        Ok(ResponseScout::new(ResponseScoutStatus::Success, Some(result)))
+        let result = db_client
+            .insert_bulk("health_metrics", metrics, None)
+            .await?;
+        Ok(ResponseScout::new(
+            ResponseScoutStatus::Success,
+            Some(result),
+        ))
     }
 
     /// Gets health metrics for a device, newest first. Optional limit.
@@ -1487,3 +3166,40 @@ impl ScoutClient {
         Ok(ResponseScout::new(ResponseScoutStatus::Success, None))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_query_pages_after_the_last_heartbeat() {
+        let heartbeat = |id: i64, timestamp: &str| Heartbeat {
+            id: Some(id),
+            timestamp: timestamp.to_string(),
+            ..Default::default()
+        };
+        let query = HeartbeatQuery {
+            since: Some("2024-01-01T00:00:00Z".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(query.cursor_filter(), None);
+
+        let page = [
+            heartbeat(7, "2024-01-02T00:00:00Z"),
+            heartbeat(5, "2024-01-01T12:00:00Z"),
+        ];
+        let next = query.next_page(&page).unwrap();
+        assert_eq!(next.since, query.since);
+        assert_eq!(next.cursor, Some(("2024-01-01T12:00:00Z".to_string(), 5)));
+        assert_eq!(
+            next.cursor_filter().unwrap(),
+            "timestamp.lt.\"2024-01-01T12:00:00Z\",and(timestamp.eq.\"2024-01-01T12:00:00Z\",id.lt.5)"
+        );
+
+        // A short page is the last one, and without a limit there is only one page
+        assert_eq!(next.next_page(&page[..1]), None);
+        assert_eq!(HeartbeatQuery::default().next_page(&page), None);
+        assert_eq!(query.next_page(&[]), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Export of image events and their tags as a COCO object detection dataset, so detectors
+//! can be retrained from field data. Tag classes are mapped to COCO categories by a
+//! [`TagTaxonomy`]; several classes can share a category.
+//!
+//! ```no_run
+//! # async fn run(mut sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::coco::{CocoMedia, CocoOptions, TagTaxonomy};
+//!
+//! let taxonomy = TagTaxonomy::new()
+//!     .with_category("elephant", Some("animal"))
+//!     .with_class("elephant_calf", "elephant")
+//!     .with_category("person", None);
+//! let options = CocoOptions {
+//!     min_confidence: Some(0.5),
+//!     taxonomy: Some(taxonomy),
+//!     media: CocoMedia::Download,
+//!     ..Default::default()
+//! };
+//! let dataset = sync_engine.export_coco(&options, "datasets/2024-06").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::{EventLocal, MediaType, TagLocal};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::Path;
+
+/// Where the images of an exported dataset point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CocoMedia {
+    /// `file_name` is the event's media URL, or else its local file path
+    #[default]
+    Reference,
+    /// Local media files are copied into `images/`; other images stay referenced
+    Copy,
+    /// Like `Copy`, downloading media that is only on the server
+    Download,
+}
+
+/// Maps tag class names to COCO categories, numbered from 1 in the order they are added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagTaxonomy {
+    categories: Vec<CocoCategory>,
+    classes: HashMap<String, u64>,
+}
+
+impl TagTaxonomy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One category per class name, in sorted order
+    pub fn from_class_names<'a>(class_names: impl IntoIterator<Item = &'a str>) -> Self {
+        let class_names: BTreeSet<&str> = class_names.into_iter().collect();
+        class_names.into_iter().fold(Self::new(), |taxonomy, name| {
+            taxonomy.with_category(name, None)
+        })
+    }
+
+    /// Adds a category; tags of the class with the same name fall under it
+    pub fn with_category(mut self, name: &str, supercategory: Option<&str>) -> Self {
+        if self.category(name).is_none() {
+            let id = self.categories.len() as u64 + 1;
+            self.categories.push(CocoCategory {
+                id,
+                name: name.to_string(),
+                supercategory: supercategory.map(str::to_string),
+            });
+            self.classes.insert(name.to_string(), id);
+        }
+        self
+    }
+
+    /// Puts tags of `class_name` under `category`, adding the category if needed
+    pub fn with_class(self, class_name: &str, category: &str) -> Self {
+        let mut taxonomy = self.with_category(category, None);
+        if let Some(id) = taxonomy.category(category).map(|category| category.id) {
+            taxonomy.classes.insert(class_name.to_string(), id);
+        }
+        taxonomy
+    }
+
+    /// Category ID of tags of `class_name`, None outside the taxonomy
+    pub fn category_id(&self, class_name: &str) -> Option<u64> {
+        self.classes.get(class_name).copied()
+    }
+
+    pub fn categories(&self) -> &[CocoCategory] {
+        &self.categories
+    }
+
+    fn category(&self, name: &str) -> Option<&CocoCategory> {
+        self.categories
+            .iter()
+            .find(|category| category.name == name)
+    }
+}
+
+/// Selection and layout of a COCO export (None = no constraint)
+#[derive(Debug, Clone, Default)]
+pub struct CocoOptions {
+    /// Only tags of these classes, and only events with at least one of them
+    pub tag_classes: Option<Vec<String>>,
+    /// Only events observed in this range
+    pub time_range: Option<Range<DateTime<Utc>>>,
+    /// Only tags at least this confident
+    pub min_confidence: Option<f64>,
+    /// Categories of the tag classes, one per exported class by default. Tags of classes
+    /// outside the taxonomy are left out.
+    pub taxonomy: Option<TagTaxonomy>,
+    /// Width and height written for every image, e.g. the camera resolution. Tag boxes
+    /// are written in the units they were recorded in.
+    pub image_size: Option<(u32, u32)>,
+    pub media: CocoMedia,
+}
+
+/// A COCO dataset, as written to `annotations.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoDataset {
+    pub info: CocoInfo,
+    pub images: Vec<CocoImage>,
+    pub annotations: Vec<CocoAnnotation>,
+    pub categories: Vec<CocoCategory>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoInfo {
+    pub description: String,
+    pub date_created: String,
+}
+
+/// One image event. `scout_event_id` and `scout_event_id_local` link it back to the event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    pub date_captured: String,
+    pub scout_event_id: Option<i64>,
+    pub scout_event_id_local: Option<String>,
+}
+
+/// One tag. `bbox` is `[left, top, width, height]` and `score` the tag's confidence.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoAnnotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u64,
+    pub bbox: [f64; 4],
+    pub area: f64,
+    pub iscrowd: u8,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoCategory {
+    pub id: u64,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supercategory: Option<String>,
+}
+
+/// Builds the dataset of the image events and tags selected by `options`, along with the
+/// event of each image. Images are ordered by observation time.
+pub(crate) fn build_dataset(
+    events: Vec<EventLocal>,
+    tags: Vec<TagLocal>,
+    options: &CocoOptions,
+) -> (CocoDataset, Vec<EventLocal>) {
+    let mut events: Vec<EventLocal> = events
+        .into_iter()
+        .filter(|event| {
+            event.media_type == MediaType::Image
+                && options.time_range.as_ref().is_none_or(|range| {
+                    DateTime::parse_from_rfc3339(&event.timestamp_observation)
+                        .is_ok_and(|observed| range.contains(&observed.with_timezone(&Utc)))
+                })
+        })
+        .collect();
+    events.sort_by(|a, b| {
+        (&a.timestamp_observation, &a.id_local).cmp(&(&b.timestamp_observation, &b.id_local))
+    });
+
+    let mut tags_by_event: HashMap<String, Vec<TagLocal>> = HashMap::new();
+    for tag in tags {
+        let selected = options
+            .tag_classes
+            .as_ref()
+            .is_none_or(|classes| classes.contains(&tag.class_name))
+            && options.min_confidence.is_none_or(|min| tag.conf >= min);
+        if let (true, Some(event_id)) = (selected, tag.ancestor_id_local.clone()) {
+            tags_by_event.entry(event_id).or_default().push(tag);
+        }
+    }
+    let taxonomy = options.taxonomy.clone().unwrap_or_else(|| {
+        TagTaxonomy::from_class_names(
+            tags_by_event
+                .values()
+                .flatten()
+                .map(|tag| tag.class_name.as_str()),
+        )
+    });
+
+    let mut dataset = CocoDataset {
+        info: CocoInfo {
+            description: "Scout events and tags".to_string(),
+            date_created: Utc::now().to_rfc3339(),
+        },
+        categories: taxonomy.categories().to_vec(),
+        ..Default::default()
+    };
+    let mut image_events = Vec::new();
+    for event in events {
+        let event_tags = event
+            .id_local
+            .as_ref()
+            .and_then(|id| tags_by_event.remove(id))
+            .unwrap_or_default();
+        if options.tag_classes.is_some() && event_tags.is_empty() {
+            continue;
+        }
+        let image_id = dataset.images.len() as u64 + 1;
+        for tag in event_tags {
+            let Some(category_id) = taxonomy.category_id(&tag.class_name) else {
+                continue;
+            };
+            dataset.annotations.push(CocoAnnotation {
+                id: dataset.annotations.len() as u64 + 1,
+                image_id,
+                category_id,
+                // Tag x and y are box centers
+                bbox: [
+                    tag.x - tag.width / 2.0,
+                    tag.y - tag.height / 2.0,
+                    tag.width,
+                    tag.height,
+                ],
+                area: tag.width * tag.height,
+                iscrowd: 0,
+                score: tag.conf,
+            });
+        }
+        dataset.images.push(CocoImage {
+            id: image_id,
+            file_name: event
+                .media_url
+                .clone()
+                .or_else(|| event.file_path.clone())
+                .unwrap_or_default(),
+            width: options.image_size.map(|(width, _)| width),
+            height: options.image_size.map(|(_, height)| height),
+            date_captured: event.timestamp_observation.clone(),
+            scout_event_id: event.id,
+            scout_event_id_local: event.id_local.clone(),
+        });
+        image_events.push(event);
+    }
+    (dataset, image_events)
+}
+
+/// Path of an event's media within the dataset, keeping the extension of its source
+pub(crate) fn media_file_name(event: &EventLocal) -> String {
+    let extension = event
+        .file_path
+        .as_deref()
+        .or(event.media_url.as_deref())
+        .map(|source| source.split(['?', '#']).next().unwrap_or(source))
+        .and_then(|source| Path::new(source).extension())
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("jpg");
+    format!(
+        "images/{}.{}",
+        event.id_local.as_deref().unwrap_or("unknown"),
+        extension
+    )
+}
@@ -0,0 +1,475 @@
+//! Model generation from the database schema (`codegen` feature). Reads the OpenAPI
+//! description PostgREST serves at the API root, generates remote/local model structs with
+//! their conversions in the style of `models/`, and reports where the current models have
+//! drifted from the schema.
+//!
+//! Generated code is a starting point for a new model version: pick the native_model id
+//! and version, then add secondary keys and the `AncestorLocal`/`RemoteIdIndexed` impls the
+//! model needs by hand.
+
+use crate::db_client::DatabaseConfig;
+use crate::models::{
+    Artifact, Connectivity, Device, Event, HealthMetric, Heartbeat, Herd, Operator, Plan, Session,
+    Tag,
+};
+use anyhow::{anyhow, Result};
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Remote tables with a hand-written model, checked by [`diff_known_models`]
+pub fn known_models() -> Vec<(&'static str, &'static [&'static str])> {
+    vec![
+        ("artifacts", model_fields::<Artifact>()),
+        ("connectivity", model_fields::<Connectivity>()),
+        ("devices", model_fields::<Device>()),
+        ("events", model_fields::<Event>()),
+        ("health_metrics", model_fields::<HealthMetric>()),
+        ("heartbeats", model_fields::<Heartbeat>()),
+        ("herds", model_fields::<Herd>()),
+        ("operators", model_fields::<Operator>()),
+        ("plans", model_fields::<Plan>()),
+        ("sessions", model_fields::<Session>()),
+        ("tags", model_fields::<Tag>()),
+    ]
+}
+
+/// A column of a table, as described by PostgREST
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// Postgres type, e.g. `bigint` or `timestamp with time zone`
+    pub format: String,
+    pub rust_type: String,
+    pub nullable: bool,
+    /// Filled in by the database when omitted on insert
+    pub has_default: bool,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+/// Differences between a model and its table
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModelDiff {
+    pub table: String,
+    /// Columns the model does not have (not sent or received)
+    pub missing_in_model: Vec<String>,
+    /// Model fields with no column (rejected by the server on write)
+    pub missing_in_schema: Vec<String>,
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing_in_model.is_empty() && self.missing_in_schema.is_empty()
+    }
+}
+
+/// Fetches the OpenAPI description PostgREST serves at the API root
+pub async fn fetch_openapi(config: &DatabaseConfig) -> Result<Value> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/", config.get_rest_url().trim_end_matches('/')))
+        .header("apikey", config.get_supabase_api_key())
+        .header("api_key", config.get_scout_api_key())
+        .header(reqwest::header::ACCEPT, "application/openapi+json")
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Failed to fetch schema: HTTP {} - {}",
+            status,
+            response.text().await?
+        ));
+    }
+    Ok(response.json().await?)
+}
+
+/// Reads the tables (and views) from a PostgREST OpenAPI description, sorted by name
+pub fn table_schemas(openapi: &Value) -> Result<Vec<TableSchema>> {
+    let definitions = openapi
+        .get("definitions")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("OpenAPI description has no definitions"))?;
+
+    let mut tables: Vec<TableSchema> = definitions
+        .iter()
+        .map(|(name, definition)| {
+            let required: Vec<&str> = definition
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|required| required.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let columns = definition
+                .get("properties")
+                .and_then(Value::as_object)
+                .map(|properties| {
+                    properties
+                        .iter()
+                        .map(|(column, property)| {
+                            column_schema(column, property, required.contains(&column.as_str()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            TableSchema {
+                name: name.clone(),
+                columns,
+            }
+        })
+        .collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tables)
+}
+
+fn column_schema(name: &str, property: &Value, required: bool) -> ColumnSchema {
+    let format = property
+        .get("format")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let rust_type = match format.as_str() {
+        "bigint" => "i64".to_string(),
+        "integer" => "i32".to_string(),
+        "smallint" => "i16".to_string(),
+        "real" => "f32".to_string(),
+        "double precision" | "numeric" => "f64".to_string(),
+        "boolean" => "bool".to_string(),
+        "json" | "jsonb" => "serde_json::Value".to_string(),
+        format if format.ends_with("[]") => {
+            match property.pointer("/items/type").and_then(Value::as_str) {
+                Some("string") => "Vec<String>".to_string(),
+                Some("integer") => "Vec<i64>".to_string(),
+                Some("number") => "Vec<f64>".to_string(),
+                _ => "Vec<serde_json::Value>".to_string(),
+            }
+        }
+        // Text, timestamps, UUIDs, enums and geometries are exchanged as strings
+        _ => "String".to_string(),
+    };
+    // PostgREST appends notes (primary/foreign key) to the column comment
+    let description = property
+        .get("description")
+        .and_then(Value::as_str)
+        .and_then(|description| description.split("Note:").next())
+        .map(str::trim)
+        .filter(|description| !description.is_empty())
+        .map(str::to_string);
+    ColumnSchema {
+        name: name.to_string(),
+        format,
+        rust_type,
+        nullable: !required,
+        has_default: property.get("default").is_some(),
+        description,
+    }
+}
+
+/// Compares a model's fields with its table's columns
+pub fn diff_model(table: &TableSchema, fields: &[&str]) -> ModelDiff {
+    ModelDiff {
+        table: table.name.clone(),
+        missing_in_model: table
+            .columns
+            .iter()
+            .filter(|column| !fields.contains(&column.name.as_str()))
+            .map(|column| column.name.clone())
+            .collect(),
+        missing_in_schema: fields
+            .iter()
+            .filter(|field| table.column(field).is_none())
+            .map(|field| field.to_string())
+            .collect(),
+    }
+}
+
+/// Diffs every model in [`known_models`] against the schema. Tables missing from the
+/// schema are reported with all model fields missing.
+pub fn diff_known_models(tables: &[TableSchema]) -> Vec<ModelDiff> {
+    known_models()
+        .into_iter()
+        .map(
+            |(name, fields)| match tables.iter().find(|table| table.name == name) {
+                Some(table) => diff_model(table, fields),
+                None => ModelDiff {
+                    table: name.to_string(),
+                    missing_in_model: Vec::new(),
+                    missing_in_schema: fields.iter().map(|field| field.to_string()).collect(),
+                },
+            },
+        )
+        .collect()
+}
+
+/// `event_session_links` -> `EventSessionLink`
+pub fn struct_name(table: &str) -> String {
+    let singular = match table.strip_suffix('s') {
+        Some(singular) if !singular.ends_with('s') => singular,
+        _ => table,
+    };
+    singular
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Generates `<Name>` (remote) and `<Name>Local` (stored) structs for a table, with
+/// `Default`, `Syncable` and conversions both ways
+pub fn generate_model(table: &TableSchema, native_model_id: u32, version: u32) -> String {
+    let name = struct_name(&table.name);
+    let columns: Vec<&ColumnSchema> = table
+        .columns
+        .iter()
+        .filter(|column| column.name != "id")
+        .collect();
+    let field_type = |column: &ColumnSchema| {
+        if column.nullable {
+            format!("Option<{}>", column.rust_type)
+        } else {
+            column.rust_type.clone()
+        }
+    };
+    let field = |column: &ColumnSchema| field_ident(&column.name);
+
+    let mut code = String::new();
+    let _ = writeln!(
+        code,
+        "// ===== {} V{} GENERATED FROM THE {} SCHEMA =====",
+        name.to_uppercase(),
+        version,
+        table.name.to_uppercase()
+    );
+    let _ = writeln!(
+        code,
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]"
+    );
+    let _ = writeln!(
+        code,
+        "#[native_model(id = {}, version = {})]",
+        native_model_id, version
+    );
+    let _ = writeln!(code, "#[native_db]");
+    let _ = writeln!(code, "pub struct {}Local {{", name);
+    let _ = writeln!(code, "    #[secondary_key(optional)]");
+    let _ = writeln!(code, "    pub id: Option<i64>,");
+    let _ = writeln!(code, "    #[primary_key]");
+    let _ = writeln!(code, "    pub id_local: Option<String>,");
+    for column in &columns {
+        if let Some(description) = &column.description {
+            let _ = writeln!(code, "    /// {}", description.replace('\n', " "));
+        }
+        let _ = writeln!(code, "    pub {}: {},", field(column), field_type(column));
+    }
+    let _ = writeln!(code, "}}\n");
+
+    let _ = writeln!(
+        code,
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]"
+    );
+    let _ = writeln!(code, "pub struct {} {{", name);
+    let _ = writeln!(
+        code,
+        "    #[serde(skip_serializing_if = \"Option::is_none\")]"
+    );
+    let _ = writeln!(code, "    pub id: Option<i64>,");
+    for column in &columns {
+        if column.nullable && column.has_default {
+            let _ = writeln!(
+                code,
+                "    #[serde(skip_serializing_if = \"Option::is_none\")]"
+            );
+        }
+        let _ = writeln!(code, "    pub {}: {},", field(column), field_type(column));
+    }
+    let _ = writeln!(code, "}}\n");
+
+    let _ = writeln!(code, "impl Default for {}Local {{", name);
+    let _ = writeln!(code, "    fn default() -> Self {{");
+    let _ = writeln!(code, "        Self {{");
+    let _ = writeln!(code, "            id: None,");
+    let _ = writeln!(code, "            id_local: None,");
+    for column in &columns {
+        let default = if column.nullable {
+            "None"
+        } else {
+            match column.rust_type.as_str() {
+                "String" => "String::new()",
+                "f32" | "f64" => "0.0",
+                "bool" => "false",
+                "serde_json::Value" => "serde_json::Value::Null",
+                rust_type if rust_type.starts_with("Vec<") => "Vec::new()",
+                _ => "0",
+            }
+        };
+        let _ = writeln!(code, "            {}: {},", field(column), default);
+    }
+    let _ = writeln!(code, "        }}\n    }}\n}}\n");
+
+    let _ = writeln!(code, "impl super::v1::Syncable for {}Local {{", name);
+    let _ = writeln!(
+        code,
+        "    fn id(&self) -> Option<i64> {{\n        self.id\n    }}\n"
+    );
+    let _ = writeln!(
+        code,
+        "    fn set_id(&mut self, id: i64) {{\n        self.id = Some(id);\n    }}\n"
+    );
+    let _ = writeln!(
+        code,
+        "    fn id_local(&self) -> Option<String> {{\n        self.id_local.clone()\n    }}\n"
+    );
+    let _ = writeln!(
+        code,
+        "    fn set_id_local(&mut self, id_local: String) {{\n        self.id_local = Some(id_local);\n    }}\n}}\n"
+    );
+
+    for (from, to, source) in [
+        (format!("{}Local", name), name.clone(), "local"),
+        (name.clone(), format!("{}Local", name), "remote"),
+    ] {
+        let _ = writeln!(code, "impl From<{}> for {} {{", from, to);
+        let _ = writeln!(code, "    fn from({}: {}) -> Self {{", source, from);
+        let _ = writeln!(code, "        Self {{");
+        let _ = writeln!(code, "            id: {}.id,", source);
+        if source == "remote" {
+            let _ = writeln!(code, "            id_local: None,");
+        }
+        for column in &columns {
+            let _ = writeln!(
+                code,
+                "            {}: {}.{},",
+                field(column),
+                source,
+                field(column)
+            );
+        }
+        let _ = writeln!(code, "        }}\n    }}\n}}\n");
+    }
+    code.truncate(code.trim_end().len());
+    code.push('\n');
+    code
+}
+
+fn field_ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "const", "crate", "dyn", "else", "enum", "fn", "for", "if",
+        "impl", "in", "let", "loop", "match", "mod", "move", "ref", "static", "struct", "super",
+        "trait", "type", "use", "where", "while",
+    ];
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Serialized field names of a model, read from its `Deserialize` impl so fields skipped
+/// when serializing are included
+pub fn model_fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields.unwrap_or_default()
+}
+
+/// Deserializer that only records the field names a struct asks for
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only struct field names are read"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(de::Error::custom("only struct field names are read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_generates_models_and_diffs_from_openapi() -> Result<()> {
+        let openapi = json!({
+            "definitions": {
+                "heartbeats": {
+                    "required": ["id", "timestamp", "device_id"],
+                    "properties": {
+                        "id": {"format": "bigint", "type": "integer"},
+                        "created_at": {"format": "timestamp with time zone", "type": "string", "default": "now()"},
+                        "timestamp": {"format": "timestamp with time zone", "type": "string"},
+                        "device_id": {"format": "bigint", "type": "integer", "description": "Reporting device\n\nNote:\nThis is a Foreign Key to `devices.id`.<fk table='devices' column='id'/>"},
+                        "firmware": {"format": "text", "type": "string"},
+                        "type": {"format": "public.heartbeat_type", "type": "string", "enum": ["boot"]},
+                        "uptime_s": {"format": "real", "type": "number"}
+                    }
+                }
+            }
+        });
+        let tables = table_schemas(&openapi)?;
+        assert_eq!(tables.len(), 1);
+        let heartbeats = &tables[0];
+        assert_eq!(
+            heartbeats
+                .column("device_id")
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Reporting device")
+        );
+
+        let code = generate_model(heartbeats, 30, 1);
+        assert!(code.contains("#[native_model(id = 30, version = 1)]"));
+        assert!(code.contains("pub struct HeartbeatLocal {"));
+        assert!(code.contains("    pub device_id: i64,"));
+        assert!(code.contains("    pub r#type: Option<String>,"));
+        assert!(code.contains("    pub uptime_s: Option<f32>,"));
+        assert!(code.contains("            r#type: remote.r#type,"));
+
+        // Heartbeat skips `id` when serializing, but it still counts as a field
+        let fields = model_fields::<Heartbeat>();
+        assert!(fields.contains(&"id"));
+        let diff = diff_model(heartbeats, fields);
+        assert_eq!(diff.missing_in_model, vec!["firmware", "type", "uptime_s"]);
+        assert!(!diff.missing_in_schema.contains(&"id".to_string()));
+
+        let diffs = diff_known_models(&tables);
+        let events = diffs.iter().find(|diff| diff.table == "events").unwrap();
+        assert!(events
+            .missing_in_schema
+            .contains(&"parent_event_id".to_string()));
+        Ok(())
+    }
+}
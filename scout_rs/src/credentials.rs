@@ -0,0 +1,216 @@
+//! Hashes of operator PINs and badges for the tokens [`SyncEngine::authenticate_operator`]
+//! caches, so a credential is never stored in plain text. Hashes are PBKDF2-HMAC-SHA256
+//! with a random salt and the device API key mixed into the salt, so cached hashes are not
+//! portable between devices.
+//!
+//! [`SyncEngine::authenticate_operator`]: crate::sync::SyncEngine::authenticate_operator
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::ops::RangeInclusive;
+
+use crate::models::OperatorCredentialType;
+
+/// PBKDF2 iterations of new hashes
+pub(crate) const ITERATIONS: u32 = 100_000;
+
+/// Iterations accepted from a stored hash, so a corrupt row cannot stall authentication
+const ACCEPTED_ITERATIONS: RangeInclusive<u32> = 10_000..=1_000_000;
+
+const SCHEME: &str = "pbkdf2-sha256";
+
+fn password(credential: &str, credential_type: OperatorCredentialType) -> String {
+    let credential_type = match credential_type {
+        OperatorCredentialType::Pin => "pin",
+        OperatorCredentialType::Badge => "badge",
+    };
+    format!("{}:{}", credential_type, credential)
+}
+
+fn derive(
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+    salt: &[u8],
+    iterations: u32,
+) -> [u8; 32] {
+    let salt = [salt, device_key.as_bytes()].concat();
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        password(credential, credential_type).as_bytes(),
+        &salt,
+        iterations,
+        &mut hash,
+    );
+    hash
+}
+
+/// Iterations to verify a stored hash with, within [`ACCEPTED_ITERATIONS`]
+fn accepted_iterations(stored: u32) -> u32 {
+    stored.clamp(*ACCEPTED_ITERATIONS.start(), *ACCEPTED_ITERATIONS.end())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Short keyed digest of a credential that narrows cached tokens to the few worth
+/// verifying. One byte only, so it tells little about the credential on its own.
+pub(crate) fn key_id(
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(device_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(password(credential, credential_type).as_bytes());
+    hex(&mac.finalize().into_bytes()[..1])
+}
+
+/// Hash of a credential with a fresh random salt, as
+/// `pbkdf2-sha256$<iterations>$<salt>$<hash>`
+pub(crate) fn hash(
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+) -> Result<String> {
+    let mut salt = [0u8; 16];
+    getrandom::getrandom(&mut salt)
+        .map_err(|e| anyhow!("Failed to generate a credential salt: {}", e))?;
+    let hash = derive(credential, credential_type, device_key, &salt, ITERATIONS);
+    Ok(format!(
+        "{}${}${}${}",
+        SCHEME,
+        ITERATIONS,
+        hex(&salt),
+        hex(&hash)
+    ))
+}
+
+/// Whether `credential_hash` from [`hash`] is a hash of the credential. Hashes in any
+/// other format never match.
+pub(crate) fn verify(
+    credential_hash: &str,
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+) -> bool {
+    let mut fields = credential_hash.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(hash), None) = (
+        fields.next(),
+        fields.next().and_then(|field| field.parse::<u32>().ok()),
+        fields.next().and_then(unhex),
+        fields.next().and_then(unhex),
+        fields.next(),
+    ) else {
+        return false;
+    };
+    let derived = derive(
+        credential,
+        credential_type,
+        device_key,
+        &salt,
+        accepted_iterations(iterations),
+    );
+    // Compared in constant time, so timing does not leak how much of a guess matches
+    hash.len() == derived.len()
+        && hash
+            .iter()
+            .zip(derived)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_verify_only_their_credential() -> Result<()> {
+        let credential_hash = hash("1234", OperatorCredentialType::Pin, "device-key")?;
+        // Salted per hash, so equal credentials hash differently
+        assert_ne!(
+            hash("1234", OperatorCredentialType::Pin, "device-key")?,
+            credential_hash
+        );
+        assert!(verify(
+            &credential_hash,
+            "1234",
+            OperatorCredentialType::Pin,
+            "device-key"
+        ));
+        assert!(!verify(
+            &credential_hash,
+            "1235",
+            OperatorCredentialType::Pin,
+            "device-key"
+        ));
+        assert!(!verify(
+            &credential_hash,
+            "1234",
+            OperatorCredentialType::Badge,
+            "device-key"
+        ));
+        assert!(!verify(
+            &credential_hash,
+            "1234",
+            OperatorCredentialType::Pin,
+            "other-device-key"
+        ));
+        assert!(!verify(
+            "1234",
+            "1234",
+            OperatorCredentialType::Pin,
+            "device-key"
+        ));
+
+        assert_eq!(
+            key_id("1234", OperatorCredentialType::Pin, "device-key"),
+            key_id("1234", OperatorCredentialType::Pin, "device-key")
+        );
+        assert_eq!(
+            key_id("1234", OperatorCredentialType::Pin, "device-key").len(),
+            2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stored_iterations_are_clamped() {
+        assert_eq!(accepted_iterations(u32::MAX), 1_000_000);
+        assert_eq!(accepted_iterations(ITERATIONS), ITERATIONS);
+
+        // A row asking for a single iteration is verified with the fewest accepted
+        let salt = [7u8; 16];
+        let stored = |derived_with: u32| {
+            let derived = derive(
+                "1234",
+                OperatorCredentialType::Pin,
+                "key",
+                &salt,
+                derived_with,
+            );
+            format!("{}$1${}${}", SCHEME, hex(&salt), hex(&derived))
+        };
+        assert!(verify(
+            &stored(10_000),
+            "1234",
+            OperatorCredentialType::Pin,
+            "key"
+        ));
+        assert!(!verify(
+            &stored(1),
+            "1234",
+            OperatorCredentialType::Pin,
+            "key"
+        ));
+    }
+}
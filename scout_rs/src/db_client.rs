@@ -1,6 +1,13 @@
+#[cfg(feature = "chaos")]
+use crate::chaos::FailureInjector;
+use crate::logging;
+use crate::secrets::{EnvSecretProvider, SecretProvider, SCOUT_API_KEY, SUPABASE_API_KEY};
+use crate::trace::{TraceContext, TRACEPARENT_HEADER};
 use anyhow::{anyhow, Result};
 use postgrest::Postgrest;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
@@ -20,8 +27,16 @@ impl DatabaseConfig {
     pub fn from_env() -> Result<Self> {
         dotenv::dotenv().ok();
 
-        let mut rest_url = std::env::var("SCOUT_DATABASE_REST_URL")
+        let rest_url = std::env::var("SCOUT_DATABASE_REST_URL")
             .map_err(|_| anyhow!("SCOUT_DATABASE_REST_URL environment variable is required"))?;
+        Self::from_secrets(&rest_url, &EnvSecretProvider)
+    }
+
+    /// Creates a database config for `rest_url` with the API keys of `secrets`, named
+    /// [`SCOUT_API_KEY`] and [`SUPABASE_API_KEY`], so the keys need not be in the
+    /// environment
+    pub fn from_secrets(rest_url: &str, secrets: &dyn SecretProvider) -> Result<Self> {
+        let mut rest_url = rest_url.to_string();
 
         // Ensure the URL has the correct PostgREST path
         if !rest_url.ends_with("/rest/v1") {
@@ -32,12 +47,15 @@ impl DatabaseConfig {
             }
         }
 
-        // Use provided API key or fall back to environment variable
-        let scout_api_key = std::env::var("SCOUT_DEVICE_API_KEY")
-            .map_err(|_| anyhow!("SCOUT_DEVICE_API_KEY environment variable not found"))?;
+        let scout_api_key = secrets
+            .get(SCOUT_API_KEY)?
+            .ok_or_else(|| anyhow!("{} secret not found", SCOUT_API_KEY))?;
 
-        let supabase_api_key = std::env::var("SUPABASE_PUBLIC_API_KEY").map_err(|_| {
-            anyhow!("SUPABASE_PUBLIC_API_KEY environment variable is required for Supabase access")
+        let supabase_api_key = secrets.get(SUPABASE_API_KEY)?.ok_or_else(|| {
+            anyhow!(
+                "{} secret is required for Supabase access",
+                SUPABASE_API_KEY
+            )
         })?;
 
         Ok(DatabaseConfig {
@@ -63,9 +81,246 @@ impl DatabaseConfig {
     }
 }
 
+/// HTTP behavior of database requests, set through [`crate::client::ScoutClientBuilder`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpOptions {
+    /// Time to establish a connection, None for reqwest's default (none)
+    pub connect_timeout: Option<Duration>,
+    /// Time for a whole request including the response body, None for no limit
+    pub request_timeout: Option<Duration>,
+    /// Retries of a request that failed to connect, timed out or got a 429, 502, 503 or
+    /// 504 response. Writes are safe to retry: bulk writes carry an idempotency key.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub retry_backoff: Duration,
+    /// Requests sent per second at most, None for no limit
+    pub max_requests_per_second: Option<f64>,
+    pub user_agent: Option<String>,
+    /// Proxy for all requests, e.g. `http://proxy:3128`. `socks5://` proxies need
+    /// reqwest's `socks` feature enabled by the application.
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            request_timeout: None,
+            max_retries: 0,
+            retry_backoff: Duration::from_millis(500),
+            max_requests_per_second: None,
+            user_agent: None,
+            proxy: None,
+        }
+    }
+}
+
+impl HttpOptions {
+    /// An HTTP client with the connect timeout, user agent and proxy
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy)
+                    .map_err(|e| anyhow!("Invalid proxy {}: {}", proxy, e))?,
+            );
+        }
+        Ok(builder.build()?)
+    }
+
+    fn is_retryable(result: &reqwest::Result<reqwest::Response>) -> bool {
+        match result {
+            Ok(response) => matches!(response.status().as_u16(), 429 | 502 | 503 | 504),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        }
+    }
+}
+
+/// Header carrying the idempotency key of a bulk write (see migration 09)
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Number of GET responses kept for conditional requests by default
+pub const DEFAULT_RESPONSE_CACHE_CAPACITY: usize = 32;
+
+/// Columns the staging marker is put before, per table; see
+/// [`ScoutDbClient::set_staging_marker`]
+pub const STAGING_MARKED_COLUMNS: [(&str, &str); 2] =
+    [("sessions", "software_version"), ("events", "message")];
+
+/// Fails for an empty marker or one with a PostgREST `like` wildcard or the `like` escape
+/// character, which would let purging match rows without the marker
+pub(crate) fn validate_staging_marker(marker: &str) -> Result<()> {
+    if marker.trim().is_empty() {
+        return Err(anyhow!("Staging marker must not be empty"));
+    }
+    if marker.contains(['*', '%', '_', '\\']) {
+        return Err(anyhow!(
+            "Staging marker {:?} must not contain the wildcards *, % or _, or a backslash",
+            marker
+        ));
+    }
+    Ok(())
+}
+
+/// Length budget of the `in.(...)` list of one ID query, well below the 8 KB URL limit of
+/// common proxies once the rest of the URL is added
+const MAX_ID_FILTER_LENGTH: usize = 4000;
+
+/// Splits IDs, without duplicates, into lists whose URL-encoded `in.(...)` filter fits in
+/// `max_length` characters
+fn id_chunks(ids: &[i64], max_length: usize) -> Vec<Vec<i64>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut chunks: Vec<Vec<i64>> = Vec::new();
+    let mut length = 0;
+    for &id in ids.iter().filter(|id| seen.insert(**id)) {
+        // Digits plus an encoded comma (%2C)
+        let id_length = id.to_string().len() + 3;
+        match chunks.last_mut() {
+            Some(chunk) if length + id_length <= max_length => chunk.push(id),
+            _ => {
+                chunks.push(vec![id]);
+                length = 0;
+            }
+        }
+        length += id_length;
+    }
+    chunks
+}
+
+/// A GET response body and the ETag it was served with
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    url: String,
+    etag: String,
+    body: String,
+}
+
+/// A write the server rejected, kept for debugging schema mismatches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedPayload {
+    pub timestamp: String,
+    pub table: String,
+    pub operation: String,
+    /// Request body with credential-like fields redacted
+    pub request: serde_json::Value,
+    pub response: String,
+}
+
+/// Error body PostgREST returns for a rejected request. Failed requests carry it as the
+/// source of their `anyhow::Error`; see [`PostgrestError::from_error`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostgrestError {
+    /// SQLSTATE code (e.g. `23505`) or PostgREST code (e.g. `PGRST116`)
+    pub code: String,
+    pub message: String,
+    pub details: Option<String>,
+    pub hint: Option<String>,
+}
+
+impl PostgrestError {
+    pub const UNIQUE_VIOLATION: &'static str = "23505";
+    pub const FOREIGN_KEY_VIOLATION: &'static str = "23503";
+    pub const INSUFFICIENT_PRIVILEGE: &'static str = "42501";
+    /// PostgREST found no function with the called name and arguments
+    pub const FUNCTION_NOT_FOUND: &'static str = "PGRST202";
+    /// PostgREST found no table or view with the requested name
+    pub const TABLE_NOT_FOUND: &'static str = "PGRST205";
+
+    /// The PostgREST error behind `error`, if the server rejected the request with one
+    pub fn from_error(error: &anyhow::Error) -> Option<&PostgrestError> {
+        error.downcast_ref()
+    }
+
+    /// Parses a response body, ignoring bodies that are not PostgREST errors
+    fn from_body(body: &str) -> Option<PostgrestError> {
+        serde_json::from_str(body).ok()
+    }
+
+    pub fn is_unique_violation(&self) -> bool {
+        self.code == Self::UNIQUE_VIOLATION
+    }
+}
+
+impl std::fmt::Display for PostgrestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Database error {}: {}", self.code, self.message)?;
+        if let Some(details) = &self.details {
+            write!(f, " ({})", details)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, " Hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PostgrestError {}
+
+/// Error for a response body that did not parse as rows. `operation` prefixes the
+/// message of bodies that are not PostgREST errors, e.g. "Database bulk insert".
+fn response_error(operation: &str, body: &str) -> anyhow::Error {
+    if let Some(error) = PostgrestError::from_body(body) {
+        return error.into();
+    }
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(error_response) => {
+            if let Some(error_msg) = error_response.get("error") {
+                anyhow!("{} error: {}", operation, error_msg)
+            } else if let Some(message) = error_response.get("message") {
+                anyhow!("{} message: {}", operation, message)
+            } else {
+                anyhow!("{} returned unexpected format: {}", operation, body)
+            }
+        }
+        Err(_) => anyhow!(
+            "Failed to parse {} response as JSON: {}",
+            operation.to_lowercase(),
+            body
+        ),
+    }
+}
+
+/// Replaces values of credential-like keys (keys, tokens, secrets, passwords)
+fn redact_payload(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if ["key", "token", "secret", "password"]
+                    .iter()
+                    .any(|sensitive| key.contains(sensitive))
+                {
+                    *field = serde_json::Value::String("[REDACTED]".to_string());
+                } else {
+                    redact_payload(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_payload),
+        _ => {}
+    }
+}
+
 pub struct ScoutDbClient {
     config: DatabaseConfig,
     client: Option<Postgrest>,
+    failed_payloads: std::collections::VecDeque<FailedPayload>,
+    failed_payload_capacity: usize,
+    response_cache: std::collections::VecDeque<CachedResponse>,
+    response_cache_capacity: usize,
+    trace_context: Option<TraceContext>,
+    http_options: HttpOptions,
+    http_client: reqwest::Client,
+    last_request: Option<Instant>,
+    staging_marker: Option<String>,
+    #[cfg(feature = "chaos")]
+    failure_injector: Option<FailureInjector>,
 }
 
 impl std::fmt::Debug for ScoutDbClient {
@@ -89,6 +344,138 @@ impl ScoutDbClient {
         Self {
             config,
             client: None,
+            failed_payloads: std::collections::VecDeque::new(),
+            failed_payload_capacity: 0,
+            response_cache: std::collections::VecDeque::new(),
+            response_cache_capacity: DEFAULT_RESPONSE_CACHE_CAPACITY,
+            trace_context: None,
+            http_options: HttpOptions::default(),
+            http_client: reqwest::Client::new(),
+            last_request: None,
+            staging_marker: None,
+            #[cfg(feature = "chaos")]
+            failure_injector: None,
+        }
+    }
+
+    /// Puts `marker` before the [`STAGING_MARKED_COLUMNS`] of rows inserted or upserted
+    /// later, so test rows can be told apart from production data and purged
+    pub fn set_staging_marker(&mut self, marker: Option<String>) -> Result<()> {
+        if let Some(marker) = &marker {
+            validate_staging_marker(marker)?;
+        }
+        self.staging_marker = marker;
+        Ok(())
+    }
+
+    /// `row` of `table` with the staging marker applied, for writes that do not name
+    /// their table, e.g. [`Self::update`]
+    pub fn staged<T>(&self, table: &str, row: &T) -> Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de> + serde::Serialize,
+    {
+        Ok(serde_json::from_str(&self.write_json(table, row)?)?)
+    }
+
+    /// `data` as JSON, with the staging marker put before the marked column of `table`
+    /// unless the value already starts with it
+    fn write_json<S: serde::Serialize + ?Sized>(&self, table: &str, data: &S) -> Result<String> {
+        let column = STAGING_MARKED_COLUMNS
+            .iter()
+            .find(|(marked_table, _)| *marked_table == table)
+            .map(|(_, column)| *column);
+        let (Some(marker), Some(column)) = (&self.staging_marker, column) else {
+            return Ok(serde_json::to_string(data)?);
+        };
+        let mut value = serde_json::to_value(data)?;
+        let rows = match &mut value {
+            serde_json::Value::Array(rows) => rows.iter_mut().collect(),
+            row => vec![row],
+        };
+        for row in rows {
+            let Some(row) = row.as_object_mut() else {
+                continue;
+            };
+            let current = row.get(column).and_then(|v| v.as_str()).unwrap_or_default();
+            if !current.starts_with(marker.as_str()) {
+                let marked = format!("{}{}", marker, current);
+                row.insert(column.to_string(), marked.into());
+            }
+        }
+        Ok(serde_json::to_string(&value)?)
+    }
+
+    /// Sends later requests with `options`, failing if they do not make a client, e.g.
+    /// for an invalid proxy URL
+    pub fn set_http_options(&mut self, options: HttpOptions) -> Result<()> {
+        self.http_client = options.client()?;
+        self.http_options = options;
+        Ok(())
+    }
+
+    /// Sends a PostgREST request on the client of the HTTP options, see [`Self::send_request`]
+    pub async fn send(&mut self, builder: postgrest::Builder) -> Result<reqwest::Response> {
+        let request = self.request(builder)?;
+        self.send_request(request).await
+    }
+
+    /// Builds a PostgREST request for the client of the HTTP options. postgrest builds
+    /// requests for its own reqwest version, so the method, URL, headers and body are
+    /// copied onto a request of ours rather than sent on postgrest's client.
+    fn request(&self, builder: postgrest::Builder) -> Result<reqwest::Request> {
+        let built = builder.build().build()?;
+        let method = reqwest::Method::from_bytes(built.method().as_str().as_bytes())?;
+        let mut request = self.http_client.request(method, built.url().as_str());
+        for (name, value) in built.headers() {
+            request = request.header(name.as_str(), value.as_bytes());
+        }
+        if let Some(body) = built.body().and_then(|body| body.as_bytes()) {
+            request = request.body(body.to_vec());
+        }
+        Ok(self.traced(request).build()?)
+    }
+
+    /// Sends a request with the request timeout, waiting for the rate limit and retrying
+    /// with backoff as the HTTP options allow
+    async fn send_request(&mut self, mut request: reqwest::Request) -> Result<reqwest::Response> {
+        if let Some(timeout) = self.http_options.request_timeout {
+            *request.timeout_mut() = Some(timeout);
+        }
+        let mut attempt = 0;
+        loop {
+            if let Some(per_second) = self.http_options.max_requests_per_second {
+                let interval = Duration::from_secs_f64(1.0 / per_second.max(f64::EPSILON));
+                if let Some(last_request) = self.last_request {
+                    tokio::time::sleep_until(last_request + interval).await;
+                }
+                self.last_request = Some(Instant::now());
+            }
+            // Streamed bodies cannot be cloned, so those requests are not retried
+            let retry = (attempt < self.http_options.max_retries)
+                .then(|| request.try_clone())
+                .flatten();
+            let description = format!("{} {}", request.method(), request.url().path());
+            let result = self.http_client.execute(request).await;
+            match retry {
+                Some(next) if HttpOptions::is_retryable(&result) => {
+                    let delay = self.http_options.retry_backoff * 2u32.saturating_pow(attempt);
+                    attempt += 1;
+                    logging::warn!(
+                        "Retrying {} in {:?} (retry {} of {}): {}",
+                        description,
+                        delay,
+                        attempt,
+                        self.http_options.max_retries,
+                        match &result {
+                            Ok(response) => response.status().to_string(),
+                            Err(e) => e.to_string(),
+                        }
+                    );
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                }
+                _ => return Ok(result?),
+            }
         }
     }
 
@@ -131,35 +518,46 @@ impl ScoutDbClient {
     where
         T: for<'de> serde::Deserialize<'de>,
     {
-        let client = self.get_client()?;
-
-        let builder = query_builder(client);
-        let response = builder.execute().await?;
-
-        let body = response.text().await?;
+        let body = self.fetch(query_builder).await?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
 
         // Try to parse as the expected type first
         if let Ok(results) = serde_json::from_str::<Vec<T>>(&body) {
             Ok(results)
         } else {
-            // If that fails, try to parse as an error response
-            if let Ok(error_response) = serde_json::from_str::<serde_json::Value>(&body) {
-                if let Some(error_msg) = error_response.get("error") {
-                    return Err(anyhow!("Database error: {}", error_msg));
-                } else if let Some(message) = error_response.get("message") {
-                    return Err(anyhow!("Database message: {}", message));
-                } else {
-                    return Err(anyhow!("Database returned unexpected format: {}", body));
-                }
-            } else {
-                return Err(anyhow!(
-                    "Failed to parse database response as JSON: {}",
-                    body
-                ));
-            }
+            // If that fails, the body is an error response
+            Err(response_error("Database", &body))
         }
     }
 
+    /// Selects the rows of `table` whose `column` is one of `ids`, split into as many
+    /// `in.(...)` queries as needed to keep each URL short
+    pub async fn query_by_ids<T>(
+        &mut self,
+        table: &str,
+        select: &str,
+        column: &str,
+        ids: &[i64],
+    ) -> Result<Vec<T>>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let mut results = Vec::new();
+        for chunk in id_chunks(ids, MAX_ID_FILTER_LENGTH) {
+            results.extend(
+                self.query::<T>(|client| {
+                    client
+                        .from(table)
+                        .select(select)
+                        .in_(column, chunk.iter().map(|id| id.to_string()))
+                })
+                .await?,
+            );
+        }
+        Ok(results)
+    }
+
     /// Executes a query that returns a single row
     pub async fn query_one<T>(
         &mut self,
@@ -168,12 +566,9 @@ impl ScoutDbClient {
     where
         T: for<'de> serde::Deserialize<'de>,
     {
-        let client = self.get_client()?;
-
-        let builder = query_builder(client);
-        let response = builder.execute().await?;
-
-        let body = response.text().await?;
+        let body = self.fetch(query_builder).await?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
         let results: Vec<T> = serde_json::from_str(&body)?;
 
         if results.is_empty() {
@@ -183,19 +578,137 @@ impl ScoutDbClient {
         Ok(results.into_iter().next().unwrap())
     }
 
+    /// Sends a request and returns the response body. GET responses served with an ETag are
+    /// cached; repeating the request sends If-None-Match, and a 304 returns the cached body.
+    async fn fetch(
+        &mut self,
+        query_builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
+    ) -> Result<String> {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("query", false).await?;
+        let builder = query_builder(self.get_client()?);
+        let mut request = self.request(builder)?;
+        if request.method() != reqwest::Method::GET || self.response_cache_capacity == 0 {
+            return Ok(self.send_request(request).await?.text().await?);
+        }
+
+        let url = request.url().to_string();
+        let cached = self
+            .response_cache
+            .iter()
+            .position(|cached| cached.url == url);
+        if let Some(index) = cached {
+            if let Ok(etag) = self.response_cache[index].etag.parse() {
+                request
+                    .headers_mut()
+                    .insert(reqwest::header::IF_NONE_MATCH, etag);
+            }
+        }
+
+        let response = self.send_request(request).await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(index) = cached {
+                logging::debug!("Using cached response for {}", url);
+                // Most recently used entries are evicted last
+                let entry = self.response_cache.remove(index).unwrap();
+                let body = entry.body.clone();
+                self.response_cache.push_back(entry);
+                return Ok(body);
+            }
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let success = response.status().is_success();
+        let body = response.text().await?;
+        if let Some(index) = cached {
+            self.response_cache.remove(index);
+        }
+        if let Some(etag) = etag.filter(|_| success) {
+            if self.response_cache.len() == self.response_cache_capacity {
+                self.response_cache.pop_front();
+            }
+            self.response_cache.push_back(CachedResponse {
+                url,
+                etag,
+                body: body.clone(),
+            });
+        }
+        Ok(body)
+    }
+
+    /// Names of the RPC functions the server exposes, read from the OpenAPI description
+    /// PostgREST serves at its root
+    pub async fn get_rpc_names(&mut self) -> Result<std::collections::BTreeSet<String>> {
+        Ok(self
+            .get_api_paths()
+            .await?
+            .iter()
+            .filter_map(|path| path.strip_prefix("rpc/"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Paths the server exposes without the leading slash: tables and views as their
+    /// names, RPC functions as `rpc/<name>`. Read from the OpenAPI description PostgREST
+    /// serves at its root.
+    pub async fn get_api_paths(&mut self) -> Result<std::collections::BTreeSet<String>> {
+        let url = format!("{}/", self.config.get_rest_url().trim_end_matches('/'));
+        let request = self
+            .http_client
+            .get(url)
+            .header("apikey", self.config.get_supabase_api_key())
+            .header(reqwest::header::ACCEPT, "application/openapi+json");
+        let request = self.traced(request).build()?;
+        let response = self.send_request(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(response_error("OpenAPI", &body));
+        }
+
+        let description: serde_json::Value = serde_json::from_str(&body)?;
+        let paths = description
+            .get("paths")
+            .and_then(|paths| paths.as_object())
+            .ok_or_else(|| anyhow!("OpenAPI description has no paths"))?;
+        Ok(paths
+            .keys()
+            .filter_map(|path| path.strip_prefix('/'))
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Keeps up to `capacity` GET responses for ETag revalidation (0 disables caching)
+    pub fn set_response_cache_capacity(&mut self, capacity: usize) {
+        self.response_cache_capacity = capacity;
+        while self.response_cache.len() > capacity {
+            self.response_cache.pop_front();
+        }
+    }
+
     /// Executes a query that doesn't return results (INSERT, UPDATE, DELETE)
     pub async fn execute(
         &mut self,
         query_builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
     ) -> Result<()> {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("execute", true).await?;
         let client = self.get_client()?;
 
-        let builder = query_builder(client);
-        let response = builder.execute().await?;
+        let request = query_builder(client);
+        let response = self.send(request).await?;
 
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await?;
+            if let Some(error) = PostgrestError::from_body(&error_text) {
+                return Err(error.into());
+            }
             return Err(anyhow!(
                 "Operation failed: HTTP {} - {}",
                 status,
@@ -211,117 +724,268 @@ impl ScoutDbClient {
     where
         T: for<'de> serde::Deserialize<'de> + serde::Serialize,
     {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("insert", true).await?;
+        let json_data = self.write_json(table, data)?;
         let client = self.get_client()?;
 
-        let json_data = serde_json::to_string(data)?;
-
-        let response = client.from(table).insert(&json_data).execute().await?;
+        let request = client.from(table).insert(&json_data);
+        let response = self.send(request).await?;
 
         let body = response.text().await?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
 
         // Try to parse as the expected type first
         if let Ok(results) = serde_json::from_str::<Vec<T>>(&body) {
             Ok(results)
         } else {
-            // If that fails, try to parse as an error response
-            if let Ok(error_response) = serde_json::from_str::<serde_json::Value>(&body) {
-                if let Some(error_msg) = error_response.get("error") {
-                    return Err(anyhow!("Database insert error: {}", error_msg));
-                } else if let Some(message) = error_response.get("message") {
-                    return Err(anyhow!("Database insert message: {}", message));
-                } else {
-                    return Err(anyhow!(
-                        "Database insert returned unexpected format: {}",
-                        body
-                    ));
-                }
-            } else {
-                return Err(anyhow!(
-                    "Failed to parse database insert response as JSON: {}",
-                    body
-                ));
-            }
+            self.record_failed_payload(table, "insert", &json_data, &body);
+            // If that fails, the body is an error response
+            Err(response_error("Database insert", &body))
+        }
+    }
+
+    /// Keeps the last `capacity` rejected write payloads and responses (0 disables capture)
+    pub fn capture_failed_payloads(&mut self, capacity: usize) {
+        self.failed_payload_capacity = capacity;
+        while self.failed_payloads.len() > capacity {
+            self.failed_payloads.pop_front();
         }
     }
 
-    /// Inserts multiple items in a single bulk operation
-    pub async fn insert_bulk<T>(&mut self, table: &str, data: &[T]) -> Result<Vec<T>>
+    /// Returns captured rejected writes, oldest first
+    pub fn failed_payloads(&self) -> Vec<FailedPayload> {
+        self.failed_payloads.iter().cloned().collect()
+    }
+
+    fn record_failed_payload(
+        &mut self,
+        table: &str,
+        operation: &str,
+        request: &str,
+        response: &str,
+    ) {
+        if self.failed_payload_capacity == 0 {
+            return;
+        }
+        let mut request = serde_json::from_str(request)
+            .unwrap_or_else(|_| serde_json::Value::String(request.to_string()));
+        redact_payload(&mut request);
+        if self.failed_payloads.len() == self.failed_payload_capacity {
+            self.failed_payloads.pop_front();
+        }
+        self.failed_payloads.push_back(FailedPayload {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            table: table.to_string(),
+            operation: operation.to_string(),
+            request,
+            response: response.to_string(),
+        });
+    }
+
+    /// Sends `traceparent` with later requests, naming `context` as their parent span
+    /// (`None` stops propagation), see [`crate::trace`]
+    pub fn set_trace_context(&mut self, context: Option<TraceContext>) {
+        self.trace_context = context;
+    }
+
+    /// Attaches the trace context header, if propagation is enabled
+    fn traced(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.trace_context {
+            Some(context) => request.header(TRACEPARENT_HEADER, context.to_string()),
+            None => request,
+        }
+    }
+
+    /// Injects the injector's failures into later requests (`chaos` feature)
+    #[cfg(feature = "chaos")]
+    pub fn set_failure_injector(&mut self, injector: FailureInjector) {
+        self.failure_injector = Some(injector);
+    }
+
+    /// Delays writes and drops requests as configured by the failure injector
+    #[cfg(feature = "chaos")]
+    async fn inject_request_failure(&self, operation: &str, write: bool) -> Result<()> {
+        let Some(injector) = &self.failure_injector else {
+            return Ok(());
+        };
+        if write {
+            injector.transaction().await;
+        }
+        injector.request(operation)
+    }
+
+    /// Corrupts the response body as configured by the failure injector
+    #[cfg(feature = "chaos")]
+    fn inject_response_failure(&self, body: String) -> String {
+        match &self.failure_injector {
+            Some(injector) => injector.response(body),
+            None => body,
+        }
+    }
+
+    /// Sends a bulk write, attaching its idempotency key if it has one
+    async fn execute_bulk(
+        &mut self,
+        builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
+        idempotency_key: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("bulk write", true).await?;
+        let builder = builder(self.get_client()?);
+        let mut request = self.request(builder)?;
+        if let Some(key) = idempotency_key {
+            request
+                .headers_mut()
+                .insert(IDEMPOTENCY_KEY_HEADER, key.parse()?);
+        }
+        self.send_request(request).await
+    }
+
+    /// Returns the rows of a batch the server already processed under `key`, in request order.
+    /// Rows sent with an ID are read back by ID; the rest map onto the IDs the original
+    /// request inserted, which are allocated in request order.
+    async fn replay_bulk<S, T>(&mut self, table: &str, data: &[S], key: &str) -> Result<Vec<T>>
     where
-        T: for<'de> serde::Deserialize<'de> + serde::Serialize,
+        S: serde::Serialize,
+        T: for<'de> serde::Deserialize<'de>,
     {
-        let client = self.get_client()?;
+        #[derive(Deserialize)]
+        struct RecordedBatch {
+            row_ids: Vec<i64>,
+        }
 
-        let json_data = serde_json::to_string(data)?;
+        let recorded: Vec<RecordedBatch> = self
+            .query(|client| {
+                client
+                    .from("batch_idempotency_keys")
+                    .select("row_ids")
+                    .eq("table_name", table)
+                    .eq("key", key)
+            })
+            .await?;
+        let Some(recorded) = recorded.into_iter().next() else {
+            return Err(anyhow!(
+                "Batch {} returned fewer rows than sent but no idempotency record exists",
+                key
+            ));
+        };
 
-        let response = client.from(table).insert(&json_data).execute().await?;
+        let sent_ids: Vec<Option<i64>> = data
+            .iter()
+            .map(|row| {
+                serde_json::to_value(row)
+                    .ok()
+                    .and_then(|value| value.get("id").and_then(|id| id.as_i64()))
+            })
+            .collect();
+        let mut inserted_ids = recorded.row_ids.into_iter();
+        let ids: Vec<i64> = sent_ids
+            .iter()
+            .map(|id| id.or_else(|| inserted_ids.next()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| anyhow!("Idempotency record for batch {} is incomplete", key))?;
+
+        let rows: Vec<serde_json::Value> = self
+            .query(|client| {
+                client
+                    .from(table)
+                    .select("*")
+                    .in_("id", ids.iter().map(|id| id.to_string()))
+            })
+            .await?;
+        logging::info!(
+            "Batch {} was already processed, returning original rows",
+            key
+        );
+        ids.iter()
+            .map(|id| {
+                let row = rows
+                    .iter()
+                    .find(|row| row.get("id").and_then(|v| v.as_i64()) == Some(*id))
+                    .ok_or_else(|| anyhow!("Row {} of batch {} no longer exists", id, key))?;
+                Ok(serde_json::from_value(row.clone())?)
+            })
+            .collect()
+    }
+
+    /// Inserts multiple items in a single bulk operation. Items need not be the row type,
+    /// e.g. local records sent through [`crate::models::AsRemote`].
+    /// If the server already processed a batch with `idempotency_key`, the write inserts no
+    /// new rows, only updates existing ones (see migration 09), and the rows from the
+    /// original request are returned instead.
+    pub async fn insert_bulk<S, T>(
+        &mut self,
+        table: &str,
+        data: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<Vec<T>>
+    where
+        S: serde::Serialize,
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let json_data = self.write_json(table, data)?;
+
+        let response = self
+            .execute_bulk(
+                |client| client.from(table).insert(&json_data),
+                idempotency_key,
+            )
+            .await?;
 
         let body = response.text().await?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
 
         // Try to parse as the expected type first
         if let Ok(results) = serde_json::from_str::<Vec<T>>(&body) {
-            Ok(results)
-        } else {
-            // If that fails, try to parse as an error response
-            if let Ok(error_response) = serde_json::from_str::<serde_json::Value>(&body) {
-                if let Some(error_msg) = error_response.get("error") {
-                    return Err(anyhow!("Database bulk insert error: {}", error_msg));
-                } else if let Some(message) = error_response.get("message") {
-                    return Err(anyhow!("Database bulk insert message: {}", message));
-                } else {
-                    return Err(anyhow!(
-                        "Database bulk insert returned unexpected format: {}",
-                        body
-                    ));
-                }
-            } else {
-                return Err(anyhow!(
-                    "Failed to parse database bulk insert response as JSON: {}",
-                    body
-                ));
+            match idempotency_key {
+                Some(key) if results.len() < data.len() => self.replay_bulk(table, data, key).await,
+                _ => Ok(results),
             }
+        } else {
+            self.record_failed_payload(table, "insert", &json_data, &body);
+            // If that fails, the body is an error response
+            Err(response_error("Database bulk insert", &body))
         }
     }
 
-    /// Upserts multiple items in a single bulk operation (insert or update on conflict)
-    pub async fn upsert_bulk<T>(&mut self, table: &str, data: &[T]) -> Result<Vec<T>>
+    /// Upserts multiple items in a single bulk operation (insert or update on conflict),
+    /// replaying batches already processed under `idempotency_key` like [`Self::insert_bulk`]
+    pub async fn upsert_bulk<S, T>(
+        &mut self,
+        table: &str,
+        data: &[S],
+        idempotency_key: Option<&str>,
+    ) -> Result<Vec<T>>
     where
-        T: for<'de> serde::Deserialize<'de> + serde::Serialize,
+        S: serde::Serialize,
+        T: for<'de> serde::Deserialize<'de>,
     {
-        let client = self.get_client()?;
-
-        let json_data = serde_json::to_string(data)?;
+        let json_data = self.write_json(table, data)?;
 
-        let response = client
-            .from(table)
-            .upsert(&json_data)
-            .on_conflict("id")
-            .execute()
+        let response = self
+            .execute_bulk(
+                |client| client.from(table).upsert(&json_data).on_conflict("id"),
+                idempotency_key,
+            )
             .await?;
 
         let body = response.text().await?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
 
         // Try to parse as the expected type first
         if let Ok(results) = serde_json::from_str::<Vec<T>>(&body) {
-            Ok(results)
-        } else {
-            // If that fails, try to parse as an error response
-            if let Ok(error_response) = serde_json::from_str::<serde_json::Value>(&body) {
-                if let Some(error_msg) = error_response.get("error") {
-                    return Err(anyhow!("Database bulk upsert error: {}", error_msg));
-                } else if let Some(message) = error_response.get("message") {
-                    return Err(anyhow!("Database bulk upsert message: {}", message));
-                } else {
-                    return Err(anyhow!(
-                        "Database bulk upsert returned unexpected format: {}",
-                        body
-                    ));
-                }
-            } else {
-                return Err(anyhow!(
-                    "Failed to parse database bulk upsert response as JSON: {}",
-                    body
-                ));
+            match idempotency_key {
+                Some(key) if results.len() < data.len() => self.replay_bulk(table, data, key).await,
+                _ => Ok(results),
             }
+        } else {
+            self.record_failed_payload(table, "upsert", &json_data, &body);
+            // If that fails, the body is an error response
+            Err(response_error("Database bulk upsert", &body))
         }
     }
 
@@ -334,15 +998,20 @@ impl ScoutDbClient {
     where
         T: for<'de> serde::Deserialize<'de> + serde::Serialize,
     {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("update", true).await?;
         let client = self.get_client()?;
 
         let json_data = serde_json::to_string(data)?;
 
-        let builder = filter_builder(client);
-        let response = builder.update(&json_data).execute().await?;
+        let request = filter_builder(client).update(&json_data);
+        let response = self.send(request).await?;
 
         let body = response.text().await?;
-        let results: Vec<T> = serde_json::from_str(&body)?;
+        #[cfg(feature = "chaos")]
+        let body = self.inject_response_failure(body);
+        let results: Vec<T> =
+            serde_json::from_str(&body).map_err(|_| response_error("Database update", &body))?;
 
         Ok(results)
     }
@@ -352,14 +1021,41 @@ impl ScoutDbClient {
         &mut self,
         filter_builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
     ) -> Result<()> {
+        self.delete_rows(filter_builder).await?;
+        Ok(())
+    }
+
+    /// Like [`Self::delete`], returning the deleted rows with the builder's selected
+    /// columns, so callers learn what one request removed
+    pub async fn delete_returning<T>(
+        &mut self,
+        filter_builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
+    ) -> Result<Vec<T>>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let body = self.delete_rows(filter_builder).await?;
+        serde_json::from_str(&body).map_err(|_| response_error("Database", &body))
+    }
+
+    /// Sends a delete request and returns the response body
+    async fn delete_rows(
+        &mut self,
+        filter_builder: impl FnOnce(&Postgrest) -> postgrest::Builder,
+    ) -> Result<String> {
+        #[cfg(feature = "chaos")]
+        self.inject_request_failure("delete", true).await?;
         let client = self.get_client()?;
 
-        let builder = filter_builder(client);
-        let response = builder.delete().execute().await?;
+        let request = filter_builder(client).delete();
+        let response = self.send(request).await?;
 
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await?;
+            if let Some(error) = PostgrestError::from_body(&error_text) {
+                return Err(error.into());
+            }
             return Err(anyhow!(
                 "Delete operation failed: HTTP {} - {}",
                 status,
@@ -367,7 +1063,7 @@ impl ScoutDbClient {
             ));
         }
 
-        Ok(())
+        Ok(response.text().await?)
     }
 }
 
@@ -376,3 +1072,569 @@ impl Drop for ScoutDbClient {
         self.disconnect();
     }
 }
+
+/// Config of a server that never answers, for tests that stay offline
+#[cfg(test)]
+pub(crate) fn offline_test_config() -> DatabaseConfig {
+    DatabaseConfig {
+        rest_url: "http://127.0.0.1:9/rest/v1".to_string(),
+        scout_api_key: "unused".to_string(),
+        supabase_api_key: "unused".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Capabilities, Capability};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_get_revalidates_cached_response_with_etag() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut conditional = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut if_none_match = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case("if-none-match") {
+                            if_none_match = Some(value.to_string());
+                        }
+                    }
+                }
+                let response = if if_none_match.as_deref() == Some("\"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                } else {
+                    let body = r#"[{"id":1,"name":"plan"}]"#;
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                conditional.push(if_none_match);
+            }
+            conditional
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        for _ in 0..2 {
+            let plans: Vec<serde_json::Value> = db_client
+                .query(|client| client.from("plans").select("*"))
+                .await?;
+            assert_eq!(plans[0]["name"], "plan");
+        }
+
+        let conditional = server.join().unwrap();
+        assert_eq!(conditional, vec![None, Some("\"v1\"".to_string())]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_write_carries_postgrest_error() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut stream = listener.incoming().next().unwrap().unwrap();
+            for line in BufReader::new(&stream).lines() {
+                if line.unwrap().is_empty() {
+                    break;
+                }
+            }
+            let body = r#"{"code":"23505","details":"Key (event_id, session_id)=(1, 2) already exists.","hint":null,"message":"duplicate key value violates unique constraint"}"#;
+            let response = format!(
+                "HTTP/1.1 409 Conflict\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        let links = vec![serde_json::json!({"event_id": 1, "session_id": 2})];
+        let error = db_client
+            .insert_bulk::<_, serde_json::Value>("event_session_links", &links, None)
+            .await
+            .unwrap_err();
+        server.join().unwrap();
+
+        let postgrest_error = PostgrestError::from_error(&error).unwrap();
+        assert!(postgrest_error.is_unique_violation());
+        assert_eq!(postgrest_error.hint, None);
+        assert!(error
+            .to_string()
+            .contains("duplicate key value violates unique constraint"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_writes_are_captured_redacted() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            for (attempt, stream) in listener.incoming().take(4).enumerate() {
+                let mut stream = stream.unwrap();
+                for line in BufReader::new(&stream).lines() {
+                    if line.unwrap().is_empty() {
+                        break;
+                    }
+                }
+                let body = format!(
+                    r#"{{"code":"PGRST204","details":null,"hint":null,"message":"rejected {}"}}"#,
+                    attempt
+                );
+                let response = format!(
+                    "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        let devices = vec![serde_json::json!({
+            "name": "trail cam",
+            "api_key": "device-secret",
+            "settings": [{ "Auth_Token": "abc", "interval": 30 }],
+        })];
+        // Capture is off by default
+        assert!(db_client
+            .insert_bulk::<_, serde_json::Value>("devices", &devices, None)
+            .await
+            .is_err());
+        assert!(db_client.failed_payloads().is_empty());
+
+        db_client.capture_failed_payloads(2);
+        for operation in ["insert", "upsert", "insert"] {
+            let result = match operation {
+                "insert" => {
+                    db_client
+                        .insert_bulk::<_, serde_json::Value>("devices", &devices, None)
+                        .await
+                }
+                _ => {
+                    db_client
+                        .upsert_bulk::<_, serde_json::Value>("devices", &devices, None)
+                        .await
+                }
+            };
+            assert!(result.is_err());
+        }
+        server.join().unwrap();
+
+        // Only the last two rejections are kept
+        let failed = db_client.failed_payloads();
+        assert_eq!(
+            failed
+                .iter()
+                .map(|payload| (payload.table.as_str(), payload.operation.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("devices", "upsert"), ("devices", "insert")]
+        );
+        assert!(failed[1].response.contains("rejected 3"));
+        assert_eq!(
+            failed[1].request,
+            serde_json::json!([{
+                "name": "trail cam",
+                "api_key": "[REDACTED]",
+                "settings": [{ "Auth_Token": "[REDACTED]", "interval": 30 }],
+            }])
+        );
+
+        db_client.capture_failed_payloads(1);
+        assert_eq!(db_client.failed_payloads().len(), 1);
+        assert!(db_client.failed_payloads()[0]
+            .response
+            .contains("rejected 3"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_requests_carry_traceparent() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut traceparents = Vec::new();
+            let mut api_keys = Vec::new();
+            for stream in listener.incoming().take(3) {
+                let mut stream = stream.unwrap();
+                let mut traceparent = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+                            traceparent = Some(value.to_string());
+                        } else if name.eq_ignore_ascii_case("apikey") {
+                            api_keys.push(value.to_string());
+                        }
+                    }
+                }
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+                traceparents.push(traceparent);
+            }
+            (traceparents, api_keys)
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "public".to_string(),
+        });
+        let flush = TraceContext::new_root();
+        db_client.set_trace_context(Some(flush));
+        db_client
+            .delete(|client| client.from("events").eq("id", "1"))
+            .await?;
+        let rpc = db_client
+            .get_client()?
+            .rpc("get_supported_model_versions", "{}");
+        db_client.send(rpc).await?;
+        db_client.set_trace_context(None);
+        db_client
+            .delete(|client| client.from("events").eq("id", "1"))
+            .await?;
+
+        // Headers postgrest sets survive sending on our client
+        let (traceparents, api_keys) = server.join().unwrap();
+        assert_eq!(
+            traceparents,
+            vec![Some(flush.to_string()), Some(flush.to_string()), None]
+        );
+        assert_eq!(api_keys, vec!["public"; 3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_rpc_names_from_openapi() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}/rest/v1", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut stream = listener.incoming().next().unwrap().unwrap();
+            let mut lines = BufReader::new(&stream).lines();
+            let request_line = lines.next().unwrap().unwrap();
+            for line in lines {
+                if line.unwrap().is_empty() {
+                    break;
+                }
+            }
+            let body = r#"{"swagger":"2.0","paths":{"/":{},"/events":{},"/rpc/get_device_by_api_key":{},"/rpc/append_session_track":{}}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            request_line
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        let names = db_client.get_rpc_names().await?;
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            vec!["append_session_track", "get_device_by_api_key"]
+        );
+        let capabilities = Capabilities::from_rpc_names(&names);
+        assert!(capabilities.supports(Capability::SessionTrackAppend));
+        assert_eq!(
+            capabilities.missing(),
+            vec![
+                Capability::EventsWithTags,
+                Capability::ArtifactsForHerd,
+                Capability::ModelVersions,
+                Capability::ConnectivityCompaction
+            ]
+        );
+        assert!(server.join().unwrap().starts_with("GET /rest/v1/ "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_staging_marker_prefixes_marked_columns_once() -> Result<()> {
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url: "http://127.0.0.1:9".to_string(),
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        assert!(db_client
+            .set_staging_marker(Some("e2e_".to_string()))
+            .is_err());
+        assert!(db_client.set_staging_marker(Some(" ".to_string())).is_err());
+        assert!(db_client
+            .set_staging_marker(Some("e2e\\".to_string()))
+            .is_err());
+        db_client.set_staging_marker(Some("[e2e] ".to_string()))?;
+
+        let sessions = serde_json::json!([
+            { "software_version": "1.2.0" },
+            { "software_version": "[e2e] 1.2.0" },
+        ]);
+        let marked: serde_json::Value =
+            serde_json::from_str(&db_client.write_json("sessions", &sessions)?)?;
+        assert_eq!(marked[0]["software_version"], "[e2e] 1.2.0");
+        assert_eq!(marked[1]["software_version"], "[e2e] 1.2.0");
+
+        let event = crate::models::Event {
+            message: None,
+            ..crate::models::Event::from(crate::models::EventLocal::default())
+        };
+        let event = db_client.staged("events", &event)?;
+        assert_eq!(event.message.as_deref(), Some("[e2e] "));
+
+        let tags = serde_json::json!([{ "class_name": "elephant" }]);
+        assert_eq!(
+            db_client.write_json("tags", &tags)?,
+            serde_json::to_string(&tags)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_id_chunks_fit_url_length() {
+        let mut ids: Vec<i64> = (1_000_000..1_001_000).collect();
+        ids.push(1_000_000);
+        let chunks = id_chunks(&ids, MAX_ID_FILTER_LENGTH);
+        // 7 digits and an encoded comma per ID
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![400, 400, 200]
+        );
+        assert_eq!(chunks.concat(), ids[..1000]);
+        assert!(id_chunks(&[], MAX_ID_FILTER_LENGTH).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_overloaded_responses_are_retried_with_http_options() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut user_agents = Vec::new();
+            for (attempt, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case("user-agent") {
+                            user_agents.push(value.to_string());
+                        }
+                    }
+                }
+                let (status, body) = match attempt {
+                    0 => ("503 Service Unavailable", "{}"),
+                    _ => ("200 OK", r#"[{"id":1}]"#),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            user_agents
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        db_client.set_http_options(HttpOptions {
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(10),
+            user_agent: Some("scout-field-device".to_string()),
+            ..Default::default()
+        })?;
+        let plans: Vec<serde_json::Value> = db_client
+            .query(|client| client.from("plans").select("*"))
+            .await?;
+        assert_eq!(plans[0]["id"], 1);
+        assert_eq!(server.join().unwrap(), vec!["scout-field-device"; 2]);
+
+        assert!(db_client
+            .set_http_options(HttpOptions {
+                proxy: Some("not a proxy".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_write_retries_resend_body_and_idempotency_key() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (attempt, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut key = None;
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    if let Some((name, value)) = line.trim_end().split_once(": ") {
+                        if name.eq_ignore_ascii_case(IDEMPOTENCY_KEY_HEADER) {
+                            key = Some(value.to_string());
+                        } else if name.eq_ignore_ascii_case("content-length") {
+                            length = value.parse().unwrap();
+                        }
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; length];
+                std::io::Read::read_exact(&mut reader, &mut body).unwrap();
+                requests.push((key, String::from_utf8(body).unwrap()));
+                let (status, body) = match attempt {
+                    0 => ("503 Service Unavailable", "{}"),
+                    _ => ("201 Created", r#"[{"id":1}]"#),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        db_client.set_http_options(HttpOptions {
+            max_retries: 1,
+            retry_backoff: Duration::from_millis(10),
+            request_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        })?;
+        let rows: Vec<serde_json::Value> = db_client
+            .insert_bulk(
+                "events",
+                &[serde_json::json!({"message": "herd"})],
+                Some("batch-1"),
+            )
+            .await?;
+        assert_eq!(rows[0]["id"], 1);
+
+        let sent = (
+            Some("batch-1".to_string()),
+            r#"[{"message":"herd"}]"#.to_string(),
+        );
+        assert_eq!(server.join().unwrap(), vec![sent.clone(), sent]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replayed_batch_reads_back_rows_recorded_for_its_table() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (index, stream) in listener.incoming().take(4).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut key = None;
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    if let Some((name, value)) = line.trim_end().split_once(": ") {
+                        if name.eq_ignore_ascii_case(IDEMPOTENCY_KEY_HEADER) {
+                            key = Some(value.to_string());
+                        } else if name.eq_ignore_ascii_case("content-length") {
+                            length = value.parse().unwrap();
+                        }
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; length];
+                std::io::Read::read_exact(&mut reader, &mut body).unwrap();
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+                requests.push((path, key));
+                // The replayed insert skips the row its first request inserted
+                let body = match index {
+                    0 => "[]",
+                    1 => r#"[{"row_ids":[5]}]"#,
+                    2 => r#"[{"id":5,"message":"herd"}]"#,
+                    _ => r#"[{"id":6,"message":"herd"}]"#,
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        let mut db_client = ScoutDbClient::new(DatabaseConfig {
+            rest_url,
+            scout_api_key: "unused".to_string(),
+            supabase_api_key: "unused".to_string(),
+        });
+        let events = [serde_json::json!({"message": "herd"})];
+        let rows: Vec<serde_json::Value> = db_client
+            .insert_bulk("events", &events, Some("events-1"))
+            .await?;
+        assert_eq!(rows, vec![serde_json::json!({"id": 5, "message": "herd"})]);
+        // The key belongs to its write only
+        let rows: Vec<serde_json::Value> = db_client.insert_bulk("events", &events, None).await?;
+        assert_eq!(rows[0]["id"], 6);
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                ("/events".to_string(), Some("events-1".to_string())),
+                (
+                    "/batch_idempotency_keys?select=row_ids&table_name=eq.events&key=eq.events-1"
+                        .to_string(),
+                    None
+                ),
+                ("/events?select=*&id=in.%285%29".to_string(), None),
+                ("/events".to_string(), None),
+            ]
+        );
+        Ok(())
+    }
+}
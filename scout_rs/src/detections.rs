@@ -0,0 +1,272 @@
+//! Import of detections from external inference runs as tags. Results in YOLO txt or COCO
+//! JSON are matched to local events by image file name or capture time, and stored as auto
+//! tags carrying the detector's name and version, to be synced with the next flush.
+//!
+//! ```no_run
+//! # fn run(mut sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::detections::{DetectionFormat, DetectionMatching};
+//!
+//! let format = DetectionFormat::Yolo {
+//!     class_names: vec!["elephant".to_string(), "person".to_string()],
+//!     image_size: Some((1920, 1080)),
+//! };
+//! let report = sync_engine.import_detections(
+//!     "runs/detect/labels",
+//!     &format,
+//!     DetectionMatching::FileName,
+//!     "yolov8n",
+//!     "2024.06",
+//! )?;
+//! println!("{} tags, unmatched: {:?}", report.imported, report.unmatched_images);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::EventLocal;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Format of a detection result file
+#[derive(Debug, Clone, PartialEq)]
+pub enum DetectionFormat {
+    /// YOLO txt files named after their image, in a directory or a single file. Each line
+    /// is `class x_center y_center width height [confidence]`, normalized to the image.
+    /// `class_names` maps class indices to names; boxes are scaled to pixels by
+    /// `image_size` when given.
+    Yolo {
+        class_names: Vec<String>,
+        image_size: Option<(u32, u32)>,
+    },
+    /// A COCO JSON file with `images`, `annotations` and `categories`, e.g. written by
+    /// [`crate::coco`]. Annotations without a `score` get confidence 1.
+    Coco,
+}
+
+/// How result images are matched to local events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetectionMatching {
+    /// The image file name without extension equals that of the event's file path or
+    /// media URL, or the event's local ID
+    FileName,
+    /// The image was captured within `tolerance` of the event's observation time: COCO
+    /// `date_captured`, or YOLO file names in Unix seconds. The closest event wins.
+    Timestamp { tolerance: Duration },
+}
+
+/// Result of [`crate::sync::SyncEngine::import_detections`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetectionImportReport {
+    /// Tags stored
+    pub imported: usize,
+    /// Tags dropped by tag suppression
+    pub suppressed: usize,
+    /// Result images no event matched
+    pub unmatched_images: Vec<String>,
+}
+
+/// One box read from a result file, with x and y at its center
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Detection {
+    pub image: String,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub class_name: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub conf: f64,
+}
+
+#[derive(Deserialize)]
+struct CocoFile {
+    images: Vec<CocoFileImage>,
+    annotations: Vec<CocoFileAnnotation>,
+    categories: Vec<CocoFileCategory>,
+}
+
+#[derive(Deserialize)]
+struct CocoFileImage {
+    id: u64,
+    file_name: String,
+    #[serde(default)]
+    date_captured: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CocoFileAnnotation {
+    image_id: u64,
+    category_id: u64,
+    bbox: [f64; 4],
+    #[serde(default)]
+    score: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct CocoFileCategory {
+    id: u64,
+    name: String,
+}
+
+/// Reads every detection of a result file or directory
+pub(crate) fn read_detections(path: &Path, format: &DetectionFormat) -> Result<Vec<Detection>> {
+    match format {
+        DetectionFormat::Coco => read_coco(path),
+        DetectionFormat::Yolo {
+            class_names,
+            image_size,
+        } => {
+            let mut files = Vec::new();
+            if path.is_dir() {
+                for entry in std::fs::read_dir(path)? {
+                    let file = entry?.path();
+                    // classes.txt lists class names, not detections
+                    if file.extension().is_some_and(|extension| extension == "txt")
+                        && file.file_name().is_some_and(|name| name != "classes.txt")
+                    {
+                        files.push(file);
+                    }
+                }
+                files.sort();
+            } else {
+                files.push(path.to_path_buf());
+            }
+            let mut detections = Vec::new();
+            for file in files {
+                detections.extend(read_yolo_file(&file, class_names, *image_size)?);
+            }
+            Ok(detections)
+        }
+    }
+}
+
+fn read_yolo_file(
+    path: &Path,
+    class_names: &[String],
+    image_size: Option<(u32, u32)>,
+) -> Result<Vec<Detection>> {
+    let image = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("Invalid YOLO file name {}", path.display()))?
+        .to_string();
+    let captured_at = image
+        .parse::<i64>()
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0));
+    let (scale_x, scale_y) = image_size.map_or((1.0, 1.0), |(width, height)| {
+        (f64::from(width), f64::from(height))
+    });
+
+    let mut detections = Vec::new();
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let invalid = || anyhow!("Invalid YOLO line {} in {}", index + 1, path.display());
+        if fields.len() != 5 && fields.len() != 6 {
+            return Err(invalid());
+        }
+        let class_index: usize = fields[0].parse().map_err(|_| invalid())?;
+        let values = fields[1..]
+            .iter()
+            .map(|field| field.parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<f64>>>()?;
+        let class_name = class_names
+            .get(class_index)
+            .ok_or_else(|| anyhow!("Unknown YOLO class {} in {}", class_index, path.display()))?;
+        detections.push(Detection {
+            image: image.clone(),
+            captured_at,
+            class_name: class_name.clone(),
+            x: values[0] * scale_x,
+            y: values[1] * scale_y,
+            width: values[2] * scale_x,
+            height: values[3] * scale_y,
+            conf: values.get(4).copied().unwrap_or(1.0),
+        });
+    }
+    Ok(detections)
+}
+
+fn read_coco(path: &Path) -> Result<Vec<Detection>> {
+    let file: CocoFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let images: HashMap<u64, &CocoFileImage> =
+        file.images.iter().map(|image| (image.id, image)).collect();
+    let categories: HashMap<u64, &str> = file
+        .categories
+        .iter()
+        .map(|category| (category.id, category.name.as_str()))
+        .collect();
+
+    file.annotations
+        .iter()
+        .map(|annotation| {
+            let image = images
+                .get(&annotation.image_id)
+                .ok_or_else(|| anyhow!("Annotation of unknown image {}", annotation.image_id))?;
+            let class_name = categories.get(&annotation.category_id).ok_or_else(|| {
+                anyhow!("Annotation of unknown category {}", annotation.category_id)
+            })?;
+            let [left, top, width, height] = annotation.bbox;
+            Ok(Detection {
+                image: image.file_name.clone(),
+                captured_at: image
+                    .date_captured
+                    .as_deref()
+                    .and_then(|captured| DateTime::parse_from_rfc3339(captured).ok())
+                    .map(|captured| captured.with_timezone(&Utc)),
+                class_name: class_name.to_string(),
+                x: left + width / 2.0,
+                y: top + height / 2.0,
+                width,
+                height,
+                conf: annotation.score.unwrap_or(1.0),
+            })
+        })
+        .collect()
+}
+
+/// File name of a path or URL without directories, extension, query or fragment
+fn file_stem(source: &str) -> Option<&str> {
+    let source = source.split(['?', '#']).next().unwrap_or(source);
+    Path::new(source).file_stem().and_then(|stem| stem.to_str())
+}
+
+/// The event a detection's image belongs to
+pub(crate) fn match_event<'a>(
+    detection: &Detection,
+    events: &'a [EventLocal],
+    matching: DetectionMatching,
+) -> Option<&'a EventLocal> {
+    match matching {
+        DetectionMatching::FileName => {
+            let image = file_stem(&detection.image)?;
+            events.iter().find(|event| {
+                event.id_local.as_deref() == Some(image)
+                    || event.file_path.as_deref().and_then(file_stem) == Some(image)
+                    || event.media_url.as_deref().and_then(file_stem) == Some(image)
+            })
+        }
+        DetectionMatching::Timestamp { tolerance } => {
+            let captured_at = detection.captured_at?;
+            events
+                .iter()
+                .filter_map(|event| {
+                    let observed =
+                        DateTime::parse_from_rfc3339(&event.timestamp_observation).ok()?;
+                    let offset = (observed.with_timezone(&Utc) - captured_at)
+                        .abs()
+                        .to_std()
+                        .ok()?;
+                    (offset <= tolerance).then_some((offset, event))
+                })
+                .min_by_key(|(offset, _)| *offset)
+                .map(|(_, event)| event)
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Scout from executors other than tokio, e.g. smol (`embedded` feature). Futures run on
+//! the caller's executor, or for the blocking calls on the calling thread, so callers need
+//! no tokio runtime of their own. tokio is still a dependency: the HTTP stack needs its
+//! reactor for sockets and timers, and a single background worker drives it and runs
+//! nothing else.
+//!
+//! [`EmbeddedSyncEngine`] is a reduced sync engine. Local writes run on the caller's
+//! thread, nothing syncs in the background, and records reach the server on `flush`.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine, events: Vec<scout_rs::models::EventLocal>) -> anyhow::Result<()> {
+//! use scout_rs::embedded::EmbeddedSyncEngine;
+//!
+//! let sync_engine = EmbeddedSyncEngine::new(sync_engine)?;
+//! sync_engine.ingest_items(events)?;
+//! // From an async task on any executor: `sync_engine.flush().await?`
+//! sync_engine.flush_blocking()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ScoutClient;
+use crate::normalize::Normalize;
+use crate::store::StoredModel;
+use crate::sync::{SyncEngine, SyncReport};
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use tokio::sync::Mutex;
+
+/// A boxed future borrowing from the value it was made from
+pub type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// tokio's reactor and timers for futures polled by other executors. Futures made by
+/// [`Self::run`] fail once the runtime is dropped.
+pub struct EmbeddedRuntime {
+    runtime: tokio::runtime::Runtime,
+}
+
+impl EmbeddedRuntime {
+    pub fn new() -> Result<Self> {
+        // The worker only drives the reactor: no task is ever spawned on it
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("scout-reactor")
+            .enable_all()
+            .build()?;
+        Ok(Self { runtime })
+    }
+
+    /// Wraps `future` so that any executor can poll it, with tokio's sockets and timers
+    /// available to it
+    pub fn run<Fut: Future>(&self, future: Fut) -> impl Future<Output = Fut::Output> {
+        WithReactor {
+            handle: self.runtime.handle().clone(),
+            future: Box::pin(future),
+        }
+    }
+
+    /// Like [`Self::run`], polling the future on the calling thread until it completes.
+    /// Must not be called from within a tokio runtime.
+    pub fn run_blocking<Fut: Future>(&self, future: Fut) -> Fut::Output {
+        block_on(self.run(future))
+    }
+}
+
+/// A future polled within the context of a tokio runtime it does not run on
+struct WithReactor<Fut> {
+    handle: tokio::runtime::Handle,
+    future: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for WithReactor<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Fut::Output> {
+        let this = &mut *self;
+        let _entered = this.handle.enter();
+        this.future.as_mut().poll(cx)
+    }
+}
+
+/// Polls a future to completion on the calling thread, parking it while the future waits
+fn block_on<Fut: Future>(future: Fut) -> Fut::Output {
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // Spurious wakeups only cause another poll
+        std::thread::park();
+    }
+}
+
+/// A [`ScoutClient`] usable from any executor
+pub struct EmbeddedClient {
+    client: Arc<Mutex<ScoutClient>>,
+    runtime: EmbeddedRuntime,
+}
+
+impl EmbeddedClient {
+    pub fn new(client: ScoutClient) -> Result<Self> {
+        Ok(Self {
+            client: Arc::new(Mutex::new(client)),
+            runtime: EmbeddedRuntime::new()?,
+        })
+    }
+
+    /// Runs requests of the client on the caller's executor, e.g.
+    /// `client.call(|client| Box::pin(async move { client.identify().await }))`
+    pub fn call<F, R>(&self, request: F) -> impl Future<Output = Result<R>> + 'static
+    where
+        F: for<'a> FnOnce(&'a mut ScoutClient) -> LocalBoxFuture<'a, Result<R>> + 'static,
+        R: 'static,
+    {
+        let client = self.client.clone();
+        self.runtime.run(async move {
+            let mut client = client.lock().await;
+            request(&mut client).await
+        })
+    }
+
+    /// Like [`Self::call`], blocking the calling thread
+    pub fn call_blocking<F, R>(&self, request: F) -> Result<R>
+    where
+        F: for<'a> FnOnce(&'a mut ScoutClient) -> LocalBoxFuture<'a, Result<R>>,
+    {
+        let client = self.client.clone();
+        self.runtime.run_blocking(async move {
+            let mut client = client.lock().await;
+            request(&mut client).await
+        })
+    }
+}
+
+/// A [`SyncEngine`] usable from any executor, flushed only when asked to. Local writes block
+/// while a flush runs, and like the blocking calls must not be made within a tokio runtime.
+pub struct EmbeddedSyncEngine {
+    sync_engine: Arc<Mutex<SyncEngine>>,
+    runtime: EmbeddedRuntime,
+}
+
+impl EmbeddedSyncEngine {
+    pub fn new(sync_engine: SyncEngine) -> Result<Self> {
+        Ok(Self {
+            sync_engine: Arc::new(Mutex::new(sync_engine)),
+            runtime: EmbeddedRuntime::new()?,
+        })
+    }
+
+    /// See [`SyncEngine::ingest_items`]
+    pub fn ingest_items<T: StoredModel + Normalize>(&self, items: Vec<T>) -> Result<()> {
+        self.sync_engine.blocking_lock().ingest_items(items)
+    }
+
+    /// See [`SyncEngine::upsert_items`]
+    pub fn upsert_items<T: StoredModel>(&self, items: Vec<T>) -> Result<()> {
+        self.sync_engine.blocking_lock().upsert_items(items)
+    }
+
+    /// See [`SyncEngine::remove_items`]
+    pub fn remove_items<T: StoredModel>(&self, items: Vec<T>) -> Result<()> {
+        self.sync_engine.blocking_lock().remove_items(items)
+    }
+
+    /// See [`SyncEngine::generate_unique_id`]
+    pub fn generate_unique_id<T: StoredModel>(&self) -> Result<u64> {
+        self.sync_engine.blocking_lock().generate_unique_id::<T>()
+    }
+
+    /// See [`SyncEngine::get_table_count`]
+    pub fn get_table_count<T: StoredModel>(&self) -> Result<u64> {
+        self.sync_engine.blocking_lock().get_table_count::<T>()
+    }
+
+    /// Sends unsynced records to the server, see [`SyncEngine::flush`]
+    pub fn flush(&self) -> impl Future<Output = Result<SyncReport>> + 'static {
+        let sync_engine = self.sync_engine.clone();
+        self.runtime
+            .run(async move { sync_engine.lock().await.flush().await })
+    }
+
+    /// Like [`Self::flush`], blocking the calling thread
+    pub fn flush_blocking(&self) -> Result<SyncReport> {
+        let sync_engine = self.sync_engine.clone();
+        self.runtime
+            .run_blocking(async move { sync_engine.lock().await.flush().await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_client::offline_test_config;
+    use crate::models::EventLocal;
+    use crate::sync::offline_test_engine;
+
+    #[test]
+    fn test_embedded_sync_engine_without_caller_runtime() -> Result<()> {
+        // Timers need the runtime the job runs on
+        let runtime = EmbeddedRuntime::new()?;
+        let slept = runtime.run_blocking(async {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            "slept"
+        });
+        assert_eq!(slept, "slept");
+
+        let client = EmbeddedClient::new(ScoutClient::new(offline_test_config()))?;
+        let identified =
+            client.call_blocking(|client| Box::pin(async move { Ok(client.is_identified()) }))?;
+        assert!(!identified);
+
+        let sync_engine = EmbeddedSyncEngine::new(offline_test_engine()?)?;
+        let id_local = sync_engine.generate_unique_id::<EventLocal>()?.to_string();
+        sync_engine.ingest_items(vec![EventLocal {
+            id_local: Some(id_local),
+            ..Default::default()
+        }])?;
+        assert_eq!(sync_engine.get_table_count::<EventLocal>()?, 1);
+
+        // The server is unreachable, but the flush runs to completion on this thread
+        let _ = sync_engine.flush_blocking();
+        assert_eq!(sync_engine.get_table_count::<EventLocal>()?, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! Validation and normalization of WKT locations before the server sees them. WKT puts
+//! longitude first (`POINT(lon lat)`); a latitude-first point is the most common mistake
+//! and is swapped back when the coordinates leave no doubt, or when a reference position
+//! of the deployment says which order is plausible. Impossible coordinates are rejected.
+//!
+//! ```
+//! use scout_rs::geometry::GeometryNormalizer;
+//!
+//! let normalizer = GeometryNormalizer::new();
+//! // Latitude cannot be 151.2, so the coordinates were swapped
+//! assert_eq!(
+//!     normalizer.normalize("point(-33.9 151.2)").unwrap(),
+//!     "POINT(151.2 -33.9)"
+//! );
+//! ```
+
+use crate::logging;
+
+/// Geometry types the server stores locations as
+const SUPPORTED_TYPES: [&str; 6] = [
+    "POINT",
+    "LINESTRING",
+    "POLYGON",
+    "MULTIPOINT",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+];
+
+/// Distance from the reference position, in degrees, within which a point is plausible
+const REFERENCE_RADIUS_DEGREES: f64 = 5.0;
+
+/// Why a location was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeometryError {
+    /// Not parseable as WKT
+    Malformed { wkt: String, reason: &'static str },
+    /// A geometry type the server does not store locations as
+    UnsupportedType(String),
+    /// A coordinate off the globe in either order, or not a finite number
+    ImpossibleCoordinate { x: f64, y: f64 },
+    /// 0, 0, which receivers report without a fix
+    NullIsland,
+}
+
+impl std::fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeometryError::Malformed { wkt, reason } => {
+                write!(f, "Malformed WKT {:?}: {}", wkt, reason)
+            }
+            GeometryError::UnsupportedType(kind) => {
+                write!(f, "Unsupported geometry type {}", kind)
+            }
+            GeometryError::ImpossibleCoordinate { x, y } => {
+                write!(f, "Impossible coordinate {} {}", x, y)
+            }
+            GeometryError::NullIsland => write!(f, "Coordinate 0 0 (no GPS fix)"),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    /// x, y and an optional z
+    Coordinate(Vec<f64>),
+}
+
+/// Validates WKT locations and fixes latitude/longitude order, see the module docs
+#[derive(Debug, Clone, Default)]
+pub struct GeometryNormalizer {
+    /// Approximate `(latitude, longitude)` of the deployment, to tell swapped points apart
+    /// when both orders are on the globe
+    pub reference: Option<(f64, f64)>,
+    /// Accept 0, 0 as a real position
+    pub allow_null_island: bool,
+}
+
+impl GeometryNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_reference(mut self, latitude: f64, longitude: f64) -> Self {
+        self.reference = Some((latitude, longitude));
+        self
+    }
+
+    pub fn with_null_island_allowed(mut self, allowed: bool) -> Self {
+        self.allow_null_island = allowed;
+        self
+    }
+
+    /// Returns the location as canonical WKT (upper-case type, `x y` pairs separated by
+    /// `, `), with swapped coordinates put back in longitude-first order
+    pub fn normalize(&self, wkt: &str) -> Result<String, GeometryError> {
+        let (srid, kind, mut tokens) = parse(wkt)?;
+
+        let coordinates = || {
+            tokens.iter().filter_map(|token| match token {
+                Token::Coordinate(values) => Some((values[0], values[1])),
+                _ => None,
+            })
+        };
+        for (x, y) in coordinates() {
+            if !x.is_finite() || !y.is_finite() || x.abs() > 180.0 || y.abs() > 180.0 {
+                return Err(GeometryError::ImpossibleCoordinate { x, y });
+            }
+            if x == 0.0 && y == 0.0 && !self.allow_null_island {
+                return Err(GeometryError::NullIsland);
+            }
+        }
+        let as_written = coordinates().all(|(_, y)| y.abs() <= 90.0);
+        let swapped = coordinates().all(|(x, _)| x.abs() <= 90.0);
+        let swap = match (as_written, swapped) {
+            (true, true) => self.closer_swapped(coordinates()),
+            (true, false) => false,
+            (false, true) => true,
+            (false, false) => {
+                let (x, y) = coordinates()
+                    .find(|(_, y)| y.abs() > 90.0)
+                    .unwrap_or_default();
+                return Err(GeometryError::ImpossibleCoordinate { x, y });
+            }
+        };
+        if swap {
+            logging::debug!("Swapping latitude-first coordinates of {}", wkt);
+            for token in tokens.iter_mut() {
+                if let Token::Coordinate(values) = token {
+                    values.swap(0, 1);
+                }
+            }
+        }
+
+        let mut normalized = String::with_capacity(wkt.len());
+        if let Some(srid) = srid {
+            normalized.push_str(&srid.to_ascii_uppercase());
+            normalized.push(';');
+        }
+        normalized.push_str(&kind);
+        for token in &tokens {
+            match token {
+                Token::Open => normalized.push('('),
+                Token::Close => normalized.push(')'),
+                Token::Comma => normalized.push_str(", "),
+                Token::Coordinate(values) => {
+                    let values: Vec<String> = values.iter().map(f64::to_string).collect();
+                    normalized.push_str(&values.join(" "));
+                }
+            }
+        }
+        Ok(normalized)
+    }
+
+    /// Whether the first point is near the reference only with its coordinates swapped
+    fn closer_swapped(&self, mut coordinates: impl Iterator<Item = (f64, f64)>) -> bool {
+        let (Some((latitude, longitude)), Some((x, y))) = (self.reference, coordinates.next())
+        else {
+            return false;
+        };
+        let near = |lon: f64, lat: f64| {
+            let d_lon = (lon - longitude).abs() % 360.0;
+            let d_lon = d_lon.min(360.0 - d_lon) * latitude.to_radians().cos();
+            (lat - latitude).hypot(d_lon) <= REFERENCE_RADIUS_DEGREES
+        };
+        !near(x, y) && near(y, x)
+    }
+}
+
+/// The `(x, y)` coordinates of a WKT location of any supported type, as written
+pub fn coordinates(wkt: &str) -> Result<Vec<(f64, f64)>, GeometryError> {
+    let (_, _, tokens) = parse(wkt)?;
+    Ok(tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Coordinate(values) => Some((values[0], values[1])),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Parses a WKT `POINT(longitude latitude)` location
+pub(crate) fn parse_point(location: &str) -> Option<(f64, f64)> {
+    let coords = location.trim().strip_prefix("POINT(")?.strip_suffix(')')?;
+    let mut parts = coords.split_whitespace();
+    let longitude = parts.next()?.parse().ok()?;
+    let latitude = parts.next()?.parse().ok()?;
+    Some((longitude, latitude))
+}
+
+/// Ray-casting point-in-polygon test over (longitude, latitude) vertices
+pub(crate) fn polygon_contains(polygon: &[(f64, f64)], longitude: f64, latitude: f64) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &(xi, yi)) in polygon.iter().enumerate() {
+        let (xj, yj) = polygon[j];
+        if (yi > latitude) != (yj > latitude)
+            && longitude < (xj - xi) * (latitude - yi) / (yj - yi) + xi
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// The EWKT spatial reference (e.g. `SRID=4326`), upper-case type and tokens of a WKT
+/// location
+fn parse(wkt: &str) -> Result<(Option<&str>, String, Vec<Token>), GeometryError> {
+    let malformed = |reason| GeometryError::Malformed {
+        wkt: wkt.to_string(),
+        reason,
+    };
+    let trimmed = wkt.trim();
+    // EWKT spatial reference, e.g. `SRID=4326;POINT(...)`
+    let (srid, geometry) = match trimmed.split_once(';') {
+        Some((srid, geometry)) if srid.to_ascii_uppercase().starts_with("SRID=") => {
+            (Some(srid), geometry.trim())
+        }
+        _ => (None, trimmed),
+    };
+    let body_start = geometry
+        .find('(')
+        .ok_or_else(|| malformed("no coordinates"))?;
+    let kind = geometry[..body_start].trim().to_ascii_uppercase();
+    if !SUPPORTED_TYPES.contains(&kind.as_str()) {
+        return Err(GeometryError::UnsupportedType(kind));
+    }
+    let tokens = tokenize(&geometry[body_start..]).map_err(malformed)?;
+    Ok((srid, kind, tokens))
+}
+
+/// Parentheses, commas and coordinates of a WKT body, checking its structure
+fn tokenize(body: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut values: Vec<f64> = Vec::new();
+    let mut number = String::new();
+    let mut depth = 0usize;
+    for c in body.chars().chain(std::iter::once(' ')) {
+        if !c.is_whitespace() && !"(),".contains(c) {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            values.push(number.parse().map_err(|_| "coordinate is not a number")?);
+            number.clear();
+        }
+        if c.is_whitespace() {
+            continue;
+        }
+        if !values.is_empty() {
+            if !(2..=3).contains(&values.len()) {
+                return Err("coordinates need 2 or 3 values");
+            }
+            tokens.push(Token::Coordinate(std::mem::take(&mut values)));
+        }
+        match c {
+            '(' => {
+                if depth == 0 && !tokens.is_empty() {
+                    return Err("text after the geometry");
+                }
+                depth += 1;
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                if matches!(tokens.last(), Some(Token::Open | Token::Comma) | None) {
+                    return Err("empty coordinate list");
+                }
+                depth = depth.checked_sub(1).ok_or("unbalanced parentheses")?;
+                tokens.push(Token::Close);
+            }
+            _ => {
+                if matches!(tokens.last(), Some(Token::Open | Token::Comma) | None) {
+                    return Err("empty coordinate list");
+                }
+                tokens.push(Token::Comma);
+            }
+        }
+    }
+    if depth != 0 {
+        return Err("unbalanced parentheses");
+    }
+    if values.is_empty() && matches!(tokens.last(), Some(Token::Close)) {
+        Ok(tokens)
+    } else {
+        Err("text after the geometry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_fixes_order_and_rejects_impossible_coordinates() {
+        let normalizer = GeometryNormalizer::new();
+        assert_eq!(
+            normalizer.normalize("POINT(-122.4 37.8)"),
+            Ok("POINT(-122.4 37.8)".to_string())
+        );
+        assert_eq!(
+            normalizer.normalize(" linestring ( 10 45.5,11   46 ) "),
+            Ok("LINESTRING(10 45.5, 11 46)".to_string())
+        );
+        assert_eq!(
+            normalizer.normalize("SRID=4326;POINT(45.5 120)"),
+            Ok("SRID=4326;POINT(120 45.5)".to_string())
+        );
+        // Both orders are on the globe; only a reference position can tell
+        assert_eq!(
+            normalizer.normalize("POINT(-1.28 36.82)"),
+            Ok("POINT(-1.28 36.82)".to_string())
+        );
+        let nairobi = GeometryNormalizer::new().with_reference(-1.29, 36.82);
+        assert_eq!(
+            nairobi.normalize("POINT(-1.28 36.82)"),
+            Ok("POINT(36.82 -1.28)".to_string())
+        );
+        assert_eq!(
+            nairobi.normalize("POINT(36.8 -1.3)"),
+            Ok("POINT(36.8 -1.3)".to_string())
+        );
+
+        assert_eq!(
+            normalizer.normalize("POINT(120 100)"),
+            Err(GeometryError::ImpossibleCoordinate { x: 120.0, y: 100.0 })
+        );
+        assert_eq!(
+            normalizer.normalize("POINT(200 10)"),
+            Err(GeometryError::ImpossibleCoordinate { x: 200.0, y: 10.0 })
+        );
+        assert_eq!(
+            normalizer.normalize("POINT(0 0)"),
+            Err(GeometryError::NullIsland)
+        );
+        assert_eq!(
+            normalizer.normalize("CIRCLE(1 2)"),
+            Err(GeometryError::UnsupportedType("CIRCLE".to_string()))
+        );
+        for malformed in [
+            "POINT",
+            "POINT(1)",
+            "POINT(1 2",
+            "POINT(1 2))",
+            "POINT(1 a)",
+        ] {
+            assert!(
+                matches!(
+                    normalizer.normalize(malformed),
+                    Err(GeometryError::Malformed { .. })
+                ),
+                "{}",
+                malformed
+            );
+        }
+    }
+}
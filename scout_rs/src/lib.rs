@@ -1,7 +1,51 @@
+pub mod boot;
+pub mod budget;
+#[cfg(feature = "chaos")]
+pub mod chaos;
 pub mod client;
+pub mod coco;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+mod credentials;
 pub mod db_client;
+pub mod detections;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod geometry;
+pub mod link_quality;
+mod logging;
+#[cfg(feature = "mavlink")]
+pub mod mavlink;
 pub mod models;
+pub mod nav;
+#[cfg(feature = "netprobe")]
+pub mod netprobe;
+#[cfg(feature = "nmea")]
+pub mod nmea;
+pub mod no_sync;
+pub mod normalize;
+mod operator_auth;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod poll;
+pub mod replica;
+pub mod retention;
+pub mod secrets;
+#[cfg(feature = "service")]
+pub mod service;
+pub mod share;
+pub mod sql_dump;
 pub mod storage;
+pub mod store;
+mod stub_sessions;
 pub mod sync;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
+#[cfg(any(feature = "mavlink", feature = "nmea"))]
+mod telemetry;
+pub mod throttle;
+pub mod trace;
+pub mod trigger;
 pub mod tus;
-pub mod ui;
\ No newline at end of file
+pub mod ui;
+pub mod wal;
\ No newline at end of file
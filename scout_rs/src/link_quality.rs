@@ -0,0 +1,307 @@
+//! Link quality monitoring. Each connectivity row is scored from its signal-to-noise
+//! ratio, and the mean over a rolling window gives the link's quality level. When the
+//! level changes, a text event is recorded at the row's location so crews can see where
+//! to reposition repeaters; degradations can also be posted to an operator webhook.
+//!
+//! ```no_run
+//! # async fn run(
+//! #     mut sync_engine: scout_rs::sync::SyncEngine,
+//! #     rows: Vec<scout_rs::models::ConnectivityLocal>,
+//! # ) -> anyhow::Result<()> {
+//! use scout_rs::link_quality::LinkQualityMonitor;
+//!
+//! let mut monitor = LinkQualityMonitor::new(10)
+//!     .with_alert_webhook("https://ops.example.org/hooks/link-quality");
+//! for row in &rows {
+//!     if let Some(change) = monitor.observe(row) {
+//!         monitor.record(&mut sync_engine, &change, 42).await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use crate::models::{ConnectivityLocal, EventLocal, MediaType};
+use crate::sync::SyncEngine;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// SNR at or above which a row scores 100
+const FULL_SCORE_SNR_DB: f64 = 30.0;
+
+/// Quality level of the link, from the rolling score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkQuality {
+    Poor,
+    Degraded,
+    Good,
+}
+
+impl LinkQuality {
+    fn name(&self) -> &'static str {
+        match self {
+            LinkQuality::Poor => "poor",
+            LinkQuality::Degraded => "degraded",
+            LinkQuality::Good => "good",
+        }
+    }
+}
+
+/// A change of the link's quality level, at the row that caused it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkQualityChange {
+    pub previous: LinkQuality,
+    pub current: LinkQuality,
+    /// Rolling score from 0 to 100
+    pub score: f64,
+    pub timestamp: String,
+    pub location: Option<String>,
+    #[serde(skip)]
+    pub session_id: Option<i64>,
+    #[serde(skip)]
+    pub ancestor_id_local: Option<String>,
+}
+
+impl LinkQualityChange {
+    pub fn is_degradation(&self) -> bool {
+        self.current < self.previous
+    }
+
+    /// The change as a text event of `device_id`, in the session of the row
+    pub fn to_event_local(&self, device_id: i64) -> EventLocal {
+        EventLocal {
+            message: Some(format!(
+                "Link quality {} from {} to {} (score {:.0})",
+                if self.is_degradation() {
+                    "dropped"
+                } else {
+                    "recovered"
+                },
+                self.previous.name(),
+                self.current.name(),
+                self.score
+            )),
+            location: self.location.clone(),
+            media_type: MediaType::Text,
+            device_id,
+            timestamp_observation: self.timestamp.clone(),
+            session_id: self.session_id,
+            ancestor_id_local: self.ancestor_id_local.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rolling link quality over the last connectivity rows; see the module docs
+#[derive(Debug, Clone)]
+pub struct LinkQualityMonitor {
+    window: usize,
+    scores: VecDeque<f64>,
+    degraded_below: f64,
+    poor_below: f64,
+    level: LinkQuality,
+    alert_webhook: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl LinkQualityMonitor {
+    /// Scores over the last `window` rows, degraded below 60 and poor below 30
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            scores: VecDeque::new(),
+            degraded_below: 60.0,
+            poor_below: 30.0,
+            level: LinkQuality::Good,
+            alert_webhook: None,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Scores below which the link is degraded and poor
+    pub fn with_thresholds(mut self, degraded_below: f64, poor_below: f64) -> Self {
+        self.degraded_below = degraded_below;
+        self.poor_below = poor_below;
+        self
+    }
+
+    /// Posts each degradation as JSON to `url` when it is recorded
+    pub fn with_alert_webhook(mut self, url: impl Into<String>) -> Self {
+        self.alert_webhook = Some(url.into());
+        self
+    }
+
+    /// Score of one row from 0 to 100: its SNR in dB, scaled so 30 dB scores 100
+    pub fn score_row(row: &ConnectivityLocal) -> f64 {
+        ((row.signal.value() - row.noise.value()) / FULL_SCORE_SNR_DB * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Mean score over the window, None before the first row
+    pub fn score(&self) -> Option<f64> {
+        (!self.scores.is_empty())
+            .then(|| self.scores.iter().sum::<f64>() / self.scores.len() as f64)
+    }
+
+    pub fn level(&self) -> LinkQuality {
+        self.level
+    }
+
+    /// Adds a row to the window, returning the change if the quality level changed
+    pub fn observe(&mut self, row: &ConnectivityLocal) -> Option<LinkQualityChange> {
+        if self.scores.len() == self.window {
+            self.scores.pop_front();
+        }
+        self.scores.push_back(Self::score_row(row));
+
+        let score = self.score()?;
+        let level = if score < self.poor_below {
+            LinkQuality::Poor
+        } else if score < self.degraded_below {
+            LinkQuality::Degraded
+        } else {
+            LinkQuality::Good
+        };
+        if level == self.level {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.level, level);
+        Some(LinkQualityChange {
+            previous,
+            current: level,
+            score,
+            timestamp: row.timestamp_start.clone(),
+            location: row.location.clone(),
+            session_id: row.session_id,
+            ancestor_id_local: row.ancestor_id_local.clone(),
+        })
+    }
+
+    /// Stores the change as an event, and posts degradations to the alert webhook. A
+    /// failed alert is logged; the event is kept either way.
+    pub async fn record(
+        &self,
+        sync_engine: &mut SyncEngine,
+        change: &LinkQualityChange,
+        device_id: i64,
+    ) -> Result<EventLocal> {
+        let mut event = change.to_event_local(device_id);
+        event.id_local = Some(sync_engine.generate_unique_id::<EventLocal>()?.to_string());
+        sync_engine.upsert_items(vec![event.clone()])?;
+
+        if change.is_degradation() {
+            if let Err(e) = self.alert(change, device_id).await {
+                logging::warn!("Failed to send link quality alert: {}", e);
+            }
+        }
+        Ok(event)
+    }
+
+    async fn alert(&self, change: &LinkQualityChange, device_id: i64) -> Result<()> {
+        #[derive(Serialize)]
+        struct Alert<'a> {
+            device_id: i64,
+            #[serde(flatten)]
+            change: &'a LinkQualityChange,
+        }
+
+        let Some(url) = &self.alert_webhook else {
+            return Ok(());
+        };
+        let response = self
+            .http_client
+            .post(url)
+            .json(&Alert { device_id, change })
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Webhook returned HTTP {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Dbm;
+    use crate::sync::offline_test_engine;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn row(signal: f64, noise: f64) -> ConnectivityLocal {
+        ConnectivityLocal {
+            signal: Dbm::new(signal).unwrap(),
+            noise: Dbm::new(noise).unwrap(),
+            timestamp_start: "2024-06-01T12:00:00Z".to_string(),
+            location: Some("POINT(36.8 -1.3)".to_string()),
+            ancestor_id_local: Some("session_1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_degradation_recorded_and_alerted() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let webhook = format!("http://{}/alerts", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut stream = listener.incoming().next().unwrap().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(": ") {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        });
+
+        let mut monitor = LinkQualityMonitor::new(2).with_alert_webhook(webhook);
+        // -70 dBm over a -95 dBm floor is 25 dB, scoring 83
+        assert_eq!(monitor.observe(&row(-70.0, -95.0)), None);
+        assert_eq!(monitor.observe(&row(-70.0, -95.0)), None);
+        // The window averages 83 and 17 to 50: degraded but not yet poor
+        let degraded = monitor.observe(&row(-90.0, -95.0)).unwrap();
+        assert_eq!(degraded.previous, LinkQuality::Good);
+        assert_eq!(degraded.current, LinkQuality::Degraded);
+        let poor = monitor.observe(&row(-90.0, -95.0)).unwrap();
+        assert_eq!(poor.current, LinkQuality::Poor);
+
+        let mut sync_engine = offline_test_engine()?;
+        let event = monitor.record(&mut sync_engine, &poor, 7).await?;
+        assert_eq!(
+            event.message.as_deref(),
+            Some("Link quality dropped from degraded to poor (score 17)")
+        );
+        assert_eq!(event.location.as_deref(), Some("POINT(36.8 -1.3)"));
+        assert_eq!(event.ancestor_id_local.as_deref(), Some("session_1"));
+        assert_eq!(sync_engine.get_table_count::<EventLocal>()?, 1);
+
+        let alert = server.join().unwrap();
+        assert_eq!(alert["device_id"], 7);
+        assert_eq!(alert["previous"], "degraded");
+        assert_eq!(alert["current"], "poor");
+
+        // Recoveries are recorded without an alert
+        let recovered = monitor.observe(&row(-60.0, -95.0)).unwrap();
+        assert!(!recovered.is_degradation());
+        monitor.record(&mut sync_engine, &recovered, 7).await?;
+        assert_eq!(sync_engine.get_table_count::<EventLocal>()?, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Logging facade for the crate. Logs go through `tracing` by default, or through the
+//! `log` crate with `default-features = false, features = ["log"]`. With neither
+//! feature enabled, logging compiles to nothing.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, error, info, warn};
+
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+pub(crate) use log::{debug, error, info, warn};
+
+#[cfg(not(any(feature = "tracing", feature = "log")))]
+mod disabled {
+    macro_rules! discard {
+        ($($arg:tt)*) => {{
+            let _ = format_args!($($arg)*);
+        }};
+    }
+    pub(crate) use discard as debug;
+    pub(crate) use discard as error;
+    pub(crate) use discard as info;
+    pub(crate) use discard as warn;
+}
+
+#[cfg(not(any(feature = "tracing", feature = "log")))]
+pub(crate) use disabled::{debug, error, info, warn};
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_logs_reach_the_tracing_subscriber() {
+        use std::sync::{Arc, Mutex};
+
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(tracing::Level::INFO)
+            .with_writer(move || Buffer(writer.clone()))
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            super::warn!("budget of {} rows used up", 3);
+            super::debug!("below the level");
+        });
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("WARN"));
+        assert!(output.contains("budget of 3 rows used up"));
+        assert!(!output.contains("below the level"));
+    }
+
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    #[test]
+    fn test_logs_reach_the_log_logger() {
+        use std::sync::Mutex;
+
+        struct Recorder(Mutex<Vec<(log::Level, String)>>);
+
+        impl log::Log for Recorder {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((record.level(), record.args().to_string()));
+            }
+
+            fn flush(&self) {}
+        }
+
+        static RECORDER: Recorder = Recorder(Mutex::new(Vec::new()));
+        log::set_logger(&RECORDER).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        super::warn!("budget of {} rows used up", 3);
+        super::debug!("below the level");
+
+        assert_eq!(
+            *RECORDER.0.lock().unwrap(),
+            vec![(log::Level::Warn, "budget of 3 rows used up".to_string())]
+        );
+    }
+}
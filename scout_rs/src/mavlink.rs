@@ -0,0 +1,338 @@
+//! MAVLink telemetry ingestion. Decodes GLOBAL_POSITION_INT and SYS_STATUS from a raw
+//! MAVLink v1/v2 byte stream (serial port, UDP) and records them on a session: streamed
+//! track points, altitude/velocity/distance stats, and connectivity entries with battery level.
+
+use crate::sync::SyncEngine;
+use crate::telemetry::TrackRecorder;
+use anyhow::Result;
+
+const MAGIC_V1: u8 = 0xFE;
+const MAGIC_V2: u8 = 0xFD;
+const MSG_ID_SYS_STATUS: u32 = 1;
+const MSG_ID_GLOBAL_POSITION_INT: u32 = 33;
+
+/// GLOBAL_POSITION_INT (#33): fused position, in MAVLink units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalPositionInt {
+    pub time_boot_ms: u32,
+    /// Latitude, degrees * 1e7
+    pub lat: i32,
+    /// Longitude, degrees * 1e7
+    pub lon: i32,
+    /// Altitude above mean sea level, millimeters
+    pub alt: i32,
+    /// Altitude above home, millimeters
+    pub relative_alt: i32,
+    /// Ground velocity north/east/down, cm/s
+    pub vx: i16,
+    pub vy: i16,
+    pub vz: i16,
+    /// Heading, centidegrees (u16::MAX if unknown)
+    pub hdg: u16,
+}
+
+/// SYS_STATUS (#1): the battery fields used for connectivity entries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SysStatus {
+    /// Millivolts (u16::MAX if unknown)
+    pub voltage_battery: u16,
+    /// Centiamperes (-1 if unknown)
+    pub current_battery: i16,
+    /// Percent (-1 if unknown)
+    pub battery_remaining: i8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MavlinkMessage {
+    GlobalPositionInt(GlobalPositionInt),
+    SysStatus(SysStatus),
+}
+
+/// Splits a MAVLink byte stream into messages. Frames with a bad checksum are skipped and
+/// the parser resynchronizes on the next start byte; messages other than the supported
+/// ones are ignored.
+#[derive(Debug, Default)]
+pub struct MavlinkParser {
+    buffer: Vec<u8>,
+}
+
+impl MavlinkParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consumes bytes and returns the messages completed by them
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<MavlinkMessage> {
+        self.buffer.extend_from_slice(bytes);
+        let mut messages = Vec::new();
+        loop {
+            let Some(start) = self
+                .buffer
+                .iter()
+                .position(|b| *b == MAGIC_V1 || *b == MAGIC_V2)
+            else {
+                self.buffer.clear();
+                break;
+            };
+            self.buffer.drain(..start);
+
+            match Self::parse_frame(&self.buffer) {
+                Frame::Incomplete => break,
+                Frame::Invalid => {
+                    self.buffer.drain(..1);
+                }
+                Frame::Skipped(length) => {
+                    self.buffer.drain(..length);
+                }
+                Frame::Message(message, length) => {
+                    messages.push(message);
+                    self.buffer.drain(..length);
+                }
+            }
+        }
+        messages
+    }
+
+    fn parse_frame(buffer: &[u8]) -> Frame {
+        let (header_len, msg_id, signed) = match buffer.first() {
+            Some(&MAGIC_V1) if buffer.len() >= 6 => (6, buffer[5] as u32, false),
+            Some(&MAGIC_V2) if buffer.len() >= 10 => (
+                10,
+                u32::from_le_bytes([buffer[7], buffer[8], buffer[9], 0]),
+                buffer[2] & 0x01 != 0,
+            ),
+            _ => return Frame::Incomplete,
+        };
+        let payload_len = buffer[1] as usize;
+        let frame_len = header_len + payload_len + 2 + if signed { 13 } else { 0 };
+        if buffer.len() < frame_len {
+            return Frame::Incomplete;
+        }
+
+        let Some((crc_extra, full_len)) = message_info(msg_id) else {
+            return Frame::Skipped(frame_len);
+        };
+        let crc_end = header_len + payload_len;
+        let mut crc = crc_x25(&buffer[1..crc_end]);
+        crc = crc_x25_accumulate(crc, crc_extra);
+        if crc.to_le_bytes() != [buffer[crc_end], buffer[crc_end + 1]] {
+            return Frame::Invalid;
+        }
+
+        // MAVLink 2 trims trailing zero bytes from payloads
+        let mut payload = buffer[header_len..crc_end].to_vec();
+        payload.resize(payload.len().max(full_len), 0);
+        match decode(msg_id, &payload) {
+            Some(message) => Frame::Message(message, frame_len),
+            None => Frame::Skipped(frame_len),
+        }
+    }
+}
+
+enum Frame {
+    Incomplete,
+    Invalid,
+    Skipped(usize),
+    Message(MavlinkMessage, usize),
+}
+
+/// CRC_EXTRA seed and full payload length of supported messages
+fn message_info(msg_id: u32) -> Option<(u8, usize)> {
+    match msg_id {
+        MSG_ID_SYS_STATUS => Some((124, 31)),
+        MSG_ID_GLOBAL_POSITION_INT => Some((104, 28)),
+        _ => None,
+    }
+}
+
+fn decode(msg_id: u32, p: &[u8]) -> Option<MavlinkMessage> {
+    let u16_at = |i: usize| u16::from_le_bytes([p[i], p[i + 1]]);
+    let i16_at = |i: usize| i16::from_le_bytes([p[i], p[i + 1]]);
+    let i32_at = |i: usize| i32::from_le_bytes([p[i], p[i + 1], p[i + 2], p[i + 3]]);
+    match msg_id {
+        MSG_ID_GLOBAL_POSITION_INT => Some(MavlinkMessage::GlobalPositionInt(GlobalPositionInt {
+            time_boot_ms: u32::from_le_bytes([p[0], p[1], p[2], p[3]]),
+            lat: i32_at(4),
+            lon: i32_at(8),
+            alt: i32_at(12),
+            relative_alt: i32_at(16),
+            vx: i16_at(20),
+            vy: i16_at(22),
+            vz: i16_at(24),
+            hdg: u16_at(26),
+        })),
+        MSG_ID_SYS_STATUS => Some(MavlinkMessage::SysStatus(SysStatus {
+            voltage_battery: u16_at(14),
+            current_battery: i16_at(16),
+            battery_remaining: p[30] as i8,
+        })),
+        _ => None,
+    }
+}
+
+/// X.25 (MCRF4XX) checksum used by MAVLink
+fn crc_x25(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .fold(0xFFFF, |crc, b| crc_x25_accumulate(crc, *b))
+}
+
+fn crc_x25_accumulate(crc: u16, byte: u8) -> u16 {
+    let mut tmp = byte ^ (crc & 0xFF) as u8;
+    tmp ^= tmp << 4;
+    let tmp = tmp as u16;
+    (crc >> 8) ^ (tmp << 8) ^ (tmp << 3) ^ (tmp >> 4)
+}
+
+/// Feeds MAVLink telemetry into a session of a [`SyncEngine`]. Positions become track
+/// points (appended every `track_batch_size` points) and session stats; each SYS_STATUS
+/// records a connectivity entry at the latest position with the battery level.
+#[derive(Debug)]
+pub struct MavlinkIngest {
+    recorder: TrackRecorder,
+    parser: MavlinkParser,
+    last_position: Option<GlobalPositionInt>,
+}
+
+impl MavlinkIngest {
+    /// Ingests into an existing local session
+    pub fn new(session_id_local: impl Into<String>, device_id: i64) -> Self {
+        Self {
+            recorder: TrackRecorder::new(session_id_local.into(), device_id),
+            parser: MavlinkParser::new(),
+            last_position: None,
+        }
+    }
+
+    /// Number of positions buffered before they are appended as a track segment
+    pub fn with_track_batch_size(mut self, track_batch_size: usize) -> Self {
+        self.recorder.track_batch_size = track_batch_size.max(1);
+        self
+    }
+
+    /// Parses raw bytes from the stream and ingests the messages they complete
+    pub fn ingest_bytes(&mut self, sync_engine: &mut SyncEngine, bytes: &[u8]) -> Result<()> {
+        for message in self.parser.push(bytes) {
+            self.ingest(sync_engine, message)?;
+        }
+        Ok(())
+    }
+
+    pub fn ingest(&mut self, sync_engine: &mut SyncEngine, message: MavlinkMessage) -> Result<()> {
+        match message {
+            MavlinkMessage::GlobalPositionInt(position) => {
+                self.last_position = Some(position);
+                self.recorder.record(
+                    sync_engine,
+                    point(&position),
+                    position.alt as f64 / 1000.0,
+                    (position.vx as f64).hypot(position.vy as f64) / 100.0,
+                )
+            }
+            MavlinkMessage::SysStatus(status) => {
+                // Connectivity entries need a location
+                let Some(position) = self.last_position else {
+                    return Ok(());
+                };
+                let heading = if position.hdg == u16::MAX {
+                    0.0
+                } else {
+                    position.hdg as f64 / 100.0
+                };
+                self.recorder.record_connectivity(
+                    sync_engine,
+                    point(&position),
+                    position.alt as f64 / 1000.0,
+                    heading,
+                    (status.battery_remaining >= 0).then_some(status.battery_remaining as f32),
+                    None,
+                )
+            }
+        }
+    }
+
+    /// Appends buffered track points and writes the stats so far onto the session
+    pub fn flush(&mut self, sync_engine: &mut SyncEngine) -> Result<()> {
+        self.recorder.flush(sync_engine)
+    }
+}
+
+/// (longitude, latitude) of a position
+fn point(position: &GlobalPositionInt) -> (f64, f64) {
+    (position.lon as f64 / 1e7, position.lat as f64 / 1e7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ConnectivityLocal, SessionLocal, Syncable};
+    use crate::sync::offline_test_engine;
+
+    fn frame_v2(msg_id: u32, payload: &[u8]) -> Vec<u8> {
+        let (crc_extra, _) = message_info(msg_id).unwrap();
+        let trimmed = payload.len() - payload.iter().rev().take_while(|b| **b == 0).count();
+        let mut frame = vec![MAGIC_V2, trimmed as u8, 0, 0, 0, 1, 1];
+        frame.extend_from_slice(&msg_id.to_le_bytes()[..3]);
+        frame.extend_from_slice(&payload[..trimmed]);
+        let crc = crc_x25_accumulate(crc_x25(&frame[1..]), crc_extra);
+        frame.extend_from_slice(&crc.to_le_bytes());
+        frame
+    }
+
+    fn position(lat: f64, lon: f64, alt_m: f64, speed_cm_s: i16) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&((lat * 1e7) as i32).to_le_bytes());
+        payload.extend_from_slice(&((lon * 1e7) as i32).to_le_bytes());
+        payload.extend_from_slice(&((alt_m * 1000.0) as i32).to_le_bytes());
+        payload.extend_from_slice(&0i32.to_le_bytes());
+        payload.extend_from_slice(&speed_cm_s.to_le_bytes());
+        payload.extend_from_slice(&[0; 4]);
+        payload.extend_from_slice(&9000u16.to_le_bytes());
+        frame_v2(MSG_ID_GLOBAL_POSITION_INT, &payload)
+    }
+
+    #[test]
+    fn test_mavlink_ingest_records_track_stats_and_connectivity() -> Result<()> {
+        let mut sync_engine = offline_test_engine()?;
+        let mut session = SessionLocal::default();
+        session.set_id_local("flight".to_string());
+        sync_engine.upsert_items(vec![session])?;
+
+        let mut status = vec![0u8; 31];
+        status[30] = 87;
+        let mut stream = vec![0x00, 0x42];
+        stream.extend(position(19.0, -155.0, 100.0, 500));
+        let mut corrupt = position(50.0, 50.0, 9000.0, 0);
+        corrupt[12] ^= 0xFF;
+        stream.extend(corrupt);
+        stream.extend(position(19.001, -155.0, 120.0, 1000));
+        stream.extend(frame_v2(MSG_ID_SYS_STATUS, &status));
+
+        let mut ingest = MavlinkIngest::new("flight", 7);
+        // Frames split across reads are reassembled
+        let (first, rest) = stream.split_at(17);
+        ingest.ingest_bytes(&mut sync_engine, first)?;
+        ingest.ingest_bytes(&mut sync_engine, rest)?;
+        ingest.flush(&mut sync_engine)?;
+
+        let segments = sync_engine.get_session_track_segments("flight")?;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].points.len(), 2);
+
+        let session = sync_engine.get_item::<SessionLocal>("flight")?.unwrap();
+        assert_eq!((session.altitude_min, session.altitude_max), (100.0, 120.0));
+        assert_eq!(session.velocity_average, 7.5);
+        assert!((session.distance_total - 111.2).abs() < 0.5);
+
+        let connectivity = sync_engine.get_all_items::<ConnectivityLocal>()?;
+        assert_eq!(connectivity.len(), 1);
+        assert_eq!(
+            connectivity[0].battery_percentage.map(f32::from),
+            Some(87.0)
+        );
+        assert_eq!(connectivity[0].heading, 90.0);
+        assert_eq!(connectivity[0].device_id, Some(7));
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const MODE_CELL: u64 = 1;
+const MAX_RESOLUTION: u8 = 15;
+const BASE_CELL_COUNT: u64 = 122;
+/// Base cells centered on an icosahedron vertex, which have no digit-1 (K axis) children
+const PENTAGON_BASE_CELLS: [u64; 12] = [4, 14, 24, 38, 49, 58, 63, 72, 83, 97, 107, 117];
+
+/// An H3 cell index, exchanged as the lowercase hex string used by the H3 libraries.
+///
+/// Parsing checks the cell encoding (mode, resolution, base cell and digits), so strings
+/// that are not H3 cells are rejected before they are stored or synced. The empty string
+/// parses to [`H3Index::NULL`], meaning no index was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct H3Index(u64);
+
+impl H3Index {
+    /// No index recorded; serialized as an empty string
+    pub const NULL: H3Index = H3Index(0);
+
+    pub fn is_null(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn resolution(&self) -> u8 {
+        ((self.0 >> 52) & 0xf) as u8
+    }
+
+    /// The cell containing this one at a lower (coarser) resolution
+    pub fn parent(&self, resolution: u8) -> Result<H3Index> {
+        if self.is_null() {
+            return Ok(Self::NULL);
+        }
+        if resolution > self.resolution() {
+            return Err(anyhow!(
+                "Cannot derive resolution {} from a resolution {} H3 cell",
+                resolution,
+                self.resolution()
+            ));
+        }
+        let mut index = (self.0 & !(0xf << 52)) | ((resolution as u64) << 52);
+        for r in resolution + 1..=self.resolution() {
+            index |= 0x7 << digit_offset(r);
+        }
+        Ok(H3Index(index))
+    }
+
+    /// This cell and its parents at each resolution from `resolutions`, e.g.
+    /// `[14, 13, 12, 11]` for the connectivity indexes
+    pub fn hierarchy<const N: usize>(&self, resolutions: [u8; N]) -> Result<[H3Index; N]> {
+        let mut cells = [Self::NULL; N];
+        for (cell, resolution) in cells.iter_mut().zip(resolutions) {
+            *cell = self.parent(resolution)?;
+        }
+        Ok(cells)
+    }
+
+    fn validate(index: u64) -> Result<()> {
+        if index >> 63 != 0 || (index >> 59) & 0xf != MODE_CELL || (index >> 56) & 0x7 != 0 {
+            return Err(anyhow!("{:x} is not an H3 cell index", index));
+        }
+        let resolution = ((index >> 52) & 0xf) as u8;
+        let base_cell = (index >> 45) & 0x7f;
+        if base_cell >= BASE_CELL_COUNT {
+            return Err(anyhow!("H3 index {:x} has an invalid base cell", index));
+        }
+        let digit = |r: u8| (index >> digit_offset(r)) & 0x7;
+        if (1..=resolution).any(|r| digit(r) == 7) {
+            return Err(anyhow!("H3 index {:x} has an invalid digit", index));
+        }
+        if (resolution + 1..=MAX_RESOLUTION).any(|r| digit(r) != 7) {
+            return Err(anyhow!(
+                "H3 index {:x} has digits below its resolution",
+                index
+            ));
+        }
+        let first_digit = (1..=resolution).map(digit).find(|digit| *digit != 0);
+        if PENTAGON_BASE_CELLS.contains(&base_cell) && first_digit == Some(1) {
+            return Err(anyhow!("H3 index {:x} is a deleted pentagon child", index));
+        }
+        Ok(())
+    }
+}
+
+fn digit_offset(resolution: u8) -> u64 {
+    (MAX_RESOLUTION - resolution) as u64 * 3
+}
+
+impl FromStr for H3Index {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if value.is_empty() {
+            return Ok(Self::NULL);
+        }
+        let index = u64::from_str_radix(value, 16)
+            .map_err(|_| anyhow!("{:?} is not a hex H3 index", value))?;
+        Self::validate(index)?;
+        Ok(H3Index(index))
+    }
+}
+
+impl TryFrom<&str> for H3Index {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for H3Index {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<u64> for H3Index {
+    type Error = Error;
+
+    fn try_from(index: u64) -> Result<Self> {
+        Self::validate(index)?;
+        Ok(H3Index(index))
+    }
+}
+
+impl From<H3Index> for String {
+    fn from(index: H3Index) -> Self {
+        index.to_string()
+    }
+}
+
+impl fmt::Display for H3Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_null() {
+            Ok(())
+        } else {
+            write!(f, "{:x}", self.0)
+        }
+    }
+}
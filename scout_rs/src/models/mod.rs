@@ -1,33 +1,70 @@
+pub mod h3;
 pub mod health_metric;
+pub mod remote;
 pub mod serde_helpers;
 pub mod v1;
 pub mod v2;
 pub mod v3;
 pub mod v4;
+pub mod v5;
+pub mod v6;
+pub mod v7;
 
 // ===== VERSIONED MODELS FOLLOWING NATIVE_DB PATTERN =====
 // Following the pattern from the native_db documentation:
 // https://docs.rs/native_db/latest/native_db/
 
 pub mod data {
-    // Type aliases pointing to the latest versions
-    pub type ConnectivityLocal = super::v4::ConnectivityLocal;
-    pub type Connectivity = super::v4::Connectivity;
-    pub type OperatorLocal = super::v2::OperatorLocal; // New model in v2
-    pub type Operator = super::v2::Operator; // New model in v2
+    // Type aliases pointing to the latest versions. Comments give the native_model
+    // version, which is not the number of the module defining it.
+    pub type ConnectivityLocal = super::v7::ConnectivityLocal; // Connectivity v7 with typed units
+    pub type Connectivity = super::v7::Connectivity;
+    pub type Dbm = super::v7::Dbm;
+    pub type Percent = super::v7::Percent;
+    pub type QualityFlag = super::v6::QualityFlag;
+    pub type OperatorLocal = super::v5::OperatorLocal; // Operator v2 with per-session sequence
+    pub type Operator = super::v5::Operator;
     pub type ArtifactLocal = super::v2::ArtifactLocal; // Artifact v2 (id 19) in v2.rs
     pub type Artifact = super::v2::Artifact;
+    pub type SyncBudgetLocal = super::v4::SyncBudgetLocal;
+    pub type TagSuppressionLocal = super::v4::TagSuppressionLocal;
+    pub type OperatorTokenLocal = super::v4::OperatorTokenLocal;
+    pub type OperatorToken = super::v4::OperatorToken;
+    pub type OperatorCredentialType = super::v4::OperatorCredentialType;
+    pub type EventSessionLinkLocal = super::v4::EventSessionLinkLocal;
+    pub type EventSessionLink = super::v4::EventSessionLink;
+    pub type SessionTrackSegmentLocal = super::v4::SessionTrackSegmentLocal;
+    pub type SessionTrackAppend = super::v4::SessionTrackAppend;
+    pub type ConnectivityCompaction = super::v4::ConnectivityCompaction;
+    pub type DeletionAuditLocal = super::v4::DeletionAuditLocal;
+    pub type PlanCacheLocal = super::v4::PlanCacheLocal;
+    pub type ArtifactCacheLocal = super::v4::ArtifactCacheLocal;
+    pub type PendingLinkLocal = super::v4::PendingLinkLocal;
+    pub type PendingLinkKind = super::v4::PendingLinkKind;
+    pub type SessionNoteLocal = super::v4::SessionNoteLocal;
+    pub type SessionNote = super::v4::SessionNote;
+    pub type PullCheckpointLocal = super::v4::PullCheckpointLocal;
+    pub type TrashLocal = super::v4::TrashLocal;
+    pub type CircuitBreakerLocal = super::v4::CircuitBreakerLocal;
+    pub type AppliedLinkLocal = super::v4::AppliedLinkLocal;
+    pub type IdMapLocal = super::v4::IdMapLocal;
+    pub type MediaUploadLocal = super::v4::MediaUploadLocal;
+    pub type HeartbeatLocal = super::v4::HeartbeatLocal;
+    pub type EventCorrelationLocal = super::v4::EventCorrelationLocal;
+    pub type SessionRedirectLocal = super::v4::SessionRedirectLocal;
+    pub type FlushResumeLocal = super::v4::FlushResumeLocal;
+
+    pub type SessionLocal = super::v7::SessionLocal; // Session v2 with visibility
+    pub type Session = super::v7::Session;
+    pub type EventLocal = super::v7::EventLocal; // Event v5 with bursts
+    pub type Event = super::v7::Event;
+    pub type TagLocal = super::v6::TagLocal; // Tag v3 with media anchor
+    pub type Tag = super::v6::Tag;
 
     // Other models that haven't changed stay at v1
     pub type Device = super::v1::Device;
     pub type DevicePrettyLocation = super::v1::DevicePrettyLocation;
     pub type Herd = super::v1::Herd;
-    pub type SessionLocal = super::v1::SessionLocal;
-    pub type Session = super::v1::Session;
-    pub type EventLocal = super::v2::EventLocal; // Event v2 with embeddings
-    pub type Event = super::v2::Event;
-    pub type TagLocal = super::v1::TagLocal;
-    pub type Tag = super::v1::Tag;
     pub type Plan = super::v1::Plan;
     pub type PlanInsert = super::v1::PlanInsert;
     pub type Layer = super::v1::Layer;
@@ -37,14 +74,17 @@ pub mod data {
     pub type HealthMetric = super::health_metric::HealthMetric;
 
     // Re-export versioned modules for direct access
-    pub use super::{v1, v2, v3, v4};
+    pub use super::{v1, v2, v3, v4, v5, v6, v7};
 }
 
 // Re-export for backward compatibility at the top level
 pub use data::*;
 
+pub use h3::H3Index;
+pub use remote::{AsRemote, PartialRow, RemoteFields};
+
 // Re-export common traits and enums that are shared across versions
 pub use v1::{
-    AncestorLocal, DeviceType, MediaType, PlanType, ResponseScout, ResponseScoutStatus, Syncable,
-    TagObservationType,
+    AncestorIndexed, AncestorLocal, DeviceType, MediaType, PlanType, RemoteIdIndexed, ResponseScout,
+    ResponseScoutStatus, Syncable, TagObservationType,
 };
@@ -0,0 +1,438 @@
+// Mapping between local models and remote rows without going through the API structs.
+//
+// Converting a batch to API structs clones every record (embeddings included) before it
+// is serialized; `AsRemote` borrows the local record and drops local-only fields while
+// serializing instead. On the way back, `PartialRow` keeps a response row as sent so
+// columns the server omitted do not blank local fields.
+
+use super::data::{ConnectivityLocal, EventLocal, OperatorLocal, TagLocal};
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Impossible, Serialize, SerializeStruct, Serializer};
+use serde::Deserialize;
+use std::fmt;
+
+/// Local models whose remote row is the local struct minus some fields
+pub trait RemoteFields: Serialize {
+    /// Fields only kept in the local store
+    const LOCAL_FIELDS: &'static [&'static str];
+    /// Fields the API struct omits while `None` (server-assigned or optional columns)
+    const OMIT_IF_NONE: &'static [&'static str];
+}
+
+impl RemoteFields for ConnectivityLocal {
+    const LOCAL_FIELDS: &'static [&'static str] = &["id_local", "ancestor_id_local"];
+    const OMIT_IF_NONE: &'static [&'static str] = &["id", "inserted_at", "hdop", "quality_flags"];
+}
+
+impl RemoteFields for EventLocal {
+    const LOCAL_FIELDS: &'static [&'static str] =
+        &["id_local", "ancestor_id_local", "parent_event_id_local"];
+    const OMIT_IF_NONE: &'static [&'static str] = &[
+        "id",
+        "embedding_qwen_vl_2b",
+        "embedding_vertex_mm_01",
+        "parent_event_id",
+        "hdop",
+        "quality_flags",
+        "burst_id",
+    ];
+}
+
+impl RemoteFields for TagLocal {
+    const LOCAL_FIELDS: &'static [&'static str] = &["id_local", "ancestor_id_local"];
+    const OMIT_IF_NONE: &'static [&'static str] = &[
+        "id",
+        "inserted_at",
+        "detector_name",
+        "detector_version",
+        "frame_number",
+        "media_offset_ms",
+    ];
+}
+
+impl RemoteFields for OperatorLocal {
+    const LOCAL_FIELDS: &'static [&'static str] = &["id_local", "ancestor_id_local"];
+    const OMIT_IF_NONE: &'static [&'static str] = &["id", "created_at", "sequence"];
+}
+
+/// A local record serialized as its remote row, e.g. `EventLocal` as `Event`
+#[derive(Debug, Clone, Copy)]
+pub struct AsRemote<'a, T>(pub &'a T);
+
+impl<T: RemoteFields> Serialize for AsRemote<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(RemoteSerializer {
+            inner: serializer,
+            local_fields: T::LOCAL_FIELDS,
+            omit_if_none: T::OMIT_IF_NONE,
+        })
+    }
+}
+
+/// A row of a server response with only the columns the server sent. Missing columns
+/// (e.g. `locations` trimmed for size) differ from columns sent as `null`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PartialRow(pub serde_json::Map<String, serde_json::Value>);
+
+impl PartialRow {
+    /// The row as `T`; missing columns get `T`'s defaults for them, if any
+    pub fn row<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(T::deserialize(serde_json::Value::Object(self.0.clone()))?)
+    }
+
+    /// `local` with the fields the row includes overwritten. Fields the server omitted and
+    /// local-only fields keep their local values.
+    pub fn merge_into<L: Serialize + DeserializeOwned>(&self, local: &L) -> anyhow::Result<L> {
+        let mut merged = serde_json::to_value(local)?;
+        if let Some(fields) = merged.as_object_mut() {
+            for (column, value) in &self.0 {
+                if let Some(field) = fields.get_mut(column) {
+                    *field = value.clone();
+                }
+            }
+        }
+        Ok(serde_json::from_value(merged)?)
+    }
+}
+
+/// Forwards to `inner`, filtering the fields of the top-level struct
+struct RemoteSerializer<S> {
+    inner: S,
+    local_fields: &'static [&'static str],
+    omit_if_none: &'static [&'static str],
+}
+
+struct RemoteStruct<S> {
+    inner: S,
+    local_fields: &'static [&'static str],
+    omit_if_none: &'static [&'static str],
+}
+
+impl<S: SerializeStruct> SerializeStruct for RemoteStruct<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &V,
+    ) -> Result<(), Self::Error> {
+        if self.local_fields.contains(&key)
+            || (self.omit_if_none.contains(&key) && value.serialize(IsNone).is_ok())
+        {
+            return self.inner.skip_field(key);
+        }
+        self.inner.serialize_field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<S: Serializer> Serializer for RemoteSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = RemoteStruct<S::SerializeStruct>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(RemoteStruct {
+            inner: self
+                .inner
+                .serialize_struct(name, len.saturating_sub(self.local_fields.len()))?,
+            local_fields: self.local_fields,
+            omit_if_none: self.omit_if_none,
+        })
+    }
+
+    // Everything else is not a model struct and is passed through unchanged
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<V: ?Sized + Serialize>(self, value: &V) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<V: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &V,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<V: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &V,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.inner.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.inner.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.inner
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.inner.serialize_map(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.inner
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Succeeds only for `None`, without serializing anything
+struct IsNone;
+
+#[derive(Debug)]
+struct NotNone;
+
+impl fmt::Display for NotNone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("value is not None")
+    }
+}
+
+impl std::error::Error for NotNone {}
+
+impl ser::Error for NotNone {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotNone
+    }
+}
+
+macro_rules! not_none {
+    ($($method:ident($($arg:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ok, NotNone> {
+                Err(NotNone)
+            }
+        )*
+    };
+}
+
+impl Serializer for IsNone {
+    type Ok = ();
+    type Error = NotNone;
+    type SerializeSeq = Impossible<(), NotNone>;
+    type SerializeTuple = Impossible<(), NotNone>;
+    type SerializeTupleStruct = Impossible<(), NotNone>;
+    type SerializeTupleVariant = Impossible<(), NotNone>;
+    type SerializeMap = Impossible<(), NotNone>;
+    type SerializeStruct = Impossible<(), NotNone>;
+    type SerializeStructVariant = Impossible<(), NotNone>;
+
+    fn serialize_none(self) -> Result<(), NotNone> {
+        Ok(())
+    }
+
+    fn serialize_some<V: ?Sized + Serialize>(self, _: &V) -> Result<(), NotNone> {
+        Err(NotNone)
+    }
+
+    fn serialize_newtype_struct<V: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: &V,
+    ) -> Result<(), NotNone> {
+        Err(NotNone)
+    }
+
+    fn serialize_newtype_variant<V: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &V,
+    ) -> Result<(), NotNone> {
+        Err(NotNone)
+    }
+
+    not_none! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(&'static str) -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Connectivity, Event, Operator, Tag};
+
+    /// The fields lists match the API struct: a record serializes to the same keys through
+    /// `AsRemote` as through the API struct, and every listed field exists locally
+    fn assert_fields_match<L, R>()
+    where
+        L: RemoteFields + Default + Clone,
+        R: From<L> + Serialize,
+    {
+        let local = L::default();
+        let keys = |value: serde_json::Value| -> Vec<String> {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let local_keys = keys(serde_json::to_value(&local).unwrap());
+        for field in L::LOCAL_FIELDS.iter().chain(L::OMIT_IF_NONE) {
+            assert!(local_keys.iter().any(|key| key == field), "{}", field);
+        }
+        assert_eq!(
+            keys(serde_json::to_value(AsRemote(&local)).unwrap()),
+            keys(serde_json::to_value(R::from(local)).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_remote_fields_match_api_structs() {
+        assert_fields_match::<ConnectivityLocal, Connectivity>();
+        assert_fields_match::<EventLocal, Event>();
+        assert_fields_match::<TagLocal, Tag>();
+        assert_fields_match::<OperatorLocal, Operator>();
+    }
+}
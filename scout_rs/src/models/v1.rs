@@ -1,3 +1,4 @@
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
@@ -13,6 +14,16 @@ pub trait Syncable {
     fn set_id_local(&mut self, id_local: String);
 }
 
+/// Local models with a secondary index on their remote `id`.
+pub trait RemoteIdIndexed {
+    fn remote_id_key() -> KeyDefinition<KeyOptions>;
+}
+
+/// Local models with a secondary index on their `ancestor_id_local`.
+pub trait AncestorIndexed: AncestorLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions>;
+}
+
 pub trait AncestorLocal {
     fn ancestor_id_local(&self) -> Option<String>;
     fn set_ancestor_id_local(&mut self, ancestor_id_local: String);
@@ -302,6 +313,7 @@ impl Syncable for Herd {
 #[native_model(id = 14, version = 1)]
 #[native_db]
 pub struct SessionLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -332,6 +344,7 @@ pub struct Session {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inserted_at: Option<String>,
     pub software_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub locations: Option<String>,
     pub altitude_max: f64,
     pub altitude_min: f64,
@@ -391,6 +404,12 @@ impl Default for Session {
     }
 }
 
+impl RemoteIdIndexed for SessionLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        SessionLocalKey::id.key_definition()
+    }
+}
+
 impl Syncable for SessionLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -1349,6 +1368,7 @@ impl EventLocal {
 #[native_model(id = 17, version = 1)]
 #[native_db]
 pub struct TagLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -1422,6 +1442,12 @@ impl Default for Tag {
     }
 }
 
+impl RemoteIdIndexed for TagLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        TagLocalKey::id.key_definition()
+    }
+}
+
 impl Syncable for TagLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -1949,6 +1975,16 @@ pub struct Heartbeat {
     pub created_at: Option<String>,
     pub timestamp: String,
     pub device_id: i64,
+    /// Battery level from 0 to 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_percentage: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_seconds: Option<i64>,
+    /// Signal in dBm of the latest connectivity reading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connectivity_mode: Option<String>,
 }
 
 impl Default for Heartbeat {
@@ -1958,6 +1994,10 @@ impl Default for Heartbeat {
             created_at: None,
             timestamp: String::new(),
             device_id: 0,
+            battery_percentage: None,
+            uptime_seconds: None,
+            signal: None,
+            connectivity_mode: None,
         }
     }
 }
@@ -1983,10 +2023,9 @@ impl Syncable for Heartbeat {
 impl Heartbeat {
     pub fn new(timestamp: String, device_id: i64) -> Self {
         Self {
-            id: None,
-            created_at: None,
             timestamp,
             device_id,
+            ..Default::default()
         }
     }
 }
@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
@@ -66,6 +67,7 @@ pub struct Connectivity {
 #[native_model(id = 18, version = 1)]
 #[native_db]
 pub struct OperatorLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -257,6 +259,12 @@ impl Syncable for Connectivity {
     fn set_id_local(&mut self, _id_local: String) {}
 }
 
+impl super::v1::RemoteIdIndexed for OperatorLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        OperatorLocalKey::id.key_definition()
+    }
+}
+
 impl Syncable for OperatorLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -493,6 +501,7 @@ impl Operator {
 #[native_model(id = 19, version = 2)]
 #[native_db]
 pub struct ArtifactLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -559,6 +568,18 @@ impl Default for ArtifactLocal {
     }
 }
 
+impl super::v1::RemoteIdIndexed for ArtifactLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        ArtifactLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for ArtifactLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        ArtifactLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
 impl super::v1::Syncable for ArtifactLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -746,6 +767,7 @@ impl From<super::v1::ArtifactV1> for Artifact {
 #[native_model(id = 16, version = 2)]
 #[native_db]
 pub struct EventLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -850,6 +872,12 @@ impl AncestorLocal for EventLocal {
     }
 }
 
+impl super::v1::RemoteIdIndexed for EventLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        EventLocalKey::id.key_definition()
+    }
+}
+
 impl Syncable for EventLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -1,15 +1,16 @@
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
 
-// Re-export from v2 (Artifact v2, Event v2, Operator)
-pub use super::v2::{Artifact, ArtifactLocal, Event, EventLocal, Operator, OperatorLocal};
+// Re-export from v2 (Artifact v2, Operator)
+pub use super::v2::{Artifact, ArtifactLocal, Operator, OperatorLocal};
 
 // Re-export all unchanged models from v1
 pub use super::v1::{
     Action, AncestorLocal, Device, DevicePrettyLocation, DeviceType, Heartbeat, Herd, Layer,
-    MediaType, Plan, PlanInsert, PlanType, ResponseScout, ResponseScoutStatus, Session, SessionLocal,
-    Syncable, Tag, TagLocal, TagObservationType, Zone,
+    MediaType, Plan, PlanInsert, PlanType, ResponseScout, ResponseScoutStatus, Session,
+    SessionLocal, Syncable, TagObservationType, Zone,
 };
 
 // ===== CONNECTIVITY V4 WITH OPTIONAL MODE FIELD =====
@@ -17,6 +18,7 @@ pub use super::v1::{
 #[native_model(id = 15, version = 4)]
 #[native_db]
 pub struct ConnectivityLocal {
+    #[secondary_key(optional)]
     pub id: Option<i64>,
     #[primary_key]
     pub id_local: Option<String>,
@@ -103,6 +105,12 @@ impl Default for ConnectivityLocal {
     }
 }
 
+impl super::v1::RemoteIdIndexed for ConnectivityLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::id.key_definition()
+    }
+}
+
 impl super::v1::Syncable for ConnectivityLocal {
     fn id(&self) -> Option<i64> {
         self.id
@@ -462,3 +470,1316 @@ impl From<super::v1::Connectivity> for Connectivity {
     }
 }
 
+// ===== TAG V2 WITH DETECTOR PROVENANCE =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 17, version = 2)]
+#[native_db]
+pub struct TagLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub inserted_at: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub conf: f64,
+    pub observation_type: TagObservationType,
+    pub class_name: String,
+    #[secondary_key]
+    pub event_id: i64,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub location: Option<String>,
+    // NEW FIELDS IN V2
+    pub detector_name: Option<String>,
+    pub detector_version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub conf: f64,
+    pub observation_type: TagObservationType,
+    pub class_name: String,
+    pub event_id: i64,
+    pub location: Option<String>,
+    // NEW FIELDS IN V2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detector_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detector_version: Option<String>,
+}
+
+impl Default for TagLocal {
+    fn default() -> Self {
+        Self {
+            id: None,
+            id_local: None,
+            inserted_at: None,
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            conf: 0.0,
+            observation_type: TagObservationType::Auto,
+            class_name: String::new(),
+            event_id: 0,
+            ancestor_id_local: None,
+            location: None,
+            detector_name: None,
+            detector_version: None,
+        }
+    }
+}
+
+impl Default for Tag {
+    fn default() -> Self {
+        Self {
+            id: None,
+            inserted_at: None,
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            conf: 0.0,
+            observation_type: TagObservationType::Manual,
+            class_name: String::new(),
+            event_id: 0,
+            location: None,
+            detector_name: None,
+            detector_version: None,
+        }
+    }
+}
+
+impl super::v1::RemoteIdIndexed for TagLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        TagLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for TagLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Tag {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None // API struct doesn't have id_local
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {
+        // API struct doesn't have id_local, so this is a no-op
+    }
+}
+
+impl super::v1::AncestorLocal for TagLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl From<TagLocal> for Tag {
+    fn from(local: TagLocal) -> Self {
+        Tag {
+            id: local.id,
+            inserted_at: local.inserted_at,
+            x: local.x,
+            y: local.y,
+            width: local.width,
+            height: local.height,
+            conf: local.conf,
+            observation_type: local.observation_type,
+            class_name: local.class_name,
+            event_id: local.event_id,
+            location: local.location,
+            detector_name: local.detector_name,
+            detector_version: local.detector_version,
+        }
+    }
+}
+
+impl From<Tag> for TagLocal {
+    fn from(tag: Tag) -> Self {
+        TagLocal {
+            id: tag.id,
+            id_local: None, // API structs don't have id_local
+            inserted_at: tag.inserted_at,
+            x: tag.x,
+            y: tag.y,
+            width: tag.width,
+            height: tag.height,
+            conf: tag.conf,
+            observation_type: tag.observation_type,
+            class_name: tag.class_name,
+            event_id: tag.event_id,
+            ancestor_id_local: None, // API structs don't have ancestor_id_local
+            location: tag.location,
+            detector_name: tag.detector_name,
+            detector_version: tag.detector_version,
+        }
+    }
+}
+
+impl Tag {
+    /// Records which model produced the tag
+    pub fn with_detector(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.detector_name = Some(name.into());
+        self.detector_version = Some(version.into());
+        self
+    }
+
+    pub fn update_event_id(&mut self, event_id: i64) {
+        self.event_id = event_id;
+    }
+
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) {
+        self.location = Some(Self::format_location(latitude, longitude));
+    }
+
+    pub fn clear_location(&mut self) {
+        self.location = None;
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    pub fn parse_location(location: &str) -> Option<(f64, f64)> {
+        if let Some(coords) = location
+            .strip_prefix("POINT(")
+            .and_then(|s| s.strip_suffix(")"))
+        {
+            let parts: Vec<&str> = coords.split_whitespace().collect();
+            if parts.len() == 2 {
+                if let (Ok(lon), Ok(lat)) = (parts[0].parse::<f64>(), parts[1].parse::<f64>()) {
+                    return Some((lat, lon));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_coordinates(&self) -> Option<(f64, f64)> {
+        self.location
+            .as_ref()
+            .and_then(|loc| Self::parse_location(loc))
+    }
+}
+
+impl TagLocal {
+    /// Records which model produced the tag
+    pub fn with_detector(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.detector_name = Some(name.into());
+        self.detector_version = Some(version.into());
+        self
+    }
+
+    pub fn update_event_id(&mut self, event_id: i64) {
+        self.event_id = event_id;
+    }
+
+    pub fn update_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) {
+        self.location = Some(Self::format_location(latitude, longitude));
+    }
+
+    pub fn clear_location(&mut self) {
+        self.location = None;
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    pub fn parse_location(location: &str) -> Option<(f64, f64)> {
+        if let Some(coords) = location
+            .strip_prefix("POINT(")
+            .and_then(|s| s.strip_suffix(")"))
+        {
+            let parts: Vec<&str> = coords.split_whitespace().collect();
+            if parts.len() == 2 {
+                if let (Ok(lon), Ok(lat)) = (parts[0].parse::<f64>(), parts[1].parse::<f64>()) {
+                    return Some((lat, lon));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_coordinates(&self) -> Option<(f64, f64)> {
+        self.location
+            .as_ref()
+            .and_then(|loc| Self::parse_location(loc))
+    }
+}
+
+// ===== MIGRATION FROM V1 TAG TO V2 =====
+impl From<super::v1::TagLocal> for TagLocal {
+    fn from(v1: super::v1::TagLocal) -> Self {
+        Self {
+            id: v1.id,
+            id_local: v1.id_local,
+            inserted_at: v1.inserted_at,
+            x: v1.x,
+            y: v1.y,
+            width: v1.width,
+            height: v1.height,
+            conf: v1.conf,
+            observation_type: v1.observation_type,
+            class_name: v1.class_name,
+            event_id: v1.event_id,
+            ancestor_id_local: v1.ancestor_id_local,
+            location: v1.location,
+            // New fields in v2 - provenance is unknown for migrated data
+            detector_name: None,
+            detector_version: None,
+        }
+    }
+}
+
+impl From<super::v1::Tag> for Tag {
+    fn from(v1: super::v1::Tag) -> Self {
+        Self {
+            id: v1.id,
+            inserted_at: v1.inserted_at,
+            x: v1.x,
+            y: v1.y,
+            width: v1.width,
+            height: v1.height,
+            conf: v1.conf,
+            observation_type: v1.observation_type,
+            class_name: v1.class_name,
+            event_id: v1.event_id,
+            location: v1.location,
+            detector_name: None,
+            detector_version: None,
+        }
+    }
+}
+
+// ===== EVENT V3 WITH PARENT EVENT =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 16, version = 3)]
+#[native_db]
+pub struct EventLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    /// Qwen VL 2B embedding (2000 dims).
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    /// Vertex multimodal embedding (1408 dims).
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    // NEW FIELDS IN V3
+    /// Remote ID of the event this one follows up on (e.g. the same animal re-sighted)
+    pub parent_event_id: Option<i64>,
+    /// Local ID of the parent event, resolved to `parent_event_id` once it is synced
+    pub parent_event_id_local: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    pub session_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    // NEW FIELD IN V3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_event_id: Option<i64>,
+}
+
+impl Default for EventLocal {
+    fn default() -> Self {
+        Self {
+            id: None,
+            id_local: None,
+            message: None,
+            media_url: None,
+            file_path: None,
+            location: None,
+            altitude: 0.0,
+            heading: 0.0,
+            media_type: super::v1::MediaType::Image,
+            device_id: 0,
+            earthranger_url: None,
+            timestamp_observation: String::new(),
+            is_public: false,
+            session_id: None,
+            ancestor_id_local: None,
+            embedding_qwen_vl_2b: None,
+            embedding_vertex_mm_01: None,
+            parent_event_id: None,
+            parent_event_id_local: None,
+        }
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self {
+            id: None,
+            message: None,
+            media_url: None,
+            file_path: None,
+            location: None,
+            altitude: 0.0,
+            heading: 0.0,
+            media_type: super::v1::MediaType::Image,
+            device_id: 0,
+            earthranger_url: None,
+            timestamp_observation: String::new(),
+            is_public: false,
+            session_id: None,
+            embedding_qwen_vl_2b: None,
+            embedding_vertex_mm_01: None,
+            parent_event_id: None,
+        }
+    }
+}
+
+impl super::v1::AncestorLocal for EventLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl super::v1::RemoteIdIndexed for EventLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        EventLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for EventLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Event {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {}
+}
+
+impl From<EventLocal> for Event {
+    fn from(local: EventLocal) -> Self {
+        Event {
+            id: local.id,
+            message: local.message,
+            media_url: local.media_url,
+            file_path: local.file_path,
+            location: local.location,
+            altitude: local.altitude,
+            heading: local.heading,
+            media_type: local.media_type,
+            device_id: local.device_id,
+            earthranger_url: local.earthranger_url,
+            timestamp_observation: local.timestamp_observation,
+            is_public: local.is_public,
+            session_id: local.session_id,
+            embedding_qwen_vl_2b: local.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: local.embedding_vertex_mm_01,
+            parent_event_id: local.parent_event_id,
+        }
+    }
+}
+
+impl From<Event> for EventLocal {
+    fn from(event: Event) -> Self {
+        EventLocal {
+            id: event.id,
+            id_local: None,
+            message: event.message,
+            media_url: event.media_url,
+            file_path: event.file_path,
+            location: event.location,
+            altitude: event.altitude,
+            heading: event.heading,
+            media_type: event.media_type,
+            device_id: event.device_id,
+            earthranger_url: event.earthranger_url,
+            timestamp_observation: event.timestamp_observation,
+            is_public: event.is_public,
+            session_id: event.session_id,
+            ancestor_id_local: None,
+            embedding_qwen_vl_2b: event.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: event.embedding_vertex_mm_01,
+            parent_event_id: event.parent_event_id,
+            parent_event_id_local: None,
+        }
+    }
+}
+
+impl Event {
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        use chrono::{DateTime, Utc};
+        let timestamp_observation_str = DateTime::from_timestamp(timestamp_observation as i64, 0)
+            .unwrap_or_else(|| DateTime::<Utc>::from_timestamp(0, 0).unwrap())
+            .to_rfc3339();
+
+        Self {
+            id: None,
+            message,
+            media_url,
+            file_path,
+            location: Some(Self::format_location(latitude, longitude)),
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            earthranger_url,
+            timestamp_observation: timestamp_observation_str,
+            is_public,
+            session_id,
+            embedding_qwen_vl_2b: None,
+            embedding_vertex_mm_01: None,
+            parent_event_id: None,
+        }
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+}
+
+impl EventLocal {
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        let location = Self::format_location(latitude, longitude);
+        let timestamp_observation =
+            chrono::DateTime::from_timestamp(timestamp_observation as i64, 0)
+                .unwrap_or_else(chrono::Utc::now)
+                .to_rfc3339();
+
+        Self {
+            id: None,
+            id_local: None,
+            message,
+            media_url,
+            file_path,
+            location: Some(location),
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            earthranger_url,
+            timestamp_observation,
+            is_public,
+            session_id,
+            ancestor_id_local: None,
+            embedding_qwen_vl_2b: None,
+            embedding_vertex_mm_01: None,
+            parent_event_id: None,
+            parent_event_id_local: None,
+        }
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    /// Links the event as a follow-up of `parent`
+    pub fn with_parent_event(mut self, parent: &EventLocal) -> Self {
+        self.parent_event_id = parent.id;
+        self.parent_event_id_local = parent.id_local.clone();
+        self
+    }
+}
+
+// ===== MIGRATION FROM V2 EVENT TO V3 =====
+impl From<super::v2::EventLocal> for EventLocal {
+    fn from(v2: super::v2::EventLocal) -> Self {
+        Self {
+            id: v2.id,
+            id_local: v2.id_local,
+            message: v2.message,
+            media_url: v2.media_url,
+            file_path: v2.file_path,
+            location: v2.location,
+            altitude: v2.altitude,
+            heading: v2.heading,
+            media_type: v2.media_type,
+            device_id: v2.device_id,
+            earthranger_url: v2.earthranger_url,
+            timestamp_observation: v2.timestamp_observation,
+            is_public: v2.is_public,
+            session_id: v2.session_id,
+            ancestor_id_local: v2.ancestor_id_local,
+            embedding_qwen_vl_2b: v2.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v2.embedding_vertex_mm_01,
+            // New fields in v3 - migrated events have no parent
+            parent_event_id: None,
+            parent_event_id_local: None,
+        }
+    }
+}
+
+impl From<super::v2::Event> for Event {
+    fn from(v2: super::v2::Event) -> Self {
+        Self {
+            id: v2.id,
+            message: v2.message,
+            media_url: v2.media_url,
+            file_path: v2.file_path,
+            location: v2.location,
+            altitude: v2.altitude,
+            heading: v2.heading,
+            media_type: v2.media_type,
+            device_id: v2.device_id,
+            earthranger_url: v2.earthranger_url,
+            timestamp_observation: v2.timestamp_observation,
+            is_public: v2.is_public,
+            session_id: v2.session_id,
+            embedding_qwen_vl_2b: v2.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v2.embedding_vertex_mm_01,
+            parent_event_id: None,
+        }
+    }
+}
+
+impl From<super::v1::Event> for Event {
+    fn from(v1: super::v1::Event) -> Self {
+        super::v2::Event::from(v1).into()
+    }
+}
+
+// ===== NEW SYNC BUDGET MODEL =====
+/// Rows and bytes sent to the remote database during one calendar month (`YYYY-MM`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 20, version = 1)]
+#[native_db]
+pub struct SyncBudgetLocal {
+    #[primary_key]
+    pub month: String,
+    pub rows_sent: u64,
+    pub bytes_sent: u64,
+}
+
+impl SyncBudgetLocal {
+    pub fn new(month: String) -> Self {
+        Self {
+            month,
+            rows_sent: 0,
+            bytes_sent: 0,
+        }
+    }
+}
+
+// ===== NEW TAG SUPPRESSION MODEL =====
+/// Provenance of a tag dropped as a duplicate at ingest, keyed by the dropped tag's local ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 21, version = 1)]
+#[native_db]
+pub struct TagSuppressionLocal {
+    #[primary_key]
+    pub suppressed_id_local: String,
+    #[secondary_key]
+    pub kept_id_local: String,
+    pub class_name: String,
+    pub suppressed_conf: f64,
+    pub kept_conf: f64,
+    pub iou: f64,
+    pub suppressed_at: String,
+}
+
+// ===== NEW OPERATOR AUTHENTICATION MODELS =====
+/// How an operator identifies on a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperatorCredentialType {
+    Pin,
+    Badge,
+}
+
+/// Session-scoped operator identity returned by the `authenticate_operator` RPC
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorToken {
+    pub user_id: String,
+    pub token: String,
+    pub expires_at: String,
+}
+
+/// Cached operator token, keyed by a salted PBKDF2 hash of the credential so a PIN or badge
+/// is never stored in plain text. Lets an operator re-identify offline until the token expires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 22, version = 1)]
+#[native_db]
+pub struct OperatorTokenLocal {
+    #[primary_key]
+    pub credential_hash: String,
+    /// Short keyed digest of the credential, so authenticating verifies only the tokens
+    /// that share it
+    #[secondary_key]
+    pub key_id: String,
+    pub user_id: String,
+    pub token: String,
+    pub expires_at: String,
+}
+
+impl OperatorTokenLocal {
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        chrono::DateTime::parse_from_rfc3339(&self.expires_at)
+            .map(|expires_at| expires_at <= now)
+            .unwrap_or(true)
+    }
+}
+
+// ===== NEW EVENT SESSION LINK MODEL =====
+/// Attributes an event to a session besides its own, e.g. a sentry tower observation that
+/// also belongs to a drone's active session. Remote IDs are resolved from the local IDs
+/// once both the event and the session are synced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 23, version = 1)]
+#[native_db]
+pub struct EventSessionLinkLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub event_id: Option<i64>,
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub event_id_local: String,
+    #[secondary_key]
+    pub session_id_local: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSessionLink {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub event_id: i64,
+    pub session_id: i64,
+}
+
+impl EventSessionLinkLocal {
+    /// Link with a deterministic local ID, so linking the same pair twice is a no-op
+    pub fn new(event_id_local: String, session_id_local: String) -> Self {
+        Self {
+            id: None,
+            id_local: Some(format!("{}:{}", event_id_local, session_id_local)),
+            event_id: None,
+            session_id: None,
+            event_id_local,
+            session_id_local,
+        }
+    }
+}
+
+impl super::v1::RemoteIdIndexed for EventSessionLinkLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        EventSessionLinkLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for EventSessionLinkLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+// ===== NEW SESSION TRACK MODEL =====
+/// Track points recorded while a session runs, appended to its remote `locations` in
+/// sequence order. Uploaded segments are kept until the session is cleaned, marking the
+/// session as streamed so later session upserts leave the remote track alone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 24, version = 1)]
+#[native_db]
+pub struct SessionTrackSegmentLocal {
+    #[primary_key]
+    pub id_local: String,
+    #[secondary_key]
+    pub session_id_local: String,
+    pub sequence: u64,
+    /// (longitude, latitude) pairs
+    pub points: Vec<(f64, f64)>,
+    pub uploaded: bool,
+}
+
+impl SessionTrackSegmentLocal {
+    pub fn new(session_id_local: String, sequence: u64, points: Vec<(f64, f64)>) -> Self {
+        Self {
+            id_local: format!("{}:{:010}", session_id_local, sequence),
+            session_id_local,
+            sequence,
+            points,
+            uploaded: false,
+        }
+    }
+}
+
+/// Result of the `append_session_track` RPC
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTrackAppend {
+    pub id: i64,
+    pub point_count: i64,
+}
+
+/// Result of the `compact_connectivity` RPC
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectivityCompaction {
+    /// Rows starting before this time were rolled up into daily summaries and deleted
+    pub cutoff: String,
+    pub summarized_days: i64,
+    pub deleted_rows: i64,
+}
+
+// ===== NEW DELETION AUDIT MODEL =====
+/// A remote deletion initiated from this device, kept locally for compliance review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 25, version = 1)]
+#[native_db]
+pub struct DeletionAuditLocal {
+    #[primary_key]
+    pub id_local: String,
+    /// Remote table the row was deleted from, e.g. `sessions`
+    pub table: String,
+    pub remote_id: i64,
+    /// user_id of the authenticated operator, if any
+    pub deleted_by: Option<String>,
+    pub deleted_at: String,
+    pub succeeded: bool,
+}
+
+// ===== NEW PLAN CACHE MODEL =====
+/// A plan downloaded for offline use, with the SHA-256 checksum of the plan as downloaded.
+/// A pinned plan is only replaced by a download with the pinned checksum.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 26, version = 1)]
+#[native_db]
+pub struct PlanCacheLocal {
+    #[primary_key]
+    pub plan_id: i64,
+    pub plan: Plan,
+    pub checksum: String,
+    pub pinned_checksum: Option<String>,
+    pub downloaded_at: String,
+}
+
+impl PlanCacheLocal {
+    pub fn new(plan_id: i64, plan: Plan) -> anyhow::Result<Self> {
+        Ok(Self {
+            plan_id,
+            checksum: Self::checksum_of(&plan)?,
+            plan,
+            pinned_checksum: None,
+            downloaded_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Hex SHA-256 of the plan's JSON serialization
+    pub fn checksum_of(plan: &Plan) -> anyhow::Result<String> {
+        use sha2::{Digest, Sha256};
+
+        Ok(Sha256::digest(serde_json::to_vec(plan)?)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+}
+
+// ===== NEW ARTIFACT CACHE MODEL =====
+/// An artifact file kept in the local download cache. `last_used_at` orders eviction when
+/// the cache is over capacity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 27, version = 1)]
+#[native_db]
+pub struct ArtifactCacheLocal {
+    #[primary_key]
+    pub artifact_id: i64,
+    pub file_path: String,
+    pub size_bytes: u64,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    pub last_used_at: String,
+}
+
+// ===== NEW PENDING LINK MODEL =====
+/// The ancestor whose descendants a pending link updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingLinkKind {
+    Session,
+    Event,
+}
+
+/// A descendant update that failed after its ancestor got a remote ID. Retried at the
+/// start of each flush until the descendants are linked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 28, version = 1)]
+#[native_db]
+pub struct PendingLinkLocal {
+    /// `<kind>:<ancestor_id_local>`
+    #[primary_key]
+    pub id_local: String,
+    pub kind: PendingLinkKind,
+    pub ancestor_id_local: String,
+    pub remote_id: i64,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: String,
+}
+
+impl PendingLinkLocal {
+    pub fn key(kind: PendingLinkKind, ancestor_id_local: &str) -> String {
+        let kind = match kind {
+            PendingLinkKind::Session => "session",
+            PendingLinkKind::Event => "event",
+        };
+        format!("{}:{}", kind, ancestor_id_local)
+    }
+}
+
+// ===== NEW SESSION NOTE MODEL =====
+/// A note attached to a session after the fact, e.g. by a reviewer explaining a coverage
+/// gap. Synced like other session descendants once the session has a remote ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 29, version = 1)]
+#[native_db]
+pub struct SessionNoteLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub session_id: Option<i64>,
+    #[secondary_key(optional)]
+    pub ancestor_id_local: Option<String>,
+    /// Operator or reviewer who wrote the note
+    pub author: String,
+    pub text: String,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionNote {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub session_id: i64,
+    pub author: String,
+    pub text: String,
+    pub timestamp: String,
+}
+
+impl super::v1::AncestorLocal for SessionNoteLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl super::v1::RemoteIdIndexed for SessionNoteLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        SessionNoteLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for SessionNoteLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        SessionNoteLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for SessionNoteLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+// ===== NEW PULL CHECKPOINT MODEL =====
+/// How far the local mirror of one herd table has been pulled. Rows are pulled in remote
+/// ID order, so an interrupted pull resumes after `last_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 30, version = 1)]
+#[native_db]
+pub struct PullCheckpointLocal {
+    /// `<herd_id>:<table>`
+    #[primary_key]
+    pub key: String,
+    pub herd_id: i64,
+    pub table: String,
+    pub last_id: i64,
+    /// Server insertion time of the last row pulled, for tables that record it
+    pub last_inserted_at: Option<String>,
+    pub rows_pulled: u64,
+    pub completed: bool,
+    pub updated_at: String,
+}
+
+impl PullCheckpointLocal {
+    pub fn key(herd_id: i64, table: &str) -> String {
+        format!("{}:{}", herd_id, table)
+    }
+}
+
+// ===== NEW TRASH MODEL =====
+/// A row removed from the local store, kept until its undo window passes. Rows removed
+/// together share an operation; later operations have higher numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 31, version = 1)]
+#[native_db]
+pub struct TrashLocal {
+    /// `<operation>:<table>:<row key>`
+    #[primary_key]
+    pub key: String,
+    pub operation: u64,
+    pub table: String,
+    /// The removed row as JSON
+    pub row: String,
+    pub trashed_at: String,
+}
+
+impl TrashLocal {
+    pub fn key(operation: u64, table: &str, row_key: &str) -> String {
+        format!("{}:{}:{}", operation, table, row_key)
+    }
+}
+
+// ===== NEW CIRCUIT BREAKER MODEL =====
+/// Consecutive failures of one server endpoint, so an open circuit stays open across
+/// restarts; see [`crate::client::CircuitBreaker`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 32, version = 1)]
+#[native_db]
+pub struct CircuitBreakerLocal {
+    #[primary_key]
+    pub endpoint: String,
+    pub consecutive_failures: u32,
+    /// When the circuit last opened, None while closed
+    pub opened_at: Option<String>,
+}
+
+// ===== NEW APPLIED LINK MODEL =====
+/// Remote ID last linked to the descendants of an ancestor, written in the same
+/// transaction as the descendants. Applying a link again only touches descendants that
+/// are unlinked or still point at the previously applied remote ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 33, version = 1)]
+#[native_db]
+pub struct AppliedLinkLocal {
+    /// `<kind>:<ancestor_id_local>`, as in [`PendingLinkLocal::key`]
+    #[primary_key]
+    pub id_local: String,
+    pub kind: PendingLinkKind,
+    pub ancestor_id_local: String,
+    pub remote_id: i64,
+    pub applied_at: String,
+}
+
+// ===== NEW ID MAP MODEL =====
+/// Remote ID a local record was synced under, kept when the record is cleaned. A record
+/// written again under the same local ID, e.g. re-imported from a backup, takes the
+/// remote ID back instead of being sent as a new row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 34, version = 1)]
+#[native_db]
+pub struct IdMapLocal {
+    /// `<model>:<id_local>`
+    #[primary_key]
+    pub key: String,
+    /// Table of the record, e.g. `events`
+    pub model: String,
+    pub id_local: String,
+    pub remote_id: i64,
+    pub first_synced_at: String,
+    /// Remote IDs the record was synced under before, oldest first
+    pub previous_remote_ids: Vec<i64>,
+}
+
+impl IdMapLocal {
+    pub fn key(model: &str, id_local: &str) -> String {
+        format!("{}:{}", model, id_local)
+    }
+}
+
+// ===== NEW MEDIA UPLOAD MODEL =====
+/// Media file of an event queued for upload to storage. The upload URL is kept across
+/// restarts, so an interrupted upload resumes from the bytes the server already has.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 35, version = 1)]
+#[native_db]
+pub struct MediaUploadLocal {
+    #[primary_key]
+    pub event_id_local: String,
+    pub file_path: String,
+    pub device_id: i64,
+    pub media_type: MediaType,
+    pub upload_url: Option<String>,
+    pub upload_url_generated_at: Option<String>,
+    /// Storage path once the file is uploaded, while the event's media URL still has to
+    /// be set on the server
+    pub storage_path: Option<String>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub queued_at: String,
+}
+
+// ===== NEW HEARTBEAT MODEL =====
+/// A heartbeat recorded by [`crate::sync::HeartbeatScheduler`], kept until a flush sends
+/// it, so heartbeats recorded offline still reach the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 36, version = 1)]
+#[native_db]
+pub struct HeartbeatLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub device_id: i64,
+    pub timestamp: String,
+    pub battery_percentage: Option<f32>,
+    pub uptime_seconds: Option<i64>,
+    pub signal: Option<f64>,
+    pub connectivity_mode: Option<String>,
+}
+
+impl From<HeartbeatLocal> for Heartbeat {
+    fn from(local: HeartbeatLocal) -> Self {
+        Self {
+            id: local.id,
+            created_at: None,
+            timestamp: local.timestamp,
+            device_id: local.device_id,
+            battery_percentage: local.battery_percentage,
+            uptime_seconds: local.uptime_seconds,
+            signal: local.signal,
+            connectivity_mode: local.connectivity_mode,
+        }
+    }
+}
+
+impl super::v1::RemoteIdIndexed for HeartbeatLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        HeartbeatLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for HeartbeatLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+// ===== NEW EVENT CORRELATION MODEL =====
+/// Events of other devices in the herd that probably show the same observation as an
+/// event of this device, e.g. an animal passing two trail cameras seconds apart. Found
+/// after a flush, see [`crate::sync::ObservationDedup`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 37, version = 1)]
+#[native_db]
+pub struct EventCorrelationLocal {
+    #[primary_key]
+    pub event_id_local: String,
+    pub event_id: i64,
+    /// `dup-<lowest remote event ID>`, the same for the correlated events on each device
+    #[secondary_key]
+    pub correlation_id: String,
+    /// Remote IDs of the other devices' events, ascending
+    pub duplicate_event_ids: Vec<i64>,
+    pub duplicate_device_ids: Vec<i64>,
+    /// Distance in meters to the nearest duplicate
+    pub nearest_distance_m: f64,
+    pub correlated_at: String,
+}
+
+// ===== NEW SESSION REDIRECT MODEL =====
+/// A session coalesced by the session rate limit and the session it was merged into, so
+/// records ingested for it later, also after a restart, follow it. See
+/// [`crate::sync::SyncEngine::with_session_rate_limit`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 38, version = 1)]
+#[native_db]
+pub struct SessionRedirectLocal {
+    #[primary_key]
+    pub session_id_local: String,
+    pub merged_into: String,
+    pub device_id: i64,
+    pub coalesced_at: String,
+}
+
+// ===== NEW FLUSH RESUME MODEL =====
+/// Phase a time-boxed flush stopped before, so the next flush resumes there, also after a
+/// restart. See [`crate::sync::SyncEngine::with_flush_time_budget`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 39, version = 1)]
+#[native_db]
+pub struct FlushResumeLocal {
+    /// Always [`FlushResumeLocal::KEY`]: there is one resume point per database
+    #[primary_key]
+    pub key: String,
+    /// Name of the phase, see [`crate::sync::SyncPhase::name`]
+    pub phase: String,
+    /// Whether the phase was added by the application rather than a built-in stage
+    pub custom: bool,
+    pub stopped_at: String,
+}
+
+impl FlushResumeLocal {
+    pub const KEY: &'static str = "flush";
+}
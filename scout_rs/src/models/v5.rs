@@ -0,0 +1,397 @@
+use super::h3::H3Index;
+use chrono::Utc;
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
+use native_db::{native_db, ToKey};
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+
+// Re-export all unchanged models from v4 (the remote Connectivity keeps its string indexes)
+pub use super::v4::*;
+
+// ===== CONNECTIVITY V5 WITH TYPED H3 INDEXES =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 15, version = 5)]
+#[native_db]
+pub struct ConnectivityLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub device_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub inserted_at: Option<String>,
+    pub timestamp_start: String,
+    pub signal: f64,
+    pub noise: f64,
+    pub altitude: f64,
+    pub heading: f64,
+    pub location: Option<String>,
+    // TYPED IN V5
+    pub h14_index: H3Index,
+    pub h13_index: H3Index,
+    pub h12_index: H3Index,
+    pub h11_index: H3Index,
+    pub battery_percentage: Option<f32>,
+    pub frequency_hz: Option<f32>,
+    pub bandwidth_hz: Option<f32>,
+    pub associated_station: Option<String>,
+    pub mode: Option<String>,
+}
+
+impl Default for ConnectivityLocal {
+    fn default() -> Self {
+        Self {
+            id: None,
+            id_local: None,
+            session_id: None,
+            device_id: None,
+            ancestor_id_local: None,
+            inserted_at: None,
+            timestamp_start: String::new(),
+            signal: 0.0,
+            noise: 0.0,
+            altitude: 0.0,
+            heading: 0.0,
+            location: None,
+            h14_index: H3Index::NULL,
+            h13_index: H3Index::NULL,
+            h12_index: H3Index::NULL,
+            h11_index: H3Index::NULL,
+            battery_percentage: None,
+            frequency_hz: None,
+            bandwidth_hz: None,
+            associated_station: None,
+            mode: None,
+        }
+    }
+}
+
+impl super::v1::RemoteIdIndexed for ConnectivityLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for ConnectivityLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::AncestorLocal for ConnectivityLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl ConnectivityLocal {
+    /// Sets the resolution 14 index and derives the resolution 13, 12 and 11 indexes from it
+    pub fn set_h3_indexes(&mut self, h14_index: H3Index) -> anyhow::Result<()> {
+        [
+            self.h14_index,
+            self.h13_index,
+            self.h12_index,
+            self.h11_index,
+        ] = h14_index.hierarchy([14, 13, 12, 11])?;
+        Ok(())
+    }
+}
+
+impl From<ConnectivityLocal> for Connectivity {
+    fn from(local: ConnectivityLocal) -> Self {
+        Self {
+            id: local.id,
+            session_id: local.session_id,
+            device_id: local.device_id,
+            inserted_at: local.inserted_at,
+            timestamp_start: local.timestamp_start,
+            signal: local.signal,
+            noise: local.noise,
+            altitude: local.altitude,
+            heading: local.heading,
+            location: local.location,
+            h14_index: local.h14_index.to_string(),
+            h13_index: local.h13_index.to_string(),
+            h12_index: local.h12_index.to_string(),
+            h11_index: local.h11_index.to_string(),
+            battery_percentage: local.battery_percentage,
+            frequency_hz: local.frequency_hz,
+            bandwidth_hz: local.bandwidth_hz,
+            associated_station: local.associated_station,
+            mode: local.mode,
+        }
+    }
+}
+
+impl From<Connectivity> for ConnectivityLocal {
+    fn from(remote: Connectivity) -> Self {
+        Self {
+            id: remote.id,
+            id_local: None,
+            session_id: remote.session_id,
+            device_id: remote.device_id,
+            ancestor_id_local: None,
+            inserted_at: remote.inserted_at,
+            timestamp_start: remote.timestamp_start,
+            signal: remote.signal,
+            noise: remote.noise,
+            altitude: remote.altitude,
+            heading: remote.heading,
+            location: remote.location,
+            h14_index: parse_or_null(&remote.h14_index),
+            h13_index: parse_or_null(&remote.h13_index),
+            h12_index: parse_or_null(&remote.h12_index),
+            h11_index: parse_or_null(&remote.h11_index),
+            battery_percentage: remote.battery_percentage,
+            frequency_hz: remote.frequency_hz,
+            bandwidth_hz: remote.bandwidth_hz,
+            associated_station: remote.associated_station,
+            mode: remote.mode,
+        }
+    }
+}
+
+/// Indexes recorded before they were validated may not be H3 cells; those are dropped
+fn parse_or_null(index: &str) -> H3Index {
+    index.parse().unwrap_or_else(|e| {
+        crate::logging::warn!("Dropping invalid H3 index: {}", e);
+        H3Index::NULL
+    })
+}
+
+// ===== MIGRATION FROM V4 TO V5 =====
+impl From<super::v4::ConnectivityLocal> for ConnectivityLocal {
+    fn from(v4: super::v4::ConnectivityLocal) -> Self {
+        Self {
+            id: v4.id,
+            id_local: v4.id_local,
+            session_id: v4.session_id,
+            device_id: v4.device_id,
+            ancestor_id_local: v4.ancestor_id_local,
+            inserted_at: v4.inserted_at,
+            timestamp_start: v4.timestamp_start,
+            signal: v4.signal,
+            noise: v4.noise,
+            altitude: v4.altitude,
+            heading: v4.heading,
+            location: v4.location,
+            h14_index: parse_or_null(&v4.h14_index),
+            h13_index: parse_or_null(&v4.h13_index),
+            h12_index: parse_or_null(&v4.h12_index),
+            h11_index: parse_or_null(&v4.h11_index),
+            battery_percentage: v4.battery_percentage,
+            frequency_hz: v4.frequency_hz,
+            bandwidth_hz: v4.bandwidth_hz,
+            associated_station: v4.associated_station,
+            mode: v4.mode,
+        }
+    }
+}
+
+// ===== OPERATOR V2 WITH PER-SESSION SEQUENCE =====
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 18, version = 2)]
+#[native_db]
+pub struct OperatorLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub created_at: Option<String>,
+    pub timestamp: Option<String>,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub user_id: String,
+    pub action: String,
+    // NEW FIELD IN V2
+    /// Position of the action among the actions of its session, starting at 1
+    pub sequence: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Operator {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    pub timestamp: Option<String>,
+    pub session_id: Option<i64>,
+    pub user_id: String,
+    pub action: String,
+    // NEW FIELD IN V2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<i64>,
+}
+
+impl super::v1::AncestorLocal for OperatorLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl super::v1::RemoteIdIndexed for OperatorLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        OperatorLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for OperatorLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        OperatorLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for OperatorLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Operator {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None // API struct doesn't have id_local
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {
+        // API struct doesn't have id_local, so this is a no-op
+    }
+}
+
+impl From<OperatorLocal> for Operator {
+    fn from(local: OperatorLocal) -> Self {
+        Operator {
+            id: local.id,
+            created_at: local.created_at,
+            timestamp: local.timestamp,
+            session_id: local.session_id,
+            user_id: local.user_id,
+            action: local.action,
+            sequence: local.sequence,
+        }
+    }
+}
+
+impl From<Operator> for OperatorLocal {
+    fn from(operator: Operator) -> Self {
+        OperatorLocal {
+            id: operator.id,
+            id_local: None, // API structs don't have id_local
+            created_at: operator.created_at,
+            timestamp: operator.timestamp,
+            session_id: operator.session_id,
+            ancestor_id_local: None, // API structs don't have ancestor_id_local
+            user_id: operator.user_id,
+            action: operator.action,
+            sequence: operator.sequence,
+        }
+    }
+}
+
+impl OperatorLocal {
+    pub fn new(user_id: String, action: String, session_id: Option<i64>) -> Self {
+        Self {
+            timestamp: Some(Utc::now().to_rfc3339()),
+            session_id,
+            user_id,
+            action,
+            ..Default::default()
+        }
+    }
+
+    /// Key of the session the action belongs to, local if known; sequences count per key
+    pub fn session_key(&self) -> Option<String> {
+        self.ancestor_id_local
+            .clone()
+            .or_else(|| self.session_id.map(|id| format!("remote:{}", id)))
+    }
+}
+
+impl Operator {
+    pub fn new(user_id: String, action: String, session_id: Option<i64>) -> Self {
+        Self {
+            timestamp: Some(Utc::now().to_rfc3339()),
+            session_id,
+            user_id,
+            action,
+            ..Default::default()
+        }
+    }
+}
+
+// ===== MIGRATION FROM V1 OPERATOR TO V2 =====
+impl From<super::v2::OperatorLocal> for OperatorLocal {
+    fn from(v1: super::v2::OperatorLocal) -> Self {
+        Self {
+            id: v1.id,
+            id_local: v1.id_local,
+            created_at: v1.created_at,
+            timestamp: v1.timestamp,
+            session_id: v1.session_id,
+            ancestor_id_local: v1.ancestor_id_local,
+            user_id: v1.user_id,
+            action: v1.action,
+            // New field in v2 - actions recorded before sequencing have no position
+            sequence: None,
+        }
+    }
+}
+
+impl From<super::v2::Operator> for Operator {
+    fn from(v1: super::v2::Operator) -> Self {
+        Self {
+            id: v1.id,
+            created_at: v1.created_at,
+            timestamp: v1.timestamp,
+            session_id: v1.session_id,
+            user_id: v1.user_id,
+            action: v1.action,
+            sequence: None,
+        }
+    }
+}
@@ -0,0 +1,1245 @@
+use super::h3::H3Index;
+use anyhow::{anyhow, Result};
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
+use native_db::{native_db, ToKey};
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+
+// Re-export all unchanged models from v5
+pub use super::v5::*;
+
+// ===== QUALITY FLAGS =====
+/// Data quality problem found by the checks run at ingest, see
+/// [`crate::sync::QualityChecks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityFlag {
+    /// GPS horizontal dilution of precision above the limit
+    HighHdop,
+    /// Altitude jumped further from the device's previous sample than the limit
+    AltitudeSpike,
+    /// Timestamp earlier than the device's previous sample
+    TimestampBackwards,
+}
+
+// ===== CONNECTIVITY V6 WITH QUALITY FLAGS =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 15, version = 6)]
+#[native_db]
+pub struct ConnectivityLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub device_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub inserted_at: Option<String>,
+    pub timestamp_start: String,
+    pub signal: f64,
+    pub noise: f64,
+    pub altitude: f64,
+    pub heading: f64,
+    pub location: Option<String>,
+    pub h14_index: H3Index,
+    pub h13_index: H3Index,
+    pub h12_index: H3Index,
+    pub h11_index: H3Index,
+    pub battery_percentage: Option<f32>,
+    pub frequency_hz: Option<f32>,
+    pub bandwidth_hz: Option<f32>,
+    pub associated_station: Option<String>,
+    pub mode: Option<String>,
+    // NEW FIELDS IN V6
+    /// GPS horizontal dilution of precision of the location, if the receiver reported it
+    pub hdop: Option<f64>,
+    /// Problems found at ingest; None if the checks found nothing or did not run
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Connectivity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub session_id: Option<i64>,
+    pub device_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    pub timestamp_start: String,
+    pub signal: f64,
+    pub noise: f64,
+    pub altitude: f64,
+    pub heading: f64,
+    pub location: Option<String>,
+    pub h14_index: String,
+    pub h13_index: String,
+    pub h12_index: String,
+    pub h11_index: String,
+    pub battery_percentage: Option<f32>,
+    pub frequency_hz: Option<f32>,
+    pub bandwidth_hz: Option<f32>,
+    pub associated_station: Option<String>,
+    pub mode: Option<String>,
+    // NEW FIELDS IN V5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdop: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+impl Default for ConnectivityLocal {
+    fn default() -> Self {
+        Self {
+            id: None,
+            id_local: None,
+            session_id: None,
+            device_id: None,
+            ancestor_id_local: None,
+            inserted_at: None,
+            timestamp_start: String::new(),
+            signal: 0.0,
+            noise: 0.0,
+            altitude: 0.0,
+            heading: 0.0,
+            location: None,
+            h14_index: H3Index::NULL,
+            h13_index: H3Index::NULL,
+            h12_index: H3Index::NULL,
+            h11_index: H3Index::NULL,
+            battery_percentage: None,
+            frequency_hz: None,
+            bandwidth_hz: None,
+            associated_station: None,
+            mode: None,
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+}
+
+impl super::v1::RemoteIdIndexed for ConnectivityLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for ConnectivityLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for ConnectivityLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::AncestorLocal for ConnectivityLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl ConnectivityLocal {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        session_id: Option<i64>,
+        device_id: Option<i64>,
+        timestamp_start: u64,
+        signal: f64,
+        noise: f64,
+        altitude: f64,
+        heading: f64,
+        location: String,
+        h14_index: H3Index,
+        h13_index: H3Index,
+        h12_index: H3Index,
+        h11_index: H3Index,
+        battery_percentage: Option<f32>,
+        frequency_hz: Option<f32>,
+        bandwidth_hz: Option<f32>,
+        associated_station: Option<String>,
+        mode: Option<String>,
+    ) -> Self {
+        use chrono::{DateTime, Utc};
+        let timestamp_start_str = DateTime::from_timestamp(timestamp_start as i64, 0)
+            .unwrap_or_else(|| DateTime::<Utc>::from_timestamp(0, 0).unwrap())
+            .to_rfc3339();
+
+        Self {
+            id: None,
+            id_local: None,
+            session_id,
+            device_id,
+            ancestor_id_local: None,
+            inserted_at: None,
+            timestamp_start: timestamp_start_str,
+            signal,
+            noise,
+            altitude,
+            heading,
+            location: Some(location),
+            h14_index,
+            h13_index,
+            h12_index,
+            h11_index,
+            battery_percentage,
+            frequency_hz,
+            bandwidth_hz,
+            associated_station,
+            mode,
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+
+    /// Sets the resolution 14 index and derives the resolution 13, 12 and 11 indexes from it
+    pub fn set_h3_indexes(&mut self, h14_index: H3Index) -> anyhow::Result<()> {
+        [
+            self.h14_index,
+            self.h13_index,
+            self.h12_index,
+            self.h11_index,
+        ] = h14_index.hierarchy([14, 13, 12, 11])?;
+        Ok(())
+    }
+}
+
+impl Connectivity {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        session_id: Option<i64>,
+        device_id: Option<i64>,
+        timestamp_start: u64,
+        signal: f64,
+        noise: f64,
+        altitude: f64,
+        heading: f64,
+        location: String,
+        h14_index: String,
+        h13_index: String,
+        h12_index: String,
+        h11_index: String,
+        battery_percentage: Option<f32>,
+        frequency_hz: Option<f32>,
+        bandwidth_hz: Option<f32>,
+        associated_station: Option<String>,
+        mode: Option<String>,
+    ) -> Self {
+        super::v4::Connectivity::new(
+            session_id,
+            device_id,
+            timestamp_start,
+            signal,
+            noise,
+            altitude,
+            heading,
+            location,
+            h14_index,
+            h13_index,
+            h12_index,
+            h11_index,
+            battery_percentage,
+            frequency_hz,
+            bandwidth_hz,
+            associated_station,
+            mode,
+        )
+        .into()
+    }
+}
+
+impl From<ConnectivityLocal> for Connectivity {
+    fn from(local: ConnectivityLocal) -> Self {
+        Self {
+            id: local.id,
+            session_id: local.session_id,
+            device_id: local.device_id,
+            inserted_at: local.inserted_at,
+            timestamp_start: local.timestamp_start,
+            signal: local.signal,
+            noise: local.noise,
+            altitude: local.altitude,
+            heading: local.heading,
+            location: local.location,
+            h14_index: local.h14_index.to_string(),
+            h13_index: local.h13_index.to_string(),
+            h12_index: local.h12_index.to_string(),
+            h11_index: local.h11_index.to_string(),
+            battery_percentage: local.battery_percentage,
+            frequency_hz: local.frequency_hz,
+            bandwidth_hz: local.bandwidth_hz,
+            associated_station: local.associated_station,
+            mode: local.mode,
+            hdop: local.hdop,
+            quality_flags: local.quality_flags,
+        }
+    }
+}
+
+impl From<Connectivity> for ConnectivityLocal {
+    fn from(mut remote: Connectivity) -> Self {
+        let (hdop, quality_flags) = (remote.hdop.take(), remote.quality_flags.take());
+        Self {
+            hdop,
+            quality_flags,
+            ..super::v5::ConnectivityLocal::from(super::v4::Connectivity::from(remote)).into()
+        }
+    }
+}
+
+// ===== MIGRATION FROM V5 TO V6 =====
+impl From<super::v5::ConnectivityLocal> for ConnectivityLocal {
+    fn from(v5: super::v5::ConnectivityLocal) -> Self {
+        Self {
+            id: v5.id,
+            id_local: v5.id_local,
+            session_id: v5.session_id,
+            device_id: v5.device_id,
+            ancestor_id_local: v5.ancestor_id_local,
+            inserted_at: v5.inserted_at,
+            timestamp_start: v5.timestamp_start,
+            signal: v5.signal,
+            noise: v5.noise,
+            altitude: v5.altitude,
+            heading: v5.heading,
+            location: v5.location,
+            h14_index: v5.h14_index,
+            h13_index: v5.h13_index,
+            h12_index: v5.h12_index,
+            h11_index: v5.h11_index,
+            battery_percentage: v5.battery_percentage,
+            frequency_hz: v5.frequency_hz,
+            bandwidth_hz: v5.bandwidth_hz,
+            associated_station: v5.associated_station,
+            mode: v5.mode,
+            // New fields in v6 - records from before the checks were never checked
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+}
+
+impl From<super::v4::Connectivity> for Connectivity {
+    fn from(v4: super::v4::Connectivity) -> Self {
+        Self {
+            id: v4.id,
+            session_id: v4.session_id,
+            device_id: v4.device_id,
+            inserted_at: v4.inserted_at,
+            timestamp_start: v4.timestamp_start,
+            signal: v4.signal,
+            noise: v4.noise,
+            altitude: v4.altitude,
+            heading: v4.heading,
+            location: v4.location,
+            h14_index: v4.h14_index,
+            h13_index: v4.h13_index,
+            h12_index: v4.h12_index,
+            h11_index: v4.h11_index,
+            battery_percentage: v4.battery_percentage,
+            frequency_hz: v4.frequency_hz,
+            bandwidth_hz: v4.bandwidth_hz,
+            associated_station: v4.associated_station,
+            mode: v4.mode,
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+}
+
+impl From<Connectivity> for super::v4::Connectivity {
+    fn from(v5: Connectivity) -> Self {
+        Self {
+            id: v5.id,
+            session_id: v5.session_id,
+            device_id: v5.device_id,
+            inserted_at: v5.inserted_at,
+            timestamp_start: v5.timestamp_start,
+            signal: v5.signal,
+            noise: v5.noise,
+            altitude: v5.altitude,
+            heading: v5.heading,
+            location: v5.location,
+            h14_index: v5.h14_index,
+            h13_index: v5.h13_index,
+            h12_index: v5.h12_index,
+            h11_index: v5.h11_index,
+            battery_percentage: v5.battery_percentage,
+            frequency_hz: v5.frequency_hz,
+            bandwidth_hz: v5.bandwidth_hz,
+            associated_station: v5.associated_station,
+            mode: v5.mode,
+        }
+    }
+}
+
+impl From<super::v3::Connectivity> for Connectivity {
+    fn from(v3: super::v3::Connectivity) -> Self {
+        super::v4::Connectivity::from(v3).into()
+    }
+}
+
+impl From<super::v2::Connectivity> for Connectivity {
+    fn from(v2: super::v2::Connectivity) -> Self {
+        super::v4::Connectivity::from(v2).into()
+    }
+}
+
+impl From<super::v1::Connectivity> for Connectivity {
+    fn from(v1: super::v1::Connectivity) -> Self {
+        super::v4::Connectivity::from(v1).into()
+    }
+}
+
+// ===== EVENT V4 WITH QUALITY FLAGS =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 16, version = 4)]
+#[native_db]
+pub struct EventLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    /// Qwen VL 2B embedding (2000 dims).
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    /// Vertex multimodal embedding (1408 dims).
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    /// Remote ID of the event this one follows up on (e.g. the same animal re-sighted)
+    pub parent_event_id: Option<i64>,
+    /// Local ID of the parent event, resolved to `parent_event_id` once it is synced
+    pub parent_event_id_local: Option<String>,
+    // NEW FIELDS IN V4
+    /// GPS horizontal dilution of precision of the location, if the receiver reported it
+    pub hdop: Option<f64>,
+    /// Problems found at ingest; None if the checks found nothing or did not run
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    pub session_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_event_id: Option<i64>,
+    // NEW FIELDS IN V4
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdop: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+impl Default for EventLocal {
+    fn default() -> Self {
+        super::v4::EventLocal::default().into()
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        super::v4::Event::default().into()
+    }
+}
+
+impl super::v1::AncestorLocal for EventLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl super::v1::RemoteIdIndexed for EventLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        EventLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for EventLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Event {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {}
+}
+
+impl From<EventLocal> for Event {
+    fn from(local: EventLocal) -> Self {
+        Event {
+            id: local.id,
+            message: local.message,
+            media_url: local.media_url,
+            file_path: local.file_path,
+            location: local.location,
+            altitude: local.altitude,
+            heading: local.heading,
+            media_type: local.media_type,
+            device_id: local.device_id,
+            earthranger_url: local.earthranger_url,
+            timestamp_observation: local.timestamp_observation,
+            is_public: local.is_public,
+            session_id: local.session_id,
+            embedding_qwen_vl_2b: local.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: local.embedding_vertex_mm_01,
+            parent_event_id: local.parent_event_id,
+            hdop: local.hdop,
+            quality_flags: local.quality_flags,
+        }
+    }
+}
+
+impl From<Event> for EventLocal {
+    fn from(event: Event) -> Self {
+        EventLocal {
+            id: event.id,
+            id_local: None,
+            message: event.message,
+            media_url: event.media_url,
+            file_path: event.file_path,
+            location: event.location,
+            altitude: event.altitude,
+            heading: event.heading,
+            media_type: event.media_type,
+            device_id: event.device_id,
+            earthranger_url: event.earthranger_url,
+            timestamp_observation: event.timestamp_observation,
+            is_public: event.is_public,
+            session_id: event.session_id,
+            ancestor_id_local: None,
+            embedding_qwen_vl_2b: event.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: event.embedding_vertex_mm_01,
+            parent_event_id: event.parent_event_id,
+            parent_event_id_local: None,
+            hdop: event.hdop,
+            quality_flags: event.quality_flags,
+        }
+    }
+}
+
+impl Event {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        super::v4::Event::new(
+            message,
+            media_url,
+            file_path,
+            earthranger_url,
+            latitude,
+            longitude,
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            timestamp_observation,
+            is_public,
+            session_id,
+        )
+        .into()
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+}
+
+impl EventLocal {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        super::v4::EventLocal::new(
+            message,
+            media_url,
+            file_path,
+            earthranger_url,
+            latitude,
+            longitude,
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            timestamp_observation,
+            is_public,
+            session_id,
+        )
+        .into()
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    /// Links the event as a follow-up of `parent`
+    pub fn with_parent_event(mut self, parent: &EventLocal) -> Self {
+        self.parent_event_id = parent.id;
+        self.parent_event_id_local = parent.id_local.clone();
+        self
+    }
+}
+
+// ===== MIGRATION FROM V3 EVENT TO V4 =====
+impl From<super::v4::EventLocal> for EventLocal {
+    fn from(v3: super::v4::EventLocal) -> Self {
+        Self {
+            id: v3.id,
+            id_local: v3.id_local,
+            message: v3.message,
+            media_url: v3.media_url,
+            file_path: v3.file_path,
+            location: v3.location,
+            altitude: v3.altitude,
+            heading: v3.heading,
+            media_type: v3.media_type,
+            device_id: v3.device_id,
+            earthranger_url: v3.earthranger_url,
+            timestamp_observation: v3.timestamp_observation,
+            is_public: v3.is_public,
+            session_id: v3.session_id,
+            ancestor_id_local: v3.ancestor_id_local,
+            embedding_qwen_vl_2b: v3.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v3.embedding_vertex_mm_01,
+            parent_event_id: v3.parent_event_id,
+            parent_event_id_local: v3.parent_event_id_local,
+            // New fields in v4 - events from before the checks were never checked
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+}
+
+impl From<super::v4::Event> for Event {
+    fn from(v3: super::v4::Event) -> Self {
+        Self {
+            id: v3.id,
+            message: v3.message,
+            media_url: v3.media_url,
+            file_path: v3.file_path,
+            location: v3.location,
+            altitude: v3.altitude,
+            heading: v3.heading,
+            media_type: v3.media_type,
+            device_id: v3.device_id,
+            earthranger_url: v3.earthranger_url,
+            timestamp_observation: v3.timestamp_observation,
+            is_public: v3.is_public,
+            session_id: v3.session_id,
+            embedding_qwen_vl_2b: v3.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v3.embedding_vertex_mm_01,
+            parent_event_id: v3.parent_event_id,
+            hdop: None,
+            quality_flags: None,
+        }
+    }
+}
+
+impl From<super::v2::Event> for Event {
+    fn from(v2: super::v2::Event) -> Self {
+        super::v4::Event::from(v2).into()
+    }
+}
+
+impl From<super::v1::Event> for Event {
+    fn from(v1: super::v1::Event) -> Self {
+        super::v4::Event::from(v1).into()
+    }
+}
+
+// ===== TAG V3 WITH MEDIA ANCHOR =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 17, version = 3)]
+#[native_db]
+pub struct TagLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub inserted_at: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub conf: f64,
+    pub observation_type: TagObservationType,
+    pub class_name: String,
+    #[secondary_key]
+    pub event_id: i64,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub location: Option<String>,
+    // NEW FIELDS IN V2
+    pub detector_name: Option<String>,
+    pub detector_version: Option<String>,
+    // NEW FIELDS IN V3
+    /// Video frame the tag was drawn on, counted from 0
+    pub frame_number: Option<u64>,
+    /// Offset of the tag into the video or audio recording, in milliseconds
+    pub media_offset_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub conf: f64,
+    pub observation_type: TagObservationType,
+    pub class_name: String,
+    pub event_id: i64,
+    pub location: Option<String>,
+    // NEW FIELDS IN V2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detector_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detector_version: Option<String>,
+    // NEW FIELDS IN V3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_number: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_offset_ms: Option<u64>,
+}
+
+impl Default for TagLocal {
+    fn default() -> Self {
+        super::v4::TagLocal::default().into()
+    }
+}
+
+impl Default for Tag {
+    fn default() -> Self {
+        super::v4::Tag::default().into()
+    }
+}
+
+impl super::v1::RemoteIdIndexed for TagLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        TagLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for TagLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        TagLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for TagLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Tag {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None // API struct doesn't have id_local
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {
+        // API struct doesn't have id_local, so this is a no-op
+    }
+}
+
+impl super::v1::AncestorLocal for TagLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl From<TagLocal> for Tag {
+    fn from(local: TagLocal) -> Self {
+        Tag {
+            id: local.id,
+            inserted_at: local.inserted_at,
+            x: local.x,
+            y: local.y,
+            width: local.width,
+            height: local.height,
+            conf: local.conf,
+            observation_type: local.observation_type,
+            class_name: local.class_name,
+            event_id: local.event_id,
+            location: local.location,
+            detector_name: local.detector_name,
+            detector_version: local.detector_version,
+            frame_number: local.frame_number,
+            media_offset_ms: local.media_offset_ms,
+        }
+    }
+}
+
+impl From<Tag> for TagLocal {
+    fn from(tag: Tag) -> Self {
+        TagLocal {
+            id: tag.id,
+            id_local: None, // API structs don't have id_local
+            inserted_at: tag.inserted_at,
+            x: tag.x,
+            y: tag.y,
+            width: tag.width,
+            height: tag.height,
+            conf: tag.conf,
+            observation_type: tag.observation_type,
+            class_name: tag.class_name,
+            event_id: tag.event_id,
+            ancestor_id_local: None, // API structs don't have ancestor_id_local
+            location: tag.location,
+            detector_name: tag.detector_name,
+            detector_version: tag.detector_version,
+            frame_number: tag.frame_number,
+            media_offset_ms: tag.media_offset_ms,
+        }
+    }
+}
+
+impl Tag {
+    /// Records which model produced the tag
+    pub fn with_detector(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.detector_name = Some(name.into());
+        self.detector_version = Some(version.into());
+        self
+    }
+
+    /// Anchors the tag to a video frame
+    pub fn with_frame_number(mut self, frame_number: u64) -> Self {
+        self.frame_number = Some(frame_number);
+        self
+    }
+
+    /// Anchors the tag to an offset into a video or audio recording
+    pub fn with_media_offset_ms(mut self, media_offset_ms: u64) -> Self {
+        self.media_offset_ms = Some(media_offset_ms);
+        self
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        _class_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        conf: f64,
+        observation_type: TagObservationType,
+        class_name: String,
+    ) -> Self {
+        Self {
+            id: None,
+            inserted_at: None,
+            x,
+            y,
+            width,
+            height,
+            conf,
+            observation_type,
+            class_name,
+            event_id: 0,
+            location: None,
+            detector_name: None,
+            detector_version: None,
+            frame_number: None,
+            media_offset_ms: None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_location(
+        _class_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        conf: f64,
+        observation_type: TagObservationType,
+        class_name: String,
+        latitude: f64,
+        longitude: f64,
+    ) -> Self {
+        let mut tag = Self::new(
+            _class_id,
+            x,
+            y,
+            width,
+            height,
+            conf,
+            observation_type,
+            class_name,
+        );
+        tag.set_location(latitude, longitude);
+        tag
+    }
+
+    pub fn update_event_id(&mut self, event_id: i64) {
+        self.event_id = event_id;
+    }
+
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) {
+        self.location = Some(Self::format_location(latitude, longitude));
+    }
+
+    pub fn clear_location(&mut self) {
+        self.location = None;
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    pub fn parse_location(location: &str) -> Option<(f64, f64)> {
+        if let Some(coords) = location
+            .strip_prefix("POINT(")
+            .and_then(|s| s.strip_suffix(")"))
+        {
+            let parts: Vec<&str> = coords.split_whitespace().collect();
+            if parts.len() == 2 {
+                if let (Ok(lon), Ok(lat)) = (parts[0].parse::<f64>(), parts[1].parse::<f64>()) {
+                    return Some((lat, lon));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_coordinates(&self) -> Option<(f64, f64)> {
+        self.location
+            .as_ref()
+            .and_then(|loc| Self::parse_location(loc))
+    }
+}
+
+impl TagLocal {
+    /// Records which model produced the tag
+    pub fn with_detector(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.detector_name = Some(name.into());
+        self.detector_version = Some(version.into());
+        self
+    }
+
+    /// Anchors the tag to a video frame
+    pub fn with_frame_number(mut self, frame_number: u64) -> Self {
+        self.frame_number = Some(frame_number);
+        self
+    }
+
+    /// Anchors the tag to an offset into a video or audio recording
+    pub fn with_media_offset_ms(mut self, media_offset_ms: u64) -> Self {
+        self.media_offset_ms = Some(media_offset_ms);
+        self
+    }
+
+    /// Checks the media anchor against the media type of the tag's event: frame numbers
+    /// only apply to video, offsets to video and audio
+    pub fn validate_media_anchor(&self, media_type: &MediaType) -> Result<()> {
+        if self.frame_number.is_some() && *media_type != MediaType::Video {
+            return Err(anyhow!(
+                "Tag {:?} has a frame number, but its event's media is {:?}",
+                self.id_local,
+                media_type
+            ));
+        }
+        if self.media_offset_ms.is_some()
+            && !matches!(media_type, MediaType::Video | MediaType::Audio)
+        {
+            return Err(anyhow!(
+                "Tag {:?} has a media offset, but its event's media is {:?}",
+                self.id_local,
+                media_type
+            ));
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        _class_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        conf: f64,
+        observation_type: TagObservationType,
+        class_name: String,
+    ) -> Self {
+        Self {
+            id: None,
+            id_local: None,
+            inserted_at: None,
+            x,
+            y,
+            width,
+            height,
+            conf,
+            observation_type,
+            class_name,
+            event_id: 0,
+            ancestor_id_local: None,
+            location: None,
+            detector_name: None,
+            detector_version: None,
+            frame_number: None,
+            media_offset_ms: None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_location(
+        _class_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        conf: f64,
+        observation_type: TagObservationType,
+        class_name: String,
+        latitude: f64,
+        longitude: f64,
+    ) -> Self {
+        let mut tag = Self::new(
+            _class_id,
+            x,
+            y,
+            width,
+            height,
+            conf,
+            observation_type,
+            class_name,
+        );
+        tag.set_location(latitude, longitude);
+        tag
+    }
+
+    pub fn update_event_id(&mut self, event_id: i64) {
+        self.event_id = event_id;
+    }
+
+    pub fn update_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) {
+        self.location = Some(Self::format_location(latitude, longitude));
+    }
+
+    pub fn clear_location(&mut self) {
+        self.location = None;
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    pub fn parse_location(location: &str) -> Option<(f64, f64)> {
+        if let Some(coords) = location
+            .strip_prefix("POINT(")
+            .and_then(|s| s.strip_suffix(")"))
+        {
+            let parts: Vec<&str> = coords.split_whitespace().collect();
+            if parts.len() == 2 {
+                if let (Ok(lon), Ok(lat)) = (parts[0].parse::<f64>(), parts[1].parse::<f64>()) {
+                    return Some((lat, lon));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_coordinates(&self) -> Option<(f64, f64)> {
+        self.location
+            .as_ref()
+            .and_then(|loc| Self::parse_location(loc))
+    }
+}
+
+// ===== MIGRATION FROM V2 TAG TO V3 =====
+impl From<super::v4::TagLocal> for TagLocal {
+    fn from(v2: super::v4::TagLocal) -> Self {
+        Self {
+            id: v2.id,
+            id_local: v2.id_local,
+            inserted_at: v2.inserted_at,
+            x: v2.x,
+            y: v2.y,
+            width: v2.width,
+            height: v2.height,
+            conf: v2.conf,
+            observation_type: v2.observation_type,
+            class_name: v2.class_name,
+            event_id: v2.event_id,
+            ancestor_id_local: v2.ancestor_id_local,
+            location: v2.location,
+            detector_name: v2.detector_name,
+            detector_version: v2.detector_version,
+            // New fields in v3 - tags from before anchors cover the whole media
+            frame_number: None,
+            media_offset_ms: None,
+        }
+    }
+}
+
+impl From<super::v4::Tag> for Tag {
+    fn from(v2: super::v4::Tag) -> Self {
+        Self {
+            id: v2.id,
+            inserted_at: v2.inserted_at,
+            x: v2.x,
+            y: v2.y,
+            width: v2.width,
+            height: v2.height,
+            conf: v2.conf,
+            observation_type: v2.observation_type,
+            class_name: v2.class_name,
+            event_id: v2.event_id,
+            location: v2.location,
+            detector_name: v2.detector_name,
+            detector_version: v2.detector_version,
+            frame_number: None,
+            media_offset_ms: None,
+        }
+    }
+}
+
+impl From<super::v1::Tag> for Tag {
+    fn from(v1: super::v1::Tag) -> Self {
+        super::v4::Tag::from(v1).into()
+    }
+}
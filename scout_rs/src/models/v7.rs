@@ -0,0 +1,1131 @@
+use super::h3::H3Index;
+use anyhow::{anyhow, Result};
+use native_db::db_type::{KeyDefinition, KeyOptions, ToKeyDefinition};
+use native_db::{native_db, ToKey};
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+
+// Re-export all unchanged models from v6
+pub use super::v6::*;
+
+// ===== EVENT V5 WITH BURSTS =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 16, version = 5)]
+#[native_db]
+pub struct EventLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    /// Qwen VL 2B embedding (2000 dims).
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    /// Vertex multimodal embedding (1408 dims).
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    /// Remote ID of the event this one follows up on (e.g. the same animal re-sighted)
+    pub parent_event_id: Option<i64>,
+    /// Local ID of the parent event, resolved to `parent_event_id` once it is synced
+    pub parent_event_id_local: Option<String>,
+    /// GPS horizontal dilution of precision of the location, if the receiver reported it
+    pub hdop: Option<f64>,
+    /// Problems found at ingest; None if the checks found nothing or did not run
+    pub quality_flags: Option<Vec<QualityFlag>>,
+    // NEW FIELD IN V5
+    /// Shared by the frames of a burst (e.g. a camera trap's multi-shot), which are one
+    /// observation
+    #[secondary_key(optional)]
+    pub burst_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub message: Option<String>,
+    pub media_url: Option<String>,
+    pub file_path: Option<String>,
+    pub location: Option<String>,
+    pub altitude: f64,
+    pub heading: f64,
+    pub media_type: super::v1::MediaType,
+    pub device_id: i64,
+    pub earthranger_url: Option<String>,
+    pub timestamp_observation: String,
+    pub is_public: bool,
+    pub session_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_qwen_vl_2b: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "super::serde_helpers::deserialize_embedding"
+    )]
+    pub embedding_vertex_mm_01: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_event_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdop: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_flags: Option<Vec<QualityFlag>>,
+    // NEW FIELD IN V5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_id: Option<String>,
+}
+
+impl Default for EventLocal {
+    fn default() -> Self {
+        super::v6::EventLocal::default().into()
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        super::v6::Event::default().into()
+    }
+}
+
+impl super::v1::AncestorLocal for EventLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl super::v1::RemoteIdIndexed for EventLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        EventLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for EventLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        EventLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for EventLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Event {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {}
+}
+
+impl From<EventLocal> for Event {
+    fn from(local: EventLocal) -> Self {
+        Event {
+            id: local.id,
+            message: local.message,
+            media_url: local.media_url,
+            file_path: local.file_path,
+            location: local.location,
+            altitude: local.altitude,
+            heading: local.heading,
+            media_type: local.media_type,
+            device_id: local.device_id,
+            earthranger_url: local.earthranger_url,
+            timestamp_observation: local.timestamp_observation,
+            is_public: local.is_public,
+            session_id: local.session_id,
+            embedding_qwen_vl_2b: local.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: local.embedding_vertex_mm_01,
+            parent_event_id: local.parent_event_id,
+            hdop: local.hdop,
+            quality_flags: local.quality_flags,
+            burst_id: local.burst_id,
+        }
+    }
+}
+
+impl From<Event> for EventLocal {
+    fn from(event: Event) -> Self {
+        EventLocal {
+            id: event.id,
+            id_local: None,
+            message: event.message,
+            media_url: event.media_url,
+            file_path: event.file_path,
+            location: event.location,
+            altitude: event.altitude,
+            heading: event.heading,
+            media_type: event.media_type,
+            device_id: event.device_id,
+            earthranger_url: event.earthranger_url,
+            timestamp_observation: event.timestamp_observation,
+            is_public: event.is_public,
+            session_id: event.session_id,
+            ancestor_id_local: None,
+            embedding_qwen_vl_2b: event.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: event.embedding_vertex_mm_01,
+            parent_event_id: event.parent_event_id,
+            parent_event_id_local: None,
+            hdop: event.hdop,
+            quality_flags: event.quality_flags,
+            burst_id: event.burst_id,
+        }
+    }
+}
+
+impl Event {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        super::v6::Event::new(
+            message,
+            media_url,
+            file_path,
+            earthranger_url,
+            latitude,
+            longitude,
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            timestamp_observation,
+            is_public,
+            session_id,
+        )
+        .into()
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+}
+
+impl EventLocal {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        message: Option<String>,
+        media_url: Option<String>,
+        file_path: Option<String>,
+        earthranger_url: Option<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+        heading: f64,
+        media_type: super::v1::MediaType,
+        device_id: i64,
+        timestamp_observation: u64,
+        is_public: bool,
+        session_id: Option<i64>,
+    ) -> Self {
+        super::v6::EventLocal::new(
+            message,
+            media_url,
+            file_path,
+            earthranger_url,
+            latitude,
+            longitude,
+            altitude,
+            heading,
+            media_type,
+            device_id,
+            timestamp_observation,
+            is_public,
+            session_id,
+        )
+        .into()
+    }
+
+    pub fn format_location(latitude: f64, longitude: f64) -> String {
+        format!("POINT({} {})", longitude, latitude)
+    }
+
+    /// Links the event as a follow-up of `parent`
+    pub fn with_parent_event(mut self, parent: &EventLocal) -> Self {
+        self.parent_event_id = parent.id;
+        self.parent_event_id_local = parent.id_local.clone();
+        self
+    }
+
+    /// Marks the event as a frame of the burst `burst_id`
+    pub fn with_burst_id(mut self, burst_id: impl Into<String>) -> Self {
+        self.burst_id = Some(burst_id.into());
+        self
+    }
+}
+
+// ===== MIGRATION FROM V4 EVENT TO V5 =====
+impl From<super::v6::EventLocal> for EventLocal {
+    fn from(v4: super::v6::EventLocal) -> Self {
+        Self {
+            id: v4.id,
+            id_local: v4.id_local,
+            message: v4.message,
+            media_url: v4.media_url,
+            file_path: v4.file_path,
+            location: v4.location,
+            altitude: v4.altitude,
+            heading: v4.heading,
+            media_type: v4.media_type,
+            device_id: v4.device_id,
+            earthranger_url: v4.earthranger_url,
+            timestamp_observation: v4.timestamp_observation,
+            is_public: v4.is_public,
+            session_id: v4.session_id,
+            ancestor_id_local: v4.ancestor_id_local,
+            embedding_qwen_vl_2b: v4.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v4.embedding_vertex_mm_01,
+            parent_event_id: v4.parent_event_id,
+            parent_event_id_local: v4.parent_event_id_local,
+            hdop: v4.hdop,
+            quality_flags: v4.quality_flags,
+            // New field in v5 - events from before bursts stand alone
+            burst_id: None,
+        }
+    }
+}
+
+impl From<super::v6::Event> for Event {
+    fn from(v4: super::v6::Event) -> Self {
+        Self {
+            id: v4.id,
+            message: v4.message,
+            media_url: v4.media_url,
+            file_path: v4.file_path,
+            location: v4.location,
+            altitude: v4.altitude,
+            heading: v4.heading,
+            media_type: v4.media_type,
+            device_id: v4.device_id,
+            earthranger_url: v4.earthranger_url,
+            timestamp_observation: v4.timestamp_observation,
+            is_public: v4.is_public,
+            session_id: v4.session_id,
+            embedding_qwen_vl_2b: v4.embedding_qwen_vl_2b,
+            embedding_vertex_mm_01: v4.embedding_vertex_mm_01,
+            parent_event_id: v4.parent_event_id,
+            hdop: v4.hdop,
+            quality_flags: v4.quality_flags,
+            burst_id: None,
+        }
+    }
+}
+
+impl From<super::v4::Event> for Event {
+    fn from(v3: super::v4::Event) -> Self {
+        super::v6::Event::from(v3).into()
+    }
+}
+
+impl From<super::v2::Event> for Event {
+    fn from(v2: super::v2::Event) -> Self {
+        super::v6::Event::from(v2).into()
+    }
+}
+
+impl From<super::v1::Event> for Event {
+    fn from(v1: super::v1::Event) -> Self {
+        super::v6::Event::from(v1).into()
+    }
+}
+
+// ===== SESSION V2 WITH VISIBILITY =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 14, version = 2)]
+#[native_db]
+pub struct SessionLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    pub device_id: i64,
+    pub timestamp_start: String,
+    pub timestamp_end: Option<String>,
+    pub inserted_at: Option<String>,
+    pub software_version: String,
+    pub locations: Option<String>,
+    pub altitude_max: f64,
+    pub altitude_min: f64,
+    pub altitude_average: f64,
+    pub velocity_max: f64,
+    pub velocity_min: f64,
+    pub velocity_average: f64,
+    pub distance_total: f64,
+    pub distance_max_from_start: f64,
+    pub earthranger_url: Option<String>,
+    // NEW FIELD IN V2
+    /// Public outreach material rather than herd-private, like `EventLocal::is_public`
+    pub is_public: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub device_id: i64,
+    pub timestamp_start: String,
+    pub timestamp_end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    pub software_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locations: Option<String>,
+    pub altitude_max: f64,
+    pub altitude_min: f64,
+    pub altitude_average: f64,
+    pub velocity_max: f64,
+    pub velocity_min: f64,
+    pub velocity_average: f64,
+    pub distance_total: f64,
+    pub distance_max_from_start: f64,
+    pub earthranger_url: Option<String>,
+    // NEW FIELD IN V2
+    #[serde(default)]
+    pub is_public: bool,
+}
+
+impl Default for SessionLocal {
+    fn default() -> Self {
+        super::v1::SessionLocal::default().into()
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        super::v1::Session::default().into()
+    }
+}
+
+impl super::v1::RemoteIdIndexed for SessionLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        SessionLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::Syncable for SessionLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::Syncable for Session {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        None
+    }
+
+    fn set_id_local(&mut self, _id_local: String) {}
+}
+
+impl From<SessionLocal> for Session {
+    fn from(local: SessionLocal) -> Self {
+        Session {
+            id: local.id,
+            device_id: local.device_id,
+            timestamp_start: local.timestamp_start,
+            timestamp_end: local.timestamp_end,
+            inserted_at: local.inserted_at,
+            software_version: local.software_version,
+            locations: local.locations,
+            altitude_max: local.altitude_max,
+            altitude_min: local.altitude_min,
+            altitude_average: local.altitude_average,
+            velocity_max: local.velocity_max,
+            velocity_min: local.velocity_min,
+            velocity_average: local.velocity_average,
+            distance_total: local.distance_total,
+            distance_max_from_start: local.distance_max_from_start,
+            earthranger_url: local.earthranger_url,
+            is_public: local.is_public,
+        }
+    }
+}
+
+impl From<Session> for SessionLocal {
+    fn from(session: Session) -> Self {
+        SessionLocal {
+            id: session.id,
+            id_local: None,
+            device_id: session.device_id,
+            timestamp_start: session.timestamp_start,
+            timestamp_end: session.timestamp_end,
+            inserted_at: session.inserted_at,
+            software_version: session.software_version,
+            locations: session.locations,
+            altitude_max: session.altitude_max,
+            altitude_min: session.altitude_min,
+            altitude_average: session.altitude_average,
+            velocity_max: session.velocity_max,
+            velocity_min: session.velocity_min,
+            velocity_average: session.velocity_average,
+            distance_total: session.distance_total,
+            distance_max_from_start: session.distance_max_from_start,
+            earthranger_url: session.earthranger_url,
+            is_public: session.is_public,
+        }
+    }
+}
+
+impl Session {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device_id: i64,
+        timestamp_start: u64,
+        timestamp_end: Option<u64>,
+        software_version: String,
+        location: Option<String>,
+        altitude_max: f64,
+        altitude_min: f64,
+        altitude_average: f64,
+        velocity_max: f64,
+        velocity_min: f64,
+        velocity_average: f64,
+        distance_total: f64,
+        distance_max_from_start: f64,
+    ) -> Self {
+        super::v1::Session::new(
+            device_id,
+            timestamp_start,
+            timestamp_end,
+            software_version,
+            location,
+            altitude_max,
+            altitude_min,
+            altitude_average,
+            velocity_max,
+            velocity_min,
+            velocity_average,
+            distance_total,
+            distance_max_from_start,
+        )
+        .into()
+    }
+
+    pub fn update_timestamp_end(&mut self, timestamp_end: u64) {
+        use chrono::{DateTime, Utc};
+        self.timestamp_end = Some(
+            DateTime::from_timestamp(timestamp_end as i64, 0)
+                .unwrap_or_else(|| DateTime::<Utc>::from_timestamp(0, 0).unwrap())
+                .to_rfc3339(),
+        );
+    }
+}
+
+impl SessionLocal {
+    pub fn update_timestamp_end(&mut self, timestamp_end: u64) {
+        use chrono::{DateTime, Utc};
+        self.timestamp_end = Some(
+            DateTime::from_timestamp(timestamp_end as i64, 0)
+                .unwrap_or_else(|| DateTime::<Utc>::from_timestamp(0, 0).unwrap())
+                .to_rfc3339(),
+        );
+    }
+}
+
+// ===== MIGRATION FROM V1 SESSION TO V2 =====
+impl From<super::v1::SessionLocal> for SessionLocal {
+    fn from(v1: super::v1::SessionLocal) -> Self {
+        Self {
+            id: v1.id,
+            id_local: v1.id_local,
+            device_id: v1.device_id,
+            timestamp_start: v1.timestamp_start,
+            timestamp_end: v1.timestamp_end,
+            inserted_at: v1.inserted_at,
+            software_version: v1.software_version,
+            locations: v1.locations,
+            altitude_max: v1.altitude_max,
+            altitude_min: v1.altitude_min,
+            altitude_average: v1.altitude_average,
+            velocity_max: v1.velocity_max,
+            velocity_min: v1.velocity_min,
+            velocity_average: v1.velocity_average,
+            distance_total: v1.distance_total,
+            distance_max_from_start: v1.distance_max_from_start,
+            earthranger_url: v1.earthranger_url,
+            // New field in v2 - sessions from before visibility stay herd-private
+            is_public: false,
+        }
+    }
+}
+
+impl From<super::v1::Session> for Session {
+    fn from(v1: super::v1::Session) -> Self {
+        Self {
+            id: v1.id,
+            device_id: v1.device_id,
+            timestamp_start: v1.timestamp_start,
+            timestamp_end: v1.timestamp_end,
+            inserted_at: v1.inserted_at,
+            software_version: v1.software_version,
+            locations: v1.locations,
+            altitude_max: v1.altitude_max,
+            altitude_min: v1.altitude_min,
+            altitude_average: v1.altitude_average,
+            velocity_max: v1.velocity_max,
+            velocity_min: v1.velocity_min,
+            velocity_average: v1.velocity_average,
+            distance_total: v1.distance_total,
+            distance_max_from_start: v1.distance_max_from_start,
+            earthranger_url: v1.earthranger_url,
+            is_public: false,
+        }
+    }
+}
+
+// ===== UNITS =====
+/// Power level in dBm, between [`Dbm::MIN`] and [`Dbm::MAX`]. Serialized as the bare number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Dbm(f64);
+
+impl Dbm {
+    /// Below the thermal noise floor of any radio we deploy
+    pub const MIN: f64 = -200.0;
+    /// 1 W, above any transmit power a device could receive
+    pub const MAX: f64 = 30.0;
+
+    pub fn new(value: f64) -> Result<Self> {
+        if !(Self::MIN..=Self::MAX).contains(&value) {
+            return Err(anyhow!(
+                "{} dBm is outside {}..={} dBm",
+                value,
+                Self::MIN,
+                Self::MAX
+            ));
+        }
+        Ok(Self(value))
+    }
+
+    /// The nearest valid level; NaN becomes [`Dbm::MIN`]
+    pub fn clamped(value: f64) -> Self {
+        Self(if value.is_nan() {
+            Self::MIN
+        } else {
+            value.clamp(Self::MIN, Self::MAX)
+        })
+    }
+
+    pub fn value(self) -> f64 {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for Dbm {
+    type Error = anyhow::Error;
+
+    fn try_from(value: f64) -> Result<Self> {
+        Self::new(value)
+    }
+}
+
+impl From<Dbm> for f64 {
+    fn from(dbm: Dbm) -> Self {
+        dbm.0
+    }
+}
+
+impl PartialEq<f64> for Dbm {
+    fn eq(&self, other: &f64) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::fmt::Display for Dbm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} dBm", self.0)
+    }
+}
+
+/// Percentage from 0 to 100. Serialized as the bare number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Percent(f32);
+
+impl Percent {
+    pub fn new(value: f32) -> Result<Self> {
+        if !(0.0..=100.0).contains(&value) {
+            return Err(anyhow!("{}% is outside 0..=100%", value));
+        }
+        Ok(Self(value))
+    }
+
+    pub fn value(self) -> f32 {
+        self.0
+    }
+}
+
+impl TryFrom<f32> for Percent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: f32) -> Result<Self> {
+        Self::new(value)
+    }
+}
+
+impl From<Percent> for f32 {
+    fn from(percent: Percent) -> Self {
+        percent.0
+    }
+}
+
+impl PartialEq<f32> for Percent {
+    fn eq(&self, other: &f32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::fmt::Display for Percent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+// ===== CONNECTIVITY V7 WITH TYPED UNITS =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[native_model(id = 15, version = 7)]
+#[native_db]
+pub struct ConnectivityLocal {
+    #[secondary_key(optional)]
+    pub id: Option<i64>,
+    #[primary_key]
+    pub id_local: Option<String>,
+    #[secondary_key]
+    pub session_id: Option<i64>,
+    #[secondary_key]
+    pub device_id: Option<i64>,
+    #[secondary_key]
+    pub ancestor_id_local: Option<String>,
+    pub inserted_at: Option<String>,
+    pub timestamp_start: String,
+    // TYPED IN V7
+    pub signal: Dbm,
+    pub noise: Dbm,
+    pub altitude: f64,
+    pub heading: f64,
+    pub location: Option<String>,
+    pub h14_index: H3Index,
+    pub h13_index: H3Index,
+    pub h12_index: H3Index,
+    pub h11_index: H3Index,
+    // TYPED IN V7
+    pub battery_percentage: Option<Percent>,
+    pub frequency_hz: Option<f32>,
+    pub bandwidth_hz: Option<f32>,
+    pub associated_station: Option<String>,
+    pub mode: Option<String>,
+    pub hdop: Option<f64>,
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+/// Same JSON as v6; the units only constrain what this client sends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Connectivity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub session_id: Option<i64>,
+    pub device_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    pub timestamp_start: String,
+    pub signal: Dbm,
+    pub noise: Dbm,
+    pub altitude: f64,
+    pub heading: f64,
+    pub location: Option<String>,
+    pub h14_index: String,
+    pub h13_index: String,
+    pub h12_index: String,
+    pub h11_index: String,
+    pub battery_percentage: Option<Percent>,
+    pub frequency_hz: Option<f32>,
+    pub bandwidth_hz: Option<f32>,
+    pub associated_station: Option<String>,
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdop: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_flags: Option<Vec<QualityFlag>>,
+}
+
+impl Default for ConnectivityLocal {
+    fn default() -> Self {
+        super::v6::ConnectivityLocal::default().into()
+    }
+}
+
+impl super::v1::RemoteIdIndexed for ConnectivityLocal {
+    fn remote_id_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::id.key_definition()
+    }
+}
+
+impl super::v1::AncestorIndexed for ConnectivityLocal {
+    fn ancestor_key() -> KeyDefinition<KeyOptions> {
+        ConnectivityLocalKey::ancestor_id_local.key_definition()
+    }
+}
+
+impl super::v1::Syncable for ConnectivityLocal {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    fn id_local(&self) -> Option<String> {
+        self.id_local.clone()
+    }
+
+    fn set_id_local(&mut self, id_local: String) {
+        self.id_local = Some(id_local);
+    }
+}
+
+impl super::v1::AncestorLocal for ConnectivityLocal {
+    fn ancestor_id_local(&self) -> Option<String> {
+        self.ancestor_id_local.clone()
+    }
+
+    fn set_ancestor_id_local(&mut self, ancestor_id_local: String) {
+        self.ancestor_id_local = Some(ancestor_id_local);
+    }
+}
+
+impl ConnectivityLocal {
+    /// Fails if signal, noise or battery percentage is out of range
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        session_id: Option<i64>,
+        device_id: Option<i64>,
+        timestamp_start: u64,
+        signal: f64,
+        noise: f64,
+        altitude: f64,
+        heading: f64,
+        location: String,
+        h14_index: H3Index,
+        h13_index: H3Index,
+        h12_index: H3Index,
+        h11_index: H3Index,
+        battery_percentage: Option<f32>,
+        frequency_hz: Option<f32>,
+        bandwidth_hz: Option<f32>,
+        associated_station: Option<String>,
+        mode: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            signal: Dbm::new(signal)?,
+            noise: Dbm::new(noise)?,
+            battery_percentage: battery_percentage.map(Percent::new).transpose()?,
+            ..super::v6::ConnectivityLocal::new(
+                session_id,
+                device_id,
+                timestamp_start,
+                0.0,
+                0.0,
+                altitude,
+                heading,
+                location,
+                h14_index,
+                h13_index,
+                h12_index,
+                h11_index,
+                None,
+                frequency_hz,
+                bandwidth_hz,
+                associated_station,
+                mode,
+            )
+            .into()
+        })
+    }
+
+    /// Sets the resolution 14 index and derives the resolution 13, 12 and 11 indexes from it
+    pub fn set_h3_indexes(&mut self, h14_index: H3Index) -> Result<()> {
+        [
+            self.h14_index,
+            self.h13_index,
+            self.h12_index,
+            self.h11_index,
+        ] = h14_index.hierarchy([14, 13, 12, 11])?;
+        Ok(())
+    }
+}
+
+impl Connectivity {
+    /// Fails if signal, noise or battery percentage is out of range
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        session_id: Option<i64>,
+        device_id: Option<i64>,
+        timestamp_start: u64,
+        signal: f64,
+        noise: f64,
+        altitude: f64,
+        heading: f64,
+        location: String,
+        h14_index: String,
+        h13_index: String,
+        h12_index: String,
+        h11_index: String,
+        battery_percentage: Option<f32>,
+        frequency_hz: Option<f32>,
+        bandwidth_hz: Option<f32>,
+        associated_station: Option<String>,
+        mode: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            signal: Dbm::new(signal)?,
+            noise: Dbm::new(noise)?,
+            battery_percentage: battery_percentage.map(Percent::new).transpose()?,
+            ..super::v6::Connectivity::new(
+                session_id,
+                device_id,
+                timestamp_start,
+                0.0,
+                0.0,
+                altitude,
+                heading,
+                location,
+                h14_index,
+                h13_index,
+                h12_index,
+                h11_index,
+                None,
+                frequency_hz,
+                bandwidth_hz,
+                associated_station,
+                mode,
+            )
+            .into()
+        })
+    }
+}
+
+impl From<ConnectivityLocal> for Connectivity {
+    fn from(local: ConnectivityLocal) -> Self {
+        Self {
+            id: local.id,
+            session_id: local.session_id,
+            device_id: local.device_id,
+            inserted_at: local.inserted_at,
+            timestamp_start: local.timestamp_start,
+            signal: local.signal,
+            noise: local.noise,
+            altitude: local.altitude,
+            heading: local.heading,
+            location: local.location,
+            h14_index: local.h14_index.to_string(),
+            h13_index: local.h13_index.to_string(),
+            h12_index: local.h12_index.to_string(),
+            h11_index: local.h11_index.to_string(),
+            battery_percentage: local.battery_percentage,
+            frequency_hz: local.frequency_hz,
+            bandwidth_hz: local.bandwidth_hz,
+            associated_station: local.associated_station,
+            mode: local.mode,
+            hdop: local.hdop,
+            quality_flags: local.quality_flags,
+        }
+    }
+}
+
+impl From<Connectivity> for ConnectivityLocal {
+    fn from(remote: Connectivity) -> Self {
+        let (signal, noise, battery_percentage) =
+            (remote.signal, remote.noise, remote.battery_percentage);
+        Self {
+            signal,
+            noise,
+            battery_percentage,
+            ..super::v6::ConnectivityLocal::from(super::v6::Connectivity::from(remote)).into()
+        }
+    }
+}
+
+// ===== MIGRATION FROM V6 CONNECTIVITY TO V7 =====
+impl From<super::v6::ConnectivityLocal> for ConnectivityLocal {
+    fn from(v6: super::v6::ConnectivityLocal) -> Self {
+        Self {
+            id: v6.id,
+            id_local: v6.id_local,
+            session_id: v6.session_id,
+            device_id: v6.device_id,
+            ancestor_id_local: v6.ancestor_id_local,
+            inserted_at: v6.inserted_at,
+            timestamp_start: v6.timestamp_start,
+            // Out of range levels were never real readings; keep them at the nearest bound
+            signal: Dbm::clamped(v6.signal),
+            noise: Dbm::clamped(v6.noise),
+            altitude: v6.altitude,
+            heading: v6.heading,
+            location: v6.location,
+            h14_index: v6.h14_index,
+            h13_index: v6.h13_index,
+            h12_index: v6.h12_index,
+            h11_index: v6.h11_index,
+            // An impossible battery level is unknown
+            battery_percentage: v6.battery_percentage.and_then(|b| Percent::new(b).ok()),
+            frequency_hz: v6.frequency_hz,
+            bandwidth_hz: v6.bandwidth_hz,
+            associated_station: v6.associated_station,
+            mode: v6.mode,
+            hdop: v6.hdop,
+            quality_flags: v6.quality_flags,
+        }
+    }
+}
+
+impl From<super::v6::Connectivity> for Connectivity {
+    fn from(v6: super::v6::Connectivity) -> Self {
+        Self {
+            id: v6.id,
+            session_id: v6.session_id,
+            device_id: v6.device_id,
+            inserted_at: v6.inserted_at,
+            timestamp_start: v6.timestamp_start,
+            signal: Dbm::clamped(v6.signal),
+            noise: Dbm::clamped(v6.noise),
+            altitude: v6.altitude,
+            heading: v6.heading,
+            location: v6.location,
+            h14_index: v6.h14_index,
+            h13_index: v6.h13_index,
+            h12_index: v6.h12_index,
+            h11_index: v6.h11_index,
+            battery_percentage: v6.battery_percentage.and_then(|b| Percent::new(b).ok()),
+            frequency_hz: v6.frequency_hz,
+            bandwidth_hz: v6.bandwidth_hz,
+            associated_station: v6.associated_station,
+            mode: v6.mode,
+            hdop: v6.hdop,
+            quality_flags: v6.quality_flags,
+        }
+    }
+}
+
+impl From<Connectivity> for super::v6::Connectivity {
+    fn from(v7: Connectivity) -> Self {
+        Self {
+            id: v7.id,
+            session_id: v7.session_id,
+            device_id: v7.device_id,
+            inserted_at: v7.inserted_at,
+            timestamp_start: v7.timestamp_start,
+            signal: v7.signal.into(),
+            noise: v7.noise.into(),
+            altitude: v7.altitude,
+            heading: v7.heading,
+            location: v7.location,
+            h14_index: v7.h14_index,
+            h13_index: v7.h13_index,
+            h12_index: v7.h12_index,
+            h11_index: v7.h11_index,
+            battery_percentage: v7.battery_percentage.map(f32::from),
+            frequency_hz: v7.frequency_hz,
+            bandwidth_hz: v7.bandwidth_hz,
+            associated_station: v7.associated_station,
+            mode: v7.mode,
+            hdop: v7.hdop,
+            quality_flags: v7.quality_flags,
+        }
+    }
+}
+
+impl From<super::v4::Connectivity> for Connectivity {
+    fn from(v4: super::v4::Connectivity) -> Self {
+        super::v6::Connectivity::from(v4).into()
+    }
+}
+
+impl From<super::v3::Connectivity> for Connectivity {
+    fn from(v3: super::v3::Connectivity) -> Self {
+        super::v6::Connectivity::from(v3).into()
+    }
+}
+
+impl From<super::v2::Connectivity> for Connectivity {
+    fn from(v2: super::v2::Connectivity) -> Self {
+        super::v6::Connectivity::from(v2).into()
+    }
+}
+
+impl From<super::v1::Connectivity> for Connectivity {
+    fn from(v1: super::v1::Connectivity) -> Self {
+        super::v6::Connectivity::from(v1).into()
+    }
+}
@@ -0,0 +1,144 @@
+//! Navigation math for pilots following the waypoints of a plan: great-circle distance,
+//! initial bearing, cross-track error from a leg and a simple ETA, on a spherical earth.
+//! Waypoints are taken as [`GeoPoint`]s, as parsed from a plan by the app, so apps need
+//! not each pull in a geo crate. Speeds for the ETA can come from the recent fixes of the
+//! session being flown, see
+//! [`SyncEngine::recent_session_speed`](crate::sync::SyncEngine::recent_session_speed).
+//!
+//! ```
+//! use scout_rs::nav::{self, GeoPoint};
+//!
+//! let waypoints = [GeoPoint::new(36.80, -1.30), GeoPoint::new(36.85, -1.30)];
+//! let position = GeoPoint::new(36.75, -1.31);
+//! let bearing = nav::bearing_deg(position, waypoints[0]);
+//! let off_track = nav::cross_track_m(waypoints[0], waypoints[1], position);
+//! let eta = nav::eta(position, &waypoints, 12.0).unwrap();
+//! assert!(bearing > 0.0 && bearing < 90.0 && off_track > 0.0);
+//! assert!(eta.as_secs() > 600);
+//! ```
+
+use std::time::Duration;
+
+/// Mean earth radius used by all helpers
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// A position in degrees, longitude first as in WKT
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GeoPoint {
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+impl GeoPoint {
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Self {
+            longitude,
+            latitude,
+        }
+    }
+
+    /// Parses a WKT `POINT(lon lat)`, as stored in record locations
+    pub fn from_wkt(wkt: &str) -> Option<Self> {
+        let coords = wkt.trim().strip_prefix("POINT(")?.strip_suffix(')')?;
+        let mut parts = coords.split_whitespace();
+        let longitude = parts.next()?.parse().ok()?;
+        let latitude = parts.next()?.parse().ok()?;
+        Some(Self::new(longitude, latitude))
+    }
+}
+
+/// Great-circle (haversine) distance in meters
+pub fn distance_m(a: GeoPoint, b: GeoPoint) -> f64 {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Initial bearing from `from` towards `to`, in degrees clockwise from north (0..360)
+pub fn bearing_deg(from: GeoPoint, to: GeoPoint) -> f64 {
+    let (lat_a, lat_b) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let d_lon = (to.longitude - from.longitude).to_radians();
+    let y = d_lon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Distance in meters of `position` from the great circle through the leg `start` to
+/// `end`; positive right of the direction of travel, negative left of it
+pub fn cross_track_m(start: GeoPoint, end: GeoPoint, position: GeoPoint) -> f64 {
+    let angular_distance = distance_m(start, position) / EARTH_RADIUS_M;
+    let angle = (bearing_deg(start, position) - bearing_deg(start, end)).to_radians();
+    (angular_distance.sin() * angle.sin()).asin() * EARTH_RADIUS_M
+}
+
+/// Length in meters of the route through `waypoints` in order
+pub fn route_length_m(waypoints: &[GeoPoint]) -> f64 {
+    waypoints
+        .windows(2)
+        .map(|leg| distance_m(leg[0], leg[1]))
+        .sum()
+}
+
+/// Time to fly from `position` through the remaining `waypoints` at `speed_m_s`; None
+/// without waypoints or a positive speed
+pub fn eta(position: GeoPoint, waypoints: &[GeoPoint], speed_m_s: f64) -> Option<Duration> {
+    let next = waypoints.first()?;
+    if !(speed_m_s.is_finite() && speed_m_s > 0.0) {
+        return None;
+    }
+    let distance = distance_m(position, *next) + route_length_m(waypoints);
+    Duration::try_from_secs_f64(distance / speed_m_s).ok()
+}
+
+/// Average ground speed in m/s along time-ordered fixes (seconds since any epoch); None
+/// if they span no time
+pub fn speed_over(fixes: &[(GeoPoint, f64)]) -> Option<f64> {
+    let (first, last) = (fixes.first()?, fixes.last()?);
+    let elapsed = last.1 - first.1;
+    if elapsed <= 0.0 {
+        return None;
+    }
+    let points: Vec<GeoPoint> = fixes.iter().map(|(point, _)| *point).collect();
+    Some(route_length_m(&points) / elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_bearing_cross_track_and_eta() {
+        let origin = GeoPoint::new(0.0, 0.0);
+        let north = GeoPoint::new(0.0, 1.0);
+        let east = GeoPoint::new(1.0, 0.0);
+        // One degree of arc on the mean sphere
+        assert!((distance_m(origin, north) - 111_195.0).abs() < 1.0);
+        assert!((bearing_deg(origin, east) - 90.0).abs() < 1e-9);
+        assert!((bearing_deg(east, origin) - 270.0).abs() < 1e-9);
+
+        // Flying east along the equator, a point north of it is to the left
+        let off_track = cross_track_m(origin, east, GeoPoint::new(0.5, 0.01));
+        assert!((off_track + 1_111.95).abs() < 0.1);
+        assert!(cross_track_m(origin, east, GeoPoint::new(0.5, -0.01)) > 0.0);
+
+        let route = [north, GeoPoint::new(0.0, 2.0)];
+        let arrival = eta(origin, &route, 111.195).unwrap();
+        assert!((arrival.as_secs_f64() - 2_000.0).abs() < 1.0);
+        assert_eq!(eta(origin, &route, 0.0), None);
+        assert_eq!(eta(origin, &[], 10.0), None);
+
+        let fixes = [
+            (origin, 0.0),
+            (north, 500.0),
+            (GeoPoint::new(0.0, 2.0), 1_000.0),
+        ];
+        assert!((speed_over(&fixes).unwrap() - 222.39).abs() < 0.01);
+        assert_eq!(speed_over(&fixes[..1]), None);
+        assert_eq!(
+            GeoPoint::from_wkt("POINT(36.8 -1.3)"),
+            Some(GeoPoint::new(36.8, -1.3))
+        );
+    }
+}
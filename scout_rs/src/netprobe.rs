@@ -0,0 +1,377 @@
+//! Modem connectivity sampling. Reads signal strength, SNR and bearer info from a cellular
+//! modem, through ModemManager's `mmcli` or AT commands on the modem's serial port, and
+//! records each sample as a connectivity row so gateways log actual link quality.
+//!
+//! ```no_run
+//! # async fn run(mut sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::netprobe::{ModemSource, NetProbe};
+//!
+//! let probe = NetProbe::new(ModemSource::ModemManager { modem: "0".to_string() }, 42)
+//!     .with_session("session_1")
+//!     .with_interval(std::time::Duration::from_secs(60));
+//! probe.run(&mut sync_engine).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use crate::models::{ConnectivityLocal, Dbm};
+use crate::sync::SyncEngine;
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Noise recorded when the modem reports no SNR, dBm
+const DEFAULT_NOISE_FLOOR_DBM: f64 = -100.0;
+/// Time an AT command may take to answer
+const AT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where modem stats are read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModemSource {
+    /// ModemManager through `mmcli`, by modem index or DBus path
+    ModemManager { modem: String },
+    /// AT commands (`AT+CSQ`, `AT+COPS?`) on the modem's serial port, e.g. `/dev/ttyUSB2`
+    At { port: PathBuf },
+}
+
+/// One reading of the modem
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModemSample {
+    /// Received signal strength, dBm
+    pub signal_dbm: f64,
+    /// Signal-to-noise ratio, dB, when the access technology reports one
+    pub snr_db: Option<f64>,
+    /// Access technology, e.g. `lte` or `umts`
+    pub access_technology: Option<String>,
+    /// Name of the network operator
+    pub operator: Option<String>,
+}
+
+/// Parses `mmcli --signal-get -J` output. The strongest technology reporting an RSSI wins.
+pub fn parse_mmcli_signal(json: &str) -> Option<ModemSample> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let signal = value.get("modem")?.get("signal")?;
+    // mmcli reports numbers as strings, and "--" when unavailable
+    let number = |technology: &str, field: &str| {
+        signal
+            .get(technology)?
+            .get(field)?
+            .as_str()?
+            .trim()
+            .parse::<f64>()
+            .ok()
+    };
+    ["5g", "lte", "umts", "gsm"]
+        .into_iter()
+        .filter_map(|technology| {
+            Some(ModemSample {
+                signal_dbm: number(technology, "rssi")?,
+                snr_db: number(technology, "snr"),
+                access_technology: Some(technology.to_string()),
+                operator: None,
+            })
+        })
+        .max_by(|a, b| a.signal_dbm.total_cmp(&b.signal_dbm))
+}
+
+/// Parses the operator name and access technologies of `mmcli -m <modem> -J` output
+pub fn parse_mmcli_modem(json: &str) -> (Option<String>, Option<String>) {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return (None, None);
+    };
+    let modem = &value["modem"];
+    let text = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .filter(|text| !text.is_empty() && *text != "--")
+            .map(str::to_string)
+    };
+    let technologies = modem["generic"]["access-technologies"]
+        .as_array()
+        .map(|technologies| {
+            technologies
+                .iter()
+                .filter_map(text)
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .filter(|technologies| !technologies.is_empty());
+    (text(&modem["3gpp"]["operator-name"]), technologies)
+}
+
+/// Signal strength in dBm of an `AT+CSQ` response, None when the modem reports it unknown
+pub fn parse_csq(response: &str) -> Option<f64> {
+    let line = response
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("+CSQ:"))?;
+    let rssi: u8 = line.split(',').next()?.trim().parse().ok()?;
+    // 0 is -113 dBm or less, 31 is -51 dBm or more, 99 is unknown
+    (rssi <= 31).then(|| -113.0 + 2.0 * f64::from(rssi))
+}
+
+/// Operator name and access technology of an `AT+COPS?` response
+pub fn parse_cops(response: &str) -> (Option<String>, Option<String>) {
+    let Some(line) = response
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("+COPS:"))
+    else {
+        return (None, None);
+    };
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let operator = fields
+        .get(2)
+        .map(|name| name.trim_matches('"').to_string())
+        .filter(|name| !name.is_empty());
+    let technology = fields.get(3).and_then(|act| match *act {
+        "0" | "1" | "3" => Some("gsm"),
+        "2" | "4" | "5" | "6" => Some("umts"),
+        "7" | "9" => Some("lte"),
+        "11" | "12" | "13" => Some("5g"),
+        _ => None,
+    });
+    (operator, technology.map(str::to_string))
+}
+
+/// Samples a modem on a schedule and records connectivity rows; see the module docs
+#[derive(Debug, Clone)]
+pub struct NetProbe {
+    source: ModemSource,
+    device_id: i64,
+    session_id_local: Option<String>,
+    interval: Duration,
+    noise_floor_dbm: f64,
+}
+
+impl NetProbe {
+    /// Samples every 30 seconds, recording rows of `device_id` outside any session
+    pub fn new(source: ModemSource, device_id: i64) -> Self {
+        Self {
+            source,
+            device_id,
+            session_id_local: None,
+            interval: Duration::from_secs(30),
+            noise_floor_dbm: DEFAULT_NOISE_FLOOR_DBM,
+        }
+    }
+
+    /// Records rows in an existing local session
+    pub fn with_session(mut self, session_id_local: impl Into<String>) -> Self {
+        self.session_id_local = Some(session_id_local.into());
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Noise recorded for samples without an SNR, dBm (-100 by default)
+    pub fn with_noise_floor(mut self, noise_floor_dbm: f64) -> Self {
+        self.noise_floor_dbm = noise_floor_dbm;
+        self
+    }
+
+    /// Reads the modem once
+    pub async fn sample(&self) -> Result<ModemSample> {
+        match &self.source {
+            ModemSource::ModemManager { modem } => {
+                let mut sample =
+                    parse_mmcli_signal(&mmcli(&["-m", modem, "--signal-get", "-J"]).await?)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "Modem {} reports no signal; is --signal-setup enabled?",
+                                modem
+                            )
+                        })?;
+                let (operator, technologies) =
+                    parse_mmcli_modem(&mmcli(&["-m", modem, "-J"]).await?);
+                sample.operator = operator;
+                sample.access_technology = technologies.or(sample.access_technology);
+                Ok(sample)
+            }
+            ModemSource::At { port } => {
+                let mut serial = tokio::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(port)
+                    .await?;
+                let signal_dbm = parse_csq(&at_command(&mut serial, "AT+CSQ").await?)
+                    .ok_or_else(|| anyhow!("Modem on {} reports unknown signal", port.display()))?;
+                let (operator, access_technology) =
+                    parse_cops(&at_command(&mut serial, "AT+COPS?").await?);
+                Ok(ModemSample {
+                    signal_dbm,
+                    snr_db: None,
+                    access_technology,
+                    operator,
+                })
+            }
+        }
+    }
+
+    /// The sample as a connectivity row taken now; fails on readings outside [`Dbm`]'s range
+    pub fn to_connectivity(&self, sample: &ModemSample) -> Result<ConnectivityLocal> {
+        Ok(ConnectivityLocal {
+            device_id: Some(self.device_id),
+            ancestor_id_local: self.session_id_local.clone(),
+            timestamp_start: chrono::Utc::now().to_rfc3339(),
+            signal: Dbm::new(sample.signal_dbm)?,
+            noise: Dbm::new(
+                sample
+                    .snr_db
+                    .map_or(self.noise_floor_dbm, |snr| sample.signal_dbm - snr),
+            )?,
+            mode: sample.access_technology.clone(),
+            associated_station: sample.operator.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Stores the sample through [`SyncEngine::ingest_items`]
+    pub fn record(
+        &self,
+        sync_engine: &mut SyncEngine,
+        sample: &ModemSample,
+    ) -> Result<ConnectivityLocal> {
+        let mut connectivity = self.to_connectivity(sample)?;
+        connectivity.id_local = Some(
+            sync_engine
+                .generate_unique_id::<ConnectivityLocal>()?
+                .to_string(),
+        );
+        sync_engine.ingest_items(vec![connectivity.clone()])?;
+        Ok(connectivity)
+    }
+
+    /// Samples and records every interval until recording fails. Failed samples, e.g.
+    /// while the modem re-registers, are logged and skipped.
+    pub async fn run(&self, sync_engine: &mut SyncEngine) -> Result<()> {
+        if let ModemSource::ModemManager { modem } = &self.source {
+            // ModemManager only polls extended signal info once a refresh rate is set
+            let rate = self.interval.as_secs().max(1).to_string();
+            if let Err(e) = mmcli(&["-m", modem, &format!("--signal-setup={}", rate)]).await {
+                logging::warn!("Failed to set up signal polling on modem {}: {}", modem, e);
+            }
+        }
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            match self.sample().await {
+                Ok(sample) => {
+                    self.record(sync_engine, &sample)?;
+                }
+                Err(e) => logging::warn!("Failed to sample modem: {}", e),
+            }
+        }
+    }
+}
+
+async fn mmcli(args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("mmcli")
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "mmcli {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Sends an AT command and reads the response up to its final `OK` or `ERROR`
+async fn at_command(serial: &mut tokio::fs::File, command: &str) -> Result<String> {
+    serial
+        .write_all(format!("{}\r", command).as_bytes())
+        .await?;
+    serial.flush().await?;
+    let read = async {
+        let mut response = String::new();
+        let mut buffer = [0; 256];
+        loop {
+            let read = serial.read(&mut buffer).await?;
+            if read == 0 {
+                return Err(anyhow!("Serial port closed"));
+            }
+            response.push_str(&String::from_utf8_lossy(&buffer[..read]));
+            let last = response
+                .lines()
+                .map(str::trim)
+                .rfind(|line| !line.is_empty());
+            match last {
+                Some("OK") => return Ok(response),
+                Some(line) if line.contains("ERROR") => {
+                    return Err(anyhow!("{} failed: {}", command, line))
+                }
+                _ => {}
+            }
+        }
+    };
+    tokio::time::timeout(AT_TIMEOUT, read)
+        .await
+        .map_err(|_| anyhow!("{} timed out", command))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::offline_test_engine;
+
+    #[test]
+    fn test_modem_samples_recorded_as_connectivity() -> Result<()> {
+        let signal = r#"{"modem":{"signal":{
+            "gsm":{"rssi":"--"},
+            "lte":{"rsrp":"-95.00","rsrq":"-10.00","rssi":"-65.00","snr":"12.00"},
+            "umts":{"ecio":"--","rscp":"--","rssi":"-80.00"},
+            "refresh":{"rate":"30"}}}}"#;
+        let sample = parse_mmcli_signal(signal).unwrap();
+        assert_eq!(sample.signal_dbm, -65.0);
+        assert_eq!(sample.snr_db, Some(12.0));
+        assert_eq!(sample.access_technology.as_deref(), Some("lte"));
+        assert_eq!(
+            parse_mmcli_modem(
+                r#"{"modem":{"3gpp":{"operator-name":"Safaricom"},"generic":{"access-technologies":["lte"]}}}"#
+            ),
+            (Some("Safaricom".to_string()), Some("lte".to_string()))
+        );
+
+        assert_eq!(
+            parse_csq("AT+CSQ\r\n+CSQ: 17,99\r\n\r\nOK\r\n"),
+            Some(-79.0)
+        );
+        assert_eq!(parse_csq("+CSQ: 99,99\r\nOK"), None);
+        assert_eq!(
+            parse_cops("+COPS: 0,0,\"Airtel KE\",7\r\nOK"),
+            (Some("Airtel KE".to_string()), Some("lte".to_string()))
+        );
+
+        let mut sync_engine = offline_test_engine()?;
+        let probe = NetProbe::new(
+            ModemSource::At {
+                port: PathBuf::from("/dev/ttyUSB2"),
+            },
+            7,
+        )
+        .with_session("gateway");
+        let recorded = probe.record(&mut sync_engine, &sample)?;
+        assert_eq!(
+            (recorded.signal.value(), recorded.noise.value()),
+            (-65.0, -77.0)
+        );
+        assert_eq!(recorded.mode.as_deref(), Some("lte"));
+        assert_eq!(recorded.ancestor_id_local.as_deref(), Some("gateway"));
+        assert_eq!(sync_engine.get_table_count::<ConnectivityLocal>()?, 1);
+
+        // Without an SNR the noise floor is recorded
+        let csq_only = ModemSample {
+            snr_db: None,
+            ..sample
+        };
+        assert_eq!(probe.to_connectivity(&csq_only)?.noise, -100.0);
+        Ok(())
+    }
+}
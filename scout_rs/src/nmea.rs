@@ -0,0 +1,339 @@
+//! NMEA 0183 GPS ingestion. Parses GGA and RMC sentences from a receiver's text stream
+//! into GPS fixes and records them on a session: streamed track points,
+//! altitude/velocity/distance stats, and connectivity entries with altitude and heading.
+//! Fixes can be sampled down to a minimum interval and filtered on fix quality.
+
+use crate::sync::SyncEngine;
+use crate::telemetry::TrackRecorder;
+use anyhow::Result;
+
+const KNOTS_TO_M_S: f64 = 0.514_444;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Sentences longer than this are line noise; the NMEA limit is 82 characters
+const MAX_SENTENCE_LEN: usize = 256;
+
+/// GGA: fix data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gga {
+    /// UTC time of day, seconds
+    pub time: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// 0 invalid, 1 GPS, 2 DGPS, 4 RTK fixed, 5 RTK float, 6 dead reckoning
+    pub fix_quality: u8,
+    pub satellites: u8,
+    pub hdop: Option<f64>,
+    /// Altitude above mean sea level, meters
+    pub altitude: Option<f64>,
+}
+
+/// RMC: recommended minimum data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rmc {
+    /// UTC time of day, seconds
+    pub time: f64,
+    /// Status `A` (valid); `V` (void) sentences are still parsed
+    pub valid: bool,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Speed over ground, knots
+    pub speed_knots: Option<f64>,
+    /// Course over ground, degrees true
+    pub course: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NmeaSentence {
+    Gga(Gga),
+    Rmc(Rmc),
+}
+
+/// A position fix: a GGA merged with the RMC of the same epoch, if one was received
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    /// UTC time of day, seconds
+    pub time: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+    /// Speed over ground, m/s
+    pub speed: f64,
+    /// Course over ground, degrees true
+    pub heading: f64,
+    pub fix_quality: u8,
+    pub satellites: u8,
+    pub hdop: Option<f64>,
+}
+
+/// Parses a single sentence, e.g. `$GPGGA,...*47`. Returns `None` for sentences with a bad
+/// checksum, other sentence types, or no position.
+pub fn parse_sentence(line: &str) -> Option<NmeaSentence> {
+    let line = line.trim().strip_prefix('$')?;
+    let (body, checksum) = line.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    if body.bytes().fold(0, |acc, b| acc ^ b) != expected {
+        return None;
+    }
+
+    let fields: Vec<&str> = body.split(',').collect();
+    // Any talker (GP, GN, GL, ...)
+    match fields[0].get(2..)? {
+        "GGA" if fields.len() >= 10 => Some(NmeaSentence::Gga(Gga {
+            time: parse_time(fields[1])?,
+            latitude: parse_coordinate(fields[2], fields[3])?,
+            longitude: parse_coordinate(fields[4], fields[5])?,
+            fix_quality: fields[6].parse().ok()?,
+            satellites: fields[7].parse().unwrap_or(0),
+            hdop: fields[8].parse().ok(),
+            altitude: fields[9].parse().ok(),
+        })),
+        "RMC" if fields.len() >= 9 => Some(NmeaSentence::Rmc(Rmc {
+            time: parse_time(fields[1])?,
+            valid: fields[2] == "A",
+            latitude: parse_coordinate(fields[3], fields[4])?,
+            longitude: parse_coordinate(fields[5], fields[6])?,
+            speed_knots: fields[7].parse().ok(),
+            course: fields[8].parse().ok(),
+        })),
+        _ => None,
+    }
+}
+
+/// `hhmmss.ss` to seconds of the day
+fn parse_time(field: &str) -> Option<f64> {
+    let hours: f64 = field.get(..2)?.parse().ok()?;
+    let minutes: f64 = field.get(2..4)?.parse().ok()?;
+    let seconds: f64 = field.get(4..)?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// `(d)ddmm.mmmm` and a hemisphere to signed decimal degrees
+fn parse_coordinate(field: &str, hemisphere: &str) -> Option<f64> {
+    let dot = field.find('.').unwrap_or(field.len());
+    let degrees: f64 = field.get(..dot.checked_sub(2)?)?.parse().ok()?;
+    let minutes: f64 = field.get(dot - 2..)?.parse().ok()?;
+    let value = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(value),
+        "S" | "W" => Some(-value),
+        _ => None,
+    }
+}
+
+/// Feeds NMEA sentences into a session of a [`SyncEngine`]. Each GGA that passes the
+/// fix-quality filters and sampling interval becomes a track point, session stats, and a
+/// connectivity entry; speed and heading come from the RMC of the same epoch.
+#[derive(Debug)]
+pub struct NmeaIngest {
+    recorder: TrackRecorder,
+    line: Vec<u8>,
+    last_rmc: Option<Rmc>,
+    last_sample: Option<f64>,
+    sample_interval: f64,
+    min_fix_quality: u8,
+    min_satellites: u8,
+    max_hdop: Option<f64>,
+}
+
+impl NmeaIngest {
+    /// Ingests into an existing local session. By default every fix with quality >= 1 is kept.
+    pub fn new(session_id_local: impl Into<String>, device_id: i64) -> Self {
+        Self {
+            recorder: TrackRecorder::new(session_id_local.into(), device_id),
+            line: Vec::new(),
+            last_rmc: None,
+            last_sample: None,
+            sample_interval: 0.0,
+            min_fix_quality: 1,
+            min_satellites: 0,
+            max_hdop: None,
+        }
+    }
+
+    /// Number of fixes buffered before they are appended as a track segment
+    pub fn with_track_batch_size(mut self, track_batch_size: usize) -> Self {
+        self.recorder.track_batch_size = track_batch_size.max(1);
+        self
+    }
+
+    /// Minimum receiver time between recorded fixes, e.g. 5s to sample a 1 Hz receiver at 0.2 Hz
+    pub fn with_sample_interval(mut self, interval: std::time::Duration) -> Self {
+        self.sample_interval = interval.as_secs_f64();
+        self
+    }
+
+    /// Drops fixes below a GGA fix quality (1 GPS, 2 DGPS, 4 RTK fixed, ...)
+    pub fn with_min_fix_quality(mut self, min_fix_quality: u8) -> Self {
+        self.min_fix_quality = min_fix_quality;
+        self
+    }
+
+    /// Drops fixes using fewer satellites
+    pub fn with_min_satellites(mut self, min_satellites: u8) -> Self {
+        self.min_satellites = min_satellites;
+        self
+    }
+
+    /// Drops fixes with a higher (or unknown) horizontal dilution of precision
+    pub fn with_max_hdop(mut self, max_hdop: f64) -> Self {
+        self.max_hdop = Some(max_hdop);
+        self
+    }
+
+    /// Splits raw bytes from the stream into lines and ingests the sentences they complete
+    pub fn ingest_bytes(&mut self, sync_engine: &mut SyncEngine, bytes: &[u8]) -> Result<()> {
+        for &byte in bytes {
+            if byte == b'\n' || byte == b'\r' {
+                let line = std::mem::take(&mut self.line);
+                if let Some(sentence) = std::str::from_utf8(&line).ok().and_then(parse_sentence) {
+                    self.ingest(sync_engine, sentence)?;
+                }
+            } else if self.line.len() < MAX_SENTENCE_LEN {
+                self.line.push(byte);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ingest(&mut self, sync_engine: &mut SyncEngine, sentence: NmeaSentence) -> Result<()> {
+        match sentence {
+            NmeaSentence::Rmc(rmc) => {
+                self.last_rmc = Some(rmc);
+                Ok(())
+            }
+            NmeaSentence::Gga(gga) => match self.fix(gga) {
+                Some(fix) => self.record(sync_engine, fix),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Appends buffered track points and writes the stats so far onto the session
+    pub fn flush(&mut self, sync_engine: &mut SyncEngine) -> Result<()> {
+        self.recorder.flush(sync_engine)
+    }
+
+    /// Merges a GGA with the RMC of the same epoch; `None` if it fails the quality filters
+    fn fix(&self, gga: Gga) -> Option<GpsFix> {
+        if gga.fix_quality < self.min_fix_quality || gga.satellites < self.min_satellites {
+            return None;
+        }
+        if let Some(max_hdop) = self.max_hdop {
+            if gga.hdop.is_none_or(|hdop| hdop > max_hdop) {
+                return None;
+            }
+        }
+        let rmc = self
+            .last_rmc
+            .filter(|rmc| rmc.valid && (rmc.time - gga.time).abs() < 0.5);
+        Some(GpsFix {
+            time: gga.time,
+            latitude: gga.latitude,
+            longitude: gga.longitude,
+            altitude: gga.altitude.unwrap_or(0.0),
+            speed: rmc
+                .and_then(|rmc| rmc.speed_knots)
+                .map_or(0.0, |knots| knots * KNOTS_TO_M_S),
+            heading: rmc.and_then(|rmc| rmc.course).unwrap_or(0.0),
+            fix_quality: gga.fix_quality,
+            satellites: gga.satellites,
+            hdop: gga.hdop,
+        })
+    }
+
+    fn record(&mut self, sync_engine: &mut SyncEngine, fix: GpsFix) -> Result<()> {
+        if let Some(last) = self.last_sample {
+            // Receiver time wraps at midnight UTC
+            if (fix.time - last).rem_euclid(SECONDS_PER_DAY) < self.sample_interval {
+                return Ok(());
+            }
+        }
+        self.last_sample = Some(fix.time);
+
+        let point = (fix.longitude, fix.latitude);
+        self.recorder
+            .record(sync_engine, point, fix.altitude, fix.speed)?;
+        self.recorder.record_connectivity(
+            sync_engine,
+            point,
+            fix.altitude,
+            fix.heading,
+            None,
+            fix.hdop,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ConnectivityLocal, SessionLocal, Syncable};
+    use crate::sync::offline_test_engine;
+
+    fn sentence(body: &str) -> String {
+        let checksum = body.bytes().fold(0, |acc, b| acc ^ b);
+        format!("${}*{:02X}\r\n", body, checksum)
+    }
+
+    #[test]
+    fn test_nmea_ingest_samples_and_filters_fixes() -> Result<()> {
+        assert_eq!(
+            parse_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"),
+            Some(NmeaSentence::Gga(Gga {
+                time: 45319.0,
+                latitude: 48.0 + 7.038 / 60.0,
+                longitude: 11.0 + 31.0 / 60.0,
+                fix_quality: 1,
+                satellites: 8,
+                hdop: Some(0.9),
+                altitude: Some(545.4),
+            }))
+        );
+        assert_eq!(
+            parse_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48"),
+            None
+        );
+
+        let mut sync_engine = offline_test_engine()?;
+        let mut session = SessionLocal::default();
+        session.set_id_local("walk".to_string());
+        sync_engine.upsert_items(vec![session])?;
+
+        let mut stream = String::new();
+        stream += &sentence("GPRMC,235958,A,1900.000,N,15500.000,W,10.0,45.0,010125,,,A");
+        stream += &sentence("GPGGA,235958,1900.000,N,15500.000,W,1,08,0.9,100.0,M,,M,,");
+        // Within the sample interval
+        stream += &sentence("GPGGA,235959,1900.030,N,15500.000,W,1,08,0.9,500.0,M,,M,,");
+        // Past midnight, but no fix / too few satellites / HDOP too high
+        stream += &sentence("GPGGA,000001,1900.060,N,15500.000,W,0,08,0.9,500.0,M,,M,,");
+        stream += &sentence("GPGGA,000002,1900.060,N,15500.000,W,1,03,0.9,500.0,M,,M,,");
+        stream += &sentence("GPGGA,000003,1900.060,N,15500.000,W,1,08,9.9,500.0,M,,M,,");
+        stream += &sentence("GNGGA,000004,1900.060,N,15500.000,W,2,08,0.9,120.0,M,,M,,");
+
+        let mut ingest = NmeaIngest::new("walk", 7)
+            .with_sample_interval(std::time::Duration::from_secs(5))
+            .with_min_satellites(4)
+            .with_max_hdop(2.0);
+        let (first, rest) = stream.as_bytes().split_at(40);
+        ingest.ingest_bytes(&mut sync_engine, first)?;
+        ingest.ingest_bytes(&mut sync_engine, rest)?;
+        ingest.flush(&mut sync_engine)?;
+
+        let segments = sync_engine.get_session_track_segments("walk")?;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].points.len(), 2);
+
+        let session = sync_engine.get_item::<SessionLocal>("walk")?.unwrap();
+        assert_eq!((session.altitude_min, session.altitude_max), (100.0, 120.0));
+        assert!((session.velocity_max - 5.144).abs() < 0.001);
+        assert!((session.distance_total - 111.2).abs() < 0.5);
+
+        let mut connectivity = sync_engine.get_all_items::<ConnectivityLocal>()?;
+        connectivity.sort_by(|a, b| a.altitude.total_cmp(&b.altitude));
+        assert_eq!(connectivity.len(), 2);
+        assert_eq!(connectivity[0].heading, 45.0);
+        assert_eq!(connectivity[1].heading, 0.0);
+        assert_eq!(connectivity[1].device_id, Some(7));
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! No-sync zones. Events, connectivity, tags, session tracks and artifacts recorded inside
+//! a zone, e.g. a reserve whose locations must not leave the device, are never sent over
+//! the network or shared. They are held locally and only leave via `export_to_json`.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine) {
+//! use scout_rs::no_sync::NoSyncZone;
+//!
+//! let sync_engine = sync_engine.with_no_sync_zones(vec![NoSyncZone {
+//!     name: "rhino reserve".to_string(),
+//!     polygon: vec![(-156.0, 19.0), (-155.0, 19.0), (-155.0, 20.0), (-156.0, 20.0)],
+//! }]);
+//! assert!(sync_engine.is_in_no_sync_zone(Some("POINT(-155.5 19.5)")));
+//! # }
+//! ```
+
+use crate::geometry;
+
+/// Area whose events, connectivity, tags, session tracks and artifacts are never sent over
+/// the network or shared. Rows recorded inside are held locally and only leave via
+/// `export_to_json`; locations that cannot be parsed are held as if inside.
+#[derive(Debug, Clone)]
+pub struct NoSyncZone {
+    pub name: String,
+    /// Polygon ring as (longitude, latitude) vertices
+    pub polygon: Vec<(f64, f64)>,
+}
+
+impl NoSyncZone {
+    pub fn contains(&self, longitude: f64, latitude: f64) -> bool {
+        geometry::polygon_contains(&self.polygon, longitude, latitude)
+    }
+}
+
+/// The no-sync zones of a sync engine
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NoSyncZones(Vec<NoSyncZone>);
+
+impl NoSyncZones {
+    pub(crate) fn new(zones: Vec<NoSyncZone>) -> Self {
+        Self(zones)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns true if any coordinate of a WKT location falls inside a zone. A location
+    /// that cannot be parsed counts as inside, so it is never sent by mistake.
+    pub(crate) fn holds_location(&self, location: Option<&str>) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        match location.map(geometry::coordinates) {
+            Some(Ok(points)) => self.holds_points(&points),
+            Some(Err(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Returns true if any (longitude, latitude) point falls inside a zone
+    pub(crate) fn holds_points(&self, points: &[(f64, f64)]) -> bool {
+        points.iter().any(|&(longitude, latitude)| {
+            self.0.iter().any(|zone| zone.contains(longitude, latitude))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zones_hold_locations_with_any_vertex_inside() {
+        let reserve = NoSyncZone {
+            name: "reserve".to_string(),
+            polygon: vec![
+                (-156.0, 19.0),
+                (-155.0, 19.0),
+                (-155.0, 20.0),
+                (-156.0, 20.0),
+            ],
+        };
+        assert!(reserve.contains(-155.15393, 19.754824));
+        assert!(!reserve.contains(-154.5, 19.5));
+        assert_eq!(
+            geometry::parse_point("POINT(-155.15393 19.754824)"),
+            Some((-155.15393, 19.754824))
+        );
+
+        let zones = NoSyncZones::new(vec![reserve]);
+        // Any vertex inside holds a path; unparseable locations are held as if inside
+        assert!(zones.holds_location(Some("LINESTRING(-154 19.5, -155.5 19.5)")));
+        assert!(!zones.holds_location(Some("LINESTRING(-154 19.5, -153 19.5)")));
+        assert!(zones.holds_location(Some("SRID=4326;POINT(-155.5 19.5)")));
+        assert!(zones.holds_location(Some("POINT(-155.5, 19.5")));
+        assert!(zones.holds_location(Some("somewhere")));
+        assert!(!zones.holds_location(None));
+        assert!(!NoSyncZones::default().holds_location(Some("somewhere")));
+    }
+}
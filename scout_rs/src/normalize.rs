@@ -0,0 +1,300 @@
+//! Normalization of records passed to `ingest_items` before they are stored: sensor units
+//! are converted to meters and m/s, and coordinates rounded. Records implement
+//! [`Normalize`], which also exposes what the session rate limit, [`QualityChecks`] and the
+//! [`GeometryNormalizer`] check at ingest.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine) {
+//! use scout_rs::normalize::{IngestNormalization, LengthUnit, SpeedUnit};
+//!
+//! let sync_engine = sync_engine.with_ingest_normalization(
+//!     IngestNormalization::new()
+//!         .with_altitude_unit(LengthUnit::Feet)
+//!         .with_speed_unit(SpeedUnit::Knots)
+//!         .with_coordinate_decimals(4),
+//! );
+//! # }
+//! ```
+//!
+//! [`QualityChecks`]: crate::sync::QualityChecks
+//! [`GeometryNormalizer`]: crate::geometry::GeometryNormalizer
+
+use crate::models::{
+    ArtifactLocal, ConnectivityLocal, EventLocal, OperatorLocal, SessionLocal, TagLocal,
+};
+use crate::sync::QualitySample;
+
+/// Unit of lengths reported by a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthUnit {
+    #[default]
+    Meters,
+    Feet,
+}
+
+impl LengthUnit {
+    fn to_meters(self, value: f64) -> f64 {
+        match self {
+            LengthUnit::Meters => value,
+            LengthUnit::Feet => value * 0.3048,
+        }
+    }
+}
+
+/// Unit of speeds reported by a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedUnit {
+    #[default]
+    MetersPerSecond,
+    KilometersPerHour,
+    Knots,
+    MilesPerHour,
+}
+
+impl SpeedUnit {
+    fn to_meters_per_second(self, value: f64) -> f64 {
+        match self {
+            SpeedUnit::MetersPerSecond => value,
+            SpeedUnit::KilometersPerHour => value / 3.6,
+            SpeedUnit::Knots => value * 0.514_444,
+            SpeedUnit::MilesPerHour => value * 0.447_04,
+        }
+    }
+}
+
+/// Normalization applied to records passed to `ingest_items` before they are stored:
+/// altitudes and distances are converted to meters, speeds to m/s, and coordinates are
+/// rounded to a number of decimals (4 decimals is about 11 m) for privacy and size.
+/// Coordinates of tags and track points are rounded too.
+#[derive(Debug, Clone, Default)]
+pub struct IngestNormalization {
+    pub altitude_unit: LengthUnit,
+    pub distance_unit: LengthUnit,
+    pub speed_unit: SpeedUnit,
+    pub coordinate_decimals: Option<u32>,
+}
+
+impl IngestNormalization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_altitude_unit(mut self, unit: LengthUnit) -> Self {
+        self.altitude_unit = unit;
+        self
+    }
+
+    pub fn with_distance_unit(mut self, unit: LengthUnit) -> Self {
+        self.distance_unit = unit;
+        self
+    }
+
+    pub fn with_speed_unit(mut self, unit: SpeedUnit) -> Self {
+        self.speed_unit = unit;
+        self
+    }
+
+    pub fn with_coordinate_decimals(mut self, decimals: u32) -> Self {
+        self.coordinate_decimals = Some(decimals);
+        self
+    }
+
+    pub(crate) fn coordinate(&self, value: f64) -> f64 {
+        match self.coordinate_decimals {
+            Some(decimals) => round_to_decimals(value, decimals),
+            None => value,
+        }
+    }
+
+    /// Rounds every coordinate of a WKT geometry
+    fn location(&self, location: &mut Option<String>) {
+        if self.coordinate_decimals.is_none() {
+            return;
+        }
+        if let Some(wkt) = location.as_mut() {
+            let mut rounded = String::with_capacity(wkt.len());
+            let mut token = String::new();
+            for c in wkt.chars().map(Some).chain(std::iter::once(None)) {
+                if let Some(c) = c.filter(|c| !c.is_whitespace() && !"(),".contains(*c)) {
+                    token.push(c);
+                    continue;
+                }
+                match token.parse::<f64>() {
+                    Ok(value) => rounded.push_str(&self.coordinate(value).to_string()),
+                    Err(_) => rounded.push_str(&token),
+                }
+                token.clear();
+                rounded.extend(c);
+            }
+            *wkt = rounded;
+        }
+    }
+}
+
+pub(crate) fn round_to_decimals(value: f64, decimals: u32) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    (value * scale).round() / scale
+}
+
+/// Records normalized by [`IngestNormalization`] in `ingest_items`
+pub trait Normalize {
+    fn normalize(&mut self, normalization: &IngestNormalization);
+
+    /// Local ID of the session the record belongs to, so records of a session coalesced
+    /// by the session rate limit follow it into the session it was merged into
+    fn session_id_local_mut(&mut self) -> Option<&mut Option<String>> {
+        None
+    }
+
+    /// The record as a session, checked by the session rate limit; None for other records
+    fn session_mut(&mut self) -> Option<&mut SessionLocal> {
+        None
+    }
+
+    /// The record as checked by [`QualityChecks`](crate::sync::QualityChecks), None for
+    /// records that are not checked
+    fn quality_sample(&mut self) -> Option<QualitySample<'_>> {
+        None
+    }
+
+    /// WKT location checked by the
+    /// [`GeometryNormalizer`](crate::geometry::GeometryNormalizer), None for records
+    /// without one
+    fn location_mut(&mut self) -> Option<&mut Option<String>> {
+        None
+    }
+}
+
+impl Normalize for EventLocal {
+    fn normalize(&mut self, normalization: &IngestNormalization) {
+        normalization.location(&mut self.location);
+        self.altitude = normalization.altitude_unit.to_meters(self.altitude);
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.location)
+    }
+
+    fn session_id_local_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.ancestor_id_local)
+    }
+
+    fn quality_sample(&mut self) -> Option<QualitySample<'_>> {
+        Some(QualitySample {
+            device_id: Some(self.device_id),
+            timestamp: &self.timestamp_observation,
+            altitude: self.altitude,
+            hdop: self.hdop,
+            quality_flags: &mut self.quality_flags,
+        })
+    }
+}
+
+impl Normalize for ConnectivityLocal {
+    fn normalize(&mut self, normalization: &IngestNormalization) {
+        normalization.location(&mut self.location);
+        self.altitude = normalization.altitude_unit.to_meters(self.altitude);
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.location)
+    }
+
+    fn session_id_local_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.ancestor_id_local)
+    }
+
+    fn quality_sample(&mut self) -> Option<QualitySample<'_>> {
+        Some(QualitySample {
+            device_id: self.device_id,
+            timestamp: &self.timestamp_start,
+            altitude: self.altitude,
+            hdop: self.hdop,
+            quality_flags: &mut self.quality_flags,
+        })
+    }
+}
+
+impl Normalize for SessionLocal {
+    fn normalize(&mut self, normalization: &IngestNormalization) {
+        normalization.location(&mut self.locations);
+        let altitude = normalization.altitude_unit;
+        self.altitude_min = altitude.to_meters(self.altitude_min);
+        self.altitude_max = altitude.to_meters(self.altitude_max);
+        self.altitude_average = altitude.to_meters(self.altitude_average);
+        let speed = normalization.speed_unit;
+        self.velocity_min = speed.to_meters_per_second(self.velocity_min);
+        self.velocity_max = speed.to_meters_per_second(self.velocity_max);
+        self.velocity_average = speed.to_meters_per_second(self.velocity_average);
+        let distance = normalization.distance_unit;
+        self.distance_total = distance.to_meters(self.distance_total);
+        self.distance_max_from_start = distance.to_meters(self.distance_max_from_start);
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.locations)
+    }
+
+    fn session_mut(&mut self) -> Option<&mut SessionLocal> {
+        Some(self)
+    }
+}
+
+impl Normalize for OperatorLocal {
+    fn normalize(&mut self, _normalization: &IngestNormalization) {}
+
+    fn session_id_local_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.ancestor_id_local)
+    }
+}
+
+impl Normalize for ArtifactLocal {
+    fn normalize(&mut self, _normalization: &IngestNormalization) {}
+
+    fn session_id_local_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.ancestor_id_local)
+    }
+}
+
+impl Normalize for TagLocal {
+    fn normalize(&mut self, normalization: &IngestNormalization) {
+        normalization.location(&mut self.location);
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_are_converted_and_coordinates_rounded() {
+        let normalization = IngestNormalization::new()
+            .with_distance_unit(LengthUnit::Feet)
+            .with_speed_unit(SpeedUnit::Knots)
+            .with_coordinate_decimals(2);
+        let mut session = SessionLocal {
+            locations: Some("MULTIPOINT((-155.123 19.987), (1e-3 -0.006))".to_string()),
+            distance_total: 1000.0,
+            velocity_max: 10.0,
+            ..Default::default()
+        };
+        session.normalize(&normalization);
+
+        assert_eq!(
+            session.locations.as_deref(),
+            Some("MULTIPOINT((-155.12 19.99), (0 -0.01))")
+        );
+        assert!((session.distance_total - 304.8).abs() < 1e-9);
+        assert!((session.velocity_max - 5.14444).abs() < 1e-9);
+        // Units left at their defaults are kept as they are
+        assert_eq!(session.altitude_max, 0.0);
+
+        let mut location = Some("somewhere".to_string());
+        normalization.location(&mut location);
+        assert_eq!(location.as_deref(), Some("somewhere"));
+    }
+}
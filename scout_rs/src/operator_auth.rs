@@ -0,0 +1,99 @@
+//! Operator authentication. Operators identify with a PIN or badge; the token the server
+//! issues for it is cached with a hash of the credential (see [`credentials`]), so they can
+//! re-identify offline until the token expires. Operator actions written while an operator
+//! is signed in are stamped with their user ID.
+
+use crate::client::ScoutClient;
+use crate::credentials;
+use crate::models::{
+    v4::OperatorTokenLocalKey, OperatorCredentialType, OperatorLocal, OperatorTokenLocal,
+};
+use crate::store::{LocalStore, Store};
+use anyhow::{anyhow, Result};
+
+/// The operator signed in on a sync engine
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OperatorAuth {
+    active: Option<OperatorTokenLocal>,
+}
+
+impl OperatorAuth {
+    pub(crate) fn sign_in(&mut self, operator: OperatorTokenLocal) {
+        self.active = Some(operator);
+    }
+
+    pub(crate) fn sign_out(&mut self) {
+        self.active = None;
+    }
+
+    /// The signed in operator, if any and not expired
+    pub(crate) fn active(&self) -> Option<&OperatorTokenLocal> {
+        self.active
+            .as_ref()
+            .filter(|operator| !operator.is_expired(chrono::Utc::now()))
+    }
+
+    /// Stamps the signed in operator's user_id on operator records that have none
+    pub(crate) fn stamp(&self, operators: &mut [OperatorLocal]) -> Result<()> {
+        if operators
+            .iter()
+            .all(|operator| !operator.user_id.is_empty())
+        {
+            return Ok(());
+        }
+        let user_id = self
+            .active()
+            .map(|operator| operator.user_id.clone())
+            .ok_or_else(|| {
+                anyhow!("No authenticated operator; call authenticate_operator first")
+            })?;
+        for operator in operators.iter_mut().filter(|o| o.user_id.is_empty()) {
+            operator.user_id = user_id.clone();
+        }
+        Ok(())
+    }
+}
+
+/// The cached token of a credential. Only tokens sharing the credential's key ID are worth
+/// a PBKDF2 verification, so the others are never hashed against it.
+pub(crate) fn cached_token(
+    store: &Store,
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+) -> Result<Option<OperatorTokenLocal>> {
+    let key_id = credentials::key_id(credential, credential_type, device_key);
+    let candidates: Vec<OperatorTokenLocal> =
+        store.find(OperatorTokenLocalKey::key_id, key_id.clone())?;
+    Ok(candidates.into_iter().find(|token| {
+        credentials::verify(
+            &token.credential_hash,
+            credential,
+            credential_type,
+            device_key,
+        )
+    }))
+}
+
+/// Exchanges a credential with the server for a token to cache
+pub(crate) async fn request_token(
+    scout_client: &mut ScoutClient,
+    credential: &str,
+    credential_type: OperatorCredentialType,
+    device_key: &str,
+) -> Result<OperatorTokenLocal> {
+    let credential_hash = credentials::hash(credential, credential_type, device_key)?;
+    let response = scout_client
+        .authenticate_operator(credential, credential_type)
+        .await?;
+    let token = response
+        .data
+        .ok_or_else(|| anyhow!("Operator authentication returned no token"))?;
+    Ok(OperatorTokenLocal {
+        credential_hash,
+        key_id: credentials::key_id(credential, credential_type, device_key),
+        user_id: token.user_id,
+        token: token.token,
+        expires_at: token.expires_at,
+    })
+}
@@ -0,0 +1,318 @@
+use crate::models::{ConnectivityLocal, EventLocal, TagLocal};
+use anyhow::Result;
+use arrow2::array::{Array, BooleanArray, Float32Array, Float64Array, Int64Array, Utf8Array};
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::{DataType, Field, Schema};
+use arrow2::io::parquet::write::{
+    transverse, CompressionOptions, Encoding, FileWriter, RowGroupIterator, Version, WriteOptions,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+/// Tables that can be exported to Parquet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParquetTable {
+    Events,
+    Tags,
+    Connectivity,
+}
+
+/// Returns true if an RFC 3339 timestamp falls in the range (None = no filter).
+/// Rows with unparseable timestamps are excluded from filtered exports.
+pub(crate) fn in_time_range(timestamp: &str, time_range: Option<&Range<DateTime<Utc>>>) -> bool {
+    match time_range {
+        None => true,
+        Some(range) => DateTime::parse_from_rfc3339(timestamp)
+            .map(|t| range.contains(&t.with_timezone(&Utc)))
+            .unwrap_or(false),
+    }
+}
+
+/// Writes events to a Parquet file. Embeddings are omitted.
+pub(crate) fn write_events(path: &Path, events: &[EventLocal]) -> Result<()> {
+    let columns: Vec<(Field, Box<dyn Array>)> = vec![
+        int64_column("id", events.iter().map(|e| e.id)),
+        utf8_column("id_local", events.iter().map(|e| e.id_local.clone())),
+        int64_column("device_id", events.iter().map(|e| Some(e.device_id))),
+        int64_column("session_id", events.iter().map(|e| e.session_id)),
+        utf8_column(
+            "timestamp_observation",
+            events.iter().map(|e| Some(e.timestamp_observation.clone())),
+        ),
+        utf8_column("message", events.iter().map(|e| e.message.clone())),
+        utf8_column("media_url", events.iter().map(|e| e.media_url.clone())),
+        utf8_column(
+            "media_type",
+            events.iter().map(|e| enum_name(&e.media_type)),
+        ),
+        utf8_column("location", events.iter().map(|e| e.location.clone())),
+        float64_column("altitude", events.iter().map(|e| e.altitude)),
+        float64_column("heading", events.iter().map(|e| e.heading)),
+        (
+            Field::new("is_public", DataType::Boolean, false),
+            BooleanArray::from_slice(events.iter().map(|e| e.is_public).collect::<Vec<_>>())
+                .boxed(),
+        ),
+    ];
+    write_columns(path, columns)
+}
+
+/// Writes tags to a Parquet file with the observation time of their parent event
+pub(crate) fn write_tags(path: &Path, tags: &[(TagLocal, Option<String>)]) -> Result<()> {
+    let columns: Vec<(Field, Box<dyn Array>)> = vec![
+        int64_column("id", tags.iter().map(|(t, _)| t.id)),
+        utf8_column("id_local", tags.iter().map(|(t, _)| t.id_local.clone())),
+        int64_column("event_id", tags.iter().map(|(t, _)| Some(t.event_id))),
+        utf8_column(
+            "event_id_local",
+            tags.iter().map(|(t, _)| t.ancestor_id_local.clone()),
+        ),
+        utf8_column(
+            "timestamp_observation",
+            tags.iter().map(|(_, timestamp)| timestamp.clone()),
+        ),
+        utf8_column(
+            "class_name",
+            tags.iter().map(|(t, _)| Some(t.class_name.clone())),
+        ),
+        utf8_column(
+            "observation_type",
+            tags.iter().map(|(t, _)| enum_name(&t.observation_type)),
+        ),
+        float64_column("conf", tags.iter().map(|(t, _)| t.conf)),
+        float64_column("x", tags.iter().map(|(t, _)| t.x)),
+        float64_column("y", tags.iter().map(|(t, _)| t.y)),
+        float64_column("width", tags.iter().map(|(t, _)| t.width)),
+        float64_column("height", tags.iter().map(|(t, _)| t.height)),
+        utf8_column("location", tags.iter().map(|(t, _)| t.location.clone())),
+        utf8_column(
+            "detector_name",
+            tags.iter().map(|(t, _)| t.detector_name.clone()),
+        ),
+        utf8_column(
+            "detector_version",
+            tags.iter().map(|(t, _)| t.detector_version.clone()),
+        ),
+    ];
+    write_columns(path, columns)
+}
+
+/// Writes connectivity entries to a Parquet file
+pub(crate) fn write_connectivity(path: &Path, entries: &[ConnectivityLocal]) -> Result<()> {
+    let columns: Vec<(Field, Box<dyn Array>)> = vec![
+        int64_column("id", entries.iter().map(|c| c.id)),
+        utf8_column("id_local", entries.iter().map(|c| c.id_local.clone())),
+        int64_column("device_id", entries.iter().map(|c| c.device_id)),
+        int64_column("session_id", entries.iter().map(|c| c.session_id)),
+        utf8_column(
+            "timestamp_start",
+            entries.iter().map(|c| Some(c.timestamp_start.clone())),
+        ),
+        float64_column("signal", entries.iter().map(|c| c.signal.value())),
+        float64_column("noise", entries.iter().map(|c| c.noise.value())),
+        float64_column("altitude", entries.iter().map(|c| c.altitude)),
+        float64_column("heading", entries.iter().map(|c| c.heading)),
+        utf8_column("location", entries.iter().map(|c| c.location.clone())),
+        utf8_column(
+            "h14_index",
+            entries.iter().map(|c| Some(c.h14_index.to_string())),
+        ),
+        (
+            Field::new("battery_percentage", DataType::Float32, true),
+            Float32Array::from(
+                entries
+                    .iter()
+                    .map(|c| c.battery_percentage.map(f32::from))
+                    .collect::<Vec<_>>(),
+            )
+            .boxed(),
+        ),
+        utf8_column("mode", entries.iter().map(|c| c.mode.clone())),
+    ];
+    write_columns(path, columns)
+}
+
+fn int64_column(name: &str, values: impl Iterator<Item = Option<i64>>) -> (Field, Box<dyn Array>) {
+    (
+        Field::new(name, DataType::Int64, true),
+        Int64Array::from(values.collect::<Vec<_>>()).boxed(),
+    )
+}
+
+fn float64_column(name: &str, values: impl Iterator<Item = f64>) -> (Field, Box<dyn Array>) {
+    (
+        Field::new(name, DataType::Float64, false),
+        Float64Array::from_vec(values.collect()).boxed(),
+    )
+}
+
+fn utf8_column(
+    name: &str,
+    values: impl Iterator<Item = Option<String>>,
+) -> (Field, Box<dyn Array>) {
+    (
+        Field::new(name, DataType::Utf8, true),
+        Utf8Array::<i32>::from(values.collect::<Vec<_>>()).boxed(),
+    )
+}
+
+/// Serialized name of a lowercase serde enum (e.g. `MediaType::Image` -> "image")
+fn enum_name<T: Serialize>(value: &T) -> Option<String> {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+}
+
+fn write_columns(path: &Path, columns: Vec<(Field, Box<dyn Array>)>) -> Result<()> {
+    let (fields, arrays): (Vec<Field>, Vec<Box<dyn Array>>) = columns.into_iter().unzip();
+    let schema = Schema::from(fields);
+    let options = WriteOptions {
+        write_statistics: true,
+        compression: CompressionOptions::Snappy,
+        version: Version::V2,
+        data_pagesize_limit: None,
+    };
+    let encodings = schema
+        .fields
+        .iter()
+        .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+        .collect();
+    let chunks = vec![Chunk::try_new(arrays)];
+    let row_groups = RowGroupIterator::try_new(chunks.into_iter(), &schema, options, encodings)?;
+
+    let file = File::create(path)?;
+    let mut writer = FileWriter::try_new(file, schema, options)?;
+    for group in row_groups {
+        writer.write(group?)?;
+    }
+    writer.end(None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow2::io::parquet::read;
+
+    /// Values of a UTF-8 column of a Parquet file, in row order
+    fn read_utf8_column(path: &Path, name: &str) -> Result<Vec<Option<String>>> {
+        let mut file = File::open(path)?;
+        let metadata = read::read_metadata(&mut file)?;
+        let schema = read::infer_schema(&metadata)?;
+        let index = schema
+            .fields
+            .iter()
+            .position(|field| field.name == name)
+            .ok_or_else(|| anyhow::Error::msg(format!("No column {}", name)))?;
+        let mut values = Vec::new();
+        for chunk in read::FileReader::new(file, metadata.row_groups, schema, None, None, None) {
+            let chunk = chunk?;
+            let column = chunk.arrays()[index]
+                .as_any()
+                .downcast_ref::<Utf8Array<i32>>()
+                .ok_or_else(|| anyhow::Error::msg(format!("{} is not UTF-8", name)))?;
+            values.extend(column.iter().map(|value| value.map(str::to_string)));
+        }
+        Ok(values)
+    }
+
+    #[test]
+    fn test_in_time_range_excludes_end_and_unparseable_timestamps() {
+        let start = "2024-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let end = "2024-01-02T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let range = start..end;
+        assert!(in_time_range("2024-01-01T00:00:00Z", Some(&range)));
+        assert!(in_time_range("2024-01-01T23:00:00-01:00", None));
+        assert!(!in_time_range("2024-01-01T23:00:00-01:00", Some(&range)));
+        assert!(!in_time_range("2024-01-02T00:00:00Z", Some(&range)));
+        assert!(!in_time_range("not a time", Some(&range)));
+        assert!(in_time_range("not a time", None));
+    }
+
+    #[test]
+    fn test_written_events_read_back_in_order() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("events.parquet");
+        let event = |id_local: &str, message: Option<&str>| EventLocal {
+            id_local: Some(id_local.to_string()),
+            message: message.map(str::to_string),
+            media_type: crate::models::MediaType::Video,
+            ..Default::default()
+        };
+        write_events(
+            &path,
+            &[event("event_1", Some("elephant")), event("event_2", None)],
+        )?;
+
+        assert_eq!(
+            read_utf8_column(&path, "id_local")?,
+            vec![Some("event_1".to_string()), Some("event_2".to_string())]
+        );
+        assert_eq!(
+            read_utf8_column(&path, "message")?,
+            vec![Some("elephant".to_string()), None]
+        );
+        assert_eq!(
+            read_utf8_column(&path, "media_type")?,
+            vec![Some("video".to_string()), Some("video".to_string())]
+        );
+        // Embeddings are left out
+        assert!(read_utf8_column(&path, "embedding_qwen_vl_2b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_parquet_filters_rows_by_time_range() -> Result<()> {
+        use crate::sync::offline_test_engine;
+
+        let temp_dir = tempfile::tempdir()?;
+        let mut sync_engine = offline_test_engine()?;
+        let event = |id_local: &str, timestamp: &str| EventLocal {
+            id_local: Some(id_local.to_string()),
+            timestamp_observation: timestamp.to_string(),
+            ..Default::default()
+        };
+        let tag = |id_local: &str, event: &str| TagLocal {
+            id_local: Some(id_local.to_string()),
+            ancestor_id_local: Some(event.to_string()),
+            ..Default::default()
+        };
+        sync_engine.upsert_items(vec![
+            event("early", "2024-01-01T00:00:00Z"),
+            event("late", "2024-03-01T00:00:00Z"),
+        ])?;
+        sync_engine.upsert_items(vec![
+            tag("early_tag", "early"),
+            tag("late_tag", "late"),
+            tag("orphan_tag", "missing"),
+        ])?;
+
+        let start = "2024-02-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        let end = "2024-04-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        let events_path = temp_dir.path().join("events.parquet");
+        sync_engine.export_parquet(ParquetTable::Events, &events_path, Some(start..end))?;
+        assert_eq!(
+            read_utf8_column(&events_path, "id_local")?,
+            vec![Some("late".to_string())]
+        );
+
+        // Tags are filtered by the observation time of their event; tags without one only
+        // appear in unfiltered exports
+        let tags_path = temp_dir.path().join("tags.parquet");
+        sync_engine.export_parquet(ParquetTable::Tags, &tags_path, Some(start..end))?;
+        assert_eq!(
+            read_utf8_column(&tags_path, "id_local")?,
+            vec![Some("late_tag".to_string())]
+        );
+        assert_eq!(
+            read_utf8_column(&tags_path, "timestamp_observation")?,
+            vec![Some("2024-03-01T00:00:00Z".to_string())]
+        );
+        sync_engine.export_parquet(ParquetTable::Tags, &tags_path, None)?;
+        assert_eq!(read_utf8_column(&tags_path, "id_local")?.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Polling fallback for plan updates where realtime websockets are blocked. Changes come
+//! in the shape of realtime broadcasts (see `core/types/realtime.ts`): the changed record
+//! and whether it was inserted, updated or deleted. The interval adapts to activity,
+//! dropping to the minimum after a change and doubling up to the maximum while nothing
+//! changes. With the client's response cache enabled, unchanged polls cost a 304.
+//!
+//! ```no_run
+//! # async fn run(client: scout_rs::client::ScoutClient) -> anyhow::Result<()> {
+//! use scout_rs::poll::PlanPoller;
+//! use std::time::Duration;
+//!
+//! let mut poller = PlanPoller::new(client, 7)
+//!     .with_intervals(Duration::from_secs(5), Duration::from_secs(120));
+//! while let Some(change) = poller.next().await {
+//!     let change = change?;
+//!     println!("{:?} {}", change.operation, change.data.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ScoutClient;
+use crate::logging;
+use crate::models::Plan;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Kind of change to a record, as in realtime broadcasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A changed record; deleted records carry their last known state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub data: T,
+    pub operation: ChangeOperation,
+}
+
+/// Poll interval that adapts to activity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveInterval {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl AdaptiveInterval {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        let max = max.max(min);
+        Self {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Back to the minimum after a change, otherwise twice as long up to the maximum
+    pub fn record(&mut self, changed: bool) {
+        self.current = if changed {
+            self.min
+        } else {
+            (self.current * 2).min(self.max)
+        };
+    }
+}
+
+/// Polls a herd's plans and yields their changes, see the module docs
+pub struct PlanPoller {
+    client: ScoutClient,
+    herd_id: i64,
+    interval: AdaptiveInterval,
+    /// Plans by ID as of the last poll; None before the first
+    known: Option<HashMap<i64, Plan>>,
+    pending: VecDeque<Change<Plan>>,
+}
+
+impl PlanPoller {
+    /// Polls every 5 seconds while plans change, backing off to 2 minutes
+    pub fn new(client: ScoutClient, herd_id: i64) -> Self {
+        Self {
+            client,
+            herd_id,
+            interval: AdaptiveInterval::new(Duration::from_secs(5), Duration::from_secs(120)),
+            known: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn with_intervals(mut self, min: Duration, max: Duration) -> Self {
+        self.interval = AdaptiveInterval::new(min, max);
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval.current()
+    }
+
+    pub fn into_client(self) -> ScoutClient {
+        self.client
+    }
+
+    /// Returns the next change, waiting for it as long as it takes. The first poll only
+    /// records the current plans, as a realtime subscription would. Errors are returned
+    /// without losing the poller's state, so polling can continue after them.
+    pub async fn next(&mut self) -> Option<Result<Change<Plan>>> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Some(Ok(change));
+            }
+            if self.known.is_some() {
+                tokio::time::sleep(self.interval.current()).await;
+            }
+            if let Err(e) = self.poll().await {
+                logging::warn!("Failed to poll plans of herd {}: {}", self.herd_id, e);
+                self.interval.record(false);
+                return Some(Err(e));
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> Result<()> {
+        let plans = self
+            .client
+            .get_plans_by_herd(self.herd_id)
+            .await?
+            .data
+            .ok_or_else(|| anyhow!("No plans returned for herd {}", self.herd_id))?;
+        let current: HashMap<i64, Plan> = plans
+            .into_iter()
+            .filter_map(|plan| Some((plan.id?, plan)))
+            .collect();
+        if let Some(known) = &self.known {
+            let changes = diff_plans(known, &current);
+            self.interval.record(!changes.is_empty());
+            self.pending.extend(changes);
+        }
+        self.known = Some(current);
+        Ok(())
+    }
+}
+
+/// Changes between two snapshots of plans by ID, ordered by plan ID
+fn diff_plans(known: &HashMap<i64, Plan>, current: &HashMap<i64, Plan>) -> Vec<Change<Plan>> {
+    let mut changes: Vec<(i64, Change<Plan>)> = Vec::new();
+    for (id, plan) in current {
+        let operation = match known.get(id) {
+            None => ChangeOperation::Insert,
+            Some(previous) if previous != plan => ChangeOperation::Update,
+            Some(_) => continue,
+        };
+        changes.push((
+            *id,
+            Change {
+                data: plan.clone(),
+                operation,
+            },
+        ));
+    }
+    for (id, plan) in known {
+        if !current.contains_key(id) {
+            changes.push((
+                *id,
+                Change {
+                    data: plan.clone(),
+                    operation: ChangeOperation::Delete,
+                },
+            ));
+        }
+    }
+    changes.sort_by_key(|(id, _)| *id);
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_changes_and_adaptive_interval() {
+        let plan = |id: i64, name: &str| {
+            (
+                id,
+                Plan {
+                    id: Some(id),
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+        let known: HashMap<i64, Plan> = [plan(1, "patrol"), plan(2, "survey")].into();
+        let current: HashMap<i64, Plan> = [plan(2, "survey north"), plan(3, "return")].into();
+        let changes: Vec<(Option<i64>, ChangeOperation)> = diff_plans(&known, &current)
+            .into_iter()
+            .map(|change| (change.data.id, change.operation))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (Some(1), ChangeOperation::Delete),
+                (Some(2), ChangeOperation::Update),
+                (Some(3), ChangeOperation::Insert),
+            ]
+        );
+        assert!(diff_plans(&current, &current).is_empty());
+
+        let mut interval = AdaptiveInterval::new(Duration::from_secs(5), Duration::from_secs(30));
+        interval.record(false);
+        interval.record(false);
+        assert_eq!(interval.current(), Duration::from_secs(20));
+        interval.record(false);
+        assert_eq!(interval.current(), Duration::from_secs(30));
+        interval.record(true);
+        assert_eq!(interval.current(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,170 @@
+//! Read-only mirrors of a device's local data, e.g. on a biologist's analysis laptop. A
+//! [`ReadOnlySyncEngine`] fills its store from a device's mirror stream (see
+//! [`SyncEngine::export_mirror`]) or by pulling a herd from the server, and offers the
+//! sync engine's queries but none of its writes, so the mirror cannot drift from its
+//! source through local edits. Nothing is ever flushed from it.
+//!
+//! ```no_run
+//! # fn run(device: scout_rs::sync::SyncEngine, client: scout_rs::client::ScoutClient) -> anyhow::Result<()> {
+//! use scout_rs::models::SessionLocal;
+//! use scout_rs::replica::ReadOnlySyncEngine;
+//! use std::io::BufReader;
+//!
+//! // On the device
+//! device.export_mirror(std::fs::File::create("device-7.mirror")?)?;
+//!
+//! // On the laptop
+//! let mut mirror = ReadOnlySyncEngine::new(client, "device-7.db")?;
+//! mirror.import_mirror(BufReader::new(std::fs::File::open("device-7.mirror")?))?;
+//! println!("{} sessions", mirror.get_table_count::<SessionLocal>()?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ScoutClient;
+use crate::models::{EventLocal, PullCheckpointLocal, RemoteIdIndexed, SessionNoteLocal, Syncable};
+use crate::store::StoredModel;
+use crate::sync::{PullReport, SyncEngine, SyncEngineConfig, SyncEngineOpenError};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// A sync engine that can only pull and query, see the module docs
+pub struct ReadOnlySyncEngine {
+    sync_engine: SyncEngine,
+}
+
+impl ReadOnlySyncEngine {
+    pub fn new(scout_client: ScoutClient, db_local_path: impl Into<PathBuf>) -> Result<Self> {
+        Ok(Self {
+            sync_engine: SyncEngine::new(scout_client, db_local_path, None, false)?,
+        })
+    }
+
+    /// Opens the mirror without blocking the async runtime, see [`SyncEngine::open`]
+    pub async fn open(config: SyncEngineConfig) -> Result<Self, SyncEngineOpenError> {
+        Ok(Self {
+            sync_engine: SyncEngine::open(config).await?,
+        })
+    }
+
+    /// Imports a device's mirror stream, replacing rows already mirrored. Returns the
+    /// number of rows imported.
+    pub fn import_mirror(&mut self, reader: impl std::io::BufRead) -> Result<u64> {
+        self.sync_engine.import_mirror(reader)
+    }
+
+    /// Pulls a herd from the server, see [`SyncEngine::pull_herd`]
+    pub async fn pull_herd(
+        &mut self,
+        herd_id: i64,
+        on_progress: Option<&(dyn Fn(&PullCheckpointLocal) + Send + Sync)>,
+        cancellation_check: Option<&(dyn Fn() -> bool + Send + Sync)>,
+    ) -> Result<PullReport> {
+        self.sync_engine
+            .pull_herd(herd_id, on_progress, cancellation_check)
+            .await
+    }
+
+    /// See [`SyncEngine::pull_checkpoints`]
+    pub fn pull_checkpoints(&self, herd_id: i64) -> Result<Vec<PullCheckpointLocal>> {
+        self.sync_engine.pull_checkpoints(herd_id)
+    }
+
+    /// See [`SyncEngine::get_item`]
+    pub fn get_item<T: StoredModel + Syncable>(&self, local_id: &str) -> Result<Option<T>> {
+        self.sync_engine.get_item(local_id)
+    }
+
+    /// See [`SyncEngine::get_by_remote_id`]
+    pub fn get_by_remote_id<T: StoredModel + Syncable + RemoteIdIndexed>(
+        &self,
+        remote_id: i64,
+    ) -> Result<Option<T>> {
+        self.sync_engine.get_by_remote_id(remote_id)
+    }
+
+    /// See [`SyncEngine::get_all`]
+    pub fn get_all<T: StoredModel>(&self) -> Result<Vec<T>> {
+        self.sync_engine.get_all()
+    }
+
+    /// See [`SyncEngine::get_table_count`]
+    pub fn get_table_count<T: StoredModel>(&self) -> Result<u64> {
+        self.sync_engine.get_table_count::<T>()
+    }
+
+    /// See [`SyncEngine::get_events_for_session`]
+    pub fn get_events_for_session(&self, session_id_local: &str) -> Result<Vec<EventLocal>> {
+        self.sync_engine.get_events_for_session(session_id_local)
+    }
+
+    /// See [`SyncEngine::get_event_chain`]
+    pub fn get_event_chain(&self, event_id_local: &str) -> Result<Vec<EventLocal>> {
+        self.sync_engine.get_event_chain(event_id_local)
+    }
+
+    /// See [`SyncEngine::get_session_notes`]
+    pub fn get_session_notes(&self, session_id_local: &str) -> Result<Vec<SessionNoteLocal>> {
+        self.sync_engine.get_session_notes(session_id_local)
+    }
+
+    /// See [`SyncEngine::export_to_json`]
+    pub fn export_to_json(&self, output_path: impl AsRef<Path>) -> Result<()> {
+        self.sync_engine.export_to_json(output_path)
+    }
+
+    /// Passes the mirror on, see [`SyncEngine::export_mirror`]
+    pub fn export_mirror(&self, writer: impl std::io::Write) -> Result<u64> {
+        self.sync_engine.export_mirror(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_client::offline_test_config;
+    use crate::models::{SessionLocal, SyncBudgetLocal, TagLocal};
+    use crate::sync::offline_test_engine;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_mirror_stream_round_trip_into_read_only_engine() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut device = offline_test_engine()?;
+        let mut session = SessionLocal::default();
+        session.set_id_local("survey".to_string());
+        let event = EventLocal {
+            id_local: Some("sighting".to_string()),
+            ancestor_id_local: Some("survey".to_string()),
+            ..Default::default()
+        };
+        let tag = TagLocal {
+            id_local: Some("elk".to_string()),
+            ancestor_id_local: Some("sighting".to_string()),
+            ..Default::default()
+        };
+        device.upsert_items(vec![session.clone()])?;
+        device.upsert_items(vec![event])?;
+        device.upsert_items(vec![tag])?;
+        device.upsert_items(vec![SyncBudgetLocal::new("2024-05".to_string())])?;
+
+        let mut stream = Vec::new();
+        assert_eq!(device.export_mirror(&mut stream)?, 3);
+
+        let mut mirror = ReadOnlySyncEngine::new(
+            ScoutClient::new(offline_test_config()),
+            temp_dir.path().join("mirror.db"),
+        )?;
+        assert_eq!(mirror.import_mirror(stream.as_slice())?, 3);
+        assert_eq!(mirror.get_item::<SessionLocal>("survey")?, Some(session));
+        assert_eq!(mirror.get_event_chain("sighting")?.len(), 1);
+        assert_eq!(mirror.get_all::<TagLocal>()?.len(), 1);
+        // Bookkeeping of the device is not mirrored
+        assert_eq!(mirror.get_table_count::<SyncBudgetLocal>()?, 0);
+
+        assert!(mirror
+            .import_mirror(&b"{\"format\":\"csv\",\"version\":1,\"exported_at\":\"\"}\n"[..])
+            .is_err());
+        Ok(())
+    }
+}
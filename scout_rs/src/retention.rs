@@ -0,0 +1,274 @@
+//! Rotation of auxiliary files the crate leaves next to the database, e.g. corrupt
+//! database copies moved aside on open. Files are kept in named groups (a directory and a
+//! file name prefix), and one [`AuxiliaryRetention`] policy bounds every group by age,
+//! number of files and total size, removing the oldest files first. What was removed is
+//! counted, so devices can report the space reclaimed.
+//!
+//! ```no_run
+//! # async fn run(sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::retention::AuxiliaryRetention;
+//! use std::time::Duration;
+//!
+//! let policy = AuxiliaryRetention::new()
+//!     .with_max_age(Duration::from_secs(30 * 24 * 3600))
+//!     .with_max_files(3)
+//!     .with_max_bytes(256 * 1024 * 1024);
+//! let mut sync_engine = sync_engine.with_auxiliary_retention(policy);
+//! let report = sync_engine.enforce_auxiliary_retention()?;
+//! println!("reclaimed {} bytes", report.bytes_reclaimed);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Limits applied to each group of auxiliary files (None = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AuxiliaryRetention {
+    /// Total size of a group's files
+    pub max_bytes: Option<u64>,
+    /// Age of a file, by its modification time
+    pub max_age: Option<Duration>,
+    /// Number of files kept per group, newest first
+    pub max_files: Option<usize>,
+}
+
+impl AuxiliaryRetention {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+}
+
+/// Files removed by rotation, in one run or since the manager was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    pub files_removed: u64,
+    pub bytes_reclaimed: u64,
+}
+
+impl std::ops::AddAssign for RetentionReport {
+    fn add_assign(&mut self, other: Self) {
+        self.files_removed += other.files_removed;
+        self.bytes_reclaimed += other.bytes_reclaimed;
+    }
+}
+
+/// Auxiliary files of one kind: the files in `dir` whose names start with `prefix`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AuxiliaryGroup {
+    name: String,
+    dir: PathBuf,
+    prefix: String,
+}
+
+/// Applies one policy to groups of auxiliary files, see the module docs
+#[derive(Debug, Clone)]
+pub struct RetentionManager {
+    policy: AuxiliaryRetention,
+    groups: Vec<AuxiliaryGroup>,
+    reclaimed: RetentionReport,
+}
+
+impl RetentionManager {
+    pub fn new(policy: AuxiliaryRetention) -> Self {
+        Self {
+            policy,
+            groups: Vec::new(),
+            reclaimed: RetentionReport::default(),
+        }
+    }
+
+    /// Adds the files in `dir` whose names start with `prefix` as a group named `name`
+    pub fn with_group(
+        mut self,
+        name: impl Into<String>,
+        dir: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+    ) -> Self {
+        self.groups.push(AuxiliaryGroup {
+            name: name.into(),
+            dir: dir.into(),
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    pub fn policy(&self) -> AuxiliaryRetention {
+        self.policy
+    }
+
+    /// Everything removed since the manager was created
+    pub fn reclaimed(&self) -> RetentionReport {
+        self.reclaimed
+    }
+
+    /// Removes the files of every group that exceed the policy. A file that cannot be
+    /// removed is logged and skipped; it is retried on the next run.
+    pub fn enforce(&mut self) -> Result<RetentionReport> {
+        self.enforce_at(SystemTime::now())
+    }
+
+    fn enforce_at(&mut self, now: SystemTime) -> Result<RetentionReport> {
+        let mut report = RetentionReport::default();
+        for group in &self.groups {
+            let mut files = group_files(group)?;
+            files.sort_by_key(|(_, modified, _)| std::cmp::Reverse(*modified));
+            let mut kept_bytes = 0u64;
+            let mut kept_files = 0usize;
+            let mut group_report = RetentionReport::default();
+            for (path, modified, size) in files {
+                let too_old = self.policy.max_age.is_some_and(|max_age| {
+                    now.duration_since(modified).unwrap_or_default() > max_age
+                });
+                let too_many = self.policy.max_files.is_some_and(|max| kept_files >= max);
+                let too_big = self
+                    .policy
+                    .max_bytes
+                    .is_some_and(|max| kept_bytes + size > max);
+                if !(too_old || too_many || too_big) {
+                    kept_bytes += size;
+                    kept_files += 1;
+                    continue;
+                }
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {
+                        group_report.files_removed += 1;
+                        group_report.bytes_reclaimed += size;
+                    }
+                    Err(e) => {
+                        logging::warn!("Failed to remove {}: {}", path.display(), e);
+                        kept_bytes += size;
+                        kept_files += 1;
+                    }
+                }
+            }
+            if group_report.files_removed > 0 {
+                logging::info!(
+                    "Removed {} {} files, reclaiming {} bytes",
+                    group_report.files_removed,
+                    group.name,
+                    group_report.bytes_reclaimed
+                );
+            }
+            report += group_report;
+        }
+        self.reclaimed += report;
+        Ok(report)
+    }
+}
+
+/// Path, modification time and size of a group's files; a missing directory has none
+fn group_files(group: &AuxiliaryGroup) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let entries = match std::fs::read_dir(&group.dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let matches = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(&group.prefix));
+        if matches && metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((entry.path(), modified, metadata.len()));
+        }
+    }
+    Ok(files)
+}
+
+/// Directory and file name prefix of files named `<path><suffix>...`
+pub(crate) fn sibling_prefix(path: &Path, suffix: &str) -> (PathBuf, String) {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (dir, format!("{}{}", name, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rotation_by_age_count_and_size() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let day = Duration::from_secs(24 * 3600);
+        let now = SystemTime::now();
+        // Written oldest first, one day apart
+        for (i, size) in [10usize, 20, 30, 40, 50].into_iter().enumerate() {
+            let path = temp_dir.path().join(format!("scout.db.corrupt-{}", i));
+            std::fs::write(&path, vec![0u8; size])?;
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(now - day * (5 - i as u32))?;
+        }
+        std::fs::write(temp_dir.path().join("scout.db"), b"kept")?;
+
+        let (dir, prefix) = sibling_prefix(&temp_dir.path().join("scout.db"), ".corrupt-");
+        let mut manager = RetentionManager::new(
+            AuxiliaryRetention::new().with_max_age(day * 9 / 2),
+        )
+        .with_group("corrupt database", dir, prefix);
+        assert_eq!(
+            manager.enforce_at(now)?,
+            RetentionReport {
+                files_removed: 1,
+                bytes_reclaimed: 10
+            }
+        );
+
+        manager.policy = manager.policy.with_max_bytes(80);
+        // Newest first: 50 and 30 fit in 80 bytes, 40 and 20 do not
+        assert_eq!(
+            manager.enforce_at(now)?,
+            RetentionReport {
+                files_removed: 2,
+                bytes_reclaimed: 60
+            }
+        );
+        manager.policy = manager.policy.with_max_files(1);
+        manager.enforce_at(now)?;
+        assert_eq!(
+            manager.reclaimed(),
+            RetentionReport {
+                files_removed: 4,
+                bytes_reclaimed: 100
+            }
+        );
+        let mut left: Vec<String> = std::fs::read_dir(temp_dir.path())?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        left.sort();
+        assert_eq!(left, vec!["scout.db", "scout.db.corrupt-4"]);
+        Ok(())
+    }
+}
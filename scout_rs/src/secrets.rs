@@ -0,0 +1,341 @@
+//! Where API keys and tokens are kept. [`DatabaseConfig::from_secrets`] reads the keys
+//! through a [`SecretProvider`] and [`ScoutClient::rotate_api_key`] stores the rotated
+//! device key through one, so devices that must not keep keys in the environment (where
+//! they show up in `/proc/<pid>/environ` and crash dumps) can use a file, the keyring or
+//! the TPM instead.
+//!
+//! ```no_run
+//! use scout_rs::db_client::DatabaseConfig;
+//! use scout_rs::secrets::CommandSecretProvider;
+//!
+//! // Keys sealed to this device's TPM by `systemd-creds`
+//! let secrets = CommandSecretProvider::systemd_creds("/etc/scout/credentials");
+//! let config_db = DatabaseConfig::from_secrets("https://example.supabase.co", &secrets)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! [`DatabaseConfig::from_secrets`]: crate::db_client::DatabaseConfig::from_secrets
+//! [`ScoutClient::rotate_api_key`]: crate::client::ScoutClient::rotate_api_key
+
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Name of the device API key
+pub const SCOUT_API_KEY: &str = "SCOUT_DEVICE_API_KEY";
+/// Name of the public Supabase API key
+pub const SUPABASE_API_KEY: &str = "SUPABASE_PUBLIC_API_KEY";
+
+/// Reads and replaces named secrets
+pub trait SecretProvider: Send + Sync {
+    /// The secret, None if it is not stored
+    fn get(&self, name: &str) -> Result<Option<String>>;
+
+    /// Replaces the secret. A failure must leave the previous value in place.
+    fn set(&self, name: &str, value: &str) -> Result<()>;
+
+    /// The secret, failing if it is not stored
+    fn require(&self, name: &str) -> Result<String> {
+        self.get(name)?
+            .ok_or_else(|| anyhow!("Secret {} is not stored", name))
+    }
+}
+
+/// Environment variables, after loading `.env`. Read-only: a key set in the environment
+/// would not survive a restart, so rotation with this provider rolls back.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        dotenv::dotenv().ok();
+        Ok(std::env::var(name).ok())
+    }
+
+    fn set(&self, name: &str, _value: &str) -> Result<()> {
+        Err(anyhow!(
+            "Cannot store {}: environment secrets are read-only",
+            name
+        ))
+    }
+}
+
+/// One file per secret in a directory, written with mode 0600 and replaced atomically so
+/// a crash never leaves a partial key. Files other users can read are refused on unix.
+#[derive(Debug, Clone)]
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// File of a secret. Names are single path components, so no secret is read or written
+    /// outside the directory.
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains("..") || name.chars().any(std::path::is_separator) {
+            return Err(anyhow!("Invalid secret name {:?}", name));
+        }
+        Ok(self.dir.join(name))
+    }
+
+    /// File a secret is written to before it replaces the secret's file. Hidden and named
+    /// after the whole name, so secrets whose names differ by extension never share one.
+    fn tmp_path(&self, name: &str) -> Result<PathBuf> {
+        self.path(name)?;
+        Ok(self.dir.join(format!(".{}.tmp", name)))
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            if mode & 0o077 != 0 {
+                return Err(anyhow!(
+                    "Secret file {} is accessible by other users (mode {:o}), expected 0600",
+                    path.display(),
+                    mode & 0o777
+                ));
+            }
+        }
+        Ok(Some(std::fs::read_to_string(&path)?.trim().to_string()))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        let path = self.path(name)?;
+        let tmp_path = self.tmp_path(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        // A temporary file left by an interrupted write may have any mode, and the mode
+        // below only applies to new files
+        match std::fs::remove_file(&tmp_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        // The key is never readable by others, not even before the rename
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(value.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp_path, &path)?;
+        // The rename is only durable once the directory entry is
+        #[cfg(unix)]
+        std::fs::File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Secrets read and stored by external commands, e.g. a keyring or TPM tool, so the crate
+/// needs no platform libraries. `{name}` in an argument is replaced by the secret's name;
+/// the get command prints the secret and the set command reads it from stdin.
+#[derive(Debug, Clone)]
+pub struct CommandSecretProvider {
+    get: Vec<String>,
+    set: Option<Vec<String>>,
+}
+
+impl CommandSecretProvider {
+    /// Reads secrets with `get`, a program and its arguments. Read-only until
+    /// [`Self::with_set`].
+    pub fn new<S: Into<String>>(get: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            get: get.into_iter().map(Into::into).collect(),
+            set: None,
+        }
+    }
+
+    /// Stores secrets with `set`, which reads the value from stdin
+    pub fn with_set<S: Into<String>>(mut self, set: impl IntoIterator<Item = S>) -> Self {
+        self.set = Some(set.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The desktop or headless keyring through libsecret's `secret-tool`, with secrets
+    /// stored under the attributes `service` and `name`
+    pub fn secret_tool(service: &str) -> Self {
+        Self::new([
+            "secret-tool",
+            "lookup",
+            "service",
+            service,
+            "name",
+            "{name}",
+        ])
+        .with_set([
+            "secret-tool",
+            "store",
+            "--label=scout {name}",
+            "service",
+            service,
+            "name",
+            "{name}",
+        ])
+    }
+
+    /// Credentials encrypted with the TPM by `systemd-creds`, one `{name}.cred` file per
+    /// secret in `dir`, so the keys only decrypt on this device
+    pub fn systemd_creds(dir: impl Into<PathBuf>) -> Self {
+        let credential = dir.into().join("{name}.cred").display().to_string();
+        Self::new([
+            "systemd-creds",
+            "decrypt",
+            "--name={name}",
+            &credential,
+            "-",
+        ])
+        .with_set([
+            "systemd-creds",
+            "encrypt",
+            "--with-key=tpm2",
+            "--name={name}",
+            "-",
+            &credential,
+        ])
+    }
+
+    fn command(args: &[String], name: &str) -> Result<Command> {
+        let (program, args) = args
+            .split_first()
+            .ok_or_else(|| anyhow!("Secret command is empty"))?;
+        let mut command = Command::new(program.replace("{name}", name));
+        command.args(args.iter().map(|arg| arg.replace("{name}", name)));
+        Ok(command)
+    }
+}
+
+impl SecretProvider for CommandSecretProvider {
+    /// None if the command fails without printing an error, as lookups of missing
+    /// secrets do
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let output = Self::command(&self.get, name)?
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| anyhow!("Failed to run {}: {}", self.get[0], e))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            if stderr.trim().is_empty() {
+                return Ok(None);
+            }
+            return Err(anyhow!("Failed to read secret {}: {}", name, stderr.trim()));
+        }
+        let value = String::from_utf8(output.stdout)?.trim().to_string();
+        Ok((!value.is_empty()).then_some(value))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        let set = self
+            .set
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cannot store {}: no set command configured", name))?;
+        let mut child = Self::command(set, name)?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run {}: {}", set[0], e))?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open stdin of {}", set[0]))?
+            .write_all(value.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to store secret {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_and_command_providers_round_trip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let files = FileSecretProvider::new(temp_dir.path().join("secrets"));
+        assert_eq!(files.get(SCOUT_API_KEY)?, None);
+        files.set(SCOUT_API_KEY, "key-1")?;
+        files.set(SCOUT_API_KEY, "key-2")?;
+        assert_eq!(files.require(SCOUT_API_KEY)?, "key-2");
+        assert_eq!(
+            std::fs::read_dir(temp_dir.path().join("secrets"))?.count(),
+            1
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = temp_dir.path().join("secrets").join(SCOUT_API_KEY);
+            assert_eq!(
+                std::fs::metadata(&path)?.permissions().mode() & 0o777,
+                0o600
+            );
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+            assert!(files.get(SCOUT_API_KEY).is_err());
+
+            // A stale temporary file readable by others is replaced, not reused
+            let tmp_path = temp_dir
+                .path()
+                .join("secrets")
+                .join(format!(".{}.tmp", SCOUT_API_KEY));
+            std::fs::write(&tmp_path, "stale")?;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o644))?;
+            files.set(SCOUT_API_KEY, "key-3")?;
+            assert_eq!(files.require(SCOUT_API_KEY)?, "key-3");
+            assert!(!tmp_path.exists());
+
+            let store = temp_dir.path().join("store");
+            let store = store.display();
+            let commands = CommandSecretProvider::new([
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("cat {}/{{name}} 2>/dev/null", store),
+            ])
+            .with_set([
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("mkdir -p {0} && cat > {0}/{{name}}", store),
+            ]);
+            assert_eq!(commands.get("token")?, None);
+            commands.set("token", "secret")?;
+            assert_eq!(commands.get("token")?, Some("secret".to_string()));
+        }
+
+        // Names are kept inside the directory
+        for name in ["", "..", "../outside", "nested/key", "a..b"] {
+            assert!(files.get(name).is_err(), "{:?}", name);
+            assert!(files.set(name, "key").is_err(), "{:?}", name);
+        }
+        assert!(!temp_dir.path().join("outside").exists());
+
+        // Names that differ only by extension are kept apart
+        files.set("key.a", "a")?;
+        files.set("key.b", "b")?;
+        assert_eq!(files.require("key.a")?, "a");
+        assert_eq!(files.require("key.b")?, "b");
+
+        assert!(EnvSecretProvider.set(SCOUT_API_KEY, "key").is_err());
+        Ok(())
+    }
+}
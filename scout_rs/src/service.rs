@@ -0,0 +1,175 @@
+//! REST front end for running the sync engine as a sidecar next to non-Rust processes.
+//! Records are ingested as the local models' JSON and written to the local store; they
+//! reach the server on the next flush.
+//!
+//! | Method | Path            | Body                       |
+//! |--------|-----------------|----------------------------|
+//! | POST   | `/events`       | `[EventLocal]`             |
+//! | POST   | `/tags`         | `[TagLocal]`               |
+//! | POST   | `/connectivity` | `[ConnectivityLocal]`      |
+//! | GET    | `/status`       | local counts, flush state  |
+//! | POST   | `/flush`        | triggers a flush           |
+
+use crate::models::{ConnectivityLocal, EventLocal, SessionLocal, TagLocal};
+use crate::sync::SyncEngine;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Sync engine shared between the service and the rest of the process
+pub type SharedSyncEngine = Arc<Mutex<SyncEngine>>;
+
+/// Router with the service endpoints, backed by a shared sync engine
+pub fn router(sync_engine: SharedSyncEngine) -> Router {
+    Router::new()
+        .route("/events", post(ingest_events))
+        .route("/tags", post(ingest_tags))
+        .route("/connectivity", post(ingest_connectivity))
+        .route("/status", get(status))
+        .route("/flush", post(flush))
+        .with_state(sync_engine)
+}
+
+/// Serves the router on `address` until the process exits
+pub async fn serve(sync_engine: SharedSyncEngine, address: &str) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    crate::logging::info!("Sync service listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(sync_engine)).await?;
+    Ok(())
+}
+
+/// Engine errors, returned as `500 {"error": ...}`
+struct ServiceError(anyhow::Error);
+
+impl From<anyhow::Error> for ServiceError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for ServiceError {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": self.0.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+async fn ingest_events(
+    State(sync_engine): State<SharedSyncEngine>,
+    Json(events): Json<Vec<EventLocal>>,
+) -> Result<Json<Value>, ServiceError> {
+    let count = events.len();
+    sync_engine.lock().await.ingest_items(events)?;
+    Ok(Json(json!({ "count": count })))
+}
+
+async fn ingest_tags(
+    State(sync_engine): State<SharedSyncEngine>,
+    Json(tags): Json<Vec<TagLocal>>,
+) -> Result<Json<Value>, ServiceError> {
+    let count = tags.len();
+    let suppressed = sync_engine.lock().await.upsert_tags(tags)?;
+    Ok(Json(json!({ "count": count, "suppressed": suppressed })))
+}
+
+async fn ingest_connectivity(
+    State(sync_engine): State<SharedSyncEngine>,
+    Json(connectivity): Json<Vec<ConnectivityLocal>>,
+) -> Result<Json<Value>, ServiceError> {
+    let count = connectivity.len();
+    sync_engine.lock().await.ingest_items(connectivity)?;
+    Ok(Json(json!({ "count": count })))
+}
+
+async fn status(State(sync_engine): State<SharedSyncEngine>) -> Result<Json<Value>, ServiceError> {
+    let sync_engine = sync_engine.lock().await;
+    let budget = sync_engine.get_remaining_budget()?;
+    Ok(Json(json!({
+        "sessions": sync_engine.get_table_count::<SessionLocal>()?,
+        "events": sync_engine.get_table_count::<EventLocal>()?,
+        "tags": sync_engine.get_table_count::<TagLocal>()?,
+        "connectivity": sync_engine.get_table_count::<ConnectivityLocal>()?,
+        "artifacts_pending_upload": sync_engine.get_artifacts_pending_upload_count()?,
+        "flush_resume_stage": sync_engine
+            .get_flush_resume_stage()
+            .map(|stage| format!("{:?}", stage)),
+        "unsupported_flush_stages": sync_engine
+            .unsupported_flush_stages()
+            .iter()
+            .map(|stage| format!("{:?}", stage))
+            .collect::<Vec<_>>(),
+        "budget": {
+            "month": budget.month,
+            "rows_sent": budget.rows_sent,
+            "bytes_sent": budget.bytes_sent,
+            "rows_remaining": budget.rows_remaining,
+            "bytes_remaining": budget.bytes_remaining,
+        },
+    })))
+}
+
+async fn flush(State(sync_engine): State<SharedSyncEngine>) -> Result<Json<Value>, ServiceError> {
+    sync_engine.lock().await.flush().await?;
+    Ok(Json(json!({ "flushed": true })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::offline_test_engine;
+
+    #[tokio::test]
+    async fn test_service_ingests_and_reports_status() -> anyhow::Result<()> {
+        let sync_engine = offline_test_engine()?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let sync_engine = Arc::new(Mutex::new(sync_engine));
+        let app = router(sync_engine.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let http = reqwest::Client::new();
+        let connectivity = ConnectivityLocal {
+            id_local: Some("c1".to_string()),
+            ..Default::default()
+        };
+        let response = http
+            .post(format!("{}/connectivity", url))
+            .json(&vec![connectivity])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json::<Value>().await?["count"], 1);
+
+        let response = http
+            .post(format!("{}/events", url))
+            .body("[{}]")
+            .send()
+            .await?;
+        assert!(response.status().is_client_error());
+
+        let status: Value = http
+            .get(format!("{}/status", url))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(status["connectivity"], 1);
+        assert_eq!(status["events"], 0);
+        assert_eq!(
+            sync_engine
+                .lock()
+                .await
+                .get_table_count::<ConnectivityLocal>()?,
+            1
+        );
+        Ok(())
+    }
+}
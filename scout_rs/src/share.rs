@@ -0,0 +1,245 @@
+//! Share bundles: events and their tags selected for a partner herd and anonymized before
+//! they leave the device. Bundles are written to a JSON file or pushed to the partner's
+//! herd through its own client.
+//!
+//! ```no_run
+//! # async fn run(
+//! #     sync_engine: scout_rs::sync::SyncEngine,
+//! #     mut partner: scout_rs::client::ScoutClient,
+//! # ) -> anyhow::Result<()> {
+//! use scout_rs::share::ShareOptions;
+//!
+//! let options = ShareOptions {
+//!     tag_classes: Some(vec!["elephant".to_string()]),
+//!     coordinate_fuzz_degrees: Some(0.01),
+//!     device_id_salt: Some("partner salt".to_string()),
+//!     ..Default::default()
+//! };
+//! sync_engine.export_share_bundle(&options, "elephants.json")?;
+//! sync_engine.push_share_bundle(&options, &mut partner).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ScoutClient;
+use crate::geometry::{parse_point, polygon_contains};
+use crate::logging;
+use crate::models::{Event, EventLocal, ResponseScoutStatus, Tag, TagLocal};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Selection and anonymization applied to data shared with a partner herd
+#[derive(Debug, Clone, Default)]
+pub struct ShareOptions {
+    /// Only events with at least one tag of these classes (None = all events)
+    pub tag_classes: Option<Vec<String>>,
+    /// Only events observed in this range
+    pub time_range: Option<std::ops::Range<chrono::DateTime<chrono::Utc>>>,
+    /// Only events located inside this polygon of (longitude, latitude) vertices
+    pub area: Option<Vec<(f64, f64)>>,
+    /// Maximum offset in degrees added to event and tag coordinates (None = exact)
+    pub coordinate_fuzz_degrees: Option<f64>,
+    /// Replaces device IDs with a hash salted by this value (None = keep device IDs).
+    /// Also salts the hashes that always replace local IDs.
+    pub device_id_salt: Option<String>,
+    /// Only public events, see [`SyncEngine::set_visibility`]
+    ///
+    /// [`SyncEngine::set_visibility`]: crate::sync::SyncEngine::set_visibility
+    pub public_only: bool,
+}
+
+/// Anonymized events and their tags, ready to hand to a partner
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShareBundle {
+    pub events: Vec<EventLocal>,
+    pub tags: Vec<TagLocal>,
+}
+
+/// SHA-256 of the salt and value, so repeated exports of the same row fuzz and hash
+/// identically across builds and platforms
+pub(crate) fn stable_digest(value: impl AsRef<[u8]>, salt: &str) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    // Length-prefixed, so no salt and value pair collides with another
+    hasher.update((salt.len() as u64).to_le_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.update(value.as_ref());
+    hasher.finalize().into()
+}
+
+/// The first 64 bits of [`stable_digest`]
+pub(crate) fn stable_hash(value: impl AsRef<[u8]>, salt: &str) -> u64 {
+    let digest = stable_digest(value, salt);
+    u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"))
+}
+
+/// Hex hash replacing a local ID in shared data; the same ID and salt always give the same
+/// hash, so partners can deduplicate repeated exports without learning the ID
+pub(crate) fn share_id(id_local: &str, salt: &str) -> String {
+    stable_digest(id_local, salt)[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Offsets a WKT point by up to `max_offset` degrees on each axis, seeded by `seed`
+fn fuzz_point(location: &str, max_offset: f64, seed: &str) -> Option<String> {
+    let (longitude, latitude) = parse_point(location)?;
+    let offset = |axis: &str| {
+        let unit = stable_hash(seed, axis) as f64 / u64::MAX as f64;
+        (unit * 2.0 - 1.0) * max_offset
+    };
+    Some(format!(
+        "POINT({} {})",
+        longitude + offset("longitude"),
+        latitude + offset("latitude")
+    ))
+}
+
+impl ShareBundle {
+    /// Selects events (and their tags) matching the share options and anonymizes them.
+    /// Local IDs are replaced by salted hashes (see [`ShareOptions::device_id_salt`]) that
+    /// still link tags to events and let partners deduplicate repeated exports. Remote IDs,
+    /// media URLs, file paths and EarthRanger links are omitted, as are events and tags
+    /// for which `is_held` is true, e.g. those inside no-sync zones.
+    pub(crate) fn build(
+        events: Vec<EventLocal>,
+        tags: Vec<TagLocal>,
+        options: &ShareOptions,
+        is_held: impl Fn(Option<&str>) -> bool,
+    ) -> Self {
+        let mut tags_by_event: HashMap<String, Vec<TagLocal>> = HashMap::new();
+        for tag in tags {
+            if let Some(event_id) = tag.ancestor_id_local.clone() {
+                tags_by_event.entry(event_id).or_default().push(tag);
+            }
+        }
+
+        let mut bundle = ShareBundle::default();
+        for mut event in events {
+            let event_tags = event
+                .id_local
+                .as_ref()
+                .and_then(|id| tags_by_event.remove(id))
+                .unwrap_or_default();
+
+            if options.public_only && !event.is_public {
+                continue;
+            }
+            // Shares leave the device, so events held in no-sync zones stay out
+            if is_held(event.location.as_deref()) {
+                continue;
+            }
+            if let Some(classes) = &options.tag_classes {
+                if !event_tags.iter().any(|t| classes.contains(&t.class_name)) {
+                    continue;
+                }
+            }
+            if let Some(range) = &options.time_range {
+                let observed = chrono::DateTime::parse_from_rfc3339(&event.timestamp_observation);
+                if !observed.is_ok_and(|t| range.contains(&t.with_timezone(&chrono::Utc))) {
+                    continue;
+                }
+            }
+            if let Some(area) = &options.area {
+                let inside = event.location.as_deref().and_then(parse_point).is_some_and(
+                    |(longitude, latitude)| polygon_contains(area, longitude, latitude),
+                );
+                if !inside {
+                    continue;
+                }
+            }
+
+            let seed = event.id_local.clone().unwrap_or_default();
+            if let Some(max_offset) = options.coordinate_fuzz_degrees {
+                event.location = event
+                    .location
+                    .as_deref()
+                    .and_then(|location| fuzz_point(location, max_offset, &seed));
+            }
+            if let Some(salt) = &options.device_id_salt {
+                event.device_id = (stable_hash(event.device_id.to_le_bytes(), salt) >> 1) as i64;
+            }
+            let salt = options.device_id_salt.as_deref().unwrap_or_default();
+            let hash_id = |id: &mut Option<String>| {
+                *id = id.as_deref().map(|id| share_id(id, salt));
+            };
+            hash_id(&mut event.id_local);
+            hash_id(&mut event.ancestor_id_local);
+            hash_id(&mut event.parent_event_id_local);
+            hash_id(&mut event.burst_id);
+            event.id = None;
+            event.session_id = None;
+            event.parent_event_id = None;
+            event.media_url = None;
+            event.file_path = None;
+            event.earthranger_url = None;
+            for mut tag in event_tags {
+                if is_held(tag.location.as_deref()) {
+                    continue;
+                }
+                if let Some(max_offset) = options.coordinate_fuzz_degrees {
+                    let tag_seed = tag.id_local.clone().unwrap_or_default();
+                    tag.location = tag
+                        .location
+                        .as_deref()
+                        .and_then(|location| fuzz_point(location, max_offset, &tag_seed));
+                }
+                hash_id(&mut tag.id_local);
+                hash_id(&mut tag.ancestor_id_local);
+                tag.id = None;
+                tag.event_id = 0;
+                bundle.tags.push(tag);
+            }
+            bundle.events.push(event);
+        }
+
+        bundle
+    }
+
+    /// Pushes an anonymized share bundle to a partner herd through that herd's client.
+    /// Events are created as the partner's device without a session.
+    /// Returns the number of events created.
+    pub(crate) async fn push(self, partner: &mut ScoutClient) -> Result<usize> {
+        let bundle = self;
+        let partner_device_id = partner
+            .get_device()
+            .await?
+            .data
+            .and_then(|device| device.id)
+            .ok_or_else(|| anyhow!("Partner client has no identified device"))?;
+
+        let mut created = 0;
+        for event in bundle.events {
+            let tags: Vec<Tag> = bundle
+                .tags
+                .iter()
+                .filter(|tag| {
+                    tag.ancestor_id_local.is_some() && tag.ancestor_id_local == event.id_local
+                })
+                .map(|tag| Tag {
+                    id: None,
+                    inserted_at: None,
+                    ..tag.clone().into()
+                })
+                .collect();
+            let event = Event {
+                id: None,
+                session_id: None,
+                parent_event_id: None,
+                device_id: partner_device_id,
+                ..event.into()
+            };
+            let response = partner.create_event_with_tags(&event, &tags, None).await?;
+            if response.status == ResponseScoutStatus::Success {
+                created += 1;
+            } else {
+                logging::warn!("Partner herd rejected shared event: {:?}", response.status);
+            }
+        }
+
+        logging::info!("Pushed {} shared events to partner herd", created);
+        Ok(created)
+    }
+}
@@ -0,0 +1,180 @@
+//! SQL dumps of local sessions for admins inspecting or replaying device data in Postgres.
+//! Rows are written as `INSERT` statements with the server's table and column names,
+//! parents before their children, in one transaction. Rows already on the server keep
+//! their IDs and are skipped if present; rows not yet synced get new IDs, and their
+//! children refer to them through the sequence of the parent table.
+//!
+//! ```no_run
+//! # fn run(sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use scout_rs::sync::ArchiveFilter;
+//!
+//! let filter = ArchiveFilter {
+//!     device_id: Some(7),
+//!     ..Default::default()
+//! };
+//! let sessions = sync_engine.export_sql_dump(&filter, "device-7.sql")?;
+//! // psql -f device-7.sql
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+
+/// The value of a foreign key column of a dumped row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RowRef {
+    /// The parent is on the server with this ID
+    Remote(i64),
+    /// The parent is the row of this table the dump inserted last
+    Inserted(&'static str),
+}
+
+impl RowRef {
+    /// Reference to a parent of `table` with remote ID `id`, if synced
+    pub(crate) fn of(id: Option<i64>, table: &'static str) -> Self {
+        id.map_or(Self::Inserted(table), Self::Remote)
+    }
+
+    fn sql(&self) -> String {
+        match self {
+            Self::Remote(id) => id.to_string(),
+            Self::Inserted(table) => {
+                format!("currval(pg_get_serial_sequence('public.{}', 'id'))", table)
+            }
+        }
+    }
+}
+
+/// Statements of a dump being written, see the module docs
+pub(crate) struct SqlDump<W: Write> {
+    writer: W,
+    rows: u64,
+}
+
+impl<W: Write> SqlDump<W> {
+    pub(crate) fn new(mut writer: W) -> Result<Self> {
+        writeln!(
+            writer,
+            "-- Scout local data, exported at {}",
+            chrono::Utc::now().to_rfc3339()
+        )?;
+        writeln!(writer, "BEGIN;")?;
+        Ok(Self { writer, rows: 0 })
+    }
+
+    /// Inserts `row` into `table`, with the foreign key columns in `refs` replaced
+    pub(crate) fn insert(
+        &mut self,
+        table: &str,
+        row: impl Serialize,
+        refs: &[(&str, RowRef)],
+    ) -> Result<()> {
+        let Value::Object(columns) = serde_json::to_value(row)? else {
+            anyhow::bail!("Row of {} is not an object", table);
+        };
+        let mut names = Vec::with_capacity(columns.len());
+        let mut values = Vec::with_capacity(columns.len());
+        for (name, value) in &columns {
+            let value = match refs.iter().find(|(column, _)| column == name) {
+                Some((_, row_ref)) => row_ref.sql(),
+                None => literal(value),
+            };
+            names.push(format!("\"{}\"", name));
+            values.push(value);
+        }
+        for (column, row_ref) in refs {
+            if !columns.contains_key(*column) {
+                names.push(format!("\"{}\"", column));
+                values.push(row_ref.sql());
+            }
+        }
+        let on_conflict = if columns.get("id").is_some_and(|id| !id.is_null()) {
+            " ON CONFLICT (\"id\") DO NOTHING"
+        } else {
+            ""
+        };
+        writeln!(
+            self.writer,
+            "INSERT INTO \"public\".\"{}\" ({}) VALUES ({}){};",
+            table,
+            names.join(", "),
+            values.join(", "),
+            on_conflict
+        )?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Ends the transaction, returning the number of rows written
+    pub(crate) fn finish(mut self) -> Result<u64> {
+        writeln!(self.writer, "COMMIT;")?;
+        self.writer.flush()?;
+        Ok(self.rows)
+    }
+}
+
+/// A JSON value as an SQL literal. Arrays of numbers are written as pgvector text
+/// (embeddings), other arrays as Postgres arrays and objects as JSON text.
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote(s),
+        Value::Array(items) if items.is_empty() => "'{}'".to_string(),
+        Value::Array(items) if items.iter().all(Value::is_number) => quote(&value.to_string()),
+        Value::Array(items) => format!(
+            "ARRAY[{}]",
+            items.iter().map(literal).collect::<Vec<_>>().join(", ")
+        ),
+        Value::Object(_) => quote(&value.to_string()),
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_are_quoted_and_linked_to_parents() -> Result<()> {
+        let mut output = Vec::new();
+        let mut dump = SqlDump::new(&mut output)?;
+        dump.insert(
+            "sessions",
+            serde_json::json!({"device_id": 7, "software_version": "it's 1.0"}),
+            &[],
+        )?;
+        dump.insert(
+            "events",
+            serde_json::json!({"embedding": [0.5, 1.0], "id": 3, "session_id": null}),
+            &[("session_id", RowRef::of(None, "sessions"))],
+        )?;
+        dump.insert(
+            "tags",
+            serde_json::json!({"quality_flags": ["high_hdop"], "observation": {"a": 1}}),
+            &[("event_id", RowRef::of(Some(3), "events"))],
+        )?;
+        assert_eq!(dump.finish()?, 3);
+
+        let sql = String::from_utf8(output)?;
+        let lines: Vec<&str> = sql.lines().skip(1).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "BEGIN;",
+                "INSERT INTO \"public\".\"sessions\" (\"device_id\", \"software_version\") VALUES (7, 'it''s 1.0');",
+                "INSERT INTO \"public\".\"events\" (\"embedding\", \"id\", \"session_id\") VALUES ('[0.5,1.0]', 3, currval(pg_get_serial_sequence('public.sessions', 'id'))) ON CONFLICT (\"id\") DO NOTHING;",
+                "INSERT INTO \"public\".\"tags\" (\"observation\", \"quality_flags\", \"event_id\") VALUES ('{\"a\":1}', ARRAY['high_hdop'], 3);",
+                "COMMIT;",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
 //! Storage module for uploading artifacts to Supabase storage using TUS protocol
 
-use crate::models::ArtifactLocal;
+use crate::logging;
+use crate::models::{ArtifactLocal, MediaType};
 use crate::tus::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse};
 use crate::tus::{Client, Error as TusError};
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
 use tokio::sync::{broadcast, watch};
 
 /// Progress information for upload operations
@@ -56,6 +60,335 @@ impl SimpleHttpHandler {
 
 const BUCKET_NAME_ARTIFACTS: &str = "artifacts";
 
+/// Directory (next to the original file) holding transformed copies produced by upload hooks,
+/// one subdirectory per version of the original, see [`resolve_upload_path`]
+const UPLOAD_STAGING_DIR: &str = ".upload_staging";
+
+/// Async transformer applied to artifact file bytes before they leave the device
+/// (e.g. blurring people or vehicle plates in images)
+pub type UploadHook =
+    Arc<dyn Fn(Vec<u8>) -> Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send>> + Send + Sync>;
+
+/// What to do with an artifact when its upload hook fails. Neither policy uploads the
+/// original, untransformed file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadHookFailurePolicy {
+    /// Withhold the file: the upload fails with [`UploadWithheld`], and event media queued
+    /// for upload is dropped from the queue instead of being retried
+    Skip,
+    /// Fail the upload so the artifact stays pending
+    Block,
+}
+
+/// Error of an upload whose hook failed under [`UploadHookFailurePolicy::Skip`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadWithheld {
+    pub file_path: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for UploadWithheld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Upload hook failed for {}, withholding the file: {}",
+            self.file_path, self.reason
+        )
+    }
+}
+
+impl std::error::Error for UploadWithheld {}
+
+#[derive(Clone)]
+struct UploadHookRegistration {
+    media_type: MediaType,
+    hook: UploadHook,
+    failure_policy: UploadHookFailurePolicy,
+}
+
+/// Image files up to this size go to the thumbnail queue by default
+pub const DEFAULT_THUMBNAIL_MAX_BYTES: u64 = 256 * 1024;
+
+/// Upload queue of an artifact, by modality and size class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UploadQueue {
+    Thumbnail,
+    Image,
+    Video,
+    Other,
+}
+
+/// Limits of one upload queue
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UploadQueuePolicy {
+    /// Combined upload rate of the queue's transfers (None = uncapped)
+    pub max_bytes_per_second: Option<u64>,
+    /// Device-local time windows `(start, end)` in which uploads may start; a window may
+    /// cross midnight. Empty means always open.
+    pub windows: Vec<(chrono::NaiveTime, chrono::NaiveTime)>,
+}
+
+impl UploadQueuePolicy {
+    pub fn is_open_at(&self, time: chrono::NaiveTime) -> bool {
+        self.windows.is_empty()
+            || self.windows.iter().any(|&(start, end)| {
+                if start <= end {
+                    start <= time && time < end
+                } else {
+                    time >= start || time < end
+                }
+            })
+    }
+}
+
+#[derive(Debug)]
+struct UploadScheduleState {
+    thumbnail_max_bytes: u64,
+    policies: HashMap<UploadQueue, UploadQueuePolicy>,
+    /// When each capped queue has sent its reserved bytes
+    next_free: HashMap<UploadQueue, std::time::Instant>,
+}
+
+/// Per-queue bandwidth caps and time windows for artifact uploads. Clones share state, so
+/// a handle kept by the application changes the schedule of running uploads.
+///
+/// ```no_run
+/// use chrono::NaiveTime;
+/// use scout_rs::storage::{UploadQueue, UploadQueuePolicy, UploadSchedule};
+///
+/// let schedule = UploadSchedule::default();
+/// schedule.set_policy(
+///     UploadQueue::Video,
+///     UploadQueuePolicy {
+///         max_bytes_per_second: Some(500_000),
+///         windows: vec![(
+///             NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+///             NaiveTime::from_hms_opt(4, 0, 0).unwrap(),
+///         )],
+///     },
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct UploadSchedule(Arc<std::sync::Mutex<UploadScheduleState>>);
+
+impl Default for UploadSchedule {
+    fn default() -> Self {
+        Self(Arc::new(std::sync::Mutex::new(UploadScheduleState {
+            thumbnail_max_bytes: DEFAULT_THUMBNAIL_MAX_BYTES,
+            policies: HashMap::new(),
+            next_free: HashMap::new(),
+        })))
+    }
+}
+
+impl UploadSchedule {
+    fn state(&self) -> std::sync::MutexGuard<'_, UploadScheduleState> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set_policy(&self, queue: UploadQueue, policy: UploadQueuePolicy) {
+        self.state().policies.insert(queue, policy);
+    }
+
+    pub fn policy(&self, queue: UploadQueue) -> UploadQueuePolicy {
+        self.state()
+            .policies
+            .get(&queue)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Images up to `bytes` are uploaded through the thumbnail queue
+    pub fn set_thumbnail_max_bytes(&self, bytes: u64) {
+        self.state().thumbnail_max_bytes = bytes;
+    }
+
+    /// Queue of an artifact, given the size of the file to upload
+    pub fn queue_for(&self, artifact: &ArtifactLocal, file_size: u64) -> UploadQueue {
+        match artifact.modality.as_deref() {
+            Some("video") => UploadQueue::Video,
+            Some("image") | None if file_size <= self.state().thumbnail_max_bytes => {
+                UploadQueue::Thumbnail
+            }
+            Some("image") | None => UploadQueue::Image,
+            Some(_) => UploadQueue::Other,
+        }
+    }
+
+    /// Whether the queue's time windows allow uploads to start now
+    pub fn is_open(&self, queue: UploadQueue) -> bool {
+        self.policy(queue).is_open_at(chrono::Local::now().time())
+    }
+
+    /// Reserves `bytes` of the queue's bandwidth, returning how long to wait before they
+    /// count as sent. Reservations are shared by all transfers of the queue.
+    fn reserve(&self, queue: UploadQueue, bytes: u64) -> std::time::Duration {
+        let mut state = self.state();
+        let Some(rate) = state
+            .policies
+            .get(&queue)
+            .and_then(|policy| policy.max_bytes_per_second)
+            .filter(|rate| *rate > 0)
+        else {
+            return std::time::Duration::ZERO;
+        };
+        let now = std::time::Instant::now();
+        let start = state.next_free.get(&queue).copied().unwrap_or(now).max(now);
+        let next_free = start + std::time::Duration::from_secs_f64(bytes as f64 / rate as f64);
+        state.next_free.insert(queue, next_free);
+        next_free - now
+    }
+}
+
+/// Staging file of a version of `original`, in a directory named by its size and
+/// modification time so that a replaced original is transformed again. None if `original`
+/// has no file name.
+fn staged_path(original: &Path, metadata: &std::fs::Metadata) -> Option<PathBuf> {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+    Some(
+        original
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(UPLOAD_STAGING_DIR)
+            .join(format!("{}-{}", metadata.len(), modified))
+            .join(original.file_name()?),
+    )
+}
+
+/// Removes the staged outputs of every version of `original` but `keep`, with the staging
+/// directories they leave empty. Best effort: files that cannot be removed are left behind.
+fn remove_staged(original: &Path, keep: Option<&Path>) {
+    let (Some(parent), Some(file_name)) = (original.parent(), original.file_name()) else {
+        return;
+    };
+    let staging_root = parent.join(UPLOAD_STAGING_DIR);
+    let Ok(versions) = std::fs::read_dir(&staging_root) else {
+        return;
+    };
+    let mut partial = file_name.to_os_string();
+    partial.push(".partial");
+    for version in versions.flatten().map(|entry| entry.path()) {
+        for staged in [version.join(file_name), version.join(&partial)] {
+            if Some(staged.as_path()) != keep {
+                let _ = std::fs::remove_file(staged);
+            }
+        }
+        // Only succeeds once the directory is empty
+        let _ = std::fs::remove_dir(&version);
+    }
+    let _ = std::fs::remove_dir(&staging_root);
+}
+
+/// Clears staging entries left by earlier runs: outputs of uploaded or replaced originals
+/// and interrupted writes. Only the output of the current version of a file of `pending`
+/// is kept, for its upload to reuse.
+pub(crate) fn clear_stale_staging(pending: &[ArtifactLocal]) {
+    let mut staging_roots = std::collections::HashSet::new();
+    let mut current = std::collections::HashSet::new();
+    for artifact in pending {
+        let original = Path::new(&artifact.file_path);
+        if let Some(parent) = original.parent() {
+            staging_roots.insert(parent.join(UPLOAD_STAGING_DIR));
+        }
+        if let Some(staged) = std::fs::metadata(original)
+            .ok()
+            .and_then(|metadata| staged_path(original, &metadata))
+        {
+            current.insert(staged);
+        }
+    }
+    for staging_root in staging_roots {
+        let Ok(versions) = std::fs::read_dir(&staging_root) else {
+            continue;
+        };
+        for version in versions.flatten().map(|entry| entry.path()) {
+            let Ok(files) = std::fs::read_dir(&version) else {
+                continue;
+            };
+            for staged in files.flatten().map(|entry| entry.path()) {
+                if !current.contains(&staged) {
+                    let _ = std::fs::remove_file(staged);
+                }
+            }
+            let _ = std::fs::remove_dir(&version);
+        }
+        let _ = std::fs::remove_dir(&staging_root);
+    }
+}
+
+/// Returns the path whose bytes should be uploaded for an artifact.
+///
+/// If a hook is registered for the artifact's modality, its output is written to a staging
+/// file with the same file name (so the remote path is unchanged) and reused on later calls
+/// until the original changes.
+async fn resolve_upload_path(
+    hooks: &[UploadHookRegistration],
+    artifact: &ArtifactLocal,
+) -> Result<PathBuf> {
+    let original = PathBuf::from(&artifact.file_path);
+    let media_type = MediaType::from(artifact.modality.as_deref().unwrap_or_default());
+    let Some(registration) = hooks.iter().find(|h| h.media_type == media_type) else {
+        return Ok(original);
+    };
+
+    let metadata = tokio::fs::metadata(&original)
+        .await
+        .map_err(|e| anyhow!("Failed to read {}: {}", artifact.file_path, e))?;
+    let staged = staged_path(&original, &metadata)
+        .ok_or_else(|| anyhow!("Invalid file path: {}", artifact.file_path))?;
+    if staged.exists() {
+        return Ok(staged);
+    }
+    // Output staged for an earlier version of the original is never uploaded now
+    remove_staged(&original, Some(&staged));
+    let staging_dir = staged.parent().unwrap_or_else(|| Path::new("."));
+
+    let bytes = tokio::fs::read(&original)
+        .await
+        .map_err(|e| anyhow!("Failed to read {}: {}", artifact.file_path, e))?;
+    match (registration.hook)(bytes).await {
+        Ok(transformed) => {
+            // Written aside and renamed into place, so an interrupted write is never
+            // mistaken for finished hook output
+            let mut partial = staged.clone().into_os_string();
+            partial.push(".partial");
+            tokio::fs::create_dir_all(staging_dir).await?;
+            tokio::fs::write(&partial, transformed).await?;
+            tokio::fs::rename(&partial, &staged).await?;
+            Ok(staged)
+        }
+        Err(e) => match registration.failure_policy {
+            UploadHookFailurePolicy::Skip => {
+                let withheld = UploadWithheld {
+                    file_path: artifact.file_path.clone(),
+                    reason: e.to_string(),
+                };
+                logging::warn!("{}", withheld);
+                Err(withheld.into())
+            }
+            UploadHookFailurePolicy::Block => Err(anyhow!(
+                "Upload hook failed for {}, blocking upload: {}",
+                artifact.file_path,
+                e
+            )),
+        },
+    }
+}
+
+/// Splits a storage path like `artifacts/1/2/clip.mp4` into bucket and object path. Paths
+/// without a bucket are in the artifacts bucket.
+pub(crate) fn split_storage_path(file_path: &str) -> (&str, &str) {
+    file_path
+        .split_once('/')
+        .unwrap_or((BUCKET_NAME_ARTIFACTS, file_path))
+}
+
 /// Generate a remote file path from a local file path
 ///
 /// Transforms a local path like `/opt/raven/blah/blah/test.mp4`
@@ -155,6 +488,8 @@ pub struct StorageClient {
     config: StorageConfig,
     http_client: reqwest::Client,
     http_handler: Box<SimpleHttpHandler>,
+    upload_hooks: Vec<UploadHookRegistration>,
+    upload_schedule: UploadSchedule,
 }
 
 impl Clone for SimpleHttpHandler {
@@ -205,9 +540,39 @@ impl StorageClient {
             config,
             http_client,
             http_handler,
+            upload_hooks: Vec::new(),
+            upload_schedule: UploadSchedule::default(),
         })
     }
 
+    /// Uploads through per-queue bandwidth caps and time windows; see [`UploadSchedule`]
+    pub fn with_upload_schedule(mut self, schedule: UploadSchedule) -> Self {
+        self.upload_schedule = schedule;
+        self
+    }
+
+    /// Handle to the upload schedule, for changing it at runtime
+    pub fn upload_schedule(&self) -> UploadSchedule {
+        self.upload_schedule.clone()
+    }
+
+    /// Registers a hook that transforms file bytes before upload for artifacts of a media type.
+    /// Registering a second hook for the same media type replaces the first.
+    pub fn with_upload_hook(
+        mut self,
+        media_type: MediaType,
+        hook: UploadHook,
+        failure_policy: UploadHookFailurePolicy,
+    ) -> Self {
+        self.upload_hooks.retain(|h| h.media_type != media_type);
+        self.upload_hooks.push(UploadHookRegistration {
+            media_type,
+            hook,
+            failure_policy,
+        });
+        self
+    }
+
     pub fn with_allowed_extensions(
         supabase_url: String,
         supabase_anon_key: String,
@@ -272,7 +637,7 @@ impl StorageClient {
                         .allowed_extensions
                         .contains(&format!(".{}", ext_str))
                     {
-                        tracing::warn!(
+                        logging::warn!(
                             "Skipping artifact {} - extension .{} not in allowed list: {:?}",
                             artifact.file_path,
                             ext_str,
@@ -282,7 +647,7 @@ impl StorageClient {
                     }
                 }
             } else {
-                tracing::warn!(
+                logging::warn!(
                     "Skipping artifact {} - no file extension found",
                     artifact.file_path
                 );
@@ -340,6 +705,8 @@ impl StorageClient {
         let chunk_size = chunk_size.unwrap_or(1024 * 1024); // Default 1MB for better progress granularity
         let max_retries = max_retries.unwrap_or(2); // Default to 2 retries
         let config = self.config.clone();
+        let upload_hooks = self.upload_hooks.clone();
+        let upload_schedule = self.upload_schedule.clone();
 
         // Create broadcast channel for progress updates
         let (progress_tx, progress_rx) = broadcast::channel(1000);
@@ -382,6 +749,8 @@ impl StorageClient {
                             config: config.clone(),
                             http_client: reqwest::Client::new(),
                             http_handler: storage_client_handler.clone(),
+                            upload_hooks: upload_hooks.clone(),
+                            upload_schedule: upload_schedule.clone(),
                         };
                         temp_client
                             .generate_upload_urls(&mut artifacts, herd_id)
@@ -406,12 +775,14 @@ impl StorageClient {
 
                 // If expired, generate a new URL
                 if is_expired && retry_count < max_retries_value {
-                    tracing::info!("Upload URL expired, generating new URL...");
+                    logging::info!("Upload URL expired, generating new URL...");
                     let mut artifacts = vec![artifact.clone()];
                     let temp_client = StorageClient {
                         config: config.clone(),
                         http_client: reqwest::Client::new(),
                         http_handler: storage_client_handler.clone(),
+                        upload_hooks: upload_hooks.clone(),
+                        upload_schedule: upload_schedule.clone(),
                     };
                     temp_client
                         .generate_upload_urls(&mut artifacts, herd_id)
@@ -423,9 +794,10 @@ impl StorageClient {
                     retry_count += 1;
                 }
 
-                // Perform TUS upload using spawn_blocking
+                // Perform TUS upload using spawn_blocking, reading from the hook output if any
                 let file_path = artifact.file_path.clone();
-                let file_path_for_blocking = file_path.clone();
+                let upload_path = resolve_upload_path(&upload_hooks, &artifact).await?;
+                let file_path_for_blocking = upload_path.clone();
                 let file_path_for_logging = file_path.clone();
                 let device_id = artifact.device_id;
                 let file_name = Path::new(&file_path)
@@ -435,10 +807,19 @@ impl StorageClient {
                     .to_string();
 
                 // Get file size for progress tracking
-                let file_size = std::fs::metadata(&file_path)
+                let file_size = std::fs::metadata(&upload_path)
                     .map(|m| m.len() as usize)
                     .unwrap_or(0);
 
+                let queue = upload_schedule.queue_for(&artifact, file_size as u64);
+                if !upload_schedule.is_open(queue) {
+                    return Err(anyhow!(
+                        "{:?} uploads are outside their allowed time windows",
+                        queue
+                    ));
+                }
+                let upload_schedule_for_blocking = upload_schedule.clone();
+
                 // Clone cancellation receiver for blocking context
                 let cancel_rx_blocking = cancel_rx.clone();
                 let upload_url_for_blocking = upload_url.clone();
@@ -448,8 +829,14 @@ impl StorageClient {
                 let upload_result = tokio::task::spawn_blocking(move || {
                     let tus_client = Client::new(storage_client_handler_for_blocking.as_ref());
 
-                    // Create progress callback
+                    // Create progress callback, which also holds chunks back to the queue's bandwidth cap
+                    let bytes_reserved = std::sync::atomic::AtomicUsize::new(0);
                     let progress_callback = move |bytes_uploaded: usize, total_bytes: usize| {
+                        let previous = bytes_reserved
+                            .swap(bytes_uploaded, std::sync::atomic::Ordering::Relaxed);
+                        let wait = upload_schedule_for_blocking
+                            .reserve(queue, bytes_uploaded.saturating_sub(previous) as u64);
+                        std::thread::sleep(wait);
                         let progress = UploadProgress {
                             bytes_uploaded,
                             total_bytes: if total_bytes > 0 {
@@ -473,7 +860,7 @@ impl StorageClient {
                     // Perform TUS upload with resumable capability, progress tracking, and cancellation
                     tus_client.upload_with_chunk_size_and_cancellation(
                         &upload_url_for_blocking,
-                        Path::new(&file_path_for_blocking),
+                        &file_path_for_blocking,
                         chunk_size,
                         Some(&progress_callback),
                         Some(&cancellation_check),
@@ -490,12 +877,17 @@ impl StorageClient {
                         // should be something like bucket_name/herd_id/device_id/name.extension
                         let storage_path =
                             format!("{}/{}", BUCKET_NAME_ARTIFACTS, storage_path_without_bucket);
-                        tracing::info!(
+                        logging::info!(
                             "Successfully uploaded {} via TUS to {}",
                             file_path_for_logging,
                             storage_path
                         );
 
+                        // Drop the staged hook output now that it has been uploaded
+                        if upload_path != Path::new(&file_path) {
+                            remove_staged(Path::new(&file_path), None);
+                        }
+
                         // Mark as uploaded
                         artifact.has_uploaded_file_to_storage = true;
                         artifact.file_path = storage_path.clone();
@@ -504,7 +896,7 @@ impl StorageClient {
                     Err(TusError::NotFoundError) => {
                         // Upload URL not found - might be expired, retry with new URL
                         if retry_count < max_retries_value {
-                            tracing::warn!(
+                            logging::warn!(
                                 "Upload URL not found (possibly expired), retrying with new URL..."
                             );
                             retry_count += 1;
@@ -514,6 +906,8 @@ impl StorageClient {
                                 config: config.clone(),
                                 http_client: reqwest::Client::new(),
                                 http_handler: storage_client_handler.clone(),
+                                upload_hooks: upload_hooks.clone(),
+                                upload_schedule: upload_schedule.clone(),
                             };
                             match temp_client
                                 .generate_upload_urls(&mut artifacts, herd_id)
@@ -539,11 +933,11 @@ impl StorageClient {
                         }
                     }
                     Err(TusError::Cancelled) => {
-                        tracing::info!("Upload was cancelled");
+                        logging::info!("Upload was cancelled");
                         return Err(anyhow!("Upload was cancelled"));
                     }
                     Err(e) => {
-                        tracing::error!("TUS upload failed for {}: {}", file_path_for_logging, e);
+                        logging::error!("TUS upload failed for {}: {}", file_path_for_logging, e);
                         return Err(anyhow!("TUS upload failed: {}", e));
                     }
                 }
@@ -588,16 +982,7 @@ impl StorageClient {
         file_path: &str,
         output_path: &std::path::Path,
     ) -> Result<()> {
-        let (bucket_name, object_path) = if file_path.contains('/') {
-            let parts: Vec<&str> = file_path.splitn(2, '/').collect();
-            if parts.len() == 2 {
-                (parts[0], parts[1])
-            } else {
-                (BUCKET_NAME_ARTIFACTS, file_path)
-            }
-        } else {
-            (BUCKET_NAME_ARTIFACTS, file_path)
-        };
+        let (bucket_name, object_path) = split_storage_path(file_path);
 
         let download_url = format!(
             "{}/storage/v1/object/public/{}/{}",
@@ -636,16 +1021,7 @@ impl StorageClient {
         file_path: &str,
         output_path: &std::path::Path,
     ) -> Result<()> {
-        let (bucket_name, object_path) = if file_path.contains('/') {
-            let parts: Vec<&str> = file_path.splitn(2, '/').collect();
-            if parts.len() == 2 {
-                (parts[0], parts[1])
-            } else {
-                (BUCKET_NAME_ARTIFACTS, file_path)
-            }
-        } else {
-            (BUCKET_NAME_ARTIFACTS, file_path)
-        };
+        let (bucket_name, object_path) = split_storage_path(file_path);
 
         let download_url = format!(
             "{}/storage/v1/object/{}/{}",
@@ -725,7 +1101,7 @@ impl StorageClient {
         let object_name = object_path.clone();
 
         let http_handler = self.http_handler.clone();
-        let file_path = artifact.file_path.clone();
+        let file_path = resolve_upload_path(&self.upload_hooks, artifact).await?;
         let endpoint = tus_endpoint.clone();
         tokio::task::spawn_blocking(move || {
             let tus_client = Client::new(http_handler.as_ref());
@@ -737,13 +1113,13 @@ impl StorageClient {
             metadata.insert("cacheControl".to_string(), "3600".to_string());
             metadata.insert("upsert".to_string(), "true".to_string());
 
-            match tus_client.create_with_metadata(&endpoint, Path::new(&file_path), metadata) {
+            match tus_client.create_with_metadata(&endpoint, &file_path, metadata) {
                 Ok(upload_url) => {
-                    tracing::debug!("Generated TUS upload URL: {}", upload_url);
+                    logging::debug!("Generated TUS upload URL: {}", upload_url);
                     Ok(upload_url)
                 }
                 Err(e) => {
-                    tracing::error!("Failed to create TUS upload: {}", e);
+                    logging::error!("Failed to create TUS upload: {}", e);
                     Err(anyhow!("TUS upload creation failed: {}", e))
                 }
             }
@@ -1435,4 +1811,153 @@ mod tests {
             "Standalone function and method should produce identical results"
         );
     }
+
+    #[tokio::test]
+    async fn test_resolve_upload_path_with_hooks() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let file_path = temp_dir.path().join("frame.jpg");
+        std::fs::write(&file_path, b"raw").expect("Failed to write test file");
+
+        let artifact = ArtifactLocal {
+            file_path: file_path.to_string_lossy().to_string(),
+            modality: Some("image".to_string()),
+            ..Default::default()
+        };
+
+        // No hook registered: the original file is uploaded
+        let path = resolve_upload_path(&[], &artifact).await.unwrap();
+        assert_eq!(path, file_path);
+
+        let redact: UploadHook =
+            Arc::new(|bytes: Vec<u8>| Box::pin(async move { Ok(bytes.to_ascii_uppercase()) }));
+        let hooks = vec![UploadHookRegistration {
+            media_type: MediaType::Image,
+            hook: redact,
+            failure_policy: UploadHookFailurePolicy::Block,
+        }];
+        let path = resolve_upload_path(&hooks, &artifact).await.unwrap();
+        assert_eq!(path.file_name(), file_path.file_name());
+        assert_eq!(std::fs::read(&path).unwrap(), b"RAW");
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"raw");
+        assert_eq!(resolve_upload_path(&hooks, &artifact).await.unwrap(), path);
+
+        // A replaced original is transformed again rather than served from stale output
+        std::fs::write(&file_path, b"new raw").expect("Failed to write test file");
+        let replaced = resolve_upload_path(&hooks, &artifact).await.unwrap();
+        assert_ne!(replaced, path);
+        assert_eq!(std::fs::read(&replaced).unwrap(), b"NEW RAW");
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+
+        // Failing hooks block the upload or withhold the file, never sending the original
+        let video_path = temp_dir.path().join("clip.mp4");
+        std::fs::write(&video_path, b"raw").expect("Failed to write test file");
+        let artifact = ArtifactLocal {
+            file_path: video_path.to_string_lossy().to_string(),
+            modality: Some("video".to_string()),
+            ..Default::default()
+        };
+        let failing: UploadHook =
+            Arc::new(|_| Box::pin(async { Err(anyhow!("detector unavailable")) }));
+        let mut hooks = vec![UploadHookRegistration {
+            media_type: MediaType::Video,
+            hook: failing,
+            failure_policy: UploadHookFailurePolicy::Block,
+        }];
+        assert!(resolve_upload_path(&hooks, &artifact).await.is_err());
+
+        hooks[0].failure_policy = UploadHookFailurePolicy::Skip;
+        let error = resolve_upload_path(&hooks, &artifact).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UploadWithheld>(),
+            Some(&UploadWithheld {
+                file_path: video_path.to_string_lossy().to_string(),
+                reason: "detector unavailable".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clear_stale_staging_keeps_outputs_of_pending_uploads() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let pending_path = temp_dir.path().join("frame.jpg");
+        std::fs::write(&pending_path, b"raw").expect("Failed to write test file");
+        let pending = ArtifactLocal {
+            file_path: pending_path.to_string_lossy().to_string(),
+            modality: Some("image".to_string()),
+            ..Default::default()
+        };
+        let redact: UploadHook =
+            Arc::new(|bytes: Vec<u8>| Box::pin(async move { Ok(bytes.to_ascii_uppercase()) }));
+        let hooks = vec![UploadHookRegistration {
+            media_type: MediaType::Image,
+            hook: redact,
+            failure_policy: UploadHookFailurePolicy::Block,
+        }];
+        let staged = resolve_upload_path(&hooks, &pending).await.unwrap();
+
+        // Left by an upload that finished before its output was dropped, and by a crash
+        let staging_root = temp_dir.path().join(UPLOAD_STAGING_DIR);
+        let uploaded = staging_root.join("3-1").join("uploaded.jpg");
+        let interrupted = staging_root.join("3-2").join("frame.jpg.partial");
+        for stale in [&uploaded, &interrupted] {
+            std::fs::create_dir_all(stale.parent().unwrap()).unwrap();
+            std::fs::write(stale, b"RAW").unwrap();
+        }
+
+        clear_stale_staging(std::slice::from_ref(&pending));
+        assert!(staged.exists());
+        assert!(!uploaded.parent().unwrap().exists());
+        assert!(!interrupted.parent().unwrap().exists());
+
+        // Output of a replaced original is stale too
+        std::fs::write(&pending_path, b"new raw").expect("Failed to write test file");
+        clear_stale_staging(std::slice::from_ref(&pending));
+        assert!(!staging_root.exists());
+    }
+
+    #[test]
+    fn test_upload_schedule_queues_windows_and_caps() {
+        let schedule = UploadSchedule::default();
+        let artifact = |modality: &str| ArtifactLocal {
+            modality: Some(modality.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            schedule.queue_for(&artifact("image"), 10_000),
+            UploadQueue::Thumbnail
+        );
+        assert_eq!(
+            schedule.queue_for(&artifact("image"), 5_000_000),
+            UploadQueue::Image
+        );
+        assert_eq!(
+            schedule.queue_for(&artifact("video"), 10_000),
+            UploadQueue::Video
+        );
+        schedule.set_thumbnail_max_bytes(1_000);
+        assert_eq!(
+            schedule.queue_for(&artifact("image"), 10_000),
+            UploadQueue::Image
+        );
+
+        let at = |hour| chrono::NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        let overnight = UploadQueuePolicy {
+            max_bytes_per_second: Some(1_000),
+            windows: vec![(at(22), at(4))],
+        };
+        assert!(overnight.is_open_at(at(23)));
+        assert!(overnight.is_open_at(at(2)));
+        assert!(!overnight.is_open_at(at(4)));
+        assert!(!overnight.is_open_at(at(12)));
+        assert!(UploadQueuePolicy::default().is_open_at(at(12)));
+
+        // Reservations of a capped queue queue up behind each other; other queues are free
+        schedule.set_policy(UploadQueue::Video, overnight);
+        let first = schedule.reserve(UploadQueue::Video, 500);
+        let second = schedule.reserve(UploadQueue::Video, 500);
+        assert!(first <= std::time::Duration::from_millis(500));
+        assert!(second > std::time::Duration::from_millis(900));
+        assert!(schedule.reserve(UploadQueue::Image, 500).is_zero());
+    }
 }
@@ -0,0 +1,1706 @@
+//! Local storage behind the sync engine. native_db is the default store; the `sqlite`
+//! feature adds a SQLite store for platforms that already ship SQLite and for auditors who
+//! prefer a standard file format. Both hold the same models with the same semantics.
+
+use crate::logging;
+use crate::models::{
+    data, AppliedLinkLocal, ArtifactCacheLocal, ArtifactLocal, CircuitBreakerLocal,
+    ConnectivityLocal, DeletionAuditLocal, EventCorrelationLocal, EventLocal,
+    EventSessionLinkLocal, FlushResumeLocal, HeartbeatLocal, IdMapLocal, MediaUploadLocal,
+    OperatorLocal, OperatorTokenLocal, PendingLinkLocal, PlanCacheLocal, PullCheckpointLocal,
+    SessionLocal, SessionNoteLocal, SessionRedirectLocal, SessionTrackSegmentLocal,
+    SyncBudgetLocal, TagLocal, TagSuppressionLocal, TrashLocal,
+};
+use crate::sync::{long_path, with_path_suffix, SyncEngineOpenError};
+use anyhow::Result;
+#[cfg(feature = "sqlite")]
+use native_db::db_type::{KeyDefinition, KeyEntry};
+use native_db::db_type::{KeyOptions, ToKeyDefinition};
+#[cfg(feature = "sqlite")]
+use native_db::Key;
+use native_db::{Builder, Database, Models, ToInput, ToKey};
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+// Static models instance shared across all native_db stores
+static MODELS: Lazy<Result<Models, String>> =
+    Lazy::new(|| define_models().map_err(|e| format!("Failed to define models: {}", e)));
+
+fn define_models() -> Result<Models, Box<native_db::db_type::Error>> {
+    let mut models = Models::new();
+    // Define all session versions for migration support
+    models.define::<data::v1::SessionLocal>()?;
+    models.define::<SessionLocal>()?;
+
+    // Define all event versions (v3 adds the parent event, v4 quality flags, v5 bursts)
+    models.define::<data::v2::EventLocal>()?;
+    models.define::<data::v4::EventLocal>()?;
+    models.define::<data::v6::EventLocal>()?;
+    models.define::<EventLocal>()?;
+
+    // Define all tag versions (v2 adds detector provenance, v3 the media anchor)
+    models.define::<data::v1::TagLocal>()?;
+    models.define::<data::v4::TagLocal>()?;
+    models.define::<TagLocal>()?;
+
+    // Define all connectivity versions for migration support
+    models.define::<data::v1::ConnectivityLocal>()?;
+
+    // Define the new connectivity model as the latest version
+    models.define::<data::v2::ConnectivityLocal>()?;
+
+    // Define v3 connectivity model
+    models.define::<data::v3::ConnectivityLocal>()?;
+
+    // Define v4 connectivity model (mode + battery_percentage)
+    models.define::<data::v4::ConnectivityLocal>()?;
+
+    // Define v5 connectivity model (typed H3 indexes)
+    models.define::<data::v5::ConnectivityLocal>()?;
+
+    // Define v6 connectivity model (quality flags)
+    models.define::<data::v6::ConnectivityLocal>()?;
+
+    // Define v7 connectivity model (typed signal, noise and battery units)
+    models.define::<ConnectivityLocal>()?;
+
+    // Define both operator versions (v2 adds the per-session sequence)
+    models.define::<data::v2::OperatorLocal>()?;
+    models.define::<OperatorLocal>()?;
+
+    // Define artifact model (new in v2)
+    models.define::<ArtifactLocal>()?;
+
+    // Define sync budget model (monthly rows/bytes sent to remote)
+    models.define::<SyncBudgetLocal>()?;
+
+    // Define tag suppression model (provenance of duplicate tags dropped at ingest)
+    models.define::<TagSuppressionLocal>()?;
+
+    // Define operator token model (cached operator authentication)
+    models.define::<OperatorTokenLocal>()?;
+
+    // Define event session link model (events observed across sessions)
+    models.define::<EventSessionLinkLocal>()?;
+
+    // Define session track segment model (track points streamed while a session runs)
+    models.define::<SessionTrackSegmentLocal>()?;
+
+    // Define deletion audit model (remote deletions initiated from this device)
+    models.define::<DeletionAuditLocal>()?;
+
+    // Define plan cache model (downloaded plans with their checksums)
+    models.define::<PlanCacheLocal>()?;
+
+    // Define artifact cache model (downloaded artifact files)
+    models.define::<ArtifactCacheLocal>()?;
+
+    // Define pending link model (descendant updates retried at each flush)
+    models.define::<PendingLinkLocal>()?;
+
+    // Define session note model (notes attached to sessions after the fact)
+    models.define::<SessionNoteLocal>()?;
+
+    // Define pull checkpoint model (progress of herd mirrors pulled from the server)
+    models.define::<PullCheckpointLocal>()?;
+
+    // Define trash model (removed rows kept for their undo window)
+    models.define::<TrashLocal>()?;
+
+    // Define circuit breaker model (failing server endpoints, kept across restarts)
+    models.define::<CircuitBreakerLocal>()?;
+
+    // Define applied link model (remote IDs last linked to each ancestor's descendants)
+    models.define::<AppliedLinkLocal>()?;
+
+    // Define ID map model (remote IDs of local records, kept beyond clean)
+    models.define::<IdMapLocal>()?;
+
+    // Define media upload model (event media files queued for storage)
+    models.define::<MediaUploadLocal>()?;
+
+    // Define heartbeat model (scheduled heartbeats queued until flushed)
+    models.define::<HeartbeatLocal>()?;
+
+    // Define event correlation model (probable duplicates among other devices' events)
+    models.define::<EventCorrelationLocal>()?;
+
+    // Define session redirect model (sessions coalesced by the session rate limit)
+    models.define::<SessionRedirectLocal>()?;
+
+    // Define flush resume model (where a time-boxed flush continues)
+    models.define::<FlushResumeLocal>()?;
+
+    Ok(models)
+}
+
+/// The native_db models, defined once
+pub(crate) fn models() -> Result<&'static Models, String> {
+    MODELS.as_ref().map_err(Clone::clone)
+}
+
+/// A model kept in the local store
+pub trait StoredModel: ToInput + Serialize + DeserializeOwned + Clone + Send + 'static {
+    /// Table holding the model in SQL stores
+    const TABLE: &'static str;
+
+    /// Primary key of the row
+    fn store_key(&self) -> String;
+
+    /// The row with the given primary key, read from the native_db primary index
+    fn get_native(r: &native_db::transaction::RTransaction, key: &str) -> Result<Option<Self>>;
+}
+
+trait StoreKey: ToKey + Sized {
+    fn store_key(&self) -> String;
+
+    /// The key a [`StoreKey::store_key`] string was made from; None if it cannot be one
+    fn from_store_key(key: &str) -> Option<Self>;
+}
+
+impl StoreKey for String {
+    fn store_key(&self) -> String {
+        self.clone()
+    }
+
+    fn from_store_key(key: &str) -> Option<Self> {
+        Some(key.to_string())
+    }
+}
+
+impl StoreKey for i64 {
+    fn store_key(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_store_key(key: &str) -> Option<Self> {
+        key.parse().ok()
+    }
+}
+
+impl StoreKey for Option<String> {
+    fn store_key(&self) -> String {
+        self.clone().unwrap_or_default()
+    }
+
+    fn from_store_key(key: &str) -> Option<Self> {
+        Some(Some(key.to_string()))
+    }
+}
+
+/// Primary index lookup of [`StoredModel::get_native`]; `field` names the key's type
+fn get_by_store_key<T: StoredModel, K: StoreKey>(
+    r: &native_db::transaction::RTransaction,
+    _field: fn(&T) -> &K,
+    key: &str,
+) -> Result<Option<T>> {
+    match K::from_store_key(key) {
+        Some(key) => Ok(r.get().primary(key)?),
+        None => Ok(None),
+    }
+}
+
+/// Invokes `$m!` with every stored model, for writes of JSON-serialized rows
+macro_rules! json_models {
+    ($m:ident) => {
+        $m!(
+            SessionLocal,
+            EventLocal,
+            TagLocal,
+            ConnectivityLocal,
+            OperatorLocal,
+            ArtifactLocal,
+            SyncBudgetLocal,
+            TagSuppressionLocal,
+            OperatorTokenLocal,
+            EventSessionLinkLocal,
+            SessionTrackSegmentLocal,
+            DeletionAuditLocal,
+            PlanCacheLocal,
+            ArtifactCacheLocal,
+            PendingLinkLocal,
+            SessionNoteLocal,
+            PullCheckpointLocal,
+            TrashLocal,
+            CircuitBreakerLocal,
+            AppliedLinkLocal,
+            IdMapLocal,
+            MediaUploadLocal,
+            HeartbeatLocal,
+            EventCorrelationLocal,
+            SessionRedirectLocal,
+            FlushResumeLocal
+        )
+    };
+}
+
+macro_rules! stored_model {
+    ($model:ty, $table:literal, $key:ident) => {
+        impl StoredModel for $model {
+            const TABLE: &'static str = $table;
+
+            fn store_key(&self) -> String {
+                StoreKey::store_key(&self.$key)
+            }
+
+            fn get_native(
+                r: &native_db::transaction::RTransaction,
+                key: &str,
+            ) -> Result<Option<Self>> {
+                get_by_store_key(r, |item: &Self| &item.$key, key)
+            }
+        }
+    };
+}
+
+stored_model!(SessionLocal, "sessions", id_local);
+stored_model!(EventLocal, "events", id_local);
+stored_model!(TagLocal, "tags", id_local);
+stored_model!(ConnectivityLocal, "connectivity", id_local);
+stored_model!(OperatorLocal, "operators", id_local);
+stored_model!(ArtifactLocal, "artifacts", id_local);
+stored_model!(SyncBudgetLocal, "sync_budgets", month);
+stored_model!(TagSuppressionLocal, "tag_suppressions", suppressed_id_local);
+stored_model!(OperatorTokenLocal, "operator_tokens", credential_hash);
+stored_model!(EventSessionLinkLocal, "event_session_links", id_local);
+stored_model!(SessionTrackSegmentLocal, "session_track_segments", id_local);
+stored_model!(DeletionAuditLocal, "deletion_audits", id_local);
+stored_model!(PlanCacheLocal, "plan_caches", plan_id);
+stored_model!(ArtifactCacheLocal, "artifact_caches", artifact_id);
+stored_model!(PendingLinkLocal, "pending_links", id_local);
+stored_model!(SessionNoteLocal, "session_notes", id_local);
+stored_model!(PullCheckpointLocal, "pull_checkpoints", key);
+stored_model!(TrashLocal, "trash", key);
+stored_model!(CircuitBreakerLocal, "circuit_breakers", endpoint);
+stored_model!(AppliedLinkLocal, "applied_links", id_local);
+stored_model!(IdMapLocal, "id_maps", key);
+stored_model!(MediaUploadLocal, "media_uploads", event_id_local);
+stored_model!(HeartbeatLocal, "heartbeats", id_local);
+stored_model!(EventCorrelationLocal, "event_correlations", event_id_local);
+stored_model!(SessionRedirectLocal, "session_redirects", session_id_local);
+stored_model!(FlushResumeLocal, "flush_resume", key);
+
+/// Writes applied together by [`LocalStore::commit`]
+#[derive(Default)]
+pub struct StoreBatch {
+    writes: Vec<Box<dyn StoreWrite>>,
+}
+
+impl StoreBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts the item, replacing any row with the same primary key
+    pub fn upsert<T: StoredModel>(&mut self, item: T) {
+        self.writes.push(Box::new(Write::Upsert(item)));
+    }
+
+    /// Removes the item; committing fails if it is not stored
+    pub fn remove<T: StoredModel>(&mut self, item: T) {
+        self.writes.push(Box::new(Write::Remove(item)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Upserts a JSON-serialized row of the table named by [`StoredModel::TABLE`]
+    pub(crate) fn upsert_json(&mut self, table: &str, body: serde_json::Value) -> Result<()> {
+        macro_rules! upsert_as {
+            ($($model:ty),*) => {
+                $(
+                    if table == <$model>::TABLE {
+                        self.upsert(serde_json::from_value::<$model>(body)?);
+                        return Ok(());
+                    }
+                )*
+            };
+        }
+        json_models!(upsert_as);
+        Err(anyhow::Error::msg(format!("Unknown table {}", table)))
+    }
+
+    /// Removes the row `store` holds under the key of a JSON-serialized row of the table
+    /// named by [`StoredModel::TABLE`]; nothing if it holds none
+    pub(crate) fn remove_json(
+        &mut self,
+        store: &Store,
+        table: &str,
+        body: serde_json::Value,
+    ) -> Result<()> {
+        macro_rules! remove_as {
+            ($($model:ty),*) => {
+                $(
+                    if table == <$model>::TABLE {
+                        let key = serde_json::from_value::<$model>(body)?.store_key();
+                        if let Some(stored) = store.get::<$model>(&key)? {
+                            self.remove(stored);
+                        }
+                        return Ok(());
+                    }
+                )*
+            };
+        }
+        json_models!(remove_as);
+        Err(anyhow::Error::msg(format!("Unknown table {}", table)))
+    }
+
+    /// Table, whether it is a removal, and JSON-serialized row of every write, in order
+    pub(crate) fn json_writes(&self) -> Result<Vec<(&'static str, bool, serde_json::Value)>> {
+        self.writes.iter().map(|write| write.json_write()).collect()
+    }
+
+    /// Table, primary key and JSON-serialized row of every item the batch removes
+    pub(crate) fn removed_rows(&self) -> Result<Vec<(&'static str, String, serde_json::Value)>> {
+        self.writes
+            .iter()
+            .filter_map(|write| write.removed_row())
+            .collect()
+    }
+}
+
+impl Clone for StoreBatch {
+    fn clone(&self) -> Self {
+        Self {
+            writes: self
+                .writes
+                .iter()
+                .map(|write| write.clone_write())
+                .collect(),
+        }
+    }
+}
+
+enum Write<T> {
+    Upsert(T),
+    Remove(T),
+}
+
+trait StoreWrite: Send {
+    fn clone_write(&self) -> Box<dyn StoreWrite>;
+
+    fn removed_row(&self) -> Option<Result<(&'static str, String, serde_json::Value)>>;
+
+    fn json_write(&self) -> Result<(&'static str, bool, serde_json::Value)>;
+
+    fn apply_native(self: Box<Self>, rw: &native_db::transaction::RwTransaction) -> Result<()>;
+
+    #[cfg(feature = "sqlite")]
+    fn apply_sqlite(self: Box<Self>, tx: &rusqlite::Transaction) -> Result<()>;
+}
+
+impl<T: StoredModel> StoreWrite for Write<T> {
+    fn clone_write(&self) -> Box<dyn StoreWrite> {
+        Box::new(match self {
+            Write::Upsert(item) => Write::Upsert(item.clone()),
+            Write::Remove(item) => Write::Remove(item.clone()),
+        })
+    }
+
+    fn removed_row(&self) -> Option<Result<(&'static str, String, serde_json::Value)>> {
+        match self {
+            Write::Upsert(_) => None,
+            Write::Remove(item) => Some(
+                serde_json::to_value(item)
+                    .map(|row| (T::TABLE, item.store_key(), row))
+                    .map_err(Into::into),
+            ),
+        }
+    }
+
+    fn json_write(&self) -> Result<(&'static str, bool, serde_json::Value)> {
+        Ok(match self {
+            Write::Upsert(item) => (T::TABLE, false, serde_json::to_value(item)?),
+            Write::Remove(item) => (T::TABLE, true, serde_json::to_value(item)?),
+        })
+    }
+
+    fn apply_native(self: Box<Self>, rw: &native_db::transaction::RwTransaction) -> Result<()> {
+        match *self {
+            Write::Upsert(item) => {
+                rw.upsert(item)?;
+            }
+            Write::Remove(item) => {
+                rw.remove(item)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn apply_sqlite(self: Box<Self>, tx: &rusqlite::Transaction) -> Result<()> {
+        SqliteStore::create_table::<T>(tx)?;
+        match *self {
+            Write::Upsert(item) => {
+                tx.execute(
+                    &format!(
+                        "INSERT INTO \"{}\" (key, body, sort_key) VALUES (?1, ?2, ?3) \
+                         ON CONFLICT(key) DO UPDATE \
+                         SET body = excluded.body, sort_key = excluded.sort_key",
+                        T::TABLE
+                    ),
+                    (
+                        item.store_key(),
+                        serde_json::to_string(&item)?,
+                        key_bytes(&item.native_db_primary_key()),
+                    ),
+                )?;
+                SqliteStore::index_keys(tx, &item)?;
+            }
+            Write::Remove(item) => {
+                let removed = tx.execute(
+                    &format!("DELETE FROM \"{}\" WHERE key = ?1", T::TABLE),
+                    [item.store_key()],
+                )?;
+                if removed == 0 {
+                    return Err(anyhow::Error::msg(format!(
+                        "Key {} not found in {}",
+                        item.store_key(),
+                        T::TABLE
+                    )));
+                }
+                tx.execute(
+                    &format!("DELETE FROM \"{}__keys\" WHERE key = ?1", T::TABLE),
+                    [item.store_key()],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Storage for the sync engine's local models
+pub trait LocalStore {
+    /// All rows of a model, in the byte order of their native_db primary keys, so that
+    /// non-negative integer keys sort numerically. Rows that fail to decode are skipped.
+    fn all<T: StoredModel>(&self) -> Result<Vec<T>>;
+
+    /// The row with the given primary key
+    fn get<T: StoredModel>(&self, key: &str) -> Result<Option<T>> {
+        Ok(self
+            .all::<T>()?
+            .into_iter()
+            .find(|item| item.store_key() == key))
+    }
+
+    /// Rows whose secondary `key` equals `value`, in primary key order
+    fn find<T: StoredModel, V: ToKey + Clone>(
+        &self,
+        key: impl ToKeyDefinition<KeyOptions>,
+        value: V,
+    ) -> Result<Vec<T>>;
+
+    /// Number of rows of a model
+    fn count<T: StoredModel>(&self) -> Result<u64>;
+
+    /// Applies all writes of the batch, or none of them
+    fn commit(&self, batch: StoreBatch) -> Result<()>;
+}
+
+/// Which store a sync engine keeps its local data in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LocalStoreBackend {
+    #[default]
+    NativeDb,
+    /// native_db kept in memory and lost when the engine is dropped, e.g. for tests,
+    /// benches and relays that never need persistence
+    InMemory,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Rows of each version of the versioned models as (version, rows), by table and oldest
+/// version first
+pub type ModelVersionRows = Vec<(&'static str, Vec<(u32, u64)>)>;
+
+/// Rows moved and dropped by one upgrade batch
+type UpgradeResult = Result<(u64, u64), Box<native_db::db_type::Error>>;
+
+/// Local store backed by native_db
+pub struct NativeDbStore {
+    database: Database<'static>,
+    in_memory: bool,
+}
+
+impl NativeDbStore {
+    /// Opens the store at `path`. Rows of older model versions stay invisible until
+    /// repaired, see [`crate::sync::EngineWarning::StrandedRows`].
+    pub(crate) fn open(
+        models: &'static Models,
+        path: &Path,
+    ) -> Result<Self, Box<native_db::db_type::Error>> {
+        Ok(Self {
+            database: Builder::new().create(models, path)?,
+            in_memory: false,
+        })
+    }
+
+    pub(crate) fn in_memory(
+        models: &'static Models,
+    ) -> Result<Self, Box<native_db::db_type::Error>> {
+        Ok(Self {
+            database: Builder::new().create_in_memory(models)?,
+            in_memory: true,
+        })
+    }
+
+    /// Checks the file and repairs it if possible; Ok(false) if it was repaired
+    pub(crate) fn check_integrity(&mut self) -> Result<bool, Box<native_db::db_type::Error>> {
+        Ok(self.database.check_integrity()?)
+    }
+
+    /// Rebuilds the remote ID index of a table whose rows predate the index
+    pub(crate) fn refresh_remote_id_index<
+        T: ToInput + std::fmt::Debug + crate::models::RemoteIdIndexed,
+    >(
+        &self,
+    ) -> Result<(), Box<native_db::db_type::Error>> {
+        let r = self.database.r_transaction()?;
+        if r.len().primary::<T>()? == 0 || r.len().secondary::<T>(T::remote_id_key())? > 0 {
+            return Ok(());
+        }
+        drop(r);
+
+        let rw = self.database.rw_transaction()?;
+        rw.refresh::<T>()?;
+        rw.commit()?;
+        Ok(())
+    }
+
+    /// Version of a model and the rows stored under it, latest version or not
+    fn version_rows<T: ToInput>(&self) -> Result<(u32, u64), Box<native_db::db_type::Error>> {
+        let r = self.database.r_transaction()?;
+        Ok((T::native_model_version(), r.len().primary::<T>()?))
+    }
+
+    /// Moves up to `limit` rows of an older model version into the latest one. A row whose
+    /// key the latest version already holds is dropped, as that copy was written after the
+    /// upgrade. Returns the rows moved and dropped.
+    fn upgrade_rows<Old: ToInput, New: ToInput>(
+        &self,
+        limit: usize,
+        upgrade: impl Fn(Old) -> New,
+    ) -> UpgradeResult {
+        let rw = self.database.rw_transaction()?;
+        let old: Vec<Old> = rw
+            .scan()
+            .primary::<Old>()?
+            .all()?
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+        let (mut moved, mut dropped) = (0, 0);
+        for item in old {
+            // insert checks the key before writing anything
+            match rw.insert(upgrade(rw.remove(item)?)) {
+                Ok(()) => moved += 1,
+                Err(native_db::db_type::Error::DuplicateKey { .. }) => dropped += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        rw.commit()?;
+        Ok((moved, dropped))
+    }
+
+    /// Rows of each version of the models stored under more than one version, by table and
+    /// oldest version first. native_db keeps every version in its own table and only reads
+    /// the latest, so rows of older versions are invisible until they are upgraded, see
+    /// [`NativeDbStore::upgrade_models`].
+    pub(crate) fn model_versions(
+        &self,
+    ) -> Result<ModelVersionRows, Box<native_db::db_type::Error>> {
+        Ok(vec![
+            (
+                SessionLocal::TABLE,
+                vec![
+                    self.version_rows::<data::v1::SessionLocal>()?,
+                    self.version_rows::<SessionLocal>()?,
+                ],
+            ),
+            (
+                EventLocal::TABLE,
+                vec![
+                    self.version_rows::<data::v2::EventLocal>()?,
+                    self.version_rows::<data::v4::EventLocal>()?,
+                    self.version_rows::<data::v6::EventLocal>()?,
+                    self.version_rows::<EventLocal>()?,
+                ],
+            ),
+            (
+                TagLocal::TABLE,
+                vec![
+                    self.version_rows::<data::v1::TagLocal>()?,
+                    self.version_rows::<data::v4::TagLocal>()?,
+                    self.version_rows::<TagLocal>()?,
+                ],
+            ),
+            (ConnectivityLocal::TABLE, self.connectivity_versions()?),
+            (
+                OperatorLocal::TABLE,
+                vec![
+                    self.version_rows::<data::v2::OperatorLocal>()?,
+                    self.version_rows::<OperatorLocal>()?,
+                ],
+            ),
+        ])
+    }
+
+    /// Rows of each connectivity version, oldest first, see [`NativeDbStore::model_versions`]
+    pub(crate) fn connectivity_versions(
+        &self,
+    ) -> Result<Vec<(u32, u64)>, Box<native_db::db_type::Error>> {
+        Ok(vec![
+            self.version_rows::<data::v1::ConnectivityLocal>()?,
+            self.version_rows::<data::v2::ConnectivityLocal>()?,
+            self.version_rows::<data::v3::ConnectivityLocal>()?,
+            self.version_rows::<data::v4::ConnectivityLocal>()?,
+            self.version_rows::<data::v5::ConnectivityLocal>()?,
+            self.version_rows::<data::v6::ConnectivityLocal>()?,
+            self.version_rows::<ConnectivityLocal>()?,
+        ])
+    }
+
+    /// Moves up to `limit` rows of the newest older version of a model holding any into the
+    /// latest, see [`NativeDbStore::upgrade_rows`]. Newer versions go first so that of two
+    /// copies of a row the newer one is kept. Returns (0, 0) once every model is upgraded.
+    pub(crate) fn upgrade_models(&self, limit: usize) -> UpgradeResult {
+        fn event_from_v4(v4: data::v4::EventLocal) -> EventLocal {
+            data::v6::EventLocal::from(v4).into()
+        }
+        let upgrades: [&dyn Fn() -> UpgradeResult; 8] = [
+            &|| self.upgrade_rows(limit, |v1: data::v1::SessionLocal| SessionLocal::from(v1)),
+            &|| self.upgrade_rows(limit, |v6: data::v6::EventLocal| EventLocal::from(v6)),
+            &|| self.upgrade_rows(limit, event_from_v4),
+            &|| self.upgrade_rows(limit, |v2: data::v2::EventLocal| event_from_v4(v2.into())),
+            &|| self.upgrade_rows(limit, |v4: data::v4::TagLocal| TagLocal::from(v4)),
+            &|| {
+                self.upgrade_rows(limit, |v1: data::v1::TagLocal| {
+                    TagLocal::from(data::v4::TagLocal::from(v1))
+                })
+            },
+            &|| self.upgrade_connectivity(limit),
+            &|| self.upgrade_rows(limit, |v2: data::v2::OperatorLocal| OperatorLocal::from(v2)),
+        ];
+        for upgrade in upgrades {
+            let upgraded = upgrade()?;
+            if upgraded != (0, 0) {
+                return Ok(upgraded);
+            }
+        }
+        Ok((0, 0))
+    }
+
+    /// Moves up to `limit` rows of the newest older connectivity version holding any into
+    /// the latest, see [`NativeDbStore::upgrade_models`]
+    pub(crate) fn upgrade_connectivity(&self, limit: usize) -> UpgradeResult {
+        fn from_v4(v4: data::v4::ConnectivityLocal) -> ConnectivityLocal {
+            data::v6::ConnectivityLocal::from(data::v5::ConnectivityLocal::from(v4)).into()
+        }
+        let upgrades: [&dyn Fn() -> UpgradeResult; 6] = [
+            &|| {
+                self.upgrade_rows(limit, |v6: data::v6::ConnectivityLocal| {
+                    ConnectivityLocal::from(v6)
+                })
+            },
+            &|| {
+                self.upgrade_rows(limit, |v5: data::v5::ConnectivityLocal| {
+                    ConnectivityLocal::from(data::v6::ConnectivityLocal::from(v5))
+                })
+            },
+            &|| self.upgrade_rows(limit, from_v4),
+            &|| self.upgrade_rows(limit, |v3: data::v3::ConnectivityLocal| from_v4(v3.into())),
+            &|| self.upgrade_rows(limit, |v2: data::v2::ConnectivityLocal| from_v4(v2.into())),
+            &|| self.upgrade_rows(limit, |v1: data::v1::ConnectivityLocal| from_v4(v1.into())),
+        ];
+        for upgrade in upgrades {
+            let upgraded = upgrade()?;
+            if upgraded != (0, 0) {
+                return Ok(upgraded);
+            }
+        }
+        Ok((0, 0))
+    }
+}
+
+impl LocalStore for NativeDbStore {
+    fn all<T: StoredModel>(&self) -> Result<Vec<T>> {
+        let r = self.database.r_transaction()?;
+        let items = r
+            .scan()
+            .primary::<T>()?
+            .all()?
+            .filter_map(|raw_item| {
+                raw_item
+                    .map_err(|e| logging::error!("Failed to read {} row: {}", T::TABLE, e))
+                    .ok()
+            })
+            .collect();
+        Ok(items)
+    }
+
+    fn get<T: StoredModel>(&self, key: &str) -> Result<Option<T>> {
+        let r = self.database.r_transaction()?;
+        T::get_native(&r, key)
+    }
+
+    fn find<T: StoredModel, V: ToKey + Clone>(
+        &self,
+        key: impl ToKeyDefinition<KeyOptions>,
+        value: V,
+    ) -> Result<Vec<T>> {
+        let r = self.database.r_transaction()?;
+        let items = r
+            .scan()
+            .secondary::<T>(key)?
+            .range(value.clone()..=value)?
+            .collect::<Result<_, _>>()?;
+        Ok(items)
+    }
+
+    fn count<T: StoredModel>(&self) -> Result<u64> {
+        let r = self.database.r_transaction()?;
+        Ok(r.len().primary::<T>()?)
+    }
+
+    fn commit(&self, batch: StoreBatch) -> Result<()> {
+        let rw = self.database.rw_transaction()?;
+        for write in batch.writes {
+            write.apply_native(&rw)?;
+        }
+        rw.commit()?;
+        Ok(())
+    }
+}
+
+/// Local store backed by SQLite. Each model is a table of JSON rows keyed by primary key,
+/// with the native_db key bytes of each row beside it: the primary key's in `sort_key`, which
+/// orders reads as native_db does, and the secondary keys' in a `<table>__keys` table that
+/// [`LocalStore::find`] queries.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+/// Bytes native_db orders and compares a key by
+#[cfg(feature = "sqlite")]
+fn key_bytes(key: &Key) -> Vec<u8> {
+    <Key as redb::Value>::as_bytes(key).to_vec()
+}
+
+/// Name of a secondary key, unique among all models. native_db keeps the name private but
+/// hashes a key by it alone, so it is read back through a hasher that keeps what it is fed.
+#[cfg(feature = "sqlite")]
+fn key_name(key: &KeyDefinition<KeyOptions>) -> String {
+    use std::hash::{Hash, Hasher};
+
+    struct Fed(Vec<u8>);
+    impl Hasher for Fed {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+    }
+
+    let mut fed = Fed(Vec::new());
+    key.hash(&mut fed);
+    // A hashed str ends with a 0xff terminator
+    if fed.0.last() == Some(&0xff) {
+        fed.0.pop();
+    }
+    String::from_utf8_lossy(&fed.0).into_owned()
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub(crate) fn open(path: &Path) -> rusqlite::Result<Self> {
+        Ok(Self {
+            connection: std::sync::Mutex::new(rusqlite::Connection::open(path)?),
+        })
+    }
+
+    /// Runs SQLite's integrity check; Ok(false) if the file is damaged
+    pub(crate) fn check_integrity(&mut self) -> rusqlite::Result<bool> {
+        let connection = self
+            .connection
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let result: String =
+            connection.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        Ok(result == "ok")
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, rusqlite::Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow::Error::msg("SQLite store lock poisoned"))
+    }
+
+    /// Creates the tables of a model, and adds the key bytes to tables of files written
+    /// before they were kept
+    fn create_table<T: StoredModel>(connection: &rusqlite::Connection) -> Result<()> {
+        connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS \"{0}\" \
+                 (key TEXT PRIMARY KEY NOT NULL, body TEXT NOT NULL, sort_key BLOB); \
+             CREATE TABLE IF NOT EXISTS \"{0}__keys\" \
+                 (key TEXT NOT NULL, name TEXT NOT NULL, value BLOB NOT NULL, \
+                  PRIMARY KEY (key, name)); \
+             CREATE INDEX IF NOT EXISTS \"{0}__keys_by_value\" ON \"{0}__keys\" (name, value)",
+            T::TABLE
+        ))?;
+        let has_sort_key = connection
+            .prepare(&format!(
+                "SELECT 1 FROM pragma_table_info('{}') WHERE name = 'sort_key'",
+                T::TABLE
+            ))?
+            .exists([])?;
+        if !has_sort_key {
+            connection.execute_batch("SAVEPOINT add_key_bytes")?;
+            match Self::add_key_bytes::<T>(connection) {
+                Ok(()) => connection.execute_batch("RELEASE add_key_bytes")?,
+                Err(e) => {
+                    connection.execute_batch("ROLLBACK TO add_key_bytes; RELEASE add_key_bytes")?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `sort_key` to a table without it and fills in the key bytes of its rows. Rows
+    /// that fail to decode are left without, as reads skip them anyway.
+    fn add_key_bytes<T: StoredModel>(connection: &rusqlite::Connection) -> Result<()> {
+        connection.execute_batch(&format!(
+            "ALTER TABLE \"{}\" ADD COLUMN sort_key BLOB",
+            T::TABLE
+        ))?;
+        let bodies = connection
+            .prepare(&format!("SELECT body FROM \"{}\"", T::TABLE))?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for item in bodies
+            .iter()
+            .filter_map(|body| serde_json::from_str::<T>(body).ok())
+        {
+            connection.execute(
+                &format!("UPDATE \"{}\" SET sort_key = ?1 WHERE key = ?2", T::TABLE),
+                (key_bytes(&item.native_db_primary_key()), item.store_key()),
+            )?;
+            Self::index_keys(connection, &item)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the secondary key bytes of a row. Optional keys that are unset are left
+    /// out, as native_db leaves them out of its indexes.
+    fn index_keys<T: StoredModel>(connection: &rusqlite::Connection, item: &T) -> Result<()> {
+        connection.execute(
+            &format!("DELETE FROM \"{}__keys\" WHERE key = ?1", T::TABLE),
+            [item.store_key()],
+        )?;
+        for (key, entry) in item.native_db_secondary_keys() {
+            let value = match entry {
+                KeyEntry::Default(value) | KeyEntry::Optional(Some(value)) => value,
+                KeyEntry::Optional(None) => continue,
+            };
+            connection.execute(
+                &format!(
+                    "INSERT INTO \"{}__keys\" (key, name, value) VALUES (?1, ?2, ?3)",
+                    T::TABLE
+                ),
+                (item.store_key(), key_name(&key), key_bytes(&value)),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the rows of a query, skipping and logging those that fail to
+    fn read_rows<T: StoredModel>(
+        connection: &rusqlite::Connection,
+        query: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<T>> {
+        let bodies = connection
+            .prepare(query)?
+            .query_map(params, |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(bodies
+            .iter()
+            .filter_map(|body| {
+                serde_json::from_str(body)
+                    .map_err(|e| logging::error!("Failed to read {} row: {}", T::TABLE, e))
+                    .ok()
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl LocalStore for SqliteStore {
+    fn all<T: StoredModel>(&self) -> Result<Vec<T>> {
+        let connection = self.connection()?;
+        Self::create_table::<T>(&connection)?;
+        Self::read_rows(
+            &connection,
+            &format!("SELECT body FROM \"{}\" ORDER BY sort_key", T::TABLE),
+            [],
+        )
+    }
+
+    fn get<T: StoredModel>(&self, key: &str) -> Result<Option<T>> {
+        use rusqlite::OptionalExtension;
+
+        let connection = self.connection()?;
+        Self::create_table::<T>(&connection)?;
+        let body: Option<String> = connection
+            .query_row(
+                &format!("SELECT body FROM \"{}\" WHERE key = ?1", T::TABLE),
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        // A row that does not deserialize is an error, as it is for native_db
+        body.map(|body| serde_json::from_str(&body))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Failed to read {} row {}: {}", T::TABLE, key, e))
+    }
+
+    fn find<T: StoredModel, V: ToKey + Clone>(
+        &self,
+        key: impl ToKeyDefinition<KeyOptions>,
+        value: V,
+    ) -> Result<Vec<T>> {
+        let connection = self.connection()?;
+        Self::create_table::<T>(&connection)?;
+        Self::read_rows(
+            &connection,
+            &format!(
+                "SELECT row.body FROM \"{0}\" row JOIN \"{0}__keys\" keys ON keys.key = row.key \
+                 WHERE keys.name = ?1 AND keys.value = ?2 ORDER BY row.sort_key",
+                T::TABLE
+            ),
+            (key_name(&key.key_definition()), key_bytes(&value.to_key())),
+        )
+    }
+
+    fn count<T: StoredModel>(&self) -> Result<u64> {
+        let connection = self.connection()?;
+        Self::create_table::<T>(&connection)?;
+        let count: i64 = connection.query_row(
+            &format!("SELECT COUNT(*) FROM \"{}\"", T::TABLE),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    fn commit(&self, batch: StoreBatch) -> Result<()> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction()?;
+        for write in batch.writes {
+            write.apply_sqlite(&tx)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Opens the native_db store and rebuilds its remote ID indexes
+fn open_native_store(
+    models: &'static Models,
+    db_local_path: &Path,
+) -> Result<NativeDbStore, Box<native_db::db_type::Error>> {
+    let store = NativeDbStore::open(models, &long_path(db_local_path))?;
+    // Index remote IDs of rows written before the index existed
+    store.refresh_remote_id_index::<SessionLocal>()?;
+    store.refresh_remote_id_index::<EventLocal>()?;
+    store.refresh_remote_id_index::<TagLocal>()?;
+    store.refresh_remote_id_index::<ConnectivityLocal>()?;
+    store.refresh_remote_id_index::<OperatorLocal>()?;
+    store.refresh_remote_id_index::<ArtifactLocal>()?;
+    store.refresh_remote_id_index::<EventSessionLinkLocal>()?;
+    store.refresh_remote_id_index::<SessionNoteLocal>()?;
+    Ok(store)
+}
+
+/// True if redb rejected the file contents (bad header or corrupted pages)
+fn is_corruption(error: &native_db::db_type::Error) -> bool {
+    use native_db::db_type::Error;
+
+    let is_invalid_data = |e: &std::io::Error| e.kind() == std::io::ErrorKind::InvalidData;
+    let storage = match error {
+        Error::RedbDatabaseError(redb::DatabaseError::Storage(storage))
+        | Error::RedbStorageError(storage) => storage,
+        Error::Redb(redb::Error::Corrupted(_)) => return true,
+        Error::Redb(redb::Error::Io(e)) | Error::Io(e) => return is_invalid_data(e),
+        _ => return false,
+    };
+    match storage {
+        redb::StorageError::Corrupted(_) => true,
+        redb::StorageError::Io(e) => is_invalid_data(e),
+        _ => false,
+    }
+}
+
+/// True if SQLite rejected the file contents
+#[cfg(feature = "sqlite")]
+fn is_sqlite_corruption(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::NotADatabase | rusqlite::ErrorCode::DatabaseCorrupt)
+    )
+}
+
+/// The store a sync engine runs on, selected by [`LocalStoreBackend`]
+pub enum Store {
+    NativeDb(NativeDbStore),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteStore),
+    /// No file is open, e.g. while it is moved or after reopening it failed. Every
+    /// operation fails with the reason rather than reading or writing a stand-in.
+    Closed {
+        backend: LocalStoreBackend,
+        reason: String,
+    },
+}
+
+fn closed_error(reason: &str) -> anyhow::Error {
+    anyhow::anyhow!("Local database is closed: {}", reason)
+}
+
+impl Store {
+    pub fn backend(&self) -> LocalStoreBackend {
+        match self {
+            Store::NativeDb(store) if store.in_memory => LocalStoreBackend::InMemory,
+            Store::NativeDb(_) => LocalStoreBackend::NativeDb,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(_) => LocalStoreBackend::Sqlite,
+            Store::Closed { backend, .. } => *backend,
+        }
+    }
+
+    /// Rows of each version of the models stored under more than one version, by table and
+    /// oldest version first. Empty for other backends than native_db, which only store the
+    /// latest versions.
+    pub(crate) fn model_versions(&self) -> Result<ModelVersionRows> {
+        match self {
+            Store::NativeDb(store) => Ok(store.model_versions()?),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(_) => Ok(Vec::new()),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    /// Rows of each connectivity version, oldest first. Other backends than native_db only
+    /// store the latest version.
+    pub(crate) fn connectivity_versions(&self) -> Result<Vec<(u32, u64)>> {
+        match self {
+            Store::NativeDb(store) => Ok(store.connectivity_versions()?),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => Ok(vec![(
+                <ConnectivityLocal as native_model::Model>::native_model_version(),
+                store.count::<ConnectivityLocal>()?,
+            )]),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    /// See [`NativeDbStore::upgrade_models`]
+    pub(crate) fn upgrade_models(&self, limit: usize) -> Result<(u64, u64)> {
+        match self {
+            Store::NativeDb(store) => Ok(store.upgrade_models(limit)?),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(_) => Ok((0, 0)),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    /// See [`NativeDbStore::upgrade_connectivity`]
+    pub(crate) fn upgrade_connectivity(&self, limit: usize) -> Result<(u64, u64)> {
+        match self {
+            Store::NativeDb(store) => Ok(store.upgrade_connectivity(limit)?),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(_) => Ok((0, 0)),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    /// Checks the file; Ok(false) if it was damaged (native_db repairs what it can)
+    pub(crate) fn check_integrity(&mut self) -> Result<bool> {
+        match self {
+            Store::NativeDb(store) => Ok(store.check_integrity()?),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => Ok(store.check_integrity()?),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    /// Opens the store of the given backend
+    pub(crate) fn open(backend: LocalStoreBackend, db_local_path: &Path) -> Result<Store> {
+        match backend {
+            LocalStoreBackend::NativeDb => {
+                let models = models().map_err(anyhow::Error::msg)?;
+                Ok(Store::NativeDb(open_native_store(models, db_local_path)?))
+            }
+            LocalStoreBackend::InMemory => Ok(Self::in_memory()?),
+            #[cfg(feature = "sqlite")]
+            LocalStoreBackend::Sqlite => {
+                Ok(Store::Sqlite(SqliteStore::open(&long_path(db_local_path))?))
+            }
+        }
+    }
+
+    /// Opens an empty native_db store in memory
+    pub(crate) fn in_memory() -> Result<Store, SyncEngineOpenError> {
+        let models = models().map_err(SyncEngineOpenError::Models)?;
+        NativeDbStore::in_memory(models)
+            .map(Store::NativeDb)
+            .map_err(SyncEngineOpenError::Database)
+    }
+
+    /// Opens the store, repairing it if possible. An unrecoverable corrupt file is moved
+    /// aside to `<path>.corrupt-<timestamp>` and replaced by an empty store.
+    pub(crate) fn open_or_rebuild(
+        backend: LocalStoreBackend,
+        db_local_path: &Path,
+    ) -> Result<Store, SyncEngineOpenError> {
+        let corruption = match backend {
+            LocalStoreBackend::NativeDb => {
+                let models = models().map_err(SyncEngineOpenError::Models)?;
+                match open_native_store(models, db_local_path) {
+                    Ok(mut store) => match store.check_integrity() {
+                        Ok(true) => return Ok(Store::NativeDb(store)),
+                        Ok(false) => {
+                            logging::warn!("Repaired local database {}", db_local_path.display());
+                            return Ok(Store::NativeDb(store));
+                        }
+                        Err(e) => e.to_string(),
+                    },
+                    // Only corruption is recoverable; e.g. a database locked by another process is not
+                    Err(e) if is_corruption(&e) => e.to_string(),
+                    Err(e) => return Err(SyncEngineOpenError::Database(e)),
+                }
+            }
+            LocalStoreBackend::InMemory => return Self::in_memory(),
+            #[cfg(feature = "sqlite")]
+            LocalStoreBackend::Sqlite => {
+                match SqliteStore::open(&long_path(db_local_path))
+                    .and_then(|mut store| store.check_integrity().map(|ok| (store, ok)))
+                {
+                    Ok((store, true)) => return Ok(Store::Sqlite(store)),
+                    Ok((_, false)) => "integrity check failed".to_string(),
+                    Err(e) if is_sqlite_corruption(&e) => e.to_string(),
+                    Err(e) => return Err(SyncEngineOpenError::Sqlite(e)),
+                }
+            }
+        };
+
+        let corrupt_path = with_path_suffix(
+            db_local_path,
+            &format!(".corrupt-{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ")),
+        );
+        logging::error!(
+            "Local database {} is corrupt ({}), preserving it as {} and starting empty",
+            db_local_path.display(),
+            corruption,
+            corrupt_path.display()
+        );
+        // The corrupt database was dropped above, so Windows no longer holds a lock on it
+        std::fs::rename(long_path(db_local_path), long_path(&corrupt_path))
+            .map_err(SyncEngineOpenError::Io)?;
+        match backend {
+            LocalStoreBackend::NativeDb => {
+                let models = models().map_err(SyncEngineOpenError::Models)?;
+                open_native_store(models, db_local_path)
+                    .map(Store::NativeDb)
+                    .map_err(SyncEngineOpenError::Database)
+            }
+            LocalStoreBackend::InMemory => Self::in_memory(),
+            #[cfg(feature = "sqlite")]
+            LocalStoreBackend::Sqlite => SqliteStore::open(&long_path(db_local_path))
+                .map(Store::Sqlite)
+                .map_err(SyncEngineOpenError::Sqlite),
+        }
+    }
+}
+
+impl LocalStore for Store {
+    fn all<T: StoredModel>(&self) -> Result<Vec<T>> {
+        match self {
+            Store::NativeDb(store) => store.all(),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.all(),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    fn get<T: StoredModel>(&self, key: &str) -> Result<Option<T>> {
+        match self {
+            Store::NativeDb(store) => store.get(key),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.get(key),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    fn find<T: StoredModel, V: ToKey + Clone>(
+        &self,
+        key: impl ToKeyDefinition<KeyOptions>,
+        value: V,
+    ) -> Result<Vec<T>> {
+        match self {
+            Store::NativeDb(store) => store.find(key, value),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.find(key, value),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    fn count<T: StoredModel>(&self) -> Result<u64> {
+        match self {
+            Store::NativeDb(store) => store.count::<T>(),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.count::<T>(),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+
+    fn commit(&self, batch: StoreBatch) -> Result<()> {
+        match self {
+            Store::NativeDb(store) => store.commit(batch),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.commit(batch),
+            Store::Closed { reason, .. } => Err(closed_error(reason)),
+        }
+    }
+}
+
+/// Expands to an array of `$f::<Model>($args)` for every stored model
+macro_rules! for_each_model {
+    ($f:ident($($arg:expr),*)) => {
+        [
+            $f::<SessionLocal>($($arg),*),
+            $f::<EventLocal>($($arg),*),
+            $f::<TagLocal>($($arg),*),
+            $f::<ConnectivityLocal>($($arg),*),
+            $f::<OperatorLocal>($($arg),*),
+            $f::<ArtifactLocal>($($arg),*),
+            $f::<SyncBudgetLocal>($($arg),*),
+            $f::<TagSuppressionLocal>($($arg),*),
+            $f::<OperatorTokenLocal>($($arg),*),
+            $f::<EventSessionLinkLocal>($($arg),*),
+            $f::<SessionTrackSegmentLocal>($($arg),*),
+            $f::<DeletionAuditLocal>($($arg),*),
+            $f::<PlanCacheLocal>($($arg),*),
+            $f::<ArtifactCacheLocal>($($arg),*),
+            $f::<PendingLinkLocal>($($arg),*),
+            $f::<SessionNoteLocal>($($arg),*),
+            $f::<PullCheckpointLocal>($($arg),*),
+            $f::<TrashLocal>($($arg),*),
+            $f::<CircuitBreakerLocal>($($arg),*),
+            $f::<AppliedLinkLocal>($($arg),*),
+            $f::<IdMapLocal>($($arg),*),
+            $f::<MediaUploadLocal>($($arg),*),
+            $f::<HeartbeatLocal>($($arg),*),
+            $f::<EventCorrelationLocal>($($arg),*),
+            $f::<SessionRedirectLocal>($($arg),*),
+            $f::<FlushResumeLocal>($($arg),*),
+        ]
+    };
+}
+
+/// Upserts every row of `from` into `to`, in one batch
+pub(crate) fn copy_rows(from: &Store, to: &Store) -> Result<()> {
+    fn copy<T: StoredModel>(from: &Store, batch: &mut StoreBatch) -> Result<()> {
+        for item in from.all::<T>()? {
+            batch.upsert(item);
+        }
+        Ok(())
+    }
+
+    let mut batch = StoreBatch::new();
+    for copied in for_each_model!(copy(from, &mut batch)) {
+        copied?;
+    }
+    to.commit(batch)
+}
+
+/// Calls `f` with every row of the tables named in `tables`, as JSON with its table,
+/// parents before descendants
+pub(crate) fn for_each_row(
+    store: &Store,
+    tables: &[&str],
+    f: &mut dyn FnMut(&'static str, serde_json::Value) -> Result<()>,
+) -> Result<()> {
+    fn rows<T: StoredModel>(
+        store: &Store,
+        tables: &[&str],
+        f: &mut dyn FnMut(&'static str, serde_json::Value) -> Result<()>,
+    ) -> Result<()> {
+        if !tables.contains(&T::TABLE) {
+            return Ok(());
+        }
+        for item in store.all::<T>()? {
+            f(T::TABLE, serde_json::to_value(item)?)?;
+        }
+        Ok(())
+    }
+
+    for visited in for_each_model!(rows(store, tables, f)) {
+        visited?;
+    }
+    Ok(())
+}
+
+/// Row count and SHA-256 of the rows of one table, for comparing copies of a store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TableDigest {
+    pub table: &'static str,
+    pub rows: u64,
+    pub sha256: String,
+}
+
+pub(crate) fn table_digests(store: &Store) -> Result<Vec<TableDigest>> {
+    fn digest<T: StoredModel>(store: &Store) -> Result<TableDigest> {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        let mut rows = 0;
+        for item in store.all::<T>()? {
+            hasher.update(serde_json::to_vec(&item)?);
+            rows += 1;
+        }
+        Ok(TableDigest {
+            table: T::TABLE,
+            rows,
+            sha256: hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        })
+    }
+
+    for_each_model!(digest(store)).into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_db::transaction::RwTransaction;
+
+    const TEST_H3_CELL: &str = "8e2800000000007";
+
+    type Write = fn(&RwTransaction) -> Result<()>;
+    type Check = fn(&NativeDbStore) -> Result<()>;
+
+    #[test]
+    fn test_upgrade_models_migrates_each_older_version() -> Result<()> {
+        let cases: &[(&str, Write, Check)] = &[
+            (
+                "v1 sessions become private",
+                |rw| {
+                    rw.insert(data::v1::SessionLocal {
+                        id_local: Some("s1".to_string()),
+                        software_version: "1.0.0".to_string(),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let session = store.get::<SessionLocal>("s1")?.unwrap();
+                    assert_eq!(session.software_version, "1.0.0");
+                    assert!(!session.is_public);
+                    Ok(())
+                },
+            ),
+            (
+                "v2 events",
+                |rw| {
+                    rw.insert(data::v2::EventLocal {
+                        id_local: Some("e1".to_string()),
+                        message: Some("herd at the river".to_string()),
+                        session_id: Some(4),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let event = store.get::<EventLocal>("e1")?.unwrap();
+                    assert_eq!(event.message.as_deref(), Some("herd at the river"));
+                    assert_eq!(event.session_id, Some(4));
+                    assert_eq!(event.parent_event_id_local, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v3 events",
+                |rw| {
+                    rw.insert(data::v4::EventLocal {
+                        id_local: Some("e1".to_string()),
+                        parent_event_id_local: Some("e0".to_string()),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let event = store.get::<EventLocal>("e1")?.unwrap();
+                    assert_eq!(event.parent_event_id_local.as_deref(), Some("e0"));
+                    assert_eq!(event.quality_flags, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v4 events",
+                |rw| {
+                    rw.insert(data::v6::EventLocal {
+                        id_local: Some("e1".to_string()),
+                        message: Some("camera trap".to_string()),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let event = store.get::<EventLocal>("e1")?.unwrap();
+                    assert_eq!(event.message.as_deref(), Some("camera trap"));
+                    assert_eq!(event.burst_id, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v1 tags",
+                |rw| {
+                    rw.insert(data::v1::TagLocal {
+                        id_local: Some("t1".to_string()),
+                        class_name: "elephant".to_string(),
+                        conf: 0.9,
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let tag = store.get::<TagLocal>("t1")?.unwrap();
+                    assert_eq!((tag.class_name.as_str(), tag.conf), ("elephant", 0.9));
+                    assert_eq!(tag.detector_name, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v2 tags",
+                |rw| {
+                    rw.insert(data::v4::TagLocal {
+                        id_local: Some("t1".to_string()),
+                        class_name: "giraffe".to_string(),
+                        detector_name: Some("megadetector".to_string()),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let tag = store.get::<TagLocal>("t1")?.unwrap();
+                    assert_eq!(tag.detector_name.as_deref(), Some("megadetector"));
+                    assert_eq!((tag.frame_number, tag.media_offset_ms), (None, None));
+                    Ok(())
+                },
+            ),
+            (
+                "v4 connectivity with an invalid index",
+                |rw| {
+                    rw.insert(data::v4::ConnectivityLocal {
+                        id_local: Some("c1".to_string()),
+                        signal: -80.0,
+                        h14_index: TEST_H3_CELL.to_string(),
+                        h13_index: "not a cell".to_string(),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let connectivity = store.get::<ConnectivityLocal>("c1")?.unwrap();
+                    assert_eq!(connectivity.signal, -80.0);
+                    assert_eq!(connectivity.h14_index.to_string(), TEST_H3_CELL);
+                    // Invalid indexes become null rather than failing the upgrade
+                    assert!(connectivity.h13_index.is_null());
+                    Ok(())
+                },
+            ),
+            (
+                "v5 connectivity",
+                |rw| {
+                    rw.insert(data::v5::ConnectivityLocal {
+                        id_local: Some("c1".to_string()),
+                        signal: -75.0,
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let connectivity = store.get::<ConnectivityLocal>("c1")?.unwrap();
+                    assert_eq!(connectivity.signal, -75.0);
+                    assert_eq!(connectivity.quality_flags, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v6 connectivity with an impossible battery level",
+                |rw| {
+                    rw.insert(data::v6::ConnectivityLocal {
+                        id_local: Some("c1".to_string()),
+                        signal: -72.0,
+                        battery_percentage: Some(140.0),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let connectivity = store.get::<ConnectivityLocal>("c1")?.unwrap();
+                    assert_eq!(connectivity.signal, -72.0);
+                    assert_eq!(connectivity.battery_percentage, None);
+                    Ok(())
+                },
+            ),
+            (
+                "v1 operators",
+                |rw| {
+                    rw.insert(data::v2::OperatorLocal {
+                        id_local: Some("o1".to_string()),
+                        user_id: "ranger".to_string(),
+                        action: "start_mission".to_string(),
+                        ..Default::default()
+                    })?;
+                    Ok(())
+                },
+                |store| {
+                    let operator = store.get::<OperatorLocal>("o1")?.unwrap();
+                    assert_eq!(operator.action, "start_mission");
+                    assert_eq!(operator.sequence, None);
+                    Ok(())
+                },
+            ),
+        ];
+
+        for (case, write, check) in cases {
+            let store = NativeDbStore::in_memory(models().map_err(anyhow::Error::msg)?)?;
+            let rw = store.database.rw_transaction()?;
+            write(&rw)?;
+            rw.commit()?;
+
+            assert_eq!(store.upgrade_models(100)?, (1, 0), "{}", case);
+            assert_eq!(store.upgrade_models(100)?, (0, 0), "{}", case);
+            check(&store)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_get_fails_on_rows_that_do_not_deserialize() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let store = SqliteStore::open(&temp_dir.path().join("store.sqlite"))?;
+        let mut batch = StoreBatch::new();
+        batch.upsert(SessionLocal {
+            id_local: Some("s1".to_string()),
+            ..Default::default()
+        });
+        store.commit(batch)?;
+        store.connection()?.execute(
+            &format!(
+                "UPDATE \"{}\" SET body = 'not json' WHERE key = 's1'",
+                SessionLocal::TABLE
+            ),
+            [],
+        )?;
+
+        assert!(store.get::<SessionLocal>("s1").is_err());
+        assert_eq!(store.get::<SessionLocal>("s2")?, None);
+        // all() skips the row and logs it instead
+        assert!(store.all::<SessionLocal>()?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_reads_in_native_db_order() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let stores = [
+            Store::NativeDb(NativeDbStore::in_memory(
+                models().map_err(anyhow::Error::msg)?,
+            )?),
+            Store::Sqlite(SqliteStore::open(&temp_dir.path().join("store.sqlite"))?),
+        ];
+        let mut batch = StoreBatch::new();
+        // As text, "10" and "100" sort before "9"
+        for artifact_id in [100, 9, 10, 2] {
+            batch.upsert(ArtifactCacheLocal {
+                artifact_id,
+                file_path: format!("cache/{}.jpg", artifact_id),
+                size_bytes: 0,
+                sha256: String::new(),
+                last_used_at: String::new(),
+            });
+        }
+        for (id_local, ancestor) in [("t3", "e1"), ("t1", "e1"), ("t2", "e2"), ("t4", "e1")] {
+            batch.upsert(TagLocal {
+                id_local: Some(id_local.to_string()),
+                ancestor_id_local: Some(ancestor.to_string()),
+                ..Default::default()
+            });
+        }
+        let removed = TagLocal {
+            id_local: Some("t4".to_string()),
+            ancestor_id_local: Some("e1".to_string()),
+            ..Default::default()
+        };
+        for store in &stores {
+            store.commit(batch.clone())?;
+            let mut batch = StoreBatch::new();
+            batch.remove(removed.clone());
+            store.commit(batch)?;
+        }
+
+        for store in &stores {
+            let backend = store.backend();
+            let artifact_ids: Vec<i64> = store
+                .all::<ArtifactCacheLocal>()?
+                .iter()
+                .map(|artifact| artifact.artifact_id)
+                .collect();
+            assert_eq!(artifact_ids, [2, 9, 10, 100], "{:?}", backend);
+
+            let tags: Vec<TagLocal> = store.find(
+                crate::models::v6::TagLocalKey::ancestor_id_local,
+                Some("e1".to_string()),
+            )?;
+            let tag_ids: Vec<_> = tags
+                .iter()
+                .filter_map(|tag| tag.id_local.as_deref())
+                .collect();
+            assert_eq!(tag_ids, ["t1", "t3"], "{:?}", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_rejected_file_contents_count_as_corruption() {
+        use native_db::db_type::Error;
+
+        let io = |kind: std::io::ErrorKind| std::io::Error::new(kind, "test");
+        assert!(is_corruption(&Error::RedbDatabaseError(
+            redb::DatabaseError::Storage(redb::StorageError::Corrupted("bad page".to_string()))
+        )));
+        assert!(is_corruption(&Error::RedbStorageError(
+            redb::StorageError::Io(io(std::io::ErrorKind::InvalidData))
+        )));
+        assert!(is_corruption(&Error::Redb(redb::Error::Corrupted(
+            "bad header".to_string()
+        ))));
+        // A database locked by another process or a missing file is not recoverable
+        assert!(!is_corruption(&Error::RedbDatabaseError(
+            redb::DatabaseError::DatabaseAlreadyOpen
+        )));
+        assert!(!is_corruption(&Error::Io(io(std::io::ErrorKind::NotFound))));
+    }
+}
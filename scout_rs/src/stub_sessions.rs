@@ -0,0 +1,99 @@
+//! Early upload of sessions. A session started with `start_session` is uploaded at once as
+//! a stub with only its device, start time and software version, so operators see it
+//! within seconds; while it is open, flushes patch its statistics at most once per
+//! interval, and the flush after it ends uploads the final row.
+
+use crate::models::SessionLocal;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Session columns patched while a stub session runs
+const SESSION_STATISTICS: [&str; 8] = [
+    "altitude_max",
+    "altitude_min",
+    "altitude_average",
+    "velocity_max",
+    "velocity_min",
+    "velocity_average",
+    "distance_total",
+    "distance_max_from_start",
+];
+
+/// Patch schedule of open stub sessions, see `SyncEngine::with_stub_sessions`
+#[derive(Debug, Clone)]
+pub(crate) struct StubSessions {
+    stats_interval: Duration,
+    /// When the statistics of open sessions were last patched, by local ID
+    patched_at: HashMap<String, Instant>,
+}
+
+impl StubSessions {
+    pub(crate) fn new(stats_interval: Duration) -> Self {
+        Self {
+            stats_interval,
+            patched_at: HashMap::new(),
+        }
+    }
+
+    /// Records that the statistics of a session were uploaded at `at`
+    pub(crate) fn record_patch(&mut self, session_id_local: String, at: Instant) {
+        self.patched_at.insert(session_id_local, at);
+    }
+
+    /// Takes open sessions that are already on the server out of `sessions`, returning
+    /// those whose statistics are due for a patch
+    pub(crate) fn take_due(
+        &mut self,
+        sessions: &mut Vec<SessionLocal>,
+        now: Instant,
+    ) -> Vec<SessionLocal> {
+        let mut due = Vec::new();
+        sessions.retain(|session| {
+            let Some(session_id_local) = session.id_local.as_ref() else {
+                return true;
+            };
+            if session.timestamp_end.is_some() {
+                self.patched_at.remove(session_id_local);
+                return true;
+            }
+            if session.id.is_none() {
+                return true;
+            }
+            let is_due = self
+                .patched_at
+                .get(session_id_local)
+                .is_none_or(|patched_at| now.duration_since(*patched_at) >= self.stats_interval);
+            if is_due {
+                due.push(session.clone());
+            }
+            false
+        });
+        due
+    }
+}
+
+/// The statistics columns of a session row as sent
+pub(crate) fn statistics(row: &serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    SESSION_STATISTICS
+        .iter()
+        .filter_map(|column| Some((column.to_string(), row.get(*column)?.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_are_taken_from_the_row_as_sent() {
+        let row = serde_json::json!({
+            "altitude_max": 12.5,
+            "distance_total": 40.0,
+            "locations": "POINT(1 2)",
+        });
+        assert_eq!(
+            serde_json::Value::Object(statistics(&row)),
+            serde_json::json!({"altitude_max": 12.5, "distance_total": 40.0})
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Synthetic session trees for benchmarks and load tests (`synthetic` feature). Datasets
+//! are deterministic for a seed, so runs of the benchmarks in `benches/` compare like with
+//! like across changes.
+//!
+//! ```
+//! use scout_rs::synthetic::SyntheticData;
+//!
+//! let mut sync_engine = scout_rs::synthetic::in_memory_engine()?;
+//! let dataset = SyntheticData {
+//!     sessions: 2,
+//!     events_per_session: 10,
+//!     ..Default::default()
+//! }
+//! .generate();
+//! assert_eq!(dataset.events.len(), 20);
+//! dataset.load_into(&mut sync_engine)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! To load-test a deployment, load a dataset into an engine built with a real client and
+//! flush it, after [`SyntheticDataset::write_images`] if the events should carry media.
+
+use crate::client::ScoutClient;
+use crate::db_client::DatabaseConfig;
+use crate::models::{
+    ConnectivityLocal, Dbm, EventLocal, MediaType, SessionLocal, SessionTrackSegmentLocal,
+    Syncable, TagLocal, TagObservationType,
+};
+use crate::sync::SyncEngine;
+use anyhow::Result;
+use std::path::Path;
+
+/// Shape of a generated dataset
+#[derive(Debug, Clone)]
+pub struct SyntheticData {
+    pub sessions: usize,
+    pub connectivity_per_session: usize,
+    pub events_per_session: usize,
+    pub tags_per_event: usize,
+    /// GPS fixes of each session's track, stored in segments of 10
+    pub track_points_per_session: usize,
+    /// Give every record a remote ID, as after a flush, so the data can be cleaned
+    pub synced: bool,
+    pub seed: u64,
+}
+
+impl Default for SyntheticData {
+    fn default() -> Self {
+        Self {
+            sessions: 10,
+            connectivity_per_session: 100,
+            events_per_session: 100,
+            tags_per_event: 2,
+            track_points_per_session: 0,
+            synced: false,
+            seed: 1,
+        }
+    }
+}
+
+/// Records generated by [`SyntheticData::generate`], parents before children
+#[derive(Debug, Clone, Default)]
+pub struct SyntheticDataset {
+    pub sessions: Vec<SessionLocal>,
+    pub connectivity: Vec<ConnectivityLocal>,
+    pub events: Vec<EventLocal>,
+    pub tags: Vec<TagLocal>,
+    pub track_segments: Vec<SessionTrackSegmentLocal>,
+}
+
+impl SyntheticDataset {
+    /// Number of records of all models
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+            + self.connectivity.len()
+            + self.events.len()
+            + self.tags.len()
+            + self.track_segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stores the records, one batch per model
+    pub fn load_into(self, sync_engine: &mut SyncEngine) -> Result<()> {
+        sync_engine.upsert_items(self.sessions)?;
+        sync_engine.upsert_items(self.connectivity)?;
+        sync_engine.upsert_items(self.events)?;
+        sync_engine.upsert_items(self.tags)?;
+        sync_engine.upsert_items(self.track_segments)?;
+        Ok(())
+    }
+
+    /// Writes a small BMP of grass with a grey animal into `dir` for every event and sets
+    /// it as the event's file
+    pub fn write_images(&mut self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (index, event) in self.events.iter_mut().enumerate() {
+            let path = dir.join(format!("event_{}.bmp", index));
+            std::fs::write(&path, synthetic_image(index * 7 % 48, index * 5 % 32))?;
+            event.file_path = Some(path.display().to_string());
+        }
+        Ok(())
+    }
+}
+
+impl SyntheticData {
+    pub fn generate(&self) -> SyntheticDataset {
+        let mut random = Random(self.seed.max(1));
+        let mut dataset = SyntheticDataset::default();
+        let remote_id = |id: usize| self.synced.then_some(id as i64 + 1);
+        let start = chrono::DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        for s in 0..self.sessions {
+            let session_id_local = format!("session_{}", s);
+            let session_start = start + chrono::Duration::hours(s as i64);
+            let mut session = SessionLocal {
+                device_id: 1,
+                timestamp_start: session_start.to_rfc3339(),
+                timestamp_end: Some((session_start + chrono::Duration::minutes(50)).to_rfc3339()),
+                software_version: "synthetic".to_string(),
+                ..Default::default()
+            };
+            session.set_id_local(session_id_local.clone());
+            session.id = remote_id(s);
+            let session_id = session.id;
+            dataset.sessions.push(session);
+
+            // A walk of about 10 m per fix from a random start
+            let (mut longitude, mut latitude) = random.coordinates();
+            let track: Vec<(f64, f64)> = (0..self.track_points_per_session)
+                .map(|_| {
+                    longitude += (random.next_f64() - 0.5) * 0.0002;
+                    latitude += (random.next_f64() - 0.5) * 0.0002;
+                    (longitude, latitude)
+                })
+                .collect();
+            for (sequence, points) in track.chunks(10).enumerate() {
+                dataset.track_segments.push(SessionTrackSegmentLocal::new(
+                    session_id_local.clone(),
+                    sequence as u64,
+                    points.to_vec(),
+                ));
+            }
+
+            for c in 0..self.connectivity_per_session {
+                let index = s * self.connectivity_per_session + c;
+                dataset.connectivity.push(ConnectivityLocal {
+                    id: remote_id(index),
+                    id_local: Some(format!("connectivity_{}", index)),
+                    session_id,
+                    ancestor_id_local: Some(session_id_local.clone()),
+                    timestamp_start: (session_start + chrono::Duration::seconds(c as i64))
+                        .to_rfc3339(),
+                    signal: Dbm::clamped(-60.0 - random.next_f64() * 30.0),
+                    noise: Dbm::clamped(-95.0),
+                    location: Some(random.point()),
+                    ..Default::default()
+                });
+            }
+
+            for e in 0..self.events_per_session {
+                let index = s * self.events_per_session + e;
+                let event_id_local = format!("event_{}", index);
+                let event_id = remote_id(index);
+                dataset.events.push(EventLocal {
+                    id: event_id,
+                    id_local: Some(event_id_local.clone()),
+                    session_id,
+                    ancestor_id_local: Some(session_id_local.clone()),
+                    device_id: 1,
+                    timestamp_observation: (session_start + chrono::Duration::seconds(e as i64))
+                        .to_rfc3339(),
+                    message: Some("Synthetic sighting".to_string()),
+                    location: Some(random.point()),
+                    media_type: MediaType::Image,
+                    ..Default::default()
+                });
+
+                for t in 0..self.tags_per_event {
+                    let index = index * self.tags_per_event + t;
+                    dataset.tags.push(TagLocal {
+                        id: remote_id(index),
+                        id_local: Some(format!("tag_{}", index)),
+                        event_id: event_id.unwrap_or(0),
+                        ancestor_id_local: Some(event_id_local.clone()),
+                        x: random.next_f64() * 1000.0,
+                        y: random.next_f64() * 1000.0,
+                        width: 50.0,
+                        height: 50.0,
+                        conf: random.next_f64(),
+                        observation_type: TagObservationType::Auto,
+                        class_name: "elephant".to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        dataset
+    }
+}
+
+/// An engine on an in-memory store with an offline client, for loading synthetic data
+pub fn in_memory_engine() -> Result<SyncEngine> {
+    let mut scout_client = ScoutClient::new(DatabaseConfig {
+        rest_url: "http://localhost".to_string(),
+        scout_api_key: String::new(),
+        supabase_api_key: String::new(),
+    });
+    scout_client.initialize_offline();
+    SyncEngine::in_memory(scout_client)
+}
+
+/// xorshift64, enough to spread synthetic values without a dependency
+struct Random(u64);
+
+impl Random {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// (longitude, latitude) within about 5 km of a water hole
+    fn coordinates(&mut self) -> (f64, f64) {
+        (
+            36.8 + (self.next_f64() - 0.5) * 0.1,
+            -1.3 + (self.next_f64() - 0.5) * 0.1,
+        )
+    }
+
+    fn point(&mut self) -> String {
+        let (longitude, latitude) = self.coordinates();
+        format!("POINT({:.6} {:.6})", longitude, latitude)
+    }
+}
+
+const IMAGE_WIDTH: usize = 64;
+const IMAGE_HEIGHT: usize = 48;
+
+/// A 24-bit BMP with a 16 px grey square at (x, y) on green
+fn synthetic_image(x: usize, y: usize) -> Vec<u8> {
+    let row_size = (IMAGE_WIDTH * 3).div_ceil(4) * 4;
+    let pixels_size = (row_size * IMAGE_HEIGHT) as u32;
+    let mut bmp = Vec::with_capacity(54 + pixels_size as usize);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&(54 + pixels_size).to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&54u32.to_le_bytes());
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(IMAGE_WIDTH as i32).to_le_bytes());
+    // Negative height: rows top to bottom
+    bmp.extend_from_slice(&(-(IMAGE_HEIGHT as i32)).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&24u16.to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&pixels_size.to_le_bytes());
+    bmp.extend_from_slice(&[0; 16]);
+    for row in 0..IMAGE_HEIGHT {
+        for column in 0..IMAGE_WIDTH {
+            let animal = (x..x + 16).contains(&column) && (y..y + 16).contains(&row);
+            // Blue, green, red
+            bmp.extend_from_slice(if animal { &[110; 3] } else { &[40, 120, 60] });
+        }
+        bmp.resize(bmp.len() + row_size - IMAGE_WIDTH * 3, 0);
+    }
+    bmp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datasets_are_deterministic_and_linked() {
+        let shape = SyntheticData {
+            sessions: 2,
+            connectivity_per_session: 3,
+            events_per_session: 4,
+            tags_per_event: 2,
+            track_points_per_session: 25,
+            synced: true,
+            seed: 7,
+        };
+        let dataset = shape.generate();
+        assert_eq!(dataset.len(), 2 + 6 + 8 + 16 + 6);
+        assert_eq!(dataset.tags, shape.generate().tags);
+
+        let last_tag = dataset.tags.last().unwrap();
+        assert_eq!(last_tag.ancestor_id_local.as_deref(), Some("event_7"));
+        assert_eq!(last_tag.event_id, 8);
+        assert_eq!(dataset.events[7].session_id, Some(2));
+    }
+}
@@ -0,0 +1,137 @@
+//! Session recording shared by the telemetry ingestion adapters (`mavlink`, `nmea`)
+
+use crate::models::{ConnectivityLocal, Percent, SessionLocal};
+use crate::nav::{self, GeoPoint};
+use crate::sync::SyncEngine;
+use anyhow::{Error, Result};
+
+/// Great-circle distance in meters between two (longitude, latitude) points
+fn distance_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+    nav::distance_m(GeoPoint::new(a.0, a.1), GeoPoint::new(b.0, b.1))
+}
+
+/// Records positions on a session: streamed track points (appended every
+/// `track_batch_size` points) and altitude/velocity/distance stats
+#[derive(Debug)]
+pub(crate) struct TrackRecorder {
+    session_id_local: String,
+    device_id: i64,
+    pub(crate) track_batch_size: usize,
+    pending_points: Vec<(f64, f64)>,
+    start: Option<(f64, f64)>,
+    previous: Option<(f64, f64)>,
+    samples: usize,
+    altitude_sum: f64,
+    velocity_sum: f64,
+    altitude_range: Option<(f64, f64)>,
+    velocity_range: Option<(f64, f64)>,
+    distance_total: f64,
+    distance_max_from_start: f64,
+}
+
+impl TrackRecorder {
+    pub(crate) fn new(session_id_local: String, device_id: i64) -> Self {
+        Self {
+            session_id_local,
+            device_id,
+            track_batch_size: 50,
+            pending_points: Vec::new(),
+            start: None,
+            previous: None,
+            samples: 0,
+            altitude_sum: 0.0,
+            velocity_sum: 0.0,
+            altitude_range: None,
+            velocity_range: None,
+            distance_total: 0.0,
+            distance_max_from_start: 0.0,
+        }
+    }
+
+    /// Adds a (longitude, latitude) point with altitude in meters and velocity in m/s.
+    /// Appends the buffered track segment once it reaches the batch size.
+    pub(crate) fn record(
+        &mut self,
+        sync_engine: &mut SyncEngine,
+        point: (f64, f64),
+        altitude: f64,
+        velocity: f64,
+    ) -> Result<()> {
+        let start = *self.start.get_or_insert(point);
+        if let Some(previous) = self.previous {
+            self.distance_total += distance_m(previous, point);
+        }
+        self.distance_max_from_start = self.distance_max_from_start.max(distance_m(start, point));
+        self.previous = Some(point);
+
+        let widen = |range: Option<(f64, f64)>, value: f64| {
+            Some(range.map_or((value, value), |(min, max)| {
+                (min.min(value), max.max(value))
+            }))
+        };
+        self.altitude_range = widen(self.altitude_range, altitude);
+        self.velocity_range = widen(self.velocity_range, velocity);
+        self.altitude_sum += altitude;
+        self.velocity_sum += velocity;
+        self.samples += 1;
+
+        self.pending_points.push(point);
+        if self.pending_points.len() >= self.track_batch_size {
+            self.flush(sync_engine)?;
+        }
+        Ok(())
+    }
+
+    /// Records a connectivity entry of the session's device at a point, flagged by the
+    /// engine's quality checks
+    pub(crate) fn record_connectivity(
+        &self,
+        sync_engine: &mut SyncEngine,
+        point: (f64, f64),
+        altitude: f64,
+        heading: f64,
+        battery_percentage: Option<f32>,
+        hdop: Option<f64>,
+    ) -> Result<()> {
+        let mut connectivity = ConnectivityLocal {
+            device_id: Some(self.device_id),
+            ancestor_id_local: Some(self.session_id_local.clone()),
+            timestamp_start: chrono::Utc::now().to_rfc3339(),
+            altitude,
+            heading,
+            location: Some(format!("POINT({} {})", point.0, point.1)),
+            battery_percentage: battery_percentage.map(Percent::new).transpose()?,
+            hdop,
+            ..Default::default()
+        };
+        connectivity.id_local = Some(
+            sync_engine
+                .generate_unique_id::<ConnectivityLocal>()?
+                .to_string(),
+        );
+        sync_engine.check_quality(std::slice::from_mut(&mut connectivity));
+        sync_engine.upsert_items(vec![connectivity])
+    }
+
+    /// Appends buffered track points and writes the stats so far onto the session
+    pub(crate) fn flush(&mut self, sync_engine: &mut SyncEngine) -> Result<()> {
+        if !self.pending_points.is_empty() {
+            let points = std::mem::take(&mut self.pending_points);
+            sync_engine.append_track_points(&self.session_id_local, points)?;
+        }
+
+        let mut session = sync_engine
+            .get_item::<SessionLocal>(&self.session_id_local)?
+            .ok_or_else(|| Error::msg(format!("Session {} not found", self.session_id_local)))?;
+        if let (Some(altitude), Some(velocity)) = (self.altitude_range, self.velocity_range) {
+            let samples = self.samples as f64;
+            (session.altitude_min, session.altitude_max) = altitude;
+            (session.velocity_min, session.velocity_max) = velocity;
+            session.altitude_average = self.altitude_sum / samples;
+            session.velocity_average = self.velocity_sum / samples;
+            session.distance_total = self.distance_total;
+            session.distance_max_from_start = self.distance_max_from_start;
+        }
+        sync_engine.upsert_items(vec![session])
+    }
+}
@@ -0,0 +1,217 @@
+//! Ingest throttling. A runaway producer, e.g. a detector emitting thousands of tags per
+//! second, can wedge the local database. Each table can be given a leaky-bucket rate limit:
+//! `burst` records are admitted at once, and the bucket refills at `per_second`. What
+//! happens to records over the limit depends on the table's [`ThrottleBehavior`].
+//!
+//! ```no_run
+//! # async fn run(
+//! #     sync_engine: scout_rs::sync::SyncEngine,
+//! #     tags: Vec<scout_rs::models::TagLocal>,
+//! # ) -> anyhow::Result<()> {
+//! use scout_rs::throttle::{IngestThrottle, RateLimit, ThrottleBehavior};
+//!
+//! let throttle = IngestThrottle::new()
+//!     .with_limit("tags", RateLimit::new(50.0, 200, ThrottleBehavior::Drop))
+//!     .with_limit("connectivity", RateLimit::new(5.0, 10, ThrottleBehavior::Coalesce));
+//! let mut sync_engine = sync_engine.with_ingest_throttle(throttle);
+//! sync_engine.upsert_tags(tags)?;
+//! let dropped = sync_engine.ingest_throttle_stats("tags").dropped;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What happens to records ingested over a table's rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleBehavior {
+    /// Records over the limit are dropped and counted
+    Drop,
+    /// Only the newest record over the limit is kept, and written once the bucket has
+    /// room again (at the latest on the next flush); older ones are counted as coalesced
+    Coalesce,
+    /// `ingest_items_paced` waits for room; other ingest calls over the limit fail
+    /// without storing anything
+    Backpressure,
+}
+
+/// Leaky-bucket limit of one table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Records per second the bucket refills at
+    pub per_second: f64,
+    /// Records admitted at once after a quiet period
+    pub burst: u32,
+    pub behavior: ThrottleBehavior,
+}
+
+impl RateLimit {
+    pub fn new(per_second: f64, burst: u32, behavior: ThrottleBehavior) -> Self {
+        Self {
+            per_second,
+            burst: burst.max(1),
+            behavior,
+        }
+    }
+}
+
+/// Throttling of one table since the throttle was configured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    pub admitted: u64,
+    pub dropped: u64,
+    pub coalesced: u64,
+    /// Ingest calls that hit the limit
+    pub throttle_events: u64,
+    /// Time `ingest_items_paced` spent waiting for room
+    pub waited: Duration,
+}
+
+/// Bucket levels are kept in femto-tokens, so that with the rate in micro-tokens per second
+/// a refill adds exactly `elapsed nanoseconds * rate` and a wait of [`IngestThrottle::time_until`]
+/// always yields the tokens it promised
+const UNITS_PER_TOKEN: u128 = 1_000_000_000_000_000;
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    units: u128,
+    updated: Instant,
+}
+
+impl RateLimit {
+    /// Bucket units the limit refills per nanosecond
+    fn units_per_nanosecond(&self) -> u128 {
+        (self.per_second.max(0.0) * 1e6).round() as u128
+    }
+
+    fn burst_units(&self) -> u128 {
+        u128::from(self.burst) * UNITS_PER_TOKEN
+    }
+}
+
+/// Rate limits by table, see the module docs
+#[derive(Debug, Clone, Default)]
+pub struct IngestThrottle {
+    limits: HashMap<String, RateLimit>,
+    buckets: HashMap<String, Bucket>,
+    /// Newest coalesced record of each table, waiting for room
+    held: HashMap<String, serde_json::Value>,
+    stats: HashMap<String, ThrottleStats>,
+}
+
+impl IngestThrottle {
+    /// Throttle without limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits ingestion into `table` (see [`crate::store::StoredModel::TABLE`])
+    pub fn with_limit(mut self, table: &str, limit: RateLimit) -> Self {
+        self.limits.insert(table.to_string(), limit);
+        self
+    }
+
+    pub fn limit(&self, table: &str) -> Option<&RateLimit> {
+        self.limits.get(table)
+    }
+
+    pub fn stats(&self, table: &str) -> ThrottleStats {
+        self.stats.get(table).copied().unwrap_or_default()
+    }
+
+    /// Tokens of the table's bucket, refilled up to now
+    fn refill(&mut self, table: &str, limit: &RateLimit) -> &mut Bucket {
+        let now = Instant::now();
+        let bucket = self
+            .buckets
+            .entry(table.to_string())
+            .or_insert_with(|| Bucket {
+                units: limit.burst_units(),
+                updated: now,
+            });
+        let elapsed = now.duration_since(bucket.updated).as_nanos();
+        bucket.units = bucket
+            .units
+            .saturating_add(elapsed.saturating_mul(limit.units_per_nanosecond()))
+            .min(limit.burst_units());
+        bucket.updated = now;
+        bucket
+    }
+
+    /// Takes up to `requested` tokens, returning how many were taken
+    pub(crate) fn take(&mut self, table: &str, requested: usize) -> usize {
+        let Some(limit) = self.limits.get(table).copied() else {
+            return requested;
+        };
+        let bucket = self.refill(table, &limit);
+        let taken = usize::try_from(bucket.units / UNITS_PER_TOKEN)
+            .unwrap_or(usize::MAX)
+            .min(requested);
+        bucket.units -= taken as u128 * UNITS_PER_TOKEN;
+
+        let stats = self.stats.entry(table.to_string()).or_default();
+        stats.admitted += taken as u64;
+        if taken < requested {
+            stats.throttle_events += 1;
+        }
+        taken
+    }
+
+    /// Time until `count` tokens are available (zero when they are now). Counts over the
+    /// burst never are, so callers split batches into chunks of at most the burst.
+    pub(crate) fn time_until(&mut self, table: &str, count: usize) -> Duration {
+        let Some(limit) = self.limits.get(table).copied() else {
+            return Duration::ZERO;
+        };
+        let rate = limit.units_per_nanosecond();
+        let bucket = self.refill(table, &limit);
+        let missing = (count as u128)
+            .saturating_mul(UNITS_PER_TOKEN)
+            .saturating_sub(bucket.units);
+        if missing == 0 || rate == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = missing.div_ceil(rate);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    pub(crate) fn record_dropped(&mut self, table: &str, count: usize) {
+        self.stats.entry(table.to_string()).or_default().dropped += count as u64;
+    }
+
+    pub(crate) fn record_rejected(&mut self, table: &str) {
+        self.stats
+            .entry(table.to_string())
+            .or_default()
+            .throttle_events += 1;
+    }
+
+    pub(crate) fn record_wait(&mut self, table: &str, waited: Duration) {
+        let stats = self.stats.entry(table.to_string()).or_default();
+        stats.waited += waited;
+        stats.throttle_events += 1;
+    }
+
+    /// Holds the newest of `superseded` more records over the limit, counting the rest
+    /// and any previously held record as coalesced
+    pub(crate) fn hold(&mut self, table: &str, newest: serde_json::Value, superseded: usize) {
+        let replaced = usize::from(self.held.insert(table.to_string(), newest).is_some());
+        self.stats.entry(table.to_string()).or_default().coalesced +=
+            (superseded + replaced) as u64;
+    }
+
+    /// Releases the held record of `table` if its bucket has room for it
+    pub(crate) fn release_held(&mut self, table: &str) -> Option<serde_json::Value> {
+        if !self.held.contains_key(table) || !self.time_until(table, 1).is_zero() {
+            return None;
+        }
+        self.take(table, 1);
+        self.held.remove(table)
+    }
+
+    /// Releases every held record regardless of the limits
+    pub(crate) fn release_all_held(&mut self) -> Vec<(String, serde_json::Value)> {
+        self.held.drain().collect()
+    }
+}
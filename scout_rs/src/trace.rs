@@ -0,0 +1,160 @@
+//! W3C trace context propagation. With propagation enabled, each flush starts a trace and
+//! every database request of the flush carries a `traceparent` header naming the flush span
+//! as its parent, so backend logs of a slow or failing sync can be found from the trace id
+//! the device logged.
+//!
+//! ```no_run
+//! # async fn run(sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! let mut sync_engine = sync_engine.with_trace_propagation();
+//! sync_engine.flush().await?;
+//! if let Some(trace) = sync_engine.last_flush_trace() {
+//!     println!("flush trace {}", trace.trace_id());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, Error, Result};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Header carrying the trace context, see <https://www.w3.org/TR/trace-context/>
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+const VERSION: u8 = 0;
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// A span of a trace, sent as the parent of the requests made within it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    sampled: bool,
+}
+
+impl TraceContext {
+    /// The root span of a new, sampled trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: (u128::from(random_id()) << 64) | u128::from(random_id()),
+            span_id: random_id(),
+            sampled: true,
+        }
+    }
+
+    /// A new span in the same trace, with this span as its parent
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: random_id(),
+            ..*self
+        }
+    }
+
+    /// The trace id as 32 lowercase hex digits
+    pub fn trace_id(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// The span id as 16 lowercase hex digits
+    pub fn span_id(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+}
+
+/// Formats the context as a `traceparent` header value
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = if self.sampled { FLAG_SAMPLED } else { 0 };
+        write!(
+            f,
+            "{:02x}-{}-{}-{:02x}",
+            VERSION,
+            self.trace_id(),
+            self.span_id(),
+            flags
+        )
+    }
+}
+
+/// Parses a `traceparent` header value, e.g. to continue a trace started upstream
+impl FromStr for TraceContext {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || anyhow!("{:?} is not a traceparent header", value);
+        let parts: Vec<&str> = value.split('-').collect();
+        let [version, trace_id, span_id, flags] = parts[..] else {
+            return Err(invalid());
+        };
+        let is_hex = |part: &str, len: usize| {
+            part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !(is_hex(version, 2) && is_hex(trace_id, 32) && is_hex(span_id, 16) && is_hex(flags, 2))
+        {
+            return Err(invalid());
+        }
+        if version != "00" {
+            return Err(anyhow!("Unsupported traceparent version {}", version));
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16)?;
+        let span_id = u64::from_str_radix(span_id, 16)?;
+        let flags = u8::from_str_radix(flags, 16)?;
+        if trace_id == 0 || span_id == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            trace_id,
+            span_id,
+            sampled: flags & FLAG_SAMPLED != 0,
+        })
+    }
+}
+
+/// A random non-zero id; ids only need to be unique, not unpredictable
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() -> Result<()> {
+        let root = TraceContext::new_root();
+        let header = root.to_string();
+        assert_eq!(header.len(), 55);
+        assert!(header.starts_with("00-") && header.ends_with("-01"));
+        assert_eq!(header.parse::<TraceContext>()?, root);
+
+        let child = root.child();
+        assert_eq!(child.trace_id(), root.trace_id());
+        assert_ne!(child.span_id(), root.span_id());
+
+        let upstream: TraceContext =
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00".parse()?;
+        assert_eq!(upstream.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(!upstream.is_sampled());
+        assert!("00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+            .parse::<TraceContext>()
+            .is_err());
+        assert!("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"
+            .parse::<TraceContext>()
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Flushes requested by the device rather than the clock. Devices often know the best
+//! moment to sync (ignition off, lid closed, a solar charge peak). A [`FlushTrigger`]
+//! attached to a [`FlushSchedule`] ends the wait for the next tick early; requests
+//! arriving while a flush runs are coalesced into one more flush. Besides calling
+//! [`FlushTrigger::request`] directly, e.g. from a GPIO handler, requests can come from
+//! SIGUSR1 or from a marker file created by another process (`touch` from a udev rule or
+//! a systemd unit).
+//!
+//! ```no_run
+//! # async fn run(mut sync_engine: scout_rs::sync::SyncEngine) -> anyhow::Result<()> {
+//! use std::time::Duration;
+//!
+//! let trigger = sync_engine.flush_trigger();
+//! #[cfg(unix)]
+//! trigger.listen_for_signal()?;
+//! trigger.watch_marker("/run/scout/flush-now", Duration::from_secs(1));
+//!
+//! let mut schedule = sync_engine.flush_schedule().expect("flush interval configured");
+//! loop {
+//!     schedule.tick().await;
+//!     let _ = sync_engine.flush().await;
+//! }
+//! # }
+//! ```
+//!
+//! [`FlushSchedule`]: crate::sync::FlushSchedule
+
+use crate::logging;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Handle requesting an immediate flush, see the module docs. Clones share requests.
+#[derive(Debug, Clone, Default)]
+pub struct FlushTrigger {
+    notify: Arc<Notify>,
+}
+
+impl FlushTrigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests a flush. Requests made before the schedule next waits are coalesced.
+    pub fn request(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Waits for the next request, or returns at once if one is pending
+    pub async fn requested(&self) {
+        self.notify.notified().await;
+    }
+
+    /// Requests a flush on every SIGUSR1 (`kill -USR1 <pid>`), until the returned task is
+    /// aborted
+    #[cfg(unix)]
+    pub fn listen_for_signal(&self) -> std::io::Result<JoinHandle<()>> {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut signals = signal(SignalKind::user_defined1())?;
+        let trigger = self.clone();
+        Ok(tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                logging::info!("Flush requested by SIGUSR1");
+                trigger.request();
+            }
+        }))
+    }
+
+    /// Checks every `poll_interval` whether a file exists at `path`; if so, removes it and
+    /// requests a flush. Runs until the returned task is aborted.
+    pub fn watch_marker(
+        &self,
+        path: impl Into<PathBuf>,
+        poll_interval: Duration,
+    ) -> JoinHandle<()> {
+        let path = path.into();
+        let trigger = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => {
+                        logging::info!("Flush requested by marker {}", path.display());
+                        trigger.request();
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    // Left in place, so an unremovable marker does not flush on every poll
+                    Err(e) => {
+                        logging::warn!("Failed to remove flush marker {}: {}", path.display(), e)
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::FlushSchedule;
+
+    #[tokio::test]
+    async fn test_trigger_ends_schedule_wait_and_coalesces() -> anyhow::Result<()> {
+        let trigger = FlushTrigger::new();
+        let mut schedule =
+            FlushSchedule::new(Duration::from_secs(3600)).with_trigger(trigger.clone());
+        // The first tick is due at once; the next one is an hour away
+        schedule.tick().await;
+
+        trigger.request();
+        trigger.request();
+        tokio::time::timeout(Duration::from_secs(5), schedule.tick()).await?;
+        // Both requests were served by the flush above
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), schedule.tick())
+                .await
+                .is_err()
+        );
+
+        let temp_dir = tempfile::tempdir()?;
+        let marker = temp_dir.path().join("flush-now");
+        let watcher = trigger.watch_marker(&marker, Duration::from_millis(10));
+        std::fs::write(&marker, b"")?;
+        tokio::time::timeout(Duration::from_secs(5), schedule.tick()).await?;
+        assert!(!marker.exists());
+        watcher.abort();
+        Ok(())
+    }
+}
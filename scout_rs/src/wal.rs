@@ -0,0 +1,203 @@
+//! Emergency write-ahead buffer for records the local store failed to write (disk full,
+//! corruption). The file is preallocated to a fixed size and used as a ring, so appending
+//! never grows it; when full, the oldest records are overwritten.
+
+use anyhow::Result;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"SCOUTWAL";
+/// Magic, capacity, offset of the oldest record, bytes in use
+const HEADER_LEN: u64 = 32;
+/// Each record is prefixed with its length as a little-endian u32
+const LENGTH_PREFIX: u64 = 4;
+
+/// Fixed-size ring of length-prefixed records, persisted to a file
+#[derive(Debug)]
+pub struct FallbackBuffer {
+    path: PathBuf,
+    file: File,
+    capacity: u64,
+    start: u64,
+    used: u64,
+}
+
+impl FallbackBuffer {
+    /// Opens the buffer at `path`, creating and preallocating it with `capacity` bytes of
+    /// record space if it does not exist. An existing buffer keeps its own capacity.
+    pub fn open(path: impl Into<PathBuf>, capacity: u64) -> Result<Self> {
+        let path = path.into();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        if file.metadata()?.len() >= HEADER_LEN {
+            let mut header = [0u8; HEADER_LEN as usize];
+            file.read_exact(&mut header)?;
+            if &header[..8] != MAGIC {
+                return Err(anyhow::Error::msg(format!(
+                    "{} is not a fallback buffer",
+                    path.display()
+                )));
+            }
+            let field = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+            let (capacity, start, used) = (field(8), field(16), field(24));
+            if start >= capacity.max(1) || used > capacity {
+                return Err(anyhow::Error::msg(format!(
+                    "Fallback buffer {} has a corrupt header",
+                    path.display()
+                )));
+            }
+            return Ok(Self {
+                path,
+                file,
+                capacity,
+                start,
+                used,
+            });
+        }
+
+        if capacity <= LENGTH_PREFIX {
+            return Err(anyhow::Error::msg("Fallback buffer capacity is too small"));
+        }
+        // Write every byte up front so later appends cannot fail for lack of disk space
+        let zeros = vec![0u8; 64 * 1024];
+        let mut remaining = HEADER_LEN + capacity;
+        while remaining > 0 {
+            let chunk = remaining.min(zeros.len() as u64) as usize;
+            file.write_all(&zeros[..chunk])?;
+            remaining -= chunk as u64;
+        }
+        let mut buffer = Self {
+            path,
+            file,
+            capacity,
+            start: 0,
+            used: 0,
+        };
+        buffer.write_header()?;
+        Ok(buffer)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    /// Appends a record, overwriting the oldest records if needed. Returns how many were dropped.
+    pub fn push(&mut self, record: &[u8]) -> Result<usize> {
+        let needed = LENGTH_PREFIX + record.len() as u64;
+        if needed > self.capacity {
+            return Err(anyhow::Error::msg(format!(
+                "Record of {} bytes does not fit in a {} byte fallback buffer",
+                record.len(),
+                self.capacity
+            )));
+        }
+
+        let mut dropped = 0;
+        while self.used + needed > self.capacity {
+            let oldest = LENGTH_PREFIX + self.read_length(self.start)?;
+            self.start = (self.start + oldest) % self.capacity;
+            self.used -= oldest;
+            dropped += 1;
+        }
+
+        // Drop the overwritten records from the header before their bytes change, so a crash
+        // mid-append never leaves the header pointing at a half-overwritten record
+        if dropped > 0 {
+            self.write_header()?;
+            self.file.sync_data()?;
+        }
+
+        let end = (self.start + self.used) % self.capacity;
+        self.write_at(end, &(record.len() as u32).to_le_bytes())?;
+        self.write_at((end + LENGTH_PREFIX) % self.capacity, record)?;
+        self.file.sync_data()?;
+        // The new record only counts once its bytes are durable
+        self.used += needed;
+        self.write_header()?;
+        self.file.sync_data()?;
+        Ok(dropped)
+    }
+
+    /// Returns all records, oldest first, without removing them
+    pub fn records(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut records = Vec::new();
+        let mut offset = self.start;
+        let mut remaining = self.used;
+        while remaining > 0 {
+            let length = self.read_length(offset)?;
+            let mut record = vec![0u8; length as usize];
+            self.read_at((offset + LENGTH_PREFIX) % self.capacity, &mut record)?;
+            records.push(record);
+            offset = (offset + LENGTH_PREFIX + length) % self.capacity;
+            remaining = remaining.saturating_sub(LENGTH_PREFIX + length);
+        }
+        Ok(records)
+    }
+
+    /// Removes all records
+    pub fn clear(&mut self) -> Result<()> {
+        self.start = 0;
+        self.used = 0;
+        self.write_header()?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    fn read_length(&mut self, offset: u64) -> Result<u64> {
+        let mut length = [0u8; LENGTH_PREFIX as usize];
+        self.read_at(offset, &mut length)?;
+        let length = u32::from_le_bytes(length) as u64;
+        if LENGTH_PREFIX + length > self.used {
+            return Err(anyhow::Error::msg(format!(
+                "Fallback buffer {} has a corrupt record",
+                self.path.display()
+            )));
+        }
+        Ok(length)
+    }
+
+    /// Writes bytes at a ring offset, wrapping around the end of the record space
+    fn write_at(&mut self, offset: u64, bytes: &[u8]) -> Result<()> {
+        let first = bytes.len().min((self.capacity - offset) as usize);
+        self.file.seek(SeekFrom::Start(HEADER_LEN + offset))?;
+        self.file.write_all(&bytes[..first])?;
+        if first < bytes.len() {
+            self.file.seek(SeekFrom::Start(HEADER_LEN))?;
+            self.file.write_all(&bytes[first..])?;
+        }
+        Ok(())
+    }
+
+    /// Reads bytes at a ring offset, wrapping around the end of the record space
+    fn read_at(&mut self, offset: u64, bytes: &mut [u8]) -> Result<()> {
+        let first = bytes.len().min((self.capacity - offset) as usize);
+        self.file.seek(SeekFrom::Start(HEADER_LEN + offset))?;
+        self.file.read_exact(&mut bytes[..first])?;
+        if first < bytes.len() {
+            self.file.seek(SeekFrom::Start(HEADER_LEN))?;
+            self.file.read_exact(&mut bytes[first..])?;
+        }
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&self.capacity.to_le_bytes());
+        header.extend_from_slice(&self.start.to_le_bytes());
+        header.extend_from_slice(&self.used.to_le_bytes());
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use scout_rs::client::*;
 use scout_rs::db_client::DatabaseConfig;
 use scout_rs::models::{
-    data, AncestorLocal, Connectivity, Event, Heartbeat, MediaType, Plan, PlanType,
+    data, AncestorLocal, Connectivity, Dbm, Event, Heartbeat, MediaType, Plan, PlanType,
     ResponseScoutStatus, Session, Syncable, Tag, TagObservationType,
 };
 use std::env;
@@ -1479,7 +1479,8 @@ async fn test_complete_data_collection_workflow() {
                     None,       // bandwidth_hz
                     None,       // associated_station
                     None,       // mode
-                );
+                )
+                .unwrap();
 
                 let connectivity_result = client.create_connectivity(&connectivity).await;
                 if let Ok(response) = connectivity_result {
@@ -1949,7 +1950,8 @@ async fn test_connectivity_batch_upsert_impl(cleanup: &TestCleanup) {
             None,       // bandwidth_hz
             None,       // associated_station
             None,       // mode
-        ),
+        )
+        .unwrap(),
         Connectivity::new(
             Some(session_id),
             None,       // device_id
@@ -1968,7 +1970,8 @@ async fn test_connectivity_batch_upsert_impl(cleanup: &TestCleanup) {
             None,       // bandwidth_hz
             None,       // associated_station
             None,       // mode
-        ),
+        )
+        .unwrap(),
     ];
 
     // Initial insert
@@ -1989,12 +1992,12 @@ async fn test_connectivity_batch_upsert_impl(cleanup: &TestCleanup) {
 
     // Modify connectivity entries for upsert
     let mut updated_connectivity = created_connectivity.clone();
-    updated_connectivity[0].signal = -65.0;
-    updated_connectivity[1].noise = -85.0;
+    updated_connectivity[0].signal = Dbm::new(-65.0).unwrap();
+    updated_connectivity[1].noise = Dbm::new(-85.0).unwrap();
 
     // Upsert the modified connectivity
     let upsert_result = client
-        .upsert_connectivity_batch(&updated_connectivity)
+        .upsert_connectivity_batch(&updated_connectivity, None)
         .await
         .expect("Connectivity batch upsert failed");
     assert_eq!(upsert_result.status, ResponseScoutStatus::Success);
@@ -2080,7 +2083,7 @@ async fn test_events_batch_upsert_impl(cleanup: &TestCleanup) {
 
     // Upsert the modified events
     let upsert_result = client
-        .upsert_events_batch(&updated_events)
+        .upsert_events_batch(&updated_events, None)
         .await
         .expect("Event batch upsert failed");
     assert_eq!(upsert_result.status, ResponseScoutStatus::Success);
@@ -2184,7 +2187,7 @@ async fn test_tags_batch_upsert_impl(cleanup: &TestCleanup) {
 
     // Upsert the modified tags
     let upsert_result = client
-        .upsert_tags_batch(&updated_tags)
+        .upsert_tags_batch(&updated_tags, None)
         .await
         .expect("Tag batch upsert failed");
     assert_eq!(upsert_result.status, ResponseScoutStatus::Success);
@@ -2219,7 +2222,7 @@ async fn test_empty_batch_upserts_impl(_cleanup: &TestCleanup) {
 
     let empty_connectivity: Vec<Connectivity> = Vec::new();
     let connectivity_result = client
-        .upsert_connectivity_batch(&empty_connectivity)
+        .upsert_connectivity_batch(&empty_connectivity, None)
         .await
         .expect("Empty connectivity upsert failed");
     assert_eq!(connectivity_result.status, ResponseScoutStatus::Success);
@@ -2227,7 +2230,7 @@ async fn test_empty_batch_upserts_impl(_cleanup: &TestCleanup) {
 
     let empty_events: Vec<Event> = Vec::new();
     let event_result = client
-        .upsert_events_batch(&empty_events)
+        .upsert_events_batch(&empty_events, None)
         .await
         .expect("Empty event upsert failed");
     assert_eq!(event_result.status, ResponseScoutStatus::Success);
@@ -2235,7 +2238,7 @@ async fn test_empty_batch_upserts_impl(_cleanup: &TestCleanup) {
 
     let empty_tags: Vec<Tag> = Vec::new();
     let tag_result = client
-        .upsert_tags_batch(&empty_tags)
+        .upsert_tags_batch(&empty_tags, None)
         .await
         .expect("Empty tag upsert failed");
     assert_eq!(tag_result.status, ResponseScoutStatus::Success);
@@ -2327,6 +2330,36 @@ async fn test_heartbeat_operations() {
     let found_hb2 = heartbeats.iter().find(|h| h.id == created_heartbeat2.id);
     assert!(found_hb1.is_some() && found_hb2.is_some());
 
+    // Page through the two heartbeats one at a time
+    let since = HeartbeatQuery {
+        since: Some(timestamp1.clone()),
+        limit: Some(1),
+        ..Default::default()
+    };
+    let first_page = client
+        .query_heartbeats_by_device(device.id.unwrap(), &since)
+        .await
+        .expect("Failed to get first page")
+        .data
+        .unwrap();
+    assert_eq!(first_page.len(), 1);
+    assert_eq!(first_page[0].id, created_heartbeat2.id);
+    let next_page = since.next_page(&first_page).unwrap();
+    let second_page = client
+        .query_heartbeats_by_device(device.id.unwrap(), &next_page)
+        .await
+        .expect("Failed to get second page")
+        .data
+        .unwrap();
+    assert_eq!(second_page.len(), 1);
+    assert_eq!(second_page[0].id, created_heartbeat1.id);
+
+    let latest = client
+        .get_latest_heartbeat(device.id.unwrap())
+        .await
+        .expect("Failed to get latest heartbeat");
+    assert_eq!(latest.data.unwrap().id, created_heartbeat2.id);
+
     // Clean up test data
     cleanup.cleanup(&mut client).await;
 }
@@ -2831,6 +2864,118 @@ fn test_gps_tracker_vehicle_device_type() {
     assert_eq!(unknown, data::v1::DeviceType::Unknown);
 }
 
+#[test]
+fn test_h3_index_validation_and_connectivity_v5() {
+    use scout_rs::models::H3Index;
+
+    // Only valid cell encodings parse; the empty string means no index
+    let h14: H3Index = "8e2800000000007".parse().unwrap();
+    assert_eq!(h14.resolution(), 14);
+    assert_eq!(h14.to_string(), "8e2800000000007");
+    assert!("h14".parse::<H3Index>().is_err());
+    assert!(H3Index::try_from("8e2800000000000").is_err()); // digit below resolution
+    assert!(H3Index::try_from("").unwrap().is_null());
+
+    // Lower resolutions are derived from the highest one
+    let [h13, h11] = h14.hierarchy([13, 11]).unwrap();
+    assert_eq!(h13.to_string(), "8d280000000003f");
+    assert_eq!(h11.resolution(), 11);
+    assert!(h11.parent(12).is_err());
+
+    let mut connectivity = data::ConnectivityLocal::default();
+    connectivity.set_h3_indexes(h14).unwrap();
+    assert_eq!(connectivity.h13_index, h13);
+    assert_eq!(connectivity.h11_index, h11);
+
+    // Indexes travel as strings and are validated on the way in
+    let json = serde_json::to_value(&connectivity).unwrap();
+    assert_eq!(json["h14_index"], "8e2800000000007");
+    let mut invalid = json.clone();
+    invalid["h12_index"] = "h12".into();
+    assert!(serde_json::from_value::<data::ConnectivityLocal>(invalid).is_err());
+    let remote: Connectivity = connectivity.clone().into();
+    assert_eq!(remote.h12_index, connectivity.h12_index.to_string());
+
+    // Legacy v4 records keep their valid indexes and drop the rest
+    let v4_connectivity = data::v4::ConnectivityLocal {
+        h14_index: "8e2800000000007".to_string(),
+        h13_index: "h13".to_string(),
+        ..Default::default()
+    };
+    let v5_connectivity: data::v5::ConnectivityLocal = v4_connectivity.into();
+    assert_eq!(v5_connectivity.h14_index, h14);
+    assert!(v5_connectivity.h13_index.is_null());
+}
+
+#[test]
+fn test_as_remote_matches_api_struct_serialization() {
+    use scout_rs::models::AsRemote;
+
+    let event = data::EventLocal {
+        id_local: Some("event_1".to_string()),
+        ancestor_id_local: Some("session_1".to_string()),
+        parent_event_id_local: Some("event_0".to_string()),
+        embedding_vertex_mm_01: Some(vec![0.5; 4]),
+        ..Default::default()
+    };
+    assert_eq!(
+        serde_json::to_value(AsRemote(&event)).unwrap(),
+        serde_json::to_value(Event::from(event.clone())).unwrap()
+    );
+
+    let mut connectivity = data::ConnectivityLocal {
+        id: Some(7),
+        id_local: Some("connectivity_1".to_string()),
+        ..Default::default()
+    };
+    connectivity
+        .set_h3_indexes("8e2800000000007".parse().unwrap())
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(AsRemote(&connectivity)).unwrap(),
+        serde_json::to_value(Connectivity::from(connectivity.clone())).unwrap()
+    );
+
+    let tag = data::TagLocal {
+        id_local: Some("tag_1".to_string()),
+        detector_name: Some("megadetector".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        serde_json::to_value(AsRemote(&tag)).unwrap(),
+        serde_json::to_value(Tag::from(tag.clone())).unwrap()
+    );
+}
+
+#[test]
+fn test_partial_row_merge_preserves_omitted_fields() {
+    use scout_rs::models::PartialRow;
+
+    let local = data::SessionLocal {
+        id_local: Some("session_1".to_string()),
+        locations: Some("LINESTRING(1 2, 3 4)".to_string()),
+        altitude_max: 120.0,
+        earthranger_url: Some("https://example.org/er".to_string()),
+        ..Default::default()
+    };
+    // The server trimmed locations, and cleared the EarthRanger link
+    let row: PartialRow = serde_json::from_value(serde_json::json!({
+        "id": 42,
+        "inserted_at": "2024-01-01T00:00:00Z",
+        "altitude_max": 95.5,
+        "earthranger_url": null,
+    }))
+    .unwrap();
+
+    let merged = row.merge_into(&local).unwrap();
+    assert_eq!(merged.id, Some(42));
+    assert_eq!(merged.inserted_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+    assert_eq!(merged.altitude_max, 95.5);
+    assert_eq!(merged.id_local, local.id_local);
+    assert_eq!(merged.locations, local.locations);
+    assert_eq!(merged.earthranger_url, None);
+}
+
 test_with_cleanup!(
     test_artifact_upload_integration,
     test_artifact_upload_integration_impl
@@ -3018,7 +3163,7 @@ async fn create_test_sync_engine() -> Result<scout_rs::sync::SyncEngine, Box<dyn
     // Create sync engine with storage
     let sync_engine = scout_rs::sync::SyncEngine::new(
         scout_client,
-        "/tmp/test_scout_integration.db".to_string(),
+        std::env::temp_dir().join("test_scout_integration.db"),
         None,
         false,
     )?
@@ -3773,3 +3918,23 @@ async fn test_offline_mode_with_sync_engine() {
     let count = sync_engine.get_table_count::<SessionLocal>().unwrap();
     assert_eq!(count, 1, "Should have 1 session stored locally");
 }
+
+#[tokio::test]
+async fn test_rotate_api_key_requires_identified_client() {
+    use scout_rs::secrets::{FileSecretProvider, SecretProvider, SCOUT_API_KEY};
+    use tempfile::tempdir;
+
+    // Rotation revokes the key it started with, so the shared test device is not rotated
+    let mut client = create_test_client();
+    let temp_dir = tempdir().unwrap();
+    let secrets = FileSecretProvider::new(temp_dir.path());
+    secrets.set(SCOUT_API_KEY, "old-key").unwrap();
+
+    assert!(client.rotate_api_key(&secrets).await.is_err());
+    assert_eq!(secrets.require(SCOUT_API_KEY).unwrap(), "old-key");
+    assert_eq!(
+        std::fs::read_dir(temp_dir.path()).unwrap().count(),
+        1,
+        "No temporary key file should be left behind"
+    );
+}